@@ -0,0 +1,224 @@
+//! Control flow analysis: a control flow graph is built for each function to find paths that
+//! reach the end of a function without returning a value, code that can never run and loops
+//! that can never be left.
+
+use crate::{
+    lang::{ExpressionValue, Func, Literal, Statement, StatementValue, Type},
+    tokenizer::Token,
+};
+
+use super::{Error, ErrorKind};
+
+const ENTRY: usize = 0;
+// Reached by `return` statements
+const EXIT: usize = 1;
+// Reached by running past the last statement of the function
+const END: usize = 2;
+
+/// Block is a basic block: a sequence of statements that are always run one after the other.
+#[derive(Default)]
+struct Block {
+    // The first statement of the block, if it has any.
+    first_token: Option<Token>,
+    successors: Vec<usize>,
+    predecessors: usize,
+}
+
+struct Loop {
+    header: usize,
+    after: usize,
+    // Number of `break` and `return` statements that leave the loop.
+    exits: usize,
+}
+
+struct Graph {
+    blocks: Vec<Block>,
+    current: usize,
+    loops: Vec<Loop>,
+    // Errors and warnings found while building the graph.
+    errors: Vec<Error>,
+}
+
+/// check_control_flow reports missing returns, unreachable code and infinite loops in `func`.
+pub(super) fn check_control_flow(func: &Func, errors: &mut Vec<Error>) {
+    let mut graph = Graph {
+        blocks: vec![Block::default(), Block::default(), Block::default()],
+        current: ENTRY,
+        loops: vec![],
+        errors: vec![],
+    };
+    graph.add_statements(&func.statements);
+    graph.edge(graph.current, END);
+
+    let reachable = graph.reachable();
+    let return_value = &func.signature.return_value;
+    if reachable[END] && *return_value != Type::Void {
+        errors.push(Error::at_token(
+            &func.first_token,
+            ErrorKind::MissingReturn(return_value.clone()),
+            "".into(),
+        ));
+    }
+
+    // Only the first statement of each stretch of unreachable code is reported. Blocks that
+    // follow unreachable blocks are unreachable as well, but have a predecessor.
+    for (i, block) in graph.blocks.iter().enumerate() {
+        if reachable[i] || block.predecessors > 0 {
+            continue;
+        }
+        if let Some(t) = &block.first_token {
+            errors.push(Error::warning_at_token(
+                t,
+                ErrorKind::UnreachableCode,
+                "".into(),
+            ));
+        }
+    }
+    errors.append(&mut graph.errors);
+}
+
+impl Graph {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.blocks[from].successors.push(to);
+        self.blocks[to].predecessors += 1;
+    }
+
+    /// jump ends the current block with an edge to `to`. Anything that follows is in a new block
+    /// that can only be reached if some other edge leads to it.
+    fn jump(&mut self, to: usize) {
+        self.edge(self.current, to);
+        self.current = self.new_block();
+    }
+
+    /// outside_loop reports `statement`, a `break` or `continue` that is not in a loop.
+    fn outside_loop(&mut self, statement: &Statement, keyword: &str) {
+        self.errors.push(Error::at_token(
+            &statement.first_token,
+            ErrorKind::OutsideLoop(keyword.into()),
+            "".into(),
+        ));
+    }
+
+    fn add_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.add_statement(statement);
+        }
+    }
+
+    fn add_statement(&mut self, statement: &Statement) {
+        let block = &mut self.blocks[self.current];
+        if block.first_token.is_none() {
+            block.first_token = Some(statement.first_token.clone());
+        }
+
+        match &statement.value {
+            StatementValue::Return(_) => {
+                for l in &mut self.loops {
+                    l.exits += 1;
+                }
+                self.jump(EXIT);
+            }
+            StatementValue::Break => match self.loops.last_mut() {
+                Some(l) => {
+                    l.exits += 1;
+                    let after = l.after;
+                    self.jump(after);
+                }
+                None => self.outside_loop(statement, "break"),
+            },
+            StatementValue::Continue => match self.loops.last().map(|l| l.header) {
+                Some(header) => self.jump(header),
+                None => self.outside_loop(statement, "continue"),
+            },
+            StatementValue::If(stmt) => {
+                let join = self.new_block();
+                let mut condition = self.current;
+                for (i, (_, body)) in stmt.branches.iter().enumerate() {
+                    self.current = self.new_block();
+                    self.edge(condition, self.current);
+                    self.add_statements(body);
+                    self.edge(self.current, join);
+
+                    if i + 1 < stmt.branches.len() {
+                        let next = self.new_block();
+                        self.edge(condition, next);
+                        condition = next;
+                    }
+                }
+                match &stmt.else_body {
+                    Some(body) => {
+                        self.current = self.new_block();
+                        self.edge(condition, self.current);
+                        self.add_statements(body);
+                        self.edge(self.current, join);
+                    }
+                    None => self.edge(condition, join),
+                }
+                self.current = join;
+            }
+            StatementValue::While(stmt) => {
+                let always = matches!(
+                    stmt.condition.value,
+                    ExpressionValue::Literal(Literal::Bool(true))
+                );
+                self.add_loop(statement, &stmt.body, !always);
+            }
+            StatementValue::For(stmt) => self.add_loop(statement, &stmt.body, true),
+            StatementValue::Loop(body) => self.add_loop(statement, body, false),
+            StatementValue::Expression(_)
+            | StatementValue::VarDeclaration(_)
+            | StatementValue::Assignment(_)
+            | StatementValue::MultiAssignment(_)
+            | StatementValue::Defer(_) => (),
+        }
+    }
+
+    /// add_loop adds a loop whose condition is checked at the start of each iteration if it is
+    /// `conditional`.
+    fn add_loop(&mut self, statement: &Statement, body: &[Statement], conditional: bool) {
+        let header = self.new_block();
+        let after = self.new_block();
+        self.edge(self.current, header);
+        if conditional {
+            self.edge(header, after);
+        }
+
+        self.loops.push(Loop {
+            header,
+            after,
+            exits: 0,
+        });
+        self.current = self.new_block();
+        self.edge(header, self.current);
+        self.add_statements(body);
+        self.edge(self.current, header);
+        let l = self.loops.pop().unwrap();
+
+        if !conditional && l.exits == 0 {
+            self.errors.push(Error::warning_at_token(
+                &statement.first_token,
+                ErrorKind::InfiniteLoop,
+                "add a `break` or `return` to leave it".into(),
+            ));
+        }
+        self.current = after;
+    }
+
+    fn reachable(&self) -> Vec<bool> {
+        let mut res = vec![false; self.blocks.len()];
+        let mut todo = vec![ENTRY];
+        while let Some(i) = todo.pop() {
+            if res[i] {
+                continue;
+            }
+            res[i] = true;
+            todo.extend(&self.blocks[i].successors);
+        }
+        res
+    }
+}
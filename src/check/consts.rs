@@ -0,0 +1,340 @@
+//! Constant evaluation: the initializers of `const` blocks are folded into literals.
+//!
+//! Constants may use literals, other constants and operators, but nothing that needs the program
+//! to run, such as function calls or variables. Integer arithmetic is checked for overflow
+//! against the type of the constant being defined.
+
+use std::collections::HashMap;
+
+use crate::{
+    lang::{float_text, Const, Expression, ExpressionValue, Func, Literal, Module, Type},
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+use super::{Error, ErrorKind};
+
+/// Evaluation is the result of evaluating a constant expression. The error is None if it has
+/// already been reported.
+type Evaluation = Result<Literal, Option<Box<Error>>>;
+
+enum State {
+    InProgress,
+    Done(Option<Literal>),
+}
+
+/// FuncKey identifies a function, a test or a method of a module, methods by their struct and
+/// their name.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum FuncKey {
+    Function(String),
+    Test(String),
+    Method(String, String),
+}
+
+struct Evaluator<'a> {
+    module: &'a Module,
+    constants: &'a HashMap<String, Const>,
+    // Values of the module constants, when evaluating the constants of a function.
+    outer: Option<&'a HashMap<String, Option<Literal>>>,
+    states: HashMap<String, State>,
+    errors: Vec<Error>,
+}
+
+/// evaluate_constants replaces the value of every constant in `module` by the literal it
+/// evaluates to, and returns the errors for those that can't be evaluated.
+pub(super) fn evaluate_constants(module: &mut Module) -> Vec<Error> {
+    let mut errors = vec![];
+
+    let globals = evaluate(module, &module.constants, None, &mut errors);
+    let mut functions: Vec<(FuncKey, &Func)> = (module.functions.iter())
+        .map(|(ident, f)| (FuncKey::Function(ident.clone()), f))
+        .chain((module.tests.iter()).map(|(ident, f)| (FuncKey::Test(ident.clone()), f)))
+        .chain(module.types.iter().flat_map(|(ttype, s)| {
+            (s.methods.iter()).map(|(ident, f)| (FuncKey::Method(ttype.clone(), ident.clone()), f))
+        }))
+        .collect();
+    functions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut locals = HashMap::new();
+    for (key, func) in functions {
+        let values = evaluate(module, &func.constants, Some(&globals), &mut errors);
+        locals.insert(key, values);
+    }
+
+    let mut take = |key| locals.remove(&key).unwrap();
+    for (ident, func) in &mut module.functions {
+        replace(&mut func.constants, take(FuncKey::Function(ident.clone())));
+    }
+    for (ident, func) in &mut module.tests {
+        replace(&mut func.constants, take(FuncKey::Test(ident.clone())));
+    }
+    for (ttype, s) in &mut module.types {
+        for (ident, func) in &mut s.methods {
+            let key = FuncKey::Method(ttype.clone(), ident.clone());
+            replace(&mut func.constants, take(key));
+        }
+    }
+    replace(&mut module.constants, globals);
+
+    errors
+}
+
+fn evaluate(
+    module: &Module,
+    constants: &HashMap<String, Const>,
+    outer: Option<&HashMap<String, Option<Literal>>>,
+    errors: &mut Vec<Error>,
+) -> HashMap<String, Option<Literal>> {
+    let mut evaluator = Evaluator {
+        module,
+        constants,
+        outer,
+        states: HashMap::new(),
+        errors: vec![],
+    };
+    // In the order they are declared in, so that the same errors are found every time
+    let mut idents: Vec<&String> = constants.keys().collect();
+    idents.sort_by_key(|ident| {
+        let t = &constants[*ident].first_token;
+        (t.line, t.column)
+    });
+    for ident in idents {
+        _ = evaluator.value_of(ident);
+    }
+    errors.append(&mut evaluator.errors);
+
+    evaluator
+        .states
+        .into_iter()
+        .map(|(ident, state)| match state {
+            State::Done(value) => (ident, value),
+            State::InProgress => unreachable!(),
+        })
+        .collect()
+}
+
+fn replace(constants: &mut HashMap<String, Const>, values: HashMap<String, Option<Literal>>) {
+    for (ident, value) in values {
+        if let (Some(cst), Some(value)) = (constants.get_mut(&ident), value) {
+            let first_token = cst.value.first_token.clone();
+            cst.value = Expression {
+                value: ExpressionValue::Literal(value),
+                first_token,
+            };
+        }
+    }
+}
+
+impl Evaluator<'_> {
+    /// value_of evaluates the constant `ident` of this evaluator, unless that has been done
+    /// already.
+    fn value_of(&mut self, ident: &str) -> Evaluation {
+        match self.states.get(ident) {
+            Some(State::Done(Some(value))) => return Ok(value.clone()),
+            Some(State::Done(None)) => return Err(None),
+            Some(State::InProgress) => {
+                let cst = &self.constants[ident];
+                return Err(Some(Box::new(Error::at_token(
+                    &cst.first_token,
+                    ErrorKind::ConstantCycle(ident.into()),
+                    "".into(),
+                ))));
+            }
+            None => (),
+        }
+
+        let cst = &self.constants[ident];
+        self.states.insert(ident.into(), State::InProgress);
+        let value = match self.evaluate(&cst.value, cst) {
+            Ok(value) => Some(value),
+            Err(Some(e)) => {
+                self.errors.push(*e);
+                None
+            }
+            Err(None) => None,
+        };
+        self.states.insert(ident.into(), State::Done(value.clone()));
+        value.ok_or(None)
+    }
+
+    fn evaluate(&mut self, expr: &Expression, cst: &Const) -> Evaluation {
+        match &expr.value {
+            ExpressionValue::Literal(Literal::Nil) => Err(not_constant(expr, "`nil`")),
+            ExpressionValue::Literal(literal) => Ok(literal.clone()),
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                if self.constants.contains_key(&ident.name) {
+                    return self.value_of(&ident.name);
+                }
+                if let Some(value) = self.outer.and_then(|o| o.get(&ident.name)) {
+                    return value.clone().ok_or(None);
+                }
+                match self.module.lookup(&ident.name) {
+                    // Undefined names are reported by name resolution
+                    None => Err(None),
+                    Some(_) => Err(not_constant(expr, "only constants")),
+                }
+            }
+            ExpressionValue::Identifier(_) => Err(not_constant(expr, "imported names")),
+            ExpressionValue::FunctionCall(_) | ExpressionValue::MethodCall(_) => {
+                Err(not_constant(expr, "function calls"))
+            }
+            ExpressionValue::MemberAccess(_) | ExpressionValue::StructLiteral(_) => {
+                Err(not_constant(expr, "structs"))
+            }
+            ExpressionValue::ListLiteral(_) | ExpressionValue::Index(_) => {
+                Err(not_constant(expr, "lists"))
+            }
+            ExpressionValue::Tuple(_) => Err(not_constant(expr, "tuples")),
+            ExpressionValue::OptionCheck(_) => Err(not_constant(expr, "optionals")),
+            ExpressionValue::Propagate(_) => Err(not_constant(expr, "results")),
+            ExpressionValue::Conversion(conversion) => {
+                let value = self.evaluate(&conversion.operand, cst)?;
+                Ok(convert(value, &conversion.ttype))
+            }
+            ExpressionValue::UnaryOperation(op) => {
+                let value = self.evaluate(&op.operand, cst)?;
+                match (&op.operator, value) {
+                    (UnaryOperator::Not, Literal::Bool(b)) => Ok(Literal::Bool(!b)),
+                    (UnaryOperator::Minus, Literal::Integer(i)) => {
+                        self.check_overflow(i.checked_neg(), cst)
+                    }
+                    (UnaryOperator::Minus, Literal::Float(f)) => Ok(Literal::Float(-f)),
+                    (UnaryOperator::Plus, v @ (Literal::Integer(_) | Literal::Float(_))) => Ok(v),
+                    // Reported by the type checker
+                    _ => Err(None),
+                }
+            }
+            ExpressionValue::BinaryOperation(op) => {
+                let lhs = self.evaluate(&op.operands[0], cst)?;
+                let rhs = self.evaluate(&op.operands[1], cst)?;
+                self.binary(&op.operator, lhs, rhs, cst)
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operator: &BinaryOperator,
+        lhs: Literal,
+        rhs: Literal,
+        cst: &Const,
+    ) -> Evaluation {
+        use BinaryOperator as Op;
+
+        let ordering = match (&lhs, &rhs) {
+            (Literal::Integer(a), Literal::Integer(b)) => a.partial_cmp(b),
+            (Literal::Float(a), Literal::Float(b)) => a.partial_cmp(b),
+            (Literal::Integer(a), Literal::Float(b)) => (*a as f64).partial_cmp(b),
+            (Literal::Float(a), Literal::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Literal::String(a), Literal::String(b)) => a.partial_cmp(b),
+            (Literal::Char(a), Literal::Char(b)) => a.partial_cmp(b),
+            (Literal::Bool(a), Literal::Bool(b)) => a.partial_cmp(b),
+            _ => None,
+        };
+        if let Some(ordering) = ordering {
+            let result = match operator {
+                Op::Equals => Some(ordering.is_eq()),
+                Op::NotEquals => Some(ordering.is_ne()),
+                Op::LessThan => Some(ordering.is_lt()),
+                Op::LessThanOrEquals => Some(ordering.is_le()),
+                Op::GreaterThan => Some(ordering.is_gt()),
+                Op::GreaterThanOrEquals => Some(ordering.is_ge()),
+                _ => None,
+            };
+            if let Some(result) = result {
+                return Ok(Literal::Bool(result));
+            }
+        }
+
+        match (lhs, rhs) {
+            (Literal::Integer(a), Literal::Integer(b)) => {
+                let value = match operator {
+                    Op::Add => a.checked_add(b),
+                    Op::Subtract => a.checked_sub(b),
+                    Op::Multiply => a.checked_mul(b),
+                    Op::Divide | Op::Modulo if b == 0 => {
+                        return Err(Some(Box::new(Error::at_token(
+                            &cst.first_token,
+                            ErrorKind::DivisionByZero,
+                            "".into(),
+                        ))))
+                    }
+                    Op::Divide => a.checked_div(b),
+                    Op::Modulo => a.checked_rem(b),
+                    Op::BinaryAnd => Some(a & b),
+                    Op::BinaryOr => Some(a | b),
+                    Op::Xor => Some(a ^ b),
+                    Op::ShiftLeft => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+                    Op::ShiftRight => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+                    _ => return Err(None),
+                };
+                self.check_overflow(value, cst)
+            }
+            (Literal::Integer(a), Literal::Float(b)) => float_binary(operator, a as f64, b, cst),
+            (Literal::Float(a), Literal::Integer(b)) => float_binary(operator, a, b as f64, cst),
+            (Literal::Float(a), Literal::Float(b)) => float_binary(operator, a, b, cst),
+            (Literal::String(a), Literal::String(b)) if *operator == Op::Add => {
+                Ok(Literal::String(a + &b))
+            }
+            (Literal::Bool(a), Literal::Bool(b)) => match operator {
+                Op::LogicalAnd => Ok(Literal::Bool(a && b)),
+                Op::LogicalOr => Ok(Literal::Bool(a || b)),
+                _ => Err(None),
+            },
+            _ => Err(None),
+        }
+    }
+
+    /// check_overflow checks that the result of an integer operation is in the range of the type
+    /// of the constant.
+    fn check_overflow(&self, value: Option<i128>, cst: &Const) -> Evaluation {
+        let range = cst.ttype.range().unwrap_or(Type::Int.range().unwrap());
+        match value {
+            Some(v) if range.0 <= v && v <= range.1 => Ok(Literal::Integer(v)),
+            _ => Err(Some(Box::new(Error::at_token(
+                &cst.first_token,
+                ErrorKind::ConstantOverflow(cst.ttype.clone()),
+                "".into(),
+            )))),
+        }
+    }
+}
+
+fn float_binary(operator: &BinaryOperator, a: f64, b: f64, cst: &Const) -> Evaluation {
+    let value = match operator {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        BinaryOperator::Divide if b == 0.0 => {
+            return Err(Some(Box::new(Error::at_token(
+                &cst.first_token,
+                ErrorKind::DivisionByZero,
+                "".into(),
+            ))))
+        }
+        BinaryOperator::Divide => a / b,
+        _ => return Err(None),
+    };
+    Ok(Literal::Float(value))
+}
+
+/// convert applies a conversion to a constant, following the rules of `Conversion`.
+fn convert(value: Literal, ttype: &Type) -> Literal {
+    match (value, ttype.range()) {
+        (Literal::Integer(i), _) if *ttype == Type::Text => Literal::String(i.to_string()),
+        (Literal::Float(f), _) if *ttype == Type::Text => Literal::String(float_text(f)),
+        (Literal::Bool(b), _) if *ttype == Type::Text => Literal::String(b.to_string()),
+        (Literal::Char(c), _) if *ttype == Type::Text => Literal::String(c.to_string()),
+        (Literal::Integer(i), _) if ttype.is_float() => Literal::Float(i as f64),
+        (Literal::Integer(i), Some(_)) => Literal::Integer(ttype.wrap(i)),
+        (Literal::Float(f), Some((min, max))) => Literal::Integer((f as i128).clamp(min, max)),
+        (value, _) => value,
+    }
+}
+
+fn not_constant(expr: &Expression, what: &str) -> Option<Box<Error>> {
+    Some(Box::new(Error::at_token(
+        &expr.first_token,
+        ErrorKind::NotConstant,
+        format!("{} cannot be used in constants", what),
+    )))
+}
@@ -0,0 +1,243 @@
+use crate::{
+    lang::{Span, Type},
+    tokenizer::Token,
+};
+
+/// Severity tells errors, which stop a module from being compiled, apart from warnings about
+/// code that is valid but likely not what was intended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub message: String,
+    pub kind: ErrorKind,
+    pub severity: Severity,
+
+    pub line: usize,
+    pub column: usize,
+    pub source: String,
+}
+
+impl Error {
+    pub(super) fn at_token(t: &Token, kind: ErrorKind, message: String) -> Self {
+        Self {
+            message,
+            kind,
+            severity: Severity::Error,
+            line: t.line,
+            column: t.column,
+            source: t.path.clone(),
+        }
+    }
+
+    pub(super) fn at_span(span: &Span, kind: ErrorKind, message: String) -> Self {
+        Self {
+            message,
+            kind,
+            severity: Severity::Error,
+            line: span.start.0,
+            column: span.start.1,
+            source: span.path.clone(),
+        }
+    }
+
+    pub(super) fn warning_at_token(t: &Token, kind: ErrorKind, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::at_token(t, kind, message)
+        }
+    }
+
+    pub(super) fn warning_at_span(span: &Span, kind: ErrorKind, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::at_span(span, kind, message)
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}:{}:{}: ",
+            self.source, self.line, self.column
+        ))?;
+        if self.severity == Severity::Warning {
+            f.write_str("warning: ")?;
+        }
+        self.kind.fmt(f)?;
+        if self.message.is_empty() {
+            return Ok(());
+        }
+        f.write_fmt(format_args!(" ({})", self.message))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    UndefinedSymbol(String),
+    UndefinedType(String),
+    ShadowedLocal(String),
+    NotAValue(String),
+    UncheckedOptional(Type),
+    NilNotAllowed(Type),
+    UnexpectedNil,
+    NotOptional(Type),
+    TypeMismatch(Type, Type),
+    OperandMismatch(String, Type, Type),
+    InvalidOperand(String, Type),
+    WrongArgumentCount(usize, usize),
+    TooFewArguments(usize, usize),
+    MissingReturnValue(Type),
+    TupleArity(usize, Type),
+    LiteralOutOfRange(i128, Type),
+    NotConstant,
+    ConstantCycle(String),
+    ConstantOverflow(Type),
+    DivisionByZero,
+    InvalidConversion(Type, Type),
+    AssignToConstant(String),
+    AssignToArgument(String),
+    AssignToReceiver(String),
+    AssignToLoopVariable(String),
+    AssignToTemporary,
+    MissingReturn(Type),
+    UnreachableCode,
+    InfiniteLoop,
+    OutsideLoop(String),
+    NotAResult(Type),
+    PropagationOutsideResult(Type),
+    IncompatibleError(Type, Type),
+    PropagationInDefer,
+    NotAList(Type),
+    HostList(Type),
+    RecursiveStruct(String),
+    InvalidMain,
+    LoopVariableCount(usize, usize),
+    UnusedImport(String),
+    UnusedVariable(String),
+    UnusedConstant(String),
+    UnusedArgument(String),
+    UninitializedVariable(String),
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::UndefinedSymbol(s) => f.write_fmt(format_args!("undefined name `{}`", s)),
+            ErrorKind::UndefinedType(t) => f.write_fmt(format_args!("undefined type `{}`", t)),
+            ErrorKind::ShadowedLocal(s) => f.write_fmt(format_args!(
+                "`{}` shadows a local declaration of the same name",
+                s
+            )),
+            ErrorKind::NotAValue(t) => f.write_fmt(format_args!("`{}` is a type, not a value", t)),
+            ErrorKind::UncheckedOptional(t) => f.write_fmt(format_args!(
+                "value of optional type `{}` used without checking for nil",
+                t
+            )),
+            ErrorKind::NilNotAllowed(t) => {
+                f.write_fmt(format_args!("`nil` is not a value of type `{}`", t))
+            }
+            ErrorKind::UnexpectedNil => f.write_str("`nil` used where a value is required"),
+            ErrorKind::NotOptional(t) => f.write_fmt(format_args!("type `{}` is not optional", t)),
+            ErrorKind::TypeMismatch(expected, found) => f.write_fmt(format_args!(
+                "mismatched types: expected `{}`, found `{}`",
+                expected, found
+            )),
+            ErrorKind::OperandMismatch(op, lhs, rhs) => f.write_fmt(format_args!(
+                "mismatched types `{}` and `{}` for operator `{}`",
+                lhs, rhs, op
+            )),
+            ErrorKind::InvalidOperand(op, t) => f.write_fmt(format_args!(
+                "operator `{}` cannot be applied to `{}`",
+                op, t
+            )),
+            ErrorKind::WrongArgumentCount(expected, found) => f.write_fmt(format_args!(
+                "expected {} arguments, found {}",
+                expected, found
+            )),
+            ErrorKind::TooFewArguments(expected, found) => f.write_fmt(format_args!(
+                "expected at least {} arguments, found {}",
+                expected, found
+            )),
+            ErrorKind::MissingReturnValue(t) => {
+                f.write_fmt(format_args!("missing return value of type `{}`", t))
+            }
+            ErrorKind::TupleArity(n, t) => {
+                f.write_fmt(format_args!("cannot assign `{}` to {} variables", t, n))
+            }
+            ErrorKind::LiteralOutOfRange(v, t) => {
+                f.write_fmt(format_args!("literal `{}` does not fit in `{}`", v, t))
+            }
+            ErrorKind::NotConstant => f.write_str("expression is not constant"),
+            ErrorKind::ConstantCycle(c) => {
+                f.write_fmt(format_args!("constant `{}` depends on itself", c))
+            }
+            ErrorKind::ConstantOverflow(t) => {
+                f.write_fmt(format_args!("constant overflows type `{}`", t))
+            }
+            ErrorKind::DivisionByZero => f.write_str("division by zero"),
+            ErrorKind::AssignToConstant(c) => {
+                f.write_fmt(format_args!("cannot assign to constant `{}`", c))
+            }
+            ErrorKind::AssignToArgument(a) => {
+                f.write_fmt(format_args!("cannot assign to argument `{}`", a))
+            }
+            ErrorKind::AssignToReceiver(r) => {
+                f.write_fmt(format_args!("cannot assign to immutable receiver `{}`", r))
+            }
+            ErrorKind::AssignToLoopVariable(v) => {
+                f.write_fmt(format_args!("cannot assign to loop variable `{}`", v))
+            }
+            ErrorKind::AssignToTemporary => f.write_str("cannot assign to a temporary value"),
+            ErrorKind::MissingReturn(t) => {
+                f.write_fmt(format_args!("not all paths return a value of type `{}`", t))
+            }
+            ErrorKind::UnreachableCode => f.write_str("unreachable code"),
+            ErrorKind::InfiniteLoop => f.write_str("loop never exits"),
+            ErrorKind::OutsideLoop(k) => f.write_fmt(format_args!("`{}` outside of a loop", k)),
+            ErrorKind::InvalidConversion(from, to) => {
+                f.write_fmt(format_args!("cannot convert `{}` to `{}`", from, to))
+            }
+            ErrorKind::NotAResult(t) => {
+                f.write_fmt(format_args!("`?` needs a result, found `{}`", t))
+            }
+            ErrorKind::PropagationOutsideResult(t) => f.write_fmt(format_args!(
+                "`?` can only be used in a function that returns a result, not `{}`",
+                t
+            )),
+            ErrorKind::IncompatibleError(e, expected) => f.write_fmt(format_args!(
+                "error of type `{}` cannot be returned as `{}`",
+                e, expected
+            )),
+            ErrorKind::PropagationInDefer => {
+                f.write_str("errors cannot be propagated out of a deferred call")
+            }
+            ErrorKind::NotAList(t) => f.write_fmt(format_args!("expected a list, found `{}`", t)),
+            ErrorKind::HostList(t) => f.write_fmt(format_args!(
+                "`{}` cannot be exchanged with the host, it holds a list",
+                t
+            )),
+            ErrorKind::InvalidMain => f.write_str("invalid signature for `main`"),
+            ErrorKind::RecursiveStruct(s) => {
+                f.write_fmt(format_args!("struct `{}` contains itself", s))
+            }
+            ErrorKind::LoopVariableCount(expected, found) => f.write_fmt(format_args!(
+                "expected at most {} loop variables, found {}",
+                expected, found
+            )),
+            ErrorKind::UnusedImport(i) => f.write_fmt(format_args!("unused import `{}`", i)),
+            ErrorKind::UnusedVariable(v) => f.write_fmt(format_args!("unused variable `{}`", v)),
+            ErrorKind::UnusedConstant(c) => f.write_fmt(format_args!("unused constant `{}`", c)),
+            ErrorKind::UnusedArgument(a) => f.write_fmt(format_args!("unused argument `{}`", a)),
+            ErrorKind::UninitializedVariable(v) => f.write_fmt(format_args!(
+                "variable `{}` is used before it is assigned",
+                v
+            )),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Lists: `list[T]{a, b}` builds a list, `xs[i]` reads or writes an element, `append(xs, v)`
+//! adds an element at the end and `len(xs)` counts the elements. `for x in xs` and
+//! `for i, x in xs` iterate over the elements, and loops over a text iterate over its
+//! characters the same way.
+//!
+//! Functions declared with `extern` or `export` neither take nor return lists, nor values holding
+//! lists, such as structs with a list field.
+
+use std::collections::HashSet;
+
+use crate::lang::{Expression, For, FuncSignature, Index, ListLiteral, Module, Type};
+
+use super::{Checker, Error, ErrorKind};
+
+impl Checker<'_> {
+    /// check_list_literal checks the elements of `literal` against its element type.
+    pub(super) fn check_list_literal(&mut self, literal: &ListLiteral) -> Option<Type> {
+        for element in &literal.elements {
+            self.expect(element, &literal.ttype);
+        }
+        Some(Type::List(Box::new(literal.ttype.clone())))
+    }
+
+    /// check_index checks `object[index]` and returns the type of the element.
+    pub(super) fn check_index(&mut self, index: &Index) -> Option<Type> {
+        let element = self.require_list(&index.object);
+        self.expect(&index.index, &Type::Int);
+        element
+    }
+
+    /// check_list_call checks a call to the builtin `append` or `len`, and returns the type of
+    /// the value returned by the call. It returns None as the outer value if `name` is neither
+    /// of those.
+    pub(super) fn check_list_call(
+        &mut self,
+        call: &Expression,
+        name: &str,
+        args: &[Expression],
+    ) -> Option<Option<Type>> {
+        let (count, ttype) = match name {
+            "append" => (2, Type::Void),
+            "len" => (1, Type::Int),
+            _ => return None,
+        };
+        if args.len() != count {
+            self.errors.push(Error::at_token(
+                &call.first_token,
+                ErrorKind::WrongArgumentCount(count, args.len()),
+                "".into(),
+            ));
+            for arg in args {
+                self.check_expression(arg);
+            }
+            return Some(Some(ttype));
+        }
+
+        let element = self.require_list(&args[0]);
+        if let Some(value) = args.get(1) {
+            match &element {
+                Some(t) => self.expect(value, t),
+                None => _ = self.check_expression(value),
+            }
+        }
+        Some(Some(ttype))
+    }
+
+    /// list_loop_bindings checks the loop variables of `stmt`, which iterates over a list of
+    /// elements of type `element`, or a text if `element` is `char`, and returns their types.
+    pub(super) fn list_loop_bindings(&mut self, stmt: &For, element: &Type) -> Vec<Type> {
+        if stmt.bindings.len() > 2 {
+            self.errors.push(Error::at_token(
+                &stmt.iterable.first_token,
+                ErrorKind::LoopVariableCount(2, stmt.bindings.len()),
+                "a list is iterated over with its elements, or their positions and the elements"
+                    .into(),
+            ));
+        }
+        stmt.list_bindings(element)
+    }
+
+    /// require_list checks that `expr` is a list and returns the type of its elements.
+    fn require_list(&mut self, expr: &Expression) -> Option<Type> {
+        match self.require_value(expr)? {
+            Type::List(element) => Some(*element),
+            t => {
+                self.errors.push(Error::at_token(
+                    &expr.first_token,
+                    ErrorKind::NotAList(t),
+                    "".into(),
+                ));
+                None
+            }
+        }
+    }
+}
+
+/// check_host_signatures checks that no list, or value holding one, is passed to or returned
+/// from the functions declared with `extern` or `export`. The host has no way to reach their
+/// elements.
+pub(super) fn check_host_signatures(module: &Module, errors: &mut Vec<Error>) {
+    let externs = (module.imports.values())
+        .filter(|i| i.external)
+        .filter_map(|i| i.signature.as_ref());
+    let signatures: Vec<&FuncSignature> = externs.chain(module.exports.values()).collect();
+    for signature in signatures {
+        for arg in &signature.args {
+            if holds_list(module, &arg.ttype, &mut HashSet::new()) {
+                errors.push(Error::at_token(
+                    &arg.first_token,
+                    ErrorKind::HostList(arg.ttype.clone()),
+                    "".into(),
+                ));
+            }
+        }
+        if holds_list(module, &signature.return_value, &mut HashSet::new()) {
+            errors.push(Error::at_token(
+                &signature.first_token,
+                ErrorKind::HostList(signature.return_value.clone()),
+                "".into(),
+            ));
+        }
+    }
+}
+
+/// holds_list returns true if a value of type `ttype` is a list or holds one. `seen` holds the
+/// structs already looked into, so that a struct containing itself is looked into once.
+fn holds_list(module: &Module, ttype: &Type, seen: &mut HashSet<String>) -> bool {
+    match ttype {
+        Type::List(_) => true,
+        Type::Optional(inner) => holds_list(module, inner, seen),
+        Type::Result(value, error) => {
+            holds_list(module, value, seen) || holds_list(module, error, seen)
+        }
+        Type::Tuple(types) => types.iter().any(|t| holds_list(module, t, seen)),
+        Type::Struct(s) if seen.insert(s.clone()) => (module.types.get(s))
+            .is_some_and(|t| t.fields.iter().any(|(_, f)| holds_list(module, f, seen))),
+        _ => false,
+    }
+}
@@ -0,0 +1,486 @@
+//! Semantic checks that run on a module once it has been parsed and finalized.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    lang::{
+        Declaration, Expression, ExpressionValue, Func, FuncSignature, Literal, Module, ScopeId,
+        ScopeKind, ScopeTree, Statement, StatementValue, StructType, Type,
+    },
+    tokenizer::{AssignOperator, BinaryOperator, Token},
+};
+
+mod cfg;
+mod consts;
+mod error;
+mod list;
+mod mutability;
+mod names;
+mod nil;
+mod result;
+mod structs;
+mod types;
+mod usage;
+
+#[cfg(test)]
+mod test;
+
+pub use error::{Error, ErrorKind, Severity};
+
+/// check_module runs all checks on `module` and returns every error found, ordered by position.
+///
+/// Constants are replaced by the values they evaluate to.
+pub fn check_module(module: &mut Module) -> Vec<Error> {
+    let mut errors = names::check_names(module);
+    // Before constants are folded away
+    errors.extend(usage::check_usage(module));
+    errors.extend(consts::evaluate_constants(module));
+    for func in functions(module) {
+        cfg::check_control_flow(func, &mut errors);
+    }
+    list::check_host_signatures(module, &mut errors);
+    structs::check_struct_recursion(module, &mut errors);
+    check_main(module, &mut errors);
+
+    let module = &*module;
+    let mut checker = Checker::new(module);
+
+    for var in module.variables.values() {
+        checker.expect(&var.initial_value, &var.ttype);
+    }
+    for cst in module.constants.values() {
+        checker.expect(&cst.value, &cst.ttype);
+    }
+    for func in functions(module) {
+        checker.check_func(func);
+    }
+
+    errors.extend(checker.errors);
+    errors.sort_by(|a, b| (&a.source, a.line, a.column).cmp(&(&b.source, b.line, b.column)));
+    errors
+}
+
+/// functions returns all functions, tests and methods of `module`.
+fn functions(module: &Module) -> Vec<&Func> {
+    let mut res: Vec<&Func> = module.functions.values().collect();
+    res.extend(module.tests.values());
+    for ttype in module.types.values() {
+        res.extend(ttype.methods.values());
+    }
+    res
+}
+
+/// check_main checks that `main`, where a program starts, takes no arguments and returns
+/// nothing or an `int`, the exit code of the program.
+fn check_main(module: &Module, errors: &mut Vec<Error>) {
+    let main = match module.functions.get("main") {
+        Some(main) => main,
+        None => return,
+    };
+    let signature = &main.signature;
+    if !signature.args.is_empty() || !matches!(signature.return_value, Type::Void | Type::Int) {
+        errors.push(Error::at_token(
+            &main.first_token,
+            ErrorKind::InvalidMain,
+            "`main` takes no arguments and returns nothing or an `int`".into(),
+        ));
+    }
+}
+
+struct Checker<'a> {
+    functions: HashMap<String, &'a FuncSignature>,
+    types: HashMap<String, &'a StructType>,
+    tree: ScopeTree,
+    scope: ScopeId,
+    // Optional variables known to hold a value at the current point of the function.
+    narrowed: HashSet<String>,
+    return_value: Type,
+    // True while checking a deferred call, which runs after the function has already returned.
+    deferring: bool,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(module: &'a Module) -> Self {
+        Self {
+            // Imports are only checked once they have a signature, from linking or `extern`
+            functions: module
+                .functions
+                .iter()
+                .map(|(ident, f)| (ident.clone(), &f.signature))
+                .chain(module.imports.iter().filter_map(|(ident, import)| {
+                    Some((ident.clone(), import.signature.as_ref()?))
+                }))
+                .collect(),
+            types: module.types.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            tree: ScopeTree::with_module(module),
+            scope: ScopeTree::MODULE,
+            narrowed: HashSet::new(),
+            return_value: Type::Void,
+            deferring: false,
+            errors: vec![],
+        }
+    }
+
+    fn check_func(&mut self, func: &Func) {
+        self.scope = self.tree.push_function(func);
+        self.narrowed.clear();
+        self.return_value = func.signature.return_value.clone();
+        self.check_statements(&func.statements);
+        self.scope = ScopeTree::MODULE;
+    }
+
+    /// check_statements checks a block of statements and returns true if the end of the block
+    /// cannot be reached.
+    fn check_statements(&mut self, statements: &[Statement]) -> bool {
+        let mut diverges = false;
+        for statement in statements {
+            diverges |= self.check_statement(statement);
+        }
+        diverges
+    }
+
+    /// check_block checks the statements of a block in a new scope of `kind`, and returns true
+    /// if the end of the block cannot be reached.
+    fn check_block(
+        &mut self,
+        statements: &[Statement],
+        kind: ScopeKind,
+        first_token: &Token,
+    ) -> bool {
+        let parent = self.scope;
+        self.scope = self.tree.push_block(parent, kind, first_token, statements);
+        let diverges = self.check_statements(statements);
+        self.scope = parent;
+        diverges
+    }
+
+    fn check_statement(&mut self, statement: &Statement) -> bool {
+        match &statement.value {
+            StatementValue::Expression(e) => {
+                self.check_expression(e);
+            }
+            StatementValue::Defer(e) => {
+                // The call runs when the block is left, by which time the optionals narrowed here
+                // may have been set to nil
+                let narrowed = std::mem::take(&mut self.narrowed);
+                self.deferring = true;
+                self.check_expression(e);
+                self.deferring = false;
+                self.narrowed = narrowed;
+            }
+            StatementValue::VarDeclaration(decl) => {
+                let ttype = self.declared_type(&decl.ident);
+                if let Some(t) = &ttype {
+                    self.expect(&decl.value, t);
+                }
+                self.assign(&decl.ident, &decl.value, ttype.as_ref());
+            }
+            StatementValue::Assignment(assignment) => {
+                self.check_assignable(&assignment.target, &statement.first_token);
+                let ident = match &assignment.target.value {
+                    ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                        Some(&ident.name)
+                    }
+                    _ => None,
+                };
+
+                if let AssignOperator::AssignAfter(op) = &assignment.operator {
+                    let target = self.check_expression(&assignment.target);
+                    let ttype = self.check_binary(op, &assignment.target, &assignment.value);
+                    if let (Some(target), Some(ttype)) = (target, ttype) {
+                        if target != ttype {
+                            self.errors.push(Error::at_token(
+                                &assignment.value.first_token,
+                                ErrorKind::TypeMismatch(target, ttype),
+                                "".into(),
+                            ));
+                        }
+                    }
+                    return false;
+                }
+
+                let ttype = match ident {
+                    Some(ident) => self.declared_type(ident),
+                    None => self.check_expression(&assignment.target),
+                };
+                if let Some(t) = &ttype {
+                    self.expect(&assignment.value, t);
+                } else {
+                    self.check_expression(&assignment.value);
+                }
+                if let Some(ident) = ident {
+                    self.assign(ident, &assignment.value, ttype.as_ref());
+                }
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                for binding in assignment.bindings.iter().filter(|b| *b != "_") {
+                    self.check_assignable_name(binding, &statement.first_token);
+                }
+                self.check_multi_assignment(&assignment.bindings, &assignment.value);
+                for binding in &assignment.bindings {
+                    self.narrowed.remove(binding);
+                }
+            }
+            StatementValue::Return(value) => {
+                let ttype = self.return_value.clone();
+                match value {
+                    Some(value) => self.expect(value, &ttype),
+                    None if ttype != Type::Void => self.errors.push(Error::at_token(
+                        &statement.first_token,
+                        ErrorKind::MissingReturnValue(ttype),
+                        "".into(),
+                    )),
+                    None => (),
+                }
+                return true;
+            }
+            StatementValue::Break | StatementValue::Continue => return true,
+            StatementValue::If(stmt) => return self.check_if(stmt, &statement.first_token),
+            StatementValue::For(stmt) => {
+                // Loops over `range(end)`, `range(start, end)` or `range(start, end, step)`
+                // count with an int
+                let types = match self.range_arguments(&stmt.iterable) {
+                    Some(args) => {
+                        if args.is_empty() || args.len() > 3 {
+                            self.errors.push(Error::at_token(
+                                &stmt.iterable.first_token,
+                                ErrorKind::WrongArgumentCount(args.len().clamp(1, 3), args.len()),
+                                "".into(),
+                            ));
+                        }
+                        for arg in args {
+                            self.expect(arg, &Type::Int);
+                        }
+                        if stmt.bindings.len() != 1 {
+                            self.errors.push(Error::at_token(
+                                &stmt.iterable.first_token,
+                                ErrorKind::LoopVariableCount(1, stmt.bindings.len()),
+                                "a range is iterated over with a single loop variable".into(),
+                            ));
+                        }
+                        vec![Type::Int]
+                    }
+                    None => match self.require_value(&stmt.iterable) {
+                        Some(t) => match t.element() {
+                            Some(element) => self.list_loop_bindings(stmt, &element),
+                            None => {
+                                self.errors.push(Error::at_token(
+                                    &stmt.iterable.first_token,
+                                    ErrorKind::NotAList(t),
+                                    "only lists, texts and `range` can be iterated over".into(),
+                                ));
+                                vec![]
+                            }
+                        },
+                        None => vec![],
+                    },
+                };
+                let parent = self.scope;
+                let t = &statement.first_token;
+                self.scope = self.tree.push_for(parent, stmt, &types, t);
+                let diverges = self.check_loop(None, &stmt.body, None, false);
+                self.scope = parent;
+                return diverges;
+            }
+            StatementValue::While(stmt) => {
+                let t = &statement.first_token;
+                return self.check_loop(Some(&stmt.condition), &stmt.body, Some(t), false);
+            }
+            StatementValue::Loop(body) => {
+                return self.check_loop(None, body, Some(&statement.first_token), true)
+            }
+        }
+        false
+    }
+
+    /// check_expression checks `expr` and returns its type, or None if the type isn't known.
+    /// Optional variables that are known to hold a value have the type of that value.
+    fn check_expression(&mut self, expr: &Expression) -> Option<Type> {
+        match &expr.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                let ttype = self.declared_type(&ident.name)?;
+                match ttype {
+                    Type::Optional(inner) if self.narrowed.contains(&ident.name) => Some(*inner),
+                    _ => Some(ttype),
+                }
+            }
+            ExpressionValue::Identifier(_) => None,
+            ExpressionValue::FunctionCall(call) => {
+                let signature = match &call.function.value {
+                    ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                        match self.functions.get(&ident.name) {
+                            Some(signature) => Some(*signature),
+                            None => {
+                                if let Some(ttype) =
+                                    self.check_list_call(expr, &ident.name, &call.args)
+                                {
+                                    return ttype;
+                                }
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+                self.check_arguments(expr, &call.args, signature)
+            }
+            ExpressionValue::MethodCall(call) => {
+                let signature = match self.require_value(&call.receiver) {
+                    Some(Type::Struct(s)) => self
+                        .types
+                        .get(&s)
+                        .and_then(|t| t.methods.get(&call.method))
+                        .map(|f| &f.signature),
+                    _ => None,
+                };
+                self.check_arguments(expr, &call.args, signature)
+            }
+            ExpressionValue::MemberAccess(access) => match self.require_value(&access.object)? {
+                Type::Struct(s) => self
+                    .types
+                    .get(&s)?
+                    .fields
+                    .iter()
+                    .find(|(f, _)| *f == access.member)
+                    .map(|(_, t)| t.clone()),
+                _ => None,
+            },
+            ExpressionValue::StructLiteral(literal) => {
+                let ttype = self.types.get(&literal.ttype).copied();
+                for (field, value) in &literal.fields {
+                    let field_type = ttype
+                        .and_then(|t| t.fields.iter().find(|(f, _)| f == field))
+                        .map(|(_, t)| t);
+                    match field_type {
+                        Some(t) => self.expect(value, t),
+                        None => {
+                            self.check_expression(value);
+                        }
+                    }
+                }
+                Some(Type::Struct(literal.ttype.clone()))
+            }
+            ExpressionValue::ListLiteral(literal) => self.check_list_literal(literal),
+            ExpressionValue::Index(index) => self.check_index(index),
+            ExpressionValue::Tuple(elements) => {
+                let types: Vec<Option<Type>> =
+                    elements.iter().map(|e| self.check_expression(e)).collect();
+                Some(Type::Tuple(
+                    types.into_iter().collect::<Option<Vec<Type>>>()?,
+                ))
+            }
+            ExpressionValue::OptionCheck(check) => {
+                self.check_optional(&check.operand);
+                Some(Type::Bool)
+            }
+            ExpressionValue::Propagate(operand) => self.check_propagate(operand),
+            ExpressionValue::Conversion(conversion) => {
+                self.check_conversion(&conversion.operand, &conversion.ttype)
+            }
+            ExpressionValue::BinaryOperation(op) => {
+                let (lhs, rhs) = (&op.operands[0], &op.operands[1]);
+                match op.operator {
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                        self.check_condition(expr);
+                        Some(Type::Bool)
+                    }
+                    BinaryOperator::NilCoalesce => {
+                        let inner = match self.check_optional(lhs)? {
+                            Type::Optional(inner) => *inner,
+                            t => t,
+                        };
+                        let ttype = self.value_type(rhs);
+                        self.expect(rhs, &Type::Optional(Box::new(inner.clone())));
+                        match ttype {
+                            Some(t @ Type::Optional(_)) => Some(t),
+                            _ => Some(inner),
+                        }
+                    }
+                    _ => self.check_binary(&op.operator, lhs, rhs),
+                }
+            }
+            ExpressionValue::UnaryOperation(op) => self.check_unary(&op.operator, &op.operand),
+            ExpressionValue::Literal(literal) => match literal {
+                Literal::Integer(_) => Some(Type::Int),
+                Literal::Float(_) => Some(Type::Float),
+                Literal::String(_) => Some(Type::Text),
+                Literal::Char(_) => Some(Type::Character),
+                Literal::Bool(_) => Some(Type::Bool),
+                Literal::Nil => None,
+            },
+        }
+    }
+
+    /// check_arguments checks the arguments of a call against `signature`, if it is known, and
+    /// returns the type of the value returned by the call.
+    ///
+    /// The values given to a variadic argument `name ...T` are converted to `T` as with `as`.
+    fn check_arguments(
+        &mut self,
+        call: &Expression,
+        args: &[Expression],
+        signature: Option<&FuncSignature>,
+    ) -> Option<Type> {
+        let signature = match signature {
+            Some(s) if s.args.len() == args.len() && !s.variadic => s,
+            Some(s) if s.variadic && s.args.len() - 1 <= args.len() => s,
+            _ => {
+                if let Some(s) = signature {
+                    let kind = match s.variadic {
+                        true => ErrorKind::TooFewArguments(s.args.len() - 1, args.len()),
+                        false => ErrorKind::WrongArgumentCount(s.args.len(), args.len()),
+                    };
+                    self.errors
+                        .push(Error::at_token(&call.first_token, kind, "".into()));
+                }
+                for arg in args {
+                    self.check_expression(arg);
+                }
+                return None;
+            }
+        };
+        let fixed = signature.args.len() - signature.variadic as usize;
+        for (arg, declared) in args.iter().zip(&signature.args[..fixed]) {
+            self.expect(arg, &declared.ttype);
+        }
+        if let (true, Some(Type::List(element))) =
+            (signature.variadic, signature.args.last().map(|a| &a.ttype))
+        {
+            for arg in &args[fixed..] {
+                self.check_conversion(arg, element);
+            }
+        }
+        Some(signature.return_value.clone())
+    }
+
+    /// range_arguments returns the arguments of `expr` if it is a call to the builtin `range`.
+    fn range_arguments<'e>(&self, expr: &'e Expression) -> Option<&'e [Expression]> {
+        match &expr.value {
+            ExpressionValue::FunctionCall(call) => match &call.function.value {
+                ExpressionValue::Identifier(ident)
+                    if ident.namespace.is_empty()
+                        && ident.name == "range"
+                        && !self.functions.contains_key("range") =>
+                {
+                    Some(&call.args)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn lookup(&self, ident: &str) -> Option<&Declaration> {
+        self.tree.lookup_value(self.scope, ident)
+    }
+
+    /// declared_type returns the type `ident` was declared with, regardless of any nil checks.
+    fn declared_type(&self, ident: &str) -> Option<Type> {
+        self.lookup(ident)?.ttype.clone()
+    }
+}
+
+fn is_nil(expr: &Expression) -> bool {
+    matches!(expr.value, ExpressionValue::Literal(Literal::Nil))
+}
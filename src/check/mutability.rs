@@ -0,0 +1,54 @@
+//! Mutability: only variables, mutable receivers, the fields of those and the elements of lists
+//! can be assigned to.
+//! Constants, arguments, immutable receivers, loop variables and temporary values such as the
+//! result of a call cannot.
+
+use crate::{
+    lang::{DeclarationKind, Expression, ExpressionValue},
+    tokenizer::Token,
+};
+
+use super::{Checker, Error, ErrorKind};
+
+impl Checker<'_> {
+    /// check_assignable reports an error if `target`, assigned to by the statement starting at
+    /// `statement`, does not refer to a place that can be written to.
+    pub(super) fn check_assignable(&mut self, target: &Expression, statement: &Token) {
+        match &target.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                self.check_assignable_name(&ident.name, statement)
+            }
+            // Writing to a field writes to the value it belongs to
+            ExpressionValue::MemberAccess(access) => {
+                self.check_assignable(&access.object, statement)
+            }
+            // Lists are shared, so their elements can be written to through any name for them
+            ExpressionValue::Index(_) => (),
+            _ => self.errors.push(Error::at_token(
+                statement,
+                ErrorKind::AssignToTemporary,
+                "".into(),
+            )),
+        }
+    }
+
+    pub(super) fn check_assignable_name(&mut self, ident: &str, statement: &Token) {
+        let decl = match self.lookup(ident) {
+            Some(decl) if !decl.mutable => decl,
+            _ => return, // Undefined names are reported by name resolution
+        };
+        let kind = match decl.kind {
+            DeclarationKind::Constant => ErrorKind::AssignToConstant(ident.into()),
+            DeclarationKind::Argument => ErrorKind::AssignToArgument(ident.into()),
+            DeclarationKind::Receiver => ErrorKind::AssignToReceiver(ident.into()),
+            DeclarationKind::LoopVariable => ErrorKind::AssignToLoopVariable(ident.into()),
+            _ => return,
+        };
+        let span = &decl.span;
+        let message = format!(
+            "`{}` is declared at {}:{}:{}",
+            ident, span.path, span.start.0, span.start.1
+        );
+        self.errors.push(Error::at_token(statement, kind, message));
+    }
+}
@@ -0,0 +1,95 @@
+//! Name resolution: every identifier has to refer to a local value, a symbol of the module, an
+//! import or a builtin. Undefined names are reported together with the closest name in scope.
+//!
+//! Names are resolved through the scope tree of the module, which also decides which local
+//! declarations illegally shadow others.
+
+use crate::lang::{DeclarationKind, Module, ScopeTree};
+
+use super::{Error, ErrorKind};
+
+/// Functions provided by the language itself.
+const BUILTINS: [&str; 5] = ["ok", "err", "range", "append", "len"];
+
+/// check_names reports every identifier in `module` that doesn't refer to anything, and every
+/// local declaration that shadows another one.
+pub(super) fn check_names(module: &Module) -> Vec<Error> {
+    let tree = ScopeTree::build(module);
+    let mut errors = vec![];
+
+    for reference in &tree.references {
+        let declaration = reference.declaration.map(|d| tree.declaration(d));
+        let is_type = declaration.is_some_and(|d| d.kind == DeclarationKind::Type);
+        let t = &reference.span;
+        let kind = match (reference.is_type, declaration) {
+            (true, Some(_)) if is_type => continue,
+            (true, _) => ErrorKind::UndefinedType(reference.name.clone()),
+            (false, Some(_)) if is_type => ErrorKind::NotAValue(reference.name.clone()),
+            (false, Some(_)) => continue,
+            (false, None) if BUILTINS.contains(&reference.name.as_str()) => continue,
+            (false, None) => ErrorKind::UndefinedSymbol(reference.name.clone()),
+        };
+
+        let message = match &kind {
+            ErrorKind::UndefinedType(name) => {
+                suggestion(name, module.types.keys().map(|s| s.as_str()))
+            }
+            ErrorKind::UndefinedSymbol(name) => {
+                let mut candidates: Vec<&str> = tree
+                    .visible_names(reference.scope)
+                    .into_iter()
+                    .filter(|n| !module.types.contains_key(*n))
+                    .collect();
+                candidates.extend(BUILTINS);
+                suggestion(name, candidates.into_iter())
+            }
+            _ => "".into(),
+        };
+        errors.push(Error::at_span(t, kind, message));
+    }
+
+    for shadowing in &tree.shadowings {
+        let declaration = tree.declaration(shadowing.declaration);
+        let shadowed = &tree.declaration(shadowing.shadowed).span;
+        errors.push(Error::at_span(
+            &declaration.span,
+            ErrorKind::ShadowedLocal(declaration.name.clone()),
+            format!(
+                "`{}` is declared at {}:{}:{}",
+                declaration.name, shadowed.path, shadowed.start.0, shadowed.start.1
+            ),
+        ));
+    }
+
+    errors
+}
+
+/// suggestion returns a "did you mean" hint naming the candidate closest to `ident`, if any is
+/// close enough to be a likely typo.
+fn suggestion<'b>(ident: &str, candidates: impl Iterator<Item = &'b str>) -> String {
+    let max_distance = (ident.chars().count() / 3).max(1);
+    let best = candidates
+        .map(|c| (edit_distance(ident, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min();
+    match best {
+        Some((_, c)) => format!("did you mean `{}`?", c),
+        None => "".into(),
+    }
+}
+
+/// edit_distance returns the Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,312 @@
+//! Nil safety: values of optional type can only be used as the type they hold once a nil check
+//! has shown that they hold a value.
+//!
+//! A local variable `x` of type `?T` is narrowed to `T` inside `if x is some { ... }`, in the
+//! `else` branch of `if x is nil { ... }`, and after `if x is nil { return }` or any other check
+//! whose failing branch cannot fall through. Assigning to `x` ends the narrowing unless the new
+//! value is known not to be nil.
+
+use std::collections::HashSet;
+
+use crate::{
+    lang::{
+        DeclarationKind, Expression, ExpressionValue, If, ScopeKind, ScopeTree, Statement,
+        StatementValue, Type,
+    },
+    tokenizer::{BinaryOperator, Token, UnaryOperator},
+};
+
+use super::{is_nil, Checker, Error, ErrorKind};
+
+/// Facts are the optional variables known to hold a value when a condition is true and when it
+/// is false.
+#[derive(Default)]
+pub(super) struct Facts {
+    when_true: HashSet<String>,
+    when_false: HashSet<String>,
+}
+
+impl Checker<'_> {
+    /// require_value checks that `expr` is neither `nil` nor an unchecked optional, and returns
+    /// the type of the value it holds.
+    pub(super) fn require_value(&mut self, expr: &Expression) -> Option<Type> {
+        if is_nil(expr) {
+            self.errors.push(Error::at_token(
+                &expr.first_token,
+                ErrorKind::UnexpectedNil,
+                "use `is nil` to check whether an optional holds a value".into(),
+            ));
+            return None;
+        }
+
+        match self.check_expression(expr)? {
+            Type::Optional(inner) => {
+                self.errors.push(Error::at_token(
+                    &expr.first_token,
+                    ErrorKind::UncheckedOptional(Type::Optional(inner.clone())),
+                    hint(expr),
+                ));
+                Some(*inner)
+            }
+            t => Some(t),
+        }
+    }
+
+    /// check_optional checks the operand of `is some`, `is nil` or `??`, which must be of an
+    /// optional type. Nil checks on variables that are already narrowed are allowed.
+    pub(super) fn check_optional(&mut self, expr: &Expression) -> Option<Type> {
+        let ttype = match &expr.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                self.declared_type(&ident.name)
+            }
+            _ => self.check_expression(expr),
+        }?;
+        if !matches!(ttype, Type::Optional(_)) {
+            self.errors.push(Error::at_token(
+                &expr.first_token,
+                ErrorKind::NotOptional(ttype.clone()),
+                "".into(),
+            ));
+        }
+        Some(ttype)
+    }
+
+    /// assign records that `value` has been assigned to the variable `ident` of type `ttype`.
+    pub(super) fn assign(&mut self, ident: &str, value: &Expression, ttype: Option<&Type>) {
+        if !self.is_narrowable(ident) {
+            return;
+        }
+        let holds_value = match (is_nil(value), ttype) {
+            (false, Some(Type::Optional(inner))) => match self.value_type(value) {
+                Some(t) => t == **inner,
+                None => false,
+            },
+            _ => false,
+        };
+        if holds_value {
+            self.narrowed.insert(ident.into());
+        } else {
+            self.narrowed.remove(ident);
+        }
+    }
+
+    /// check_condition checks a condition and returns what it tells about optional variables.
+    pub(super) fn check_condition(&mut self, condition: &Expression) -> Facts {
+        match &condition.value {
+            ExpressionValue::OptionCheck(check) => {
+                self.check_expression(condition);
+                let mut facts = Facts::default();
+                if let ExpressionValue::Identifier(ident) = &check.operand.value {
+                    if ident.namespace.is_empty() && self.is_narrowable(&ident.name) {
+                        match check.is_some {
+                            true => facts.when_true.insert(ident.name.clone()),
+                            false => facts.when_false.insert(ident.name.clone()),
+                        };
+                    }
+                }
+                facts
+            }
+            ExpressionValue::UnaryOperation(op) if op.operator == UnaryOperator::Not => {
+                let facts = self.check_condition(&op.operand);
+                Facts {
+                    when_true: facts.when_false,
+                    when_false: facts.when_true,
+                }
+            }
+            ExpressionValue::BinaryOperation(op)
+                if op.operator == BinaryOperator::LogicalAnd
+                    || op.operator == BinaryOperator::LogicalOr =>
+            {
+                let and = op.operator == BinaryOperator::LogicalAnd;
+                let lhs = self.check_condition(&op.operands[0]);
+
+                // The right hand side is only evaluated if the left hand side is true for `&&`
+                // and false for `||`.
+                let saved = self.narrowed.clone();
+                let known = if and { &lhs.when_true } else { &lhs.when_false };
+                self.narrowed.extend(known.iter().cloned());
+                let rhs = self.check_condition(&op.operands[1]);
+                self.narrowed = saved;
+
+                if and {
+                    Facts {
+                        when_true: &lhs.when_true | &rhs.when_true,
+                        when_false: &lhs.when_false & &rhs.when_false,
+                    }
+                } else {
+                    Facts {
+                        when_true: &lhs.when_true & &rhs.when_true,
+                        when_false: &lhs.when_false | &rhs.when_false,
+                    }
+                }
+            }
+            _ => {
+                if let Some(t) = self.require_value(condition) {
+                    if t != Type::Bool {
+                        self.errors.push(Error::at_token(
+                            &condition.first_token,
+                            ErrorKind::TypeMismatch(Type::Bool, t),
+                            "".into(),
+                        ));
+                    }
+                }
+                Facts::default()
+            }
+        }
+    }
+
+    /// check_if checks an if chain and returns true if none of its branches falls through.
+    pub(super) fn check_if(&mut self, stmt: &If, first_token: &Token) -> bool {
+        let mut state = self.narrowed.clone();
+        let mut falls_through = vec![];
+
+        for (condition, body) in &stmt.branches {
+            self.narrowed = state.clone();
+            let facts = self.check_condition(condition);
+            self.narrowed = &state | &facts.when_true;
+            let t = body
+                .first()
+                .map_or(&condition.first_token, |s| &s.first_token);
+            if !self.check_block(body, ScopeKind::Block, t) {
+                falls_through.push(self.narrowed.clone());
+            }
+            state = &state | &facts.when_false;
+        }
+
+        self.narrowed = state.clone();
+        match &stmt.else_body {
+            Some(body) => {
+                let t = body.first().map_or(first_token, |s| &s.first_token);
+                if !self.check_block(body, ScopeKind::Block, t) {
+                    falls_through.push(self.narrowed.clone());
+                }
+            }
+            None => falls_through.push(state),
+        }
+
+        let mut states = falls_through.into_iter();
+        match states.next() {
+            Some(first) => {
+                self.narrowed = states.fold(first, |acc, s| &acc & &s);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// check_loop checks the body of a loop, which runs while `condition` holds if there is one.
+    /// The body opens a scope at `first_token`, unless the scope of the loop is already open.
+    /// It returns true if the loop can only be left through a `return`.
+    pub(super) fn check_loop(
+        &mut self,
+        condition: Option<&Expression>,
+        body: &[Statement],
+        first_token: Option<&Token>,
+        infinite: bool,
+    ) -> bool {
+        // Later iterations start with whatever the previous one left behind, so anything
+        // assigned in the body can't be relied upon anywhere in the loop.
+        let mut assigned = HashSet::new();
+        assigned_in(body, &mut assigned);
+        let entry: HashSet<String> = self.narrowed.difference(&assigned).cloned().collect();
+
+        self.narrowed = entry.clone();
+        let facts = condition.map(|c| self.check_condition(c));
+        if let Some(facts) = &facts {
+            self.narrowed.extend(facts.when_true.iter().cloned());
+        }
+        match first_token {
+            Some(t) => self.check_block(body, ScopeKind::Loop, t),
+            None => self.check_statements(body),
+        };
+
+        self.narrowed = entry;
+        let breaks = contains_break(body);
+        if let (Some(facts), false) = (&facts, breaks) {
+            self.narrowed.extend(facts.when_false.iter().cloned());
+        }
+        infinite && !breaks
+    }
+
+    /// is_narrowable returns true if `ident` is an optional local variable, argument or
+    /// receiver. Globals are not narrowed, since any function call may change them.
+    fn is_narrowable(&self, ident: &str) -> bool {
+        self.lookup(ident).is_some_and(|decl| {
+            let local = matches!(
+                decl.kind,
+                DeclarationKind::Variable | DeclarationKind::Argument | DeclarationKind::Receiver
+            );
+            local
+                && decl.scope != ScopeTree::MODULE
+                && matches!(decl.ttype, Some(Type::Optional(_)))
+        })
+    }
+
+    /// value_type returns the type of `expr` without reporting any errors.
+    pub(super) fn value_type(&mut self, expr: &Expression) -> Option<Type> {
+        let errors = self.errors.len();
+        let ttype = self.check_expression(expr);
+        self.errors.truncate(errors);
+        ttype
+    }
+}
+
+pub(super) fn hint(expr: &Expression) -> String {
+    match &expr.value {
+        ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => format!(
+            "check it with `if {} is some` or provide a default with `??`",
+            ident.name
+        ),
+        _ => "store it in a variable and check it with `is some`, or provide a default with `??`"
+            .into(),
+    }
+}
+
+/// assigned_in collects the variables assigned anywhere in `statements`.
+fn assigned_in(statements: &[Statement], res: &mut HashSet<String>) {
+    for statement in statements {
+        match &statement.value {
+            StatementValue::VarDeclaration(decl) => {
+                res.insert(decl.ident.clone());
+            }
+            StatementValue::Assignment(assignment) => {
+                if let ExpressionValue::Identifier(ident) = &assignment.target.value {
+                    if ident.namespace.is_empty() {
+                        res.insert(ident.name.clone());
+                    }
+                }
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                res.extend(assignment.bindings.iter().cloned());
+            }
+            StatementValue::If(stmt) => {
+                for (_, body) in &stmt.branches {
+                    assigned_in(body, res);
+                }
+                if let Some(body) = &stmt.else_body {
+                    assigned_in(body, res);
+                }
+            }
+            StatementValue::For(stmt) => assigned_in(&stmt.body, res),
+            StatementValue::While(stmt) => assigned_in(&stmt.body, res),
+            StatementValue::Loop(body) => assigned_in(body, res),
+            StatementValue::Expression(_)
+            | StatementValue::Defer(_)
+            | StatementValue::Return(_)
+            | StatementValue::Break
+            | StatementValue::Continue => (),
+        }
+    }
+}
+
+/// contains_break returns true if `statements` contain a `break` out of the enclosing loop.
+fn contains_break(statements: &[Statement]) -> bool {
+    statements.iter().any(|s| match &s.value {
+        StatementValue::Break => true,
+        StatementValue::If(stmt) => {
+            stmt.branches.iter().any(|(_, body)| contains_break(body))
+                || stmt.else_body.as_ref().is_some_and(|b| contains_break(b))
+        }
+        _ => false,
+    })
+}
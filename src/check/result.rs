@@ -0,0 +1,69 @@
+//! Results: `ok(value)` and `err(error)` build a value of type `result[T, E]`, and `expr?` takes
+//! the value out of a result, returning its error from the enclosing function if there is one.
+
+use crate::lang::{Expression, ExpressionValue, Type};
+
+use super::{Checker, Error, ErrorKind};
+
+impl Checker<'_> {
+    /// expect_result checks `ok(value)` and `err(error)` against the result type they are used
+    /// as. It returns false if `expr` is neither of those.
+    pub(super) fn expect_result(&mut self, expr: &Expression, value: &Type, error: &Type) -> bool {
+        let call = match &expr.value {
+            ExpressionValue::FunctionCall(call) if call.args.len() == 1 => call,
+            _ => return false,
+        };
+        let expected = match &call.function.value {
+            ExpressionValue::Identifier(ident)
+                if ident.namespace.is_empty() && !self.functions.contains_key(&ident.name) =>
+            {
+                match ident.name.as_str() {
+                    "ok" => value,
+                    "err" => error,
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+
+        self.expect(&call.args[0], expected);
+        true
+    }
+
+    /// check_propagate checks `operand?` and returns the type of the value it evaluates to.
+    pub(super) fn check_propagate(&mut self, operand: &Expression) -> Option<Type> {
+        let (value, error) = match self.require_value(operand)? {
+            Type::Result(value, error) => (value, error),
+            t => {
+                self.errors.push(Error::at_token(
+                    &operand.first_token,
+                    ErrorKind::NotAResult(t),
+                    "".into(),
+                ));
+                return None;
+            }
+        };
+
+        match &self.return_value {
+            _ if self.deferring => {
+                self.errors.push(Error::at_token(
+                    &operand.first_token,
+                    ErrorKind::PropagationInDefer,
+                    "".into(),
+                ));
+            }
+            Type::Result(_, expected) if **expected == *error => (),
+            Type::Result(_, expected) => {
+                let kind = ErrorKind::IncompatibleError(*error, *expected.clone());
+                self.errors
+                    .push(Error::at_token(&operand.first_token, kind, "".into()));
+            }
+            t => {
+                let kind = ErrorKind::PropagationOutsideResult(t.clone());
+                self.errors
+                    .push(Error::at_token(&operand.first_token, kind, "".into()));
+            }
+        }
+        Some(*value)
+    }
+}
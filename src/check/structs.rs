@@ -0,0 +1,63 @@
+//! Structs: a struct cannot contain itself, whether directly, through other structs, tuples,
+//! optionals and results, or through a list.
+//!
+//! Values are not boxed, so a struct containing itself other than through a list would have no
+//! end. Through a list, its values could hold references to themselves, which reference counting
+//! never frees.
+
+use std::collections::HashSet;
+
+use crate::lang::{Module, Type};
+
+use super::{Error, ErrorKind};
+
+/// check_struct_recursion reports every struct of `module` that contains itself, at its
+/// declaration.
+pub(super) fn check_struct_recursion(module: &Module, errors: &mut Vec<Error>) {
+    for s in module.types.values() {
+        let mut seen = HashSet::new();
+        let found = (s.fields.iter())
+            .find_map(|(_, ttype)| contains(module, &s.ident, ttype, false, &mut seen));
+        let message = match found {
+            Some(true) => "through a list, which is not supported",
+            Some(false) => "not supported yet",
+            None => continue,
+        };
+        errors.push(Error::at_token(
+            &s.first_token,
+            ErrorKind::RecursiveStruct(s.ident.clone()),
+            message.into(),
+        ));
+    }
+}
+
+/// contains returns whether a value of type `ttype` holds a value of struct `name`, and if so,
+/// whether it does through a list, or `list` is already true. `seen` holds the structs already
+/// looked into.
+fn contains(
+    module: &Module,
+    name: &str,
+    ttype: &Type,
+    list: bool,
+    seen: &mut HashSet<String>,
+) -> Option<bool> {
+    match ttype {
+        Type::Struct(s) if s == name => Some(list),
+        Type::Struct(s) => {
+            if !seen.insert(s.clone()) {
+                return None;
+            }
+            (module.types.get(s)?.fields.iter())
+                .find_map(|(_, t)| contains(module, name, t, list, seen))
+        }
+        Type::Tuple(types) => types
+            .iter()
+            .find_map(|t| contains(module, name, t, list, seen)),
+        Type::Optional(t) => contains(module, name, t, list, seen),
+        Type::Result(t, e) => {
+            contains(module, name, t, list, seen).or_else(|| contains(module, name, e, list, seen))
+        }
+        Type::List(t) => contains(module, name, t, true, seen),
+        _ => None,
+    }
+}
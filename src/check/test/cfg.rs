@@ -0,0 +1,135 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::{check, warnings};
+
+#[test]
+fn all_paths_must_return() {
+    let errors = check(
+        "
+func fizzbuzz(i int) text {
+	if i % 15 == 0 {
+		return \"fizzbuzz\"
+	} else if i % 3 == 0 {
+		return \"fizz\"
+	} else if i % 5 == 0 {
+		return \"buzz\"
+	} else {
+		return \"\"
+	}
+}
+
+func sign(i int) int {
+	if i < 0 {
+		return -1
+	} else if i > 0 {
+		return 1
+	}
+}
+
+func search(i int) int {
+	loop {
+		if i > 10 {
+			return i
+		}
+		i += 1
+	}
+}
+
+func count(i int) int {
+	while i > 0 {
+		return i
+	}
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors
+        .iter()
+        .filter(|e| matches!(e.kind, ErrorKind::MissingReturn(_)))
+        .map(|e| (&e.kind, e.line))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::MissingReturn(Type::Int), 14),
+            (&ErrorKind::MissingReturn(Type::Int), 31),
+        ]
+    );
+}
+
+#[test]
+fn unreachable_code_and_infinite_loops_are_reported() {
+    let warnings = warnings(
+        "
+func main() {
+	var {
+		i int = 0
+	}
+	loop {
+		i += 1
+	}
+	i = 2
+}
+
+func early() int {
+	return 1
+	early()
+	early()
+}
+
+func skipped() {
+	while true {
+		break
+		skipped()
+	}
+	for i in range(3) {
+		continue
+		skipped()
+	}
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = warnings.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::InfiniteLoop, 6),
+            (&ErrorKind::UnreachableCode, 9),
+            (&ErrorKind::UnreachableCode, 14),
+            (&ErrorKind::UnreachableCode, 21),
+            (&ErrorKind::UnreachableCode, 25),
+        ]
+    );
+}
+
+#[test]
+fn break_and_continue_need_a_loop() {
+    let errors = check(
+        "
+func main() {
+	var {
+		i int = 0
+	}
+	if i > 0 {
+		break
+	}
+	while i < 3 {
+		i += 1
+		continue
+	}
+	continue
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize, usize)> =
+        errors.iter().map(|e| (&e.kind, e.line, e.column)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::OutsideLoop("break".into()), 7, 3),
+            (&ErrorKind::OutsideLoop("continue".into()), 13, 2),
+        ]
+    );
+}
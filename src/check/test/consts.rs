@@ -0,0 +1,86 @@
+use crate::{
+    check::{ErrorKind, Severity},
+    lang::{ExpressionValue, Literal, Type},
+};
+
+use super::{check, check_source};
+
+#[test]
+fn constants_are_folded() {
+    let (module, errors) = check_source(
+        "
+const {
+	kib int = 1024
+	mib int = kib * kib
+	greeting text = \"hello\" + \", \" + name
+	name text = \"world\"
+	big bool = mib > 1000 && !(kib == 0)
+	half float = 1 / 2.0
+	low int8 = int8(300)
+	floor int8 = -1000.5 as int8
+}
+
+func main() {
+	const {
+		blocks int = mib / block
+		block int = 4 * kib
+	}
+}
+",
+    );
+    let errors: Vec<_> = errors
+        .iter()
+        .filter(|e| e.severity == Severity::Error)
+        .collect();
+    assert!(errors.is_empty(), "{}", errors[0]);
+
+    let value = |v: &ExpressionValue| match v {
+        ExpressionValue::Literal(Literal::Integer(i)) => format!("{}", i),
+        ExpressionValue::Literal(Literal::Float(f)) => format!("{}", f),
+        ExpressionValue::Literal(Literal::String(s)) => s.clone(),
+        ExpressionValue::Literal(Literal::Bool(b)) => format!("{}", b),
+        _ => "not folded".into(),
+    };
+    let constant = |name: &str| value(&module.constants[name].value.value);
+    assert_eq!(constant("mib"), "1048576");
+    assert_eq!(constant("greeting"), "hello, world");
+    assert_eq!(constant("big"), "true");
+    assert_eq!(constant("half"), "0.5");
+    assert_eq!(constant("low"), "44");
+    assert_eq!(constant("floor"), "-128");
+
+    let main = &module.functions["main"];
+    assert_eq!(value(&main.constants["blocks"].value.value), "256");
+}
+
+#[test]
+fn invalid_constants_are_reported() {
+    let errors = check(
+        "
+const {
+	a int = b + 1
+	b int = a * 2
+	c int = 10 / (5 - 5)
+	d int8 = 100 + 100 - 100
+	e int = one()
+	f int = counter
+}
+
+var {
+	counter int = 0
+}
+
+func one() int {
+	return 1
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(found.len(), 5);
+    assert_eq!(found[0], (&ErrorKind::ConstantCycle("a".into()), 3));
+    assert_eq!(found[1], (&ErrorKind::DivisionByZero, 5));
+    assert_eq!(found[2], (&ErrorKind::ConstantOverflow(Type::Int8), 6));
+    assert_eq!(found[3], (&ErrorKind::NotConstant, 7));
+    assert_eq!(found[4], (&ErrorKind::NotConstant, 8));
+}
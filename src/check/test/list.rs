@@ -0,0 +1,188 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::check;
+
+#[test]
+fn lists_are_built_and_iterated_over() {
+    let errors = check(
+        "
+func total(values list[int32]) int {
+	var {
+		res int = 0
+	}
+	for value in values {
+		res += value
+	}
+	for i, value in values {
+		values[i] = value * 2
+	}
+	return res + len(values)
+}
+
+func count(s text, wanted char) int {
+	var {
+		res int = 0
+	}
+	for c in s {
+		if c == wanted {
+			res += 1
+		}
+	}
+	for i, c in s + \"!\" {
+		if c == '!' {
+			res += i
+		}
+	}
+	return res
+}
+
+func main() {
+	var {
+		values list[int32] = list[int32]{1, 2, 3}
+	}
+	append(values, 4)
+	values[0] = values[3]
+	total(values)
+	count(\"tiger\", 'g')
+}
+",
+    );
+
+    assert!(errors.is_empty(), "{}", errors[0]);
+}
+
+#[test]
+fn elements_must_have_the_element_type() {
+    let errors = check(
+        "
+func main() {
+	var {
+		names list[text] = list[text]{\"a\", 1}
+		count int = 0
+	}
+	append(names, 'b')
+	names[0] = count
+	count = names[\"first\"]
+	append(names)
+	count[0] = 1
+	for a, b, c in names {
+	}
+	for a, b in range(3) {
+	}
+	for n in count {
+	}
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::TypeMismatch(Type::Text, Type::Int), 4),
+            (&ErrorKind::TypeMismatch(Type::Text, Type::Character), 7),
+            (&ErrorKind::TypeMismatch(Type::Text, Type::Int), 8),
+            (&ErrorKind::TypeMismatch(Type::Int, Type::Text), 9),
+            (&ErrorKind::TypeMismatch(Type::Int, Type::Text), 9),
+            (&ErrorKind::WrongArgumentCount(2, 1), 10),
+            (&ErrorKind::NotAList(Type::Int), 11),
+            (&ErrorKind::LoopVariableCount(2, 3), 12),
+            (&ErrorKind::LoopVariableCount(1, 2), 14),
+            (&ErrorKind::NotAList(Type::Int), 16),
+        ]
+    );
+}
+
+#[test]
+fn lists_are_not_compared() {
+    let errors = check(
+        "
+func same(a list[int], b list[int]) bool {
+	return a == b
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    let list = Type::List(Box::new(Type::Int));
+    assert_eq!(kinds, vec![&ErrorKind::InvalidOperand("==".into(), list)]);
+}
+
+#[test]
+fn lists_are_not_exchanged_with_the_host() {
+    let errors = check(
+        "
+extern func total(values list[int]) int
+extern func first(values ?list[int], count int) int
+extern func lines() list[text]
+
+export func sum(values list[int]) int {
+	return total(values) + first(values, 1) + len(lines())
+}
+
+export func split(s text) (text, list[text]) {
+	return s, list[text]{s}
+}
+",
+    );
+
+    let list = |t| Type::List(Box::new(t));
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::HostList(list(Type::Int)), 2),
+            (
+                &ErrorKind::HostList(Type::Optional(Box::new(list(Type::Int)))),
+                3
+            ),
+            (&ErrorKind::HostList(list(Type::Text)), 4),
+            (&ErrorKind::HostList(list(Type::Int)), 6),
+            (
+                &ErrorKind::HostList(Type::Tuple(vec![Type::Text, list(Type::Text)])),
+                10
+            ),
+        ]
+    );
+}
+
+#[test]
+fn structs_holding_lists_are_not_exchanged_with_the_host() {
+    let errors = check(
+        "
+struct Bag {
+	items list[int]
+}
+
+struct Shelf {
+	bags (Bag, Bag)
+}
+
+struct Point {
+	x int
+	y int
+}
+
+export func count(b Bag) int {
+	return len(b.items)
+}
+
+export func top(s Shelf) int {
+	return 0
+}
+
+export func origin() Point {
+	return Point{x = 0, y = 0}
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::HostList(Type::Struct("Bag".into())), 15),
+            (&ErrorKind::HostList(Type::Struct("Shelf".into())), 19),
+        ]
+    );
+}
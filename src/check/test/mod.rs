@@ -0,0 +1,39 @@
+use crate::{lang::Module, parser::Parser};
+
+use super::{check_module, Error, Severity};
+
+mod cfg;
+mod consts;
+mod list;
+mod mutability;
+mod names;
+mod nil;
+mod result;
+mod structs;
+mod types;
+mod usage;
+
+/// check returns the errors found in `src`, leaving out any warnings.
+fn check(src: &str) -> Vec<Error> {
+    let (_, errors) = check_source(src);
+    errors
+        .into_iter()
+        .filter(|e| e.severity == Severity::Error)
+        .collect()
+}
+
+fn warnings(src: &str) -> Vec<Error> {
+    let (_, errors) = check_source(src);
+    errors
+        .into_iter()
+        .filter(|e| e.severity == Severity::Warning)
+        .collect()
+}
+
+fn check_source(src: &str) -> (Module, Vec<Error>) {
+    let mut parser = Parser::new("test".into());
+    parser.add_source(src.as_bytes(), None).unwrap();
+    let mut module = parser.finalize().unwrap();
+    let errors = check_module(&mut module);
+    (module, errors)
+}
@@ -0,0 +1,83 @@
+use crate::check::ErrorKind;
+
+use super::check;
+
+#[test]
+fn only_variables_can_be_assigned() {
+    let errors = check(
+        "
+const {
+	limit int = 10
+}
+
+struct Point {
+	x int
+}
+
+func origin() Point {
+	return Point{x = 0}
+}
+
+func (p Point) reset() {
+	p.x = 0
+}
+
+func (var p Point) move(dx int) {
+	p.x += dx
+}
+
+func step(n int) {
+	var {
+		total int = 0
+		p Point = origin()
+	}
+	total = n
+	p.x = total
+	limit = 5
+	n += 1
+	for i in range(limit) {
+		i = 0
+	}
+	origin().x = 1
+	total, n = (1, 2)
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize, &str)> = errors
+        .iter()
+        .filter(|e| !matches!(e.kind, ErrorKind::UndefinedSymbol(_)))
+        .map(|e| (&e.kind, e.line, e.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                &ErrorKind::AssignToReceiver("p".into()),
+                15,
+                "`p` is declared at -:14:7"
+            ),
+            (
+                &ErrorKind::AssignToConstant("limit".into()),
+                29,
+                "`limit` is declared at -:3:2"
+            ),
+            (
+                &ErrorKind::AssignToArgument("n".into()),
+                30,
+                "`n` is declared at -:22:11"
+            ),
+            (
+                &ErrorKind::AssignToLoopVariable("i".into()),
+                32,
+                "`i` is declared at -:31:2"
+            ),
+            (&ErrorKind::AssignToTemporary, 34, ""),
+            (
+                &ErrorKind::AssignToArgument("n".into()),
+                35,
+                "`n` is declared at -:22:11"
+            ),
+        ]
+    );
+}
@@ -0,0 +1,171 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::check;
+
+#[test]
+fn names_must_be_defined() {
+    let errors = check(
+        "
+use {
+	io.print_line
+}
+
+struct Point {
+	x int
+}
+
+func main() {
+	var {
+		count int = 0
+	}
+	for i in rng(3) {
+		count += i
+	}
+	print_lne(cont)
+	print_line(i)
+	io.print_line(Point)
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, &str)> = errors
+        .iter()
+        .map(|e| (&e.kind, e.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::UndefinedSymbol("rng".into()), ""),
+            (
+                &ErrorKind::UndefinedSymbol("print_lne".into()),
+                "did you mean `print_line`?"
+            ),
+            (
+                &ErrorKind::UndefinedSymbol("cont".into()),
+                "did you mean `count`?"
+            ),
+            (&ErrorKind::UndefinedSymbol("i".into()), ""),
+            (&ErrorKind::UndefinedSymbol("io".into()), ""),
+            (&ErrorKind::NotAValue("Point".into()), ""),
+        ]
+    );
+}
+
+#[test]
+fn types_must_be_defined() {
+    let errors = check(
+        "
+struct Point {
+	x int
+}
+
+func origin() ?Pont {
+	return nil
+}
+",
+    );
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::UndefinedType("Pont".into()));
+    assert_eq!(errors[0].message, "did you mean `Point`?");
+}
+
+#[test]
+fn members_of_undefined_types_are_not_reported() {
+    let errors = check(
+        "
+struct Point {
+	x int
+}
+
+func (p Point) length() int {
+	return p.x
+}
+
+func scale(p Pont) int {
+	return p.x * p.length()
+}
+",
+    );
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::UndefinedType("Pont".into()));
+    assert_eq!(errors[0].message, "did you mean `Point`?");
+}
+
+#[test]
+fn block_scopes() {
+    let errors = check(
+        "
+func pick(limit int) int {
+	if limit > 10 {
+		var {
+			big int = limit * 2
+		}
+		return big
+	}
+	for limit in range(3) {
+		return limit
+	}
+	var {
+		small int = 1
+	}
+	return small + big
+}
+
+func range(n int) int {
+	return n
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (&ErrorKind::ShadowedLocal("limit".into()), 9),
+            (&ErrorKind::NotAList(Type::Int), 9),
+            (&ErrorKind::UndefinedSymbol("big".into()), 15),
+        ]
+    );
+}
+
+#[test]
+fn sibling_blocks_declare_the_same_names() {
+    let errors = check(
+        "
+func pick(limit int) int {
+	if limit > 10 {
+		var {
+			x int = limit * 2
+		}
+		return x
+	} else {
+		var {
+			x text = \"small\"
+		}
+		x = 1
+	}
+	for i in range(3) {
+		var {
+			x bool = i > 1
+		}
+		if x {
+			return i
+		}
+	}
+	return 0
+}
+
+func range(n int) list[int] {
+	return list[int]{n}
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        found,
+        vec![(&ErrorKind::TypeMismatch(Type::Text, Type::Int), 12)]
+    );
+}
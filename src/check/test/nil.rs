@@ -0,0 +1,191 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::check;
+
+#[test]
+fn optionals_must_be_checked_before_use() {
+    let errors = check(
+        "
+func twice(x ?int) int {
+	return x * 2
+}
+
+func first(x option[int]) int {
+	return x
+}
+",
+    );
+
+    assert_eq!(errors.len(), 2);
+    let optional = Type::Optional(Box::new(Type::Int));
+    assert_eq!(
+        errors[0].kind,
+        ErrorKind::UncheckedOptional(optional.clone())
+    );
+    assert_eq!(errors[0].line, 3);
+    assert_eq!(errors[1].kind, ErrorKind::UncheckedOptional(optional));
+    assert_eq!(errors[1].line, 7);
+}
+
+#[test]
+fn checks_narrow_optionals() {
+    let errors = check(
+        "
+func twice(x ?int) int {
+	if x is some {
+		return x * 2
+	}
+	return 0
+}
+
+func early(x ?int) int {
+	if x is nil {
+		return 0
+	}
+	return x
+}
+
+func both(x ?int, y ?int) int {
+	if x is some && y is some {
+		return x + y
+	} else if !(x is nil) {
+		return x
+	}
+	return y ?? 0
+}
+
+func assigned() int {
+	var {
+		x ?int = next()
+	}
+	if x is nil {
+		x = 1
+	}
+	return x
+}
+
+func waited() int {
+	var {
+		x ?int = nil
+	}
+	while x is nil {
+		x = next()
+	}
+	return x
+}
+
+func next() ?int {
+	return nil
+}
+",
+    );
+
+    assert!(errors.is_empty(), "{}", errors[0]);
+}
+
+#[test]
+fn narrowing_ends() {
+    let errors = check(
+        "
+func reassigned(y ?int) int {
+	var {
+		x ?int = 1
+	}
+	if x is some {
+		x = y
+		return x
+	}
+	return 0
+}
+
+func joined() int {
+	var {
+		x ?int = nil
+	}
+	if x is some {
+		x = x + 1
+	}
+	return x
+}
+
+func looped(y ?int) int {
+	var {
+		x ?int = nil
+	}
+	if x is some {
+		loop {
+			if x > 1 {
+				break
+			}
+			x = y
+		}
+	}
+	return 0
+}
+",
+    );
+
+    let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+    assert_eq!(lines, vec![8, 20, 29]);
+}
+
+#[test]
+fn deferred_calls_are_not_narrowed() {
+    let errors = check(
+        "
+use {
+	io.print_line
+}
+
+func main() {
+	var {
+		a ?int = 0
+	}
+	if a is some {
+		defer print_line(a + 1)
+		a = nil
+	}
+	defer print_line(a ?? 0)
+}
+",
+    );
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind,
+        ErrorKind::UncheckedOptional(Type::Optional(Box::new(Type::Int)))
+    );
+    assert_eq!(errors[0].line, 11);
+}
+
+#[test]
+fn nil_needs_an_optional() {
+    let errors = check(
+        "
+var {
+	a ?int = nil
+	b int = nil
+}
+
+func main() {
+	var {
+		c int = 1
+	}
+	if c is some {
+		c = a ?? 2
+	}
+	c = c + nil
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::NilNotAllowed(Type::Int),
+            &ErrorKind::NotOptional(Type::Int),
+            &ErrorKind::UnexpectedNil,
+        ]
+    );
+}
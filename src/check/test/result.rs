@@ -0,0 +1,86 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::check;
+
+#[test]
+fn errors_are_propagated() {
+    let errors = check(
+        "
+struct IOError {
+	code int
+}
+
+func read(path text) result[text, IOError] {
+	if path == \"\" {
+		return err(IOError{code = 2})
+	}
+	return ok(\"contents\")
+}
+
+func length(path text) result[int, IOError] {
+	var {
+		contents text = read(path)?
+	}
+	return ok(1)
+}
+",
+    );
+
+    assert!(errors.is_empty(), "{}", errors[0]);
+}
+
+#[test]
+fn propagation_needs_a_compatible_result() {
+    let errors = check(
+        "
+func parse(s text) result[int, text] {
+	return err(nil)
+}
+
+func twice(s text) int {
+	return parse(s)? * 2
+}
+
+func describe(s text) result[text, int] {
+	var {
+		n int = parse(s)?
+	}
+	return ok(\"number\")
+}
+
+func plain(n int) result[int, text] {
+	return ok(n?)
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::NilNotAllowed(Type::Text),
+            &ErrorKind::PropagationOutsideResult(Type::Int),
+            &ErrorKind::IncompatibleError(Type::Text, Type::Int),
+            &ErrorKind::NotAResult(Type::Int),
+        ]
+    );
+}
+
+#[test]
+fn deferred_calls_cannot_propagate() {
+    let errors = check(
+        "
+func close(handle int) result[int, text] {
+	return ok(handle)
+}
+
+func run(handle int) result[int, text] {
+	defer close(close(handle)?)
+	return close(handle)
+}
+",
+    );
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::PropagationInDefer);
+}
@@ -0,0 +1,61 @@
+use crate::check::ErrorKind;
+
+use super::check;
+
+#[test]
+fn structs_cannot_contain_themselves() {
+    let errors = check(
+        "
+struct Node {
+	value int
+	next ?Node
+}
+
+struct Tree {
+	label text
+	forest Forest
+}
+
+struct Forest {
+	trees list[Tree]
+}
+
+struct Pair {
+	left Forest
+	right (int, ?Node)
+}
+",
+    );
+
+    let found: Vec<(&ErrorKind, usize, usize, &str)> = errors
+        .iter()
+        .map(|e| (&e.kind, e.line, e.column, e.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                &ErrorKind::RecursiveStruct("Node".into()),
+                2,
+                8,
+                "not supported yet"
+            ),
+            (
+                &ErrorKind::RecursiveStruct("Tree".into()),
+                7,
+                8,
+                "through a list, which is not supported"
+            ),
+            (
+                &ErrorKind::RecursiveStruct("Forest".into()),
+                12,
+                8,
+                "through a list, which is not supported"
+            ),
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "-:2:8: struct `Node` contains itself (not supported yet)"
+    );
+}
@@ -0,0 +1,249 @@
+use crate::{check::ErrorKind, lang::Type};
+
+use super::check;
+
+#[test]
+fn well_typed_code_passes() {
+    let errors = check(
+        "
+struct Point {
+	x float
+	y float
+}
+
+func (p Point) scaled(f float) Point {
+	return Point{x = p.x * f, y = p.y * f}
+}
+
+func divmod(a int8, b int8) (int8, int8) {
+	return a / b, a % b
+}
+
+func main() {
+	var {
+		small int8 = -5
+		q int8 = 0
+		r int8 = 0
+		p Point = Point{x = 1, y = 2.5}
+		name text = \"a\" + \"b\"
+	}
+	q, r = divmod(small, 2)
+	small += 1
+	p = p.scaled(2)
+	if q < r && name != \"\" {
+		small = q << 1
+	}
+}
+",
+    );
+
+    assert!(errors.is_empty(), "{}", errors[0]);
+}
+
+#[test]
+fn mismatches_name_both_types() {
+    let errors = check(
+        "
+func add(a int, b int) int {
+	return a + b
+}
+
+func main() {
+	var {
+		x int = \"abc\"
+		y float = 1.5
+		z bool = x + y
+	}
+	x = add(1)
+	x = add(1, true)
+	if x {
+		return 1
+	}
+	y = y % 2.0
+	z = !x
+}
+
+func value() int {
+	return
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::TypeMismatch(Type::Int, Type::Text),
+            &ErrorKind::OperandMismatch("+".into(), Type::Int, Type::Float),
+            &ErrorKind::WrongArgumentCount(2, 1),
+            &ErrorKind::TypeMismatch(Type::Int, Type::Bool),
+            &ErrorKind::TypeMismatch(Type::Bool, Type::Int),
+            &ErrorKind::TypeMismatch(Type::Void, Type::Int),
+            &ErrorKind::InvalidOperand("%".into(), Type::Float),
+            &ErrorKind::InvalidOperand("!".into(), Type::Int),
+            &ErrorKind::MissingReturnValue(Type::Int),
+        ]
+    );
+}
+
+#[test]
+fn tuples_are_checked_element_by_element() {
+    let errors = check(
+        "
+func pair() (int, text) {
+	return 1, 2
+}
+
+func main() {
+	var {
+		a int = 0
+		b int = 0
+	}
+	a, b = pair()
+	a, b = (1, 2, 3)
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::TypeMismatch(Type::Text, Type::Int),
+            &ErrorKind::TypeMismatch(Type::Int, Type::Text),
+            &ErrorKind::TupleArity(2, Type::Tuple(vec![Type::Int, Type::Int, Type::Int])),
+        ]
+    );
+}
+
+#[test]
+fn literals_must_fit_their_type() {
+    let errors = check(
+        "
+func main() {
+	var {
+		a int8 = -128
+		b int8 = 128
+		c uint = -1
+		d uint8 = 255
+		e ?int16 = 40000
+	}
+	a = a + 1000
+	d = uint8(256)
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::LiteralOutOfRange(128, Type::Int8),
+            &ErrorKind::LiteralOutOfRange(-1, Type::UInt),
+            &ErrorKind::LiteralOutOfRange(40000, Type::Int16),
+            &ErrorKind::LiteralOutOfRange(1000, Type::Int8),
+            &ErrorKind::LiteralOutOfRange(256, Type::UInt8),
+        ]
+    );
+}
+
+#[test]
+fn only_exact_conversions_are_implicit() {
+    let errors = check(
+        "
+func main() {
+	var {
+		small int8 = 1
+		unsigned uint32 = 1
+		wide int64 = small
+		signed int64 = unsigned
+		half float32 = small
+		ratio float64 = unsigned
+		narrow int8 = wide
+		whole int32 = ratio
+		lossy float32 = unsigned
+	}
+	narrow = int8(wide)
+	whole = ratio as int32
+	lossy = float32(unsigned) + half
+	wide = wide + small
+	narrow = int8(\"1\")
+}
+",
+    );
+
+    let kinds: Vec<&ErrorKind> = errors.iter().map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            &ErrorKind::TypeMismatch(Type::Int8, Type::Int64),
+            &ErrorKind::TypeMismatch(Type::Int32, Type::Float64),
+            &ErrorKind::TypeMismatch(Type::Float32, Type::UInt32),
+            &ErrorKind::InvalidConversion(Type::Text, Type::Int8),
+        ]
+    );
+}
+
+#[test]
+fn variadic_arguments_convert_their_values() {
+    let errors = check(
+        "
+struct Point {
+	x int
+}
+
+func join(separator text, values ...text) text {
+	return separator
+}
+
+func sum(values ...float) float {
+	return 0
+}
+
+func main() {
+	var {
+		name text = join(\", \", 1, 2.5, 'c', true, \"text\") + 3 as text
+		total float = sum() + sum(1, 2.5, int8(3))
+	}
+	name = join()
+	name = join(\"\", list[int]{1})
+	total = sum(\"1\")
+	name = Point{x = 1} as text
+}
+",
+    );
+
+    let kinds: Vec<(&ErrorKind, usize)> = errors.iter().map(|e| (&e.kind, e.line)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (&ErrorKind::TooFewArguments(1, 0), 19),
+            (
+                &ErrorKind::InvalidConversion(Type::List(Box::new(Type::Int)), Type::Text),
+                20
+            ),
+            (&ErrorKind::InvalidConversion(Type::Text, Type::Float), 21),
+            (
+                &ErrorKind::InvalidConversion(Type::Struct("Point".into()), Type::Text),
+                22
+            ),
+        ]
+    );
+}
+
+#[test]
+fn main_takes_no_arguments_and_returns_an_exit_code() {
+    assert!(check("\nfunc main() {\n}\n").is_empty());
+    assert!(check("\nfunc main() int {\n\treturn 1\n}\n").is_empty());
+
+    for src in [
+        "\nfunc main(n int) {\n}\n",
+        "\nfunc main() result[int, text] {\n\treturn ok(0)\n}\n",
+        "\nfunc main() int8 {\n\treturn 0\n}\n",
+    ] {
+        let errors = check(src);
+        let found: Vec<(&ErrorKind, usize, usize)> =
+            errors.iter().map(|e| (&e.kind, e.line, e.column)).collect();
+        assert_eq!(found, vec![(&ErrorKind::InvalidMain, 2, 1)], "{}", src);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::check::ErrorKind;
+
+use super::{check, warnings};
+
+#[test]
+fn unused_declarations() {
+    let found: Vec<(ErrorKind, usize)> = warnings(
+        "
+use {
+	fmt
+	_io
+}
+
+extern \"host\" func log(message text)
+
+func area(w int, h int, _scale int) int {
+	const {
+		unit int = 1
+		sides int = 4
+	}
+	var {
+		perimeter int = w * sides
+		_spare int = 0
+	}
+	return w * unit
+}
+",
+    )
+    .into_iter()
+    .map(|e| (e.kind, e.line))
+    .collect();
+
+    assert_eq!(
+        found,
+        vec![
+            (ErrorKind::UnusedImport("fmt".into()), 3),
+            (ErrorKind::UnusedArgument("h".into()), 9),
+            (ErrorKind::UnusedVariable("perimeter".into()), 15),
+        ]
+    );
+}
+
+#[test]
+fn assignments_are_not_reads() {
+    let found: Vec<ErrorKind> = warnings(
+        "
+func count() int {
+	var {
+		written int = 0
+		total int = 0
+	}
+	written = 1
+	total += 1
+	return total
+}
+",
+    )
+    .into_iter()
+    .map(|e| e.kind)
+    .collect();
+
+    assert_eq!(found, vec![ErrorKind::UnusedVariable("written".into())]);
+}
+
+#[test]
+fn read_before_assignment() {
+    let src = "
+func pick(flag bool) int {
+	if flag {
+		var {
+			total int = picked * 2
+			picked int = 1
+		}
+		return total
+	}
+	return 0
+}
+";
+    assert!(check(src).is_empty());
+    let found: Vec<(ErrorKind, usize)> = warnings(src)
+        .into_iter()
+        .map(|e| (e.kind, e.line))
+        .collect();
+
+    assert_eq!(
+        found,
+        vec![(ErrorKind::UninitializedVariable("picked".into()), 5)]
+    );
+}
@@ -0,0 +1,293 @@
+//! Type checking: every expression gets a type, and values must have the type that their use
+//! requires.
+//!
+//! Integer and floating point literals have no fixed type of their own. They take the type that
+//! is expected of them, or the type of the other operand of a binary operation, as long as that
+//! is a numeric type and the value is in its range.
+//!
+//! Other values are only converted implicitly between numeric types if the conversion is exact,
+//! see `Type::widens_to`. Everything else needs an explicit conversion.
+
+use crate::{
+    lang::{Expression, ExpressionValue, Literal, Type},
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+use super::{is_nil, nil::hint, Checker, Error, ErrorKind};
+
+impl Checker<'_> {
+    /// expect checks that `expr` can be used where a value of type `expected` is required.
+    pub(super) fn expect(&mut self, expr: &Expression, expected: &Type) {
+        if is_nil(expr) {
+            if !matches!(expected, Type::Optional(_)) {
+                self.errors.push(Error::at_token(
+                    &expr.first_token,
+                    ErrorKind::NilNotAllowed(expected.clone()),
+                    "".into(),
+                ));
+            }
+            return;
+        }
+        if let Type::Result(value, error) = expected {
+            if self.expect_result(expr, value, error) {
+                return;
+            }
+        }
+        if let (ExpressionValue::Tuple(elements), Type::Tuple(types)) = (&expr.value, expected) {
+            if elements.len() == types.len() {
+                for (element, ttype) in elements.iter().zip(types) {
+                    self.expect(element, ttype);
+                }
+                return;
+            }
+        }
+        if literal_fits(expr, expected) {
+            self.check_expression(expr);
+            self.check_literal_range(expr, expected);
+            return;
+        }
+
+        let found = match self.check_expression(expr) {
+            Some(t) => t,
+            None => return,
+        };
+        match (&found, expected) {
+            (Type::Optional(_), Type::Optional(_)) => (),
+            (Type::Optional(_), _) => {
+                self.errors.push(Error::at_token(
+                    &expr.first_token,
+                    ErrorKind::UncheckedOptional(found.clone()),
+                    hint(expr),
+                ));
+                return;
+            }
+            _ => (),
+        }
+
+        if !compatible(&found, expected) {
+            self.errors.push(Error::at_token(
+                &expr.first_token,
+                ErrorKind::TypeMismatch(expected.clone(), found),
+                "".into(),
+            ));
+        }
+    }
+
+    /// check_binary checks the operands of a binary operator other than `&&`, `||` and `??`, and
+    /// returns the type of the result.
+    pub(super) fn check_binary(
+        &mut self,
+        operator: &BinaryOperator,
+        lhs: &Expression,
+        rhs: &Expression,
+    ) -> Option<Type> {
+        let lhs_type = self.require_value(lhs);
+        let rhs_type = self.require_value(rhs);
+        let comparison = matches!(
+            operator,
+            BinaryOperator::Equals
+                | BinaryOperator::NotEquals
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEquals
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEquals
+        );
+        let result = |t| if comparison { Some(Type::Bool) } else { t };
+
+        let ttype = match (lhs_type, rhs_type) {
+            (Some(l), Some(r)) if l == r => l,
+            (Some(_), Some(r)) if literal_fits(lhs, &r) => {
+                self.check_literal_range(lhs, &r);
+                r
+            }
+            (Some(l), Some(_)) if literal_fits(rhs, &l) => {
+                self.check_literal_range(rhs, &l);
+                l
+            }
+            (Some(l), Some(r)) if l.widens_to(&r) => r,
+            (Some(l), Some(r)) if r.widens_to(&l) => l,
+            (Some(l), Some(r)) => {
+                self.errors.push(Error::at_token(
+                    &lhs.first_token,
+                    ErrorKind::OperandMismatch(operator.to_string(), l, r),
+                    "".into(),
+                ));
+                return result(None);
+            }
+            _ => return result(None),
+        };
+
+        let valid = match operator {
+            BinaryOperator::Add => ttype.is_numeric() || ttype == Type::Text,
+            BinaryOperator::Subtract | BinaryOperator::Multiply | BinaryOperator::Divide => {
+                ttype.is_numeric()
+            }
+            BinaryOperator::Modulo
+            | BinaryOperator::BinaryOr
+            | BinaryOperator::BinaryAnd
+            | BinaryOperator::Xor
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight => ttype.is_integer(),
+            BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEquals
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEquals => {
+                ttype.is_numeric() || ttype == Type::Text || ttype == Type::Character
+            }
+            BinaryOperator::Equals | BinaryOperator::NotEquals => {
+                ttype != Type::Void && !matches!(ttype, Type::List(_))
+            }
+            BinaryOperator::LogicalOr | BinaryOperator::LogicalAnd => ttype == Type::Bool,
+            BinaryOperator::NilCoalesce => true,
+        };
+        if !valid {
+            self.errors.push(Error::at_token(
+                &lhs.first_token,
+                ErrorKind::InvalidOperand(operator.to_string(), ttype),
+                "".into(),
+            ));
+            return result(None);
+        }
+        result(Some(ttype))
+    }
+
+    /// check_unary checks the operand of a unary operator and returns the type of the result.
+    pub(super) fn check_unary(
+        &mut self,
+        operator: &UnaryOperator,
+        operand: &Expression,
+    ) -> Option<Type> {
+        let ttype = self.require_value(operand)?;
+        let valid = match operator {
+            UnaryOperator::Not => ttype == Type::Bool,
+            UnaryOperator::Minus | UnaryOperator::Plus => ttype.is_numeric(),
+        };
+        if !valid {
+            self.errors.push(Error::at_token(
+                &operand.first_token,
+                ErrorKind::InvalidOperand(operator.to_string(), ttype),
+                "".into(),
+            ));
+            return None;
+        }
+        Some(ttype)
+    }
+
+    /// check_conversion checks an explicit conversion of `operand` to `ttype`. Numbers convert to
+    /// each other, and numbers, booleans, characters and texts to the text they are printed as.
+    pub(super) fn check_conversion(&mut self, operand: &Expression, ttype: &Type) -> Option<Type> {
+        if literal_fits(operand, ttype) {
+            self.check_expression(operand);
+            self.check_literal_range(operand, ttype);
+            return Some(ttype.clone());
+        }
+
+        let found = self.require_value(operand)?;
+        let valid = match ttype {
+            Type::Text => {
+                found.is_numeric() || matches!(found, Type::Bool | Type::Character | Type::Text)
+            }
+            _ => found.is_numeric() && ttype.is_numeric(),
+        };
+        if !valid {
+            self.errors.push(Error::at_token(
+                &operand.first_token,
+                ErrorKind::InvalidConversion(found, ttype.clone()),
+                "".into(),
+            ));
+        }
+        Some(ttype.clone())
+    }
+
+    /// check_literal_range reports integer literals that are out of range for `ttype`.
+    fn check_literal_range(&mut self, expr: &Expression, ttype: &Type) {
+        let ttype = match ttype {
+            Type::Optional(inner) => inner,
+            t => t,
+        };
+        if let (Some(v), Some((min, max))) = (literal_value(expr), ttype.range()) {
+            if v < min || v > max {
+                self.errors.push(Error::at_token(
+                    &expr.first_token,
+                    ErrorKind::LiteralOutOfRange(v, ttype.clone()),
+                    format!("`{}` holds values from {} to {}", ttype, min, max),
+                ));
+            }
+        }
+    }
+
+    /// check_multi_assignment checks that `value` is a tuple with an element for each binding,
+    /// and that each element fits the variable it is assigned to.
+    pub(super) fn check_multi_assignment(&mut self, bindings: &[String], value: &Expression) {
+        let types = match self.check_expression(value) {
+            Some(Type::Tuple(types)) if types.len() == bindings.len() => types,
+            Some(t) => {
+                self.errors.push(Error::at_token(
+                    &value.first_token,
+                    ErrorKind::TupleArity(bindings.len(), t),
+                    "".into(),
+                ));
+                return;
+            }
+            None => return,
+        };
+
+        for (binding, found) in bindings.iter().zip(types) {
+            let expected = match self.declared_type(binding) {
+                Some(t) => t,
+                None => continue, // `_` or undefined
+            };
+            if !compatible(&found, &expected) {
+                self.errors.push(Error::at_token(
+                    &value.first_token,
+                    ErrorKind::TypeMismatch(expected, found),
+                    format!("in the assignment to `{}`", binding),
+                ));
+            }
+        }
+    }
+}
+
+/// compatible returns true if a value of type `found` can be used as `expected`.
+fn compatible(found: &Type, expected: &Type) -> bool {
+    match expected {
+        Type::Optional(inner) => found == expected || compatible(found, inner),
+        _ => found == expected || found.widens_to(expected),
+    }
+}
+
+/// literal_value returns the value of an integer literal, including any sign in front of it.
+fn literal_value(expr: &Expression) -> Option<i128> {
+    match &expr.value {
+        ExpressionValue::Literal(Literal::Integer(v)) => Some(*v),
+        ExpressionValue::UnaryOperation(op) => match op.operator {
+            UnaryOperator::Minus => literal_value(&op.operand).map(|v| -v),
+            UnaryOperator::Plus => literal_value(&op.operand),
+            UnaryOperator::Not => None,
+        },
+        _ => None,
+    }
+}
+
+/// literal_fits returns true if `expr` is a numeric literal that can take the type `ttype`.
+fn literal_fits(expr: &Expression, ttype: &Type) -> bool {
+    if let Type::Optional(inner) = ttype {
+        return literal_fits(expr, inner);
+    }
+    match &expr.value {
+        ExpressionValue::Literal(Literal::Integer(_)) => ttype.is_numeric(),
+        ExpressionValue::Literal(Literal::Float(_)) => ttype.is_float(),
+        ExpressionValue::UnaryOperation(op) if op.operator != UnaryOperator::Not => {
+            literal_fits(&op.operand, ttype)
+        }
+        ExpressionValue::BinaryOperation(op) => match op.operator {
+            BinaryOperator::Add
+            | BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo => op.operands.iter().all(|o| literal_fits(o, ttype)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
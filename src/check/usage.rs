@@ -0,0 +1,289 @@
+//! Usage warnings: imports, local variables, constants and arguments that are never used, and
+//! variables that are read before the statement declaring them has run.
+//!
+//! None of these are reported for names starting with `_`.
+
+use std::collections::HashSet;
+
+use crate::{
+    lang::{
+        DeclarationId, DeclarationKind, Expression, ExpressionValue, Module, ScopeId, ScopeKind,
+        ScopeTree, Statement, StatementValue,
+    },
+    tokenizer::Token,
+};
+
+use super::{functions, Error, ErrorKind};
+
+struct Usage {
+    tree: ScopeTree,
+    scope: ScopeId,
+    // Declarations that have been read.
+    read: HashSet<DeclarationId>,
+    // Local variables that are known to be assigned. None if the current statement cannot be
+    // reached.
+    assigned: Option<HashSet<DeclarationId>>,
+    warnings: Vec<Error>,
+}
+
+/// check_usage returns warnings for everything in `module` that is declared but never used.
+pub(super) fn check_usage(module: &Module) -> Vec<Error> {
+    let mut usage = Usage {
+        tree: ScopeTree::with_module(module),
+        scope: ScopeTree::MODULE,
+        read: HashSet::new(),
+        assigned: None,
+        warnings: vec![],
+    };
+
+    for expr in module
+        .constants
+        .values()
+        .map(|c| &c.value)
+        .chain(module.variables.values().map(|v| &v.initial_value))
+    {
+        usage.read_expression(expr);
+    }
+
+    for func in functions(module) {
+        usage.scope = usage.tree.push_function(func);
+        usage.assigned = Some(HashSet::new());
+        for cst in func.constants.values() {
+            usage.read_expression(&cst.value);
+        }
+        usage.statements(&func.statements);
+    }
+    usage.scope = ScopeTree::MODULE;
+
+    usage.report_unused();
+    usage.warnings
+}
+
+impl Usage {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    /// block checks the statements of a block in a new scope of `kind`.
+    fn block(&mut self, statements: &[Statement], kind: ScopeKind, first_token: &Token) {
+        let parent = self.scope;
+        self.scope = self.tree.push_block(parent, kind, first_token, statements);
+        self.statements(statements);
+        self.scope = parent;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let t = &statement.first_token;
+        match &statement.value {
+            StatementValue::Expression(e) | StatementValue::Defer(e) => self.read_expression(e),
+            StatementValue::VarDeclaration(decl) => {
+                self.read_expression(&decl.value);
+                self.assign(&decl.ident);
+            }
+            StatementValue::Assignment(assignment) => {
+                self.read_expression(&assignment.value);
+                match &assignment.target.value {
+                    // Assigning to a variable doesn't use it, unless the operator reads it too
+                    ExpressionValue::Identifier(ident)
+                        if ident.namespace.is_empty()
+                            && assignment.operator == crate::tokenizer::AssignOperator::Assign =>
+                    {
+                        self.assign(&ident.name)
+                    }
+                    _ => self.read_expression(&assignment.target),
+                }
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                self.read_expression(&assignment.value);
+                for binding in &assignment.bindings {
+                    self.assign(binding);
+                }
+            }
+            StatementValue::Return(value) => {
+                if let Some(value) = value {
+                    self.read_expression(value);
+                }
+                self.assigned = None;
+            }
+            StatementValue::Break | StatementValue::Continue => self.assigned = None,
+            StatementValue::If(stmt) => {
+                let mut state = self.assigned.clone();
+                let mut after: Option<HashSet<DeclarationId>> = None;
+                for (condition, body) in &stmt.branches {
+                    self.assigned = state.clone();
+                    self.read_expression(condition);
+                    state = self.assigned.clone();
+                    let first_token = body
+                        .first()
+                        .map_or(&condition.first_token, |s| &s.first_token);
+                    self.block(body, ScopeKind::Block, first_token);
+                    after = join(after, self.assigned.take());
+                }
+                self.assigned = state.clone();
+                if let Some(body) = &stmt.else_body {
+                    let first_token = body.first().map_or(t, |s| &s.first_token);
+                    self.block(body, ScopeKind::Block, first_token);
+                    self.assigned = join(after, self.assigned.take());
+                } else {
+                    self.assigned = join(after, state);
+                }
+            }
+            StatementValue::For(stmt) => {
+                self.read_expression(&stmt.iterable);
+                let parent = self.scope;
+                self.scope = self.tree.push_for(parent, stmt, &[], t);
+                let entry = self.assigned.clone();
+                self.statements(&stmt.body);
+                self.assigned = entry;
+                self.scope = parent;
+            }
+            StatementValue::While(stmt) => {
+                self.read_expression(&stmt.condition);
+                self.loop_body(&stmt.body, t);
+            }
+            StatementValue::Loop(body) => self.loop_body(body, t),
+        }
+    }
+
+    /// loop_body checks the body of a loop, which may run any number of times. Assignments in
+    /// the body are not relied upon after the loop.
+    fn loop_body(&mut self, body: &[Statement], first_token: &Token) {
+        let entry = self.assigned.clone();
+        self.block(body, ScopeKind::Loop, first_token);
+        self.assigned = entry;
+    }
+
+    /// local_variable returns the declaration of `ident` if it is a local variable.
+    fn local_variable(&self, ident: &str) -> Option<DeclarationId> {
+        let id = self.tree.lookup(self.scope, ident)?;
+        let decl = self.tree.declaration(id);
+        let local = decl.kind == DeclarationKind::Variable && decl.scope != ScopeTree::MODULE;
+        local.then_some(id)
+    }
+
+    fn assign(&mut self, ident: &str) {
+        let id = self.local_variable(ident);
+        if let (Some(assigned), Some(id)) = (&mut self.assigned, id) {
+            assigned.insert(id);
+        }
+    }
+
+    fn read_expression(&mut self, expr: &Expression) {
+        let mut reads = vec![];
+        identifiers(expr, &mut |ident, t| {
+            reads.push((ident.to_string(), t.clone()))
+        });
+        for (ident, t) in reads {
+            self.read(&ident, &t);
+        }
+    }
+
+    fn read(&mut self, ident: &str, t: &Token) {
+        if let Some(id) = self.local_variable(ident) {
+            let unassigned = self.assigned.as_ref().is_some_and(|a| !a.contains(&id));
+            if unassigned && !ident.starts_with('_') {
+                self.warnings.push(Error::warning_at_token(
+                    t,
+                    ErrorKind::UninitializedVariable(ident.into()),
+                    "".into(),
+                ));
+                // Only report the first read
+                self.assign(ident);
+            }
+        }
+        if let Some(id) = self.tree.lookup(self.scope, ident) {
+            self.read.insert(id);
+        }
+    }
+
+    /// report_unused warns about the local variables, constants and arguments, and the imports,
+    /// that have not been read.
+    fn report_unused(&mut self) {
+        for (id, decl) in self.tree.declarations.iter().enumerate() {
+            if self.read.contains(&id) || decl.name.starts_with('_') {
+                continue;
+            }
+            let name = decl.name.clone();
+            let local = decl.scope != ScopeTree::MODULE;
+            let (kind, message) = match decl.kind {
+                DeclarationKind::Import => (ErrorKind::UnusedImport(name), ""),
+                DeclarationKind::Constant if local => (ErrorKind::UnusedConstant(name), ""),
+                DeclarationKind::Variable if local => (ErrorKind::UnusedVariable(name), ""),
+                DeclarationKind::Argument => (
+                    ErrorKind::UnusedArgument(name),
+                    "rename it to `_` followed by its name if that is intended",
+                ),
+                _ => continue,
+            };
+            self.warnings
+                .push(Error::warning_at_span(&decl.span, kind, message.into()));
+        }
+    }
+}
+
+/// join returns the variables assigned on both of two paths. None is a path that cannot be
+/// reached, which doesn't restrict the other one.
+fn join(
+    a: Option<HashSet<DeclarationId>>,
+    b: Option<HashSet<DeclarationId>>,
+) -> Option<HashSet<DeclarationId>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(&a & &b),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// identifiers calls `f` for the first word of every identifier in `expr`.
+fn identifiers(expr: &Expression, f: &mut impl FnMut(&str, &Token)) {
+    match &expr.value {
+        ExpressionValue::Identifier(ident) => f(
+            ident.namespace.first().unwrap_or(&ident.name),
+            &expr.first_token,
+        ),
+        ExpressionValue::FunctionCall(call) => {
+            identifiers(&call.function, f);
+            for arg in &call.args {
+                identifiers(arg, f);
+            }
+        }
+        ExpressionValue::MethodCall(call) => {
+            identifiers(&call.receiver, f);
+            for arg in &call.args {
+                identifiers(arg, f);
+            }
+        }
+        ExpressionValue::MemberAccess(access) => identifiers(&access.object, f),
+        ExpressionValue::StructLiteral(literal) => {
+            for (_, value) in &literal.fields {
+                identifiers(value, f);
+            }
+        }
+        ExpressionValue::ListLiteral(literal) => {
+            for element in &literal.elements {
+                identifiers(element, f);
+            }
+        }
+        ExpressionValue::Index(index) => {
+            identifiers(&index.object, f);
+            identifiers(&index.index, f);
+        }
+        ExpressionValue::Tuple(elements) => {
+            for element in elements {
+                identifiers(element, f);
+            }
+        }
+        ExpressionValue::OptionCheck(check) => identifiers(&check.operand, f),
+        ExpressionValue::Propagate(operand) => identifiers(operand, f),
+        ExpressionValue::Conversion(conversion) => identifiers(&conversion.operand, f),
+        ExpressionValue::BinaryOperation(op) => {
+            for operand in &op.operands {
+                identifiers(operand, f);
+            }
+        }
+        ExpressionValue::UnaryOperation(op) => identifiers(&op.operand, f),
+        ExpressionValue::Literal(_) => (),
+    }
+}
@@ -1,32 +1,209 @@
 use std::{
-    fs::{self, File},
-    io,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display, Write as _},
+    fs,
+    io::{self, Write as _},
+    path::Path,
 };
 
-use crate::parser;
+use crate::{check, codegen, hir, lang, link, opt, tokenizer::TokenStream};
 
-use super::CommandOpts;
+use super::{CommandOpts, Emit};
 
-fn run_internal(opts: &CommandOpts) -> io::Result<()> {
+/// run_internal builds the programs in each directory of `opts.path_specs`, and returns how many
+/// of them failed to build.
+fn run_internal(opts: &CommandOpts) -> io::Result<usize> {
+    let mut failed = 0;
     for path in &opts.path_specs {
-        for entry in fs::read_dir(&path)? {
+        let mut files = vec![];
+        for entry in fs::read_dir(path)? {
             let entry = entry?;
-            let path = entry.path().to_string_lossy().to_string();
-            println!("{}", path);
+            if entry.path().extension() == Some("tiger".as_ref()) {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+
+        let mut programs = vec![];
+        let mut load_failed = false;
+        for file in files {
+            if opts.emit == Emit::Tokens {
+                report(opts, file.to_string_lossy());
+                match tokens(&file) {
+                    Ok(tokens) => write(opts, &file, tokens.as_bytes())?,
+                    Err(e) => {
+                        report(opts, e);
+                        failed += 1;
+                    }
+                }
+                continue;
+            }
+
+            // Modules are looked up next to the module being built
+            match link::load(&file, &[path.into()]) {
+                Ok(assembly) => programs.push((file, assembly)),
+                Err(errors) => {
+                    report(opts, file.to_string_lossy());
+                    for e in &errors {
+                        report(opts, e);
+                    }
+                    failed += 1;
+                    load_failed = true;
+                }
+            }
+        }
+
+        // Modules that other modules import from are built as part of those. The modules a
+        // program that failed to load imports from are unknown, so once one has failed, modules
+        // without a `main` are taken to be among them.
+        let libraries: HashSet<String> = (programs.iter())
+            .flat_map(|(_, assembly)| assembly.modules.iter().skip(1))
+            .map(|m| m.identifier.clone())
+            .collect();
+        for (file, assembly) in programs {
+            let entry = &assembly.modules[0];
+            let library = libraries.contains(&entry.identifier)
+                || (load_failed && !entry.functions.contains_key("main"));
+            if !library {
+                report(opts, file.to_string_lossy());
+                if !build(opts, &file, assembly)? {
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
+/// build checks, lowers, optimizes and compiles the program made of `assembly`, whose entry
+/// module is at `path`, and writes the output asked for. It returns false if errors were
+/// reported instead.
+fn build(opts: &CommandOpts, path: &Path, assembly: lang::Assembly) -> io::Result<bool> {
+    if opts.emit == Emit::Ast {
+        write(opts, path, ast(&assembly).as_bytes())?;
+        return Ok(true);
+    }
+
+    let mut modules = vec![];
+    let mut failed = false;
+    for mut module in assembly.modules {
+        let errors = check::check_module(&mut module);
+        for e in &errors {
+            report(opts, e);
+        }
+        if errors.iter().any(|e| e.severity == check::Severity::Error) {
+            failed = true;
+            continue;
+        }
+        match hir::lower(&module) {
+            Ok(hir) => modules.push(hir),
+            Err(e) => {
+                report(opts, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Ok(false);
+    }
+
+    let mut hir = link::merge(modules);
+    opt::optimize(&mut hir, opts.opt_level);
+    if opts.emit == Emit::Hir {
+        write(opts, path, format!("{:#?}\n", hir).as_bytes())?;
+        return Ok(true);
+    }
 
-            let file = File::open(entry.path())?;
-            let module_name = entry.file_name().to_string_lossy().to_string();
-            let mut parser = parser::Parser::new(module_name);
-	    let res = parser.add_source(file, Some(path.clone()));
-	    if let Err(e) = res {
-		println!("Failed to process {}: {}", path, e);
-	    }
+    let res = codegen::compile_for(&hir, opts.target).and_then(|(wasm, stats)| {
+        if opts.validate {
+            codegen::validate(&wasm)?;
+        }
+        Ok((wasm, stats))
+    });
+    match res {
+        Ok((wasm, stats)) => {
+            if opts.verbose {
+                report(opts, format!("  wasm: {} bytes", wasm.len()));
+                report(opts, format!("  {}", stats));
+            }
+            match opts.emit {
+                Emit::Wat => match wasmprinter::print_bytes(&wasm) {
+                    Ok(wat) => write(opts, path, wat.as_bytes())?,
+                    Err(e) => {
+                        report(opts, e);
+                        return Ok(false);
+                    }
+                },
+                _ => write(opts, path, &wasm)?,
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            report(opts, e);
+            Ok(false)
         }
     }
+}
+
+/// report prints a message about the build of a program. Messages go to the standard error when
+/// the output goes to the standard output.
+fn report(opts: &CommandOpts, message: impl Display) {
+    match opts.stdout {
+        true => eprintln!("{}", message),
+        false => println!("{}", message),
+    }
+}
+
+/// write writes the output of the build of the program at `path`, next to it with the extension
+/// of the output, or to the standard output.
+fn write(opts: &CommandOpts, path: &Path, output: &[u8]) -> io::Result<()> {
+    match opts.stdout {
+        true => io::stdout().write_all(output),
+        false => fs::write(path.with_extension(opts.emit.extension()), output),
+    }
+}
 
-    Ok(())
+/// tokens returns the tokens of the file at `path`, one per line after its position.
+fn tokens(path: &Path) -> Result<String, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut res = String::new();
+    for token in TokenStream::new(file, Some(path.to_string_lossy().into())) {
+        let token = token.map_err(|e| e.to_string())?;
+        _ = writeln!(res, "{}:{}\t{}", token.line, token.column, token.value);
+    }
+    Ok(res)
+}
+
+/// ast returns the declarations of each module of `assembly`, sorted by kind and name.
+fn ast(assembly: &lang::Assembly) -> String {
+    let mut res = String::new();
+    for module in &assembly.modules {
+        _ = writeln!(res, "module {}", module.identifier);
+        declarations(&mut res, "import", &module.imports);
+        declarations(&mut res, "export", &module.exports);
+        declarations(&mut res, "const", &module.constants);
+        declarations(&mut res, "var", &module.variables);
+        declarations(&mut res, "struct", &module.types);
+        declarations(&mut res, "func", &module.functions);
+        declarations(&mut res, "test", &module.tests);
+        res.push('\n');
+    }
+    res
+}
+
+fn declarations<T: Debug>(res: &mut String, kind: &str, items: &HashMap<String, T>) {
+    let mut names: Vec<&String> = items.keys().collect();
+    names.sort();
+    for name in names {
+        _ = writeln!(res, "\n{} {} {:#?}", kind, name, items[name]);
+    }
 }
 
 pub fn run(opts: &CommandOpts) -> Result<(), String> {
-    run_internal(opts).map_err(|e| e.to_string())
+    match run_internal(opts).map_err(|e| e.to_string())? {
+        0 => Ok(()),
+        1 => Err("1 program failed to build".into()),
+        n => Err(format!("{} programs failed to build", n)),
+    }
 }
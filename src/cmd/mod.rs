@@ -1,4 +1,6 @@
-use std::{env, process};
+use std::{env, process, str::FromStr};
+
+use crate::{codegen, opt};
 
 const BUILD_CMD: &str = "build";
 const SUBCOMMANDS: [&str; 1] = [BUILD_CMD];
@@ -8,6 +10,62 @@ mod build;
 pub struct CommandOpts {
     subcommand: String,
     path_specs: Vec<String>,
+    opt_level: opt::Level,
+    target: codegen::Target,
+    /// Whether to validate the WASM binaries that are produced, which is the default in debug
+    /// builds of the compiler.
+    validate: bool,
+    /// Whether to report details of the binaries that are produced.
+    verbose: bool,
+    emit: Emit,
+    /// Whether to write the output to the standard output rather than next to each program.
+    stdout: bool,
+}
+
+/// Emit is the stage of the compilation whose output `build` writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Emit {
+    /// The tokens of the entry point of the program.
+    Tokens,
+    /// The AST of each module of the program, as parsed.
+    Ast,
+    /// The HIR of the program, once its modules are merged and optimized.
+    Hir,
+    /// The WASM binary, in the WebAssembly text format.
+    Wat,
+    #[default]
+    Wasm,
+}
+
+impl Emit {
+    /// extension returns the extension of the files this output is written to.
+    fn extension(self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::Hir => "hir",
+            Emit::Wat => "wat",
+            Emit::Wasm => "wasm",
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(Emit::Tokens),
+            "ast" => Ok(Emit::Ast),
+            "hir" => Ok(Emit::Hir),
+            "wat" => Ok(Emit::Wat),
+            "wasm" => Ok(Emit::Wasm),
+            _ => Err(format!(
+                "unknown output `{}`: expected one of tokens, ast, hir, wat or wasm",
+                s
+            )),
+        }
+    }
 }
 
 impl CommandOpts {
@@ -15,6 +73,12 @@ impl CommandOpts {
         let mut res = Self {
             subcommand: "".to_string(),
             path_specs: vec![],
+            opt_level: opt::Level::default(),
+            target: codegen::Target::default(),
+            validate: cfg!(debug_assertions),
+            verbose: false,
+            emit: Emit::default(),
+            stdout: false,
         };
 
         let mut pos_args = vec![];
@@ -57,8 +121,26 @@ impl CommandOpts {
         Ok(res)
     }
 
-    fn parse_flag(&mut self, _flag: String, _args: &mut env::Args) -> Result<(), String> {
-        todo!()
+    fn parse_flag(&mut self, flag: String, args: &mut env::Args) -> Result<(), String> {
+        match flag.as_str() {
+            "--target" => match args.next() {
+                Some(target) => self.target = target.parse()?,
+                None => return Err("missing target after '--target'".into()),
+            },
+            "--emit" => match args.next() {
+                Some(emit) => self.emit = emit.parse()?,
+                None => return Err("missing output after '--emit'".into()),
+            },
+            "--stdout" => self.stdout = true,
+            "--validate" => self.validate = true,
+            "--no-validate" => self.validate = false,
+            "-v" | "--verbose" => self.verbose = true,
+            _ => match flag.strip_prefix("-O") {
+                Some(level) => self.opt_level = level.parse()?,
+                None => return Err(format!("unknown flag '{}'", flag)),
+            },
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,76 @@
+//! The `tiger.exports` custom section describes the functions a binary exports, with their Tiger
+//! types, for hosts to call them correctly. It holds Tiger source declaring the structs of the
+//! program and each exported function as an `extern` function, which is how the host sees it,
+//! e.g.:
+//!
+//! ```text
+//! struct Point {
+//!     x int
+//!     y int
+//! }
+//!
+//! extern func distance(a Point, b Point) float
+//! ```
+//!
+//! `read` parses it back. The values of these types are passed as described in the
+//! documentation of `codegen`.
+
+use std::{borrow::Cow, fmt::Write};
+
+use wasm_encoder::CustomSection;
+use wasmparser::{Parser, Payload};
+
+use crate::{
+    hir::{self, FunctionKind},
+    lang, parser,
+};
+
+/// The name of the custom section.
+pub const SECTION: &str = "tiger.exports";
+
+/// section returns the custom section describing the exports of `module`.
+pub(super) fn section(module: &hir::Module) -> CustomSection<'static> {
+    let mut src = String::new();
+    for s in &module.structs {
+        _ = writeln!(src, "struct {} {{", s.name);
+        for (name, ttype) in &s.fields {
+            _ = writeln!(src, "\t{} {}", name, ttype);
+        }
+        src.push_str("}\n\n");
+    }
+    for func in &module.functions {
+        if func.kind != FunctionKind::Function || !(func.exported || func.name == "main") {
+            continue;
+        }
+        let params: Vec<String> = (func.params.iter())
+            .map(|p| format!("{} {}", func.locals[*p].name, func.locals[*p].ttype))
+            .collect();
+        _ = write!(src, "extern func {}({})", func.name, params.join(", "));
+        if func.return_type != lang::Type::Void {
+            _ = write!(src, " {}", func.return_type);
+        }
+        src.push('\n');
+    }
+    CustomSection {
+        name: Cow::Borrowed(SECTION),
+        data: Cow::Owned(src.into_bytes()),
+    }
+}
+
+/// read returns the declarations of the `tiger.exports` section of `wasm`, as a module whose
+/// imports are the exported functions, or `None` if there is no such section.
+pub fn read(wasm: &[u8]) -> Result<Option<lang::Module>, String> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.map_err(|e| e.to_string())? {
+            Payload::CustomSection(reader) if reader.name() == SECTION => {
+                let mut parser = parser::Parser::new("exports".into());
+                let res = parser
+                    .add_source(reader.data(), Some(SECTION.into()))
+                    .and_then(|_| parser.finalize());
+                return res.map(Some).map_err(|e| e.to_string());
+            }
+            _ => (),
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,1373 @@
+//! Code generation for function bodies.
+//!
+//! Lists are stored in linear memory, see `memory` for their layout. A list value is the address
+//! of its list block, which every copy of the value shares.
+//!
+//! Texts and lists are reference counted. Every expression produces a value that holds its own
+//! references to the texts and the lists in it: reading a variable or an element adds references,
+//! and values that are done with, such as the operands of an operator, the discarded result of a
+//! call, the old value of an assigned variable or element, the list an element is read from and
+//! the locals of a returning function, release theirs. The elements of a list hold references
+//! like any other value, which are released with the list. Functions own their arguments, except
+//! imported ones, which only borrow them for the duration of the call.
+
+use std::ops::Range;
+
+use wasm_encoder::{BlockType, Function, Instruction, MemArg, ValType};
+
+use crate::{
+    hir::{Callee, Expression, ExpressionKind, Literal, Local, LocalId, Place, Statement},
+    lang::Type,
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+use super::{
+    memory::{layout, LIST_DATA, LIST_LENGTH},
+    types::is_signed,
+    wasi::Intrinsic,
+    Context, Error,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Label is what a `br` to an enclosing block does.
+#[derive(Clone, Copy, PartialEq)]
+enum Label {
+    Break,
+    Continue,
+    Other,
+}
+
+/// Slots is where a value stored in locals or globals lives: its first local or global, and the
+/// number of WASM values it is made of.
+#[derive(Clone, Copy)]
+enum Slots {
+    Local(u32, usize),
+    Global(u32, usize),
+}
+
+impl Slots {
+    fn get(&self, i: usize) -> Instruction<'static> {
+        match *self {
+            Slots::Local(first, _) => Instruction::LocalGet(first + i as u32),
+            Slots::Global(first, _) => Instruction::GlobalGet(first + i as u32),
+        }
+    }
+
+    fn set(&self, i: usize) -> Instruction<'static> {
+        match *self {
+            Slots::Local(first, _) => Instruction::LocalSet(first + i as u32),
+            Slots::Global(first, _) => Instruction::GlobalSet(first + i as u32),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match *self {
+            Slots::Local(_, len) | Slots::Global(_, len) => len,
+        }
+    }
+
+    /// part returns the slots of `len` values starting at `offset`.
+    fn part(&self, offset: usize, len: usize) -> Slots {
+        match *self {
+            Slots::Local(first, _) => Slots::Local(first + offset as u32, len),
+            Slots::Global(first, _) => Slots::Global(first + offset as u32, len),
+        }
+    }
+}
+
+pub(super) struct FunctionCompiler<'a, 'c> {
+    ctx: &'c mut Context<'a>,
+    name: &'a str,
+    locals: &'a [Local],
+    return_type: &'a Type,
+    /// The mutable receiver, whose value the function returns after its result
+    receiver: Option<LocalId>,
+
+    /// The first WASM local of each local
+    slots: Vec<u32>,
+    params: u32,
+    /// The types of the WASM locals after the parameters
+    local_types: Vec<ValType>,
+    code: Vec<Instruction<'static>>,
+    labels: Vec<Label>,
+    /// The expressions deferred so far in each block being compiled, the innermost block last.
+    /// Which `defer` statements have run is known at every point of the code, so leaving a
+    /// block evaluates them without keeping track of them at run time.
+    defers: Vec<Vec<&'a Expression>>,
+    /// The number of blocks around the body of each loop being compiled
+    loops: Vec<usize>,
+}
+
+impl<'a, 'c> FunctionCompiler<'a, 'c> {
+    pub fn new(
+        ctx: &'c mut Context<'a>,
+        name: &'a str,
+        params: &[LocalId],
+        locals: &'a [Local],
+        return_type: &'a Type,
+        receiver: Option<LocalId>,
+    ) -> Self {
+        let mut res = Self {
+            ctx,
+            name,
+            locals,
+            return_type,
+            receiver,
+            slots: vec![0; locals.len()],
+            params: 0,
+            local_types: vec![],
+            code: vec![],
+            labels: vec![],
+            defers: vec![],
+            loops: vec![],
+        };
+
+        for id in params {
+            res.slots[*id] = res.params;
+            res.params += res.ctx.types.repr(&locals[*id].ttype).len() as u32;
+        }
+        for (id, local) in locals.iter().enumerate() {
+            if !params.contains(&id) {
+                let repr = res.ctx.types.repr(&local.ttype);
+                res.slots[id] = res.new_locals(&repr).first().copied().unwrap_or(0);
+            }
+        }
+        res
+    }
+
+    /// local_names returns the name of each WASM local holding a parameter or a local, which is
+    /// the name of the local, followed by the index of the value for those made of several.
+    pub fn local_names(&self) -> Vec<(u32, String)> {
+        let mut res = vec![];
+        for (id, local) in self.locals.iter().enumerate() {
+            let len = self.ctx.types.repr(&local.ttype).len() as u32;
+            for i in 0..len {
+                let name = match len {
+                    1 => local.name.clone(),
+                    _ => format!("{}.{}", local.name, i),
+                };
+                res.push((self.slots[id] + i, name));
+            }
+        }
+        res.sort_by_key(|(index, _)| *index);
+        res
+    }
+
+    pub fn compile(mut self, body: &'a [Statement]) -> Result<Function> {
+        self.block(body)?;
+        if !self.ctx.types.repr(self.return_type).is_empty() {
+            // The checker makes sure that functions returning a value don't end
+            self.code.push(Instruction::Unreachable);
+        } else {
+            self.push_receiver();
+            self.release_locals();
+        }
+        self.code.push(Instruction::End);
+
+        let mut res = Function::new_with_locals_types(self.local_types);
+        for i in &self.code {
+            res.instruction(i);
+        }
+        Ok(res)
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error {
+            message,
+            function: Some(self.name.to_string()),
+            offset: None,
+        }
+    }
+
+    fn new_locals(&mut self, types: &[ValType]) -> Vec<u32> {
+        let first = self.params + self.local_types.len() as u32;
+        self.local_types.extend(types);
+        (first..first + types.len() as u32).collect()
+    }
+
+    /// run_defers evaluates the expressions deferred so far in the blocks from the `first`
+    /// enclosing one in, the innermost block and the last deferred expression first.
+    fn run_defers(&mut self, first: usize) -> Result<()> {
+        let deferred: Vec<&'a Expression> = self.defers[first..]
+            .iter()
+            .rev()
+            .flat_map(|block| block.iter().rev().copied())
+            .collect();
+        for e in deferred {
+            self.expression(e)?;
+            self.drop_values(&e.ttype);
+        }
+        Ok(())
+    }
+
+    /// return_value returns from the function with the value on the stack, after running the
+    /// deferred expressions.
+    fn return_value(&mut self) -> Result<()> {
+        if self.defers.iter().any(|block| !block.is_empty()) {
+            let slots = self.stash(self.return_type);
+            self.run_defers(0)?;
+            for i in 0..slots.len() {
+                self.code.push(slots.get(i));
+            }
+        }
+        self.push_receiver();
+        self.release_locals();
+        self.code.push(Instruction::Return);
+        Ok(())
+    }
+
+    /// push_receiver pushes the value of the mutable receiver, if the function has one.
+    fn push_receiver(&mut self) {
+        let (locals, id) = match self.receiver {
+            Some(id) => (self.locals, id),
+            None => return,
+        };
+        let ttype = &locals[id].ttype;
+        let slots = Slots::Local(self.slots[id], self.ctx.types.repr(ttype).len());
+        for i in 0..slots.len() {
+            self.code.push(slots.get(i));
+        }
+        self.retain(slots, ttype, 0..slots.len());
+    }
+
+    /// block compiles a block of statements, followed by the expressions deferred in it for
+    /// when its end is reached.
+    fn block(&mut self, statements: &'a [Statement]) -> Result<()> {
+        self.defers.push(vec![]);
+        self.statements(statements)?;
+        self.run_defers(self.defers.len() - 1)?;
+        self.defers.pop();
+        Ok(())
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) -> Result<()> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &'a Statement) -> Result<()> {
+        match statement {
+            Statement::Expression(e) => {
+                self.expression(e)?;
+                self.drop_values(&e.ttype);
+            }
+            Statement::Assign(place, value) => {
+                self.expression(value)?;
+                let indexed = match place {
+                    Place::Index(list, index) => {
+                        let len = self.ctx.types.repr(&value.ttype).len();
+                        Some((&**list, &**index, 0..len))
+                    }
+                    Place::Field(object, index) => self.indexed(object).map(|(l, i, range)| {
+                        let (offset, repr) = self.ctx.types.field(&object.ttype, *index);
+                        let start = range.start + offset;
+                        (l, i, start..start + repr.len())
+                    }),
+                    _ => None,
+                };
+                match indexed {
+                    Some((list, index, range)) => {
+                        self.store_element(list, index, range, &value.ttype)?
+                    }
+                    None => self.store(place)?,
+                }
+            }
+            Statement::AssignTuple(places, value) => {
+                let types = match &value.ttype {
+                    Type::Tuple(types) => types,
+                    t => return Err(self.error(format!("cannot destructure a {}", t))),
+                };
+                self.expression(value)?;
+                for (place, ttype) in places.iter().zip(types).rev() {
+                    match place {
+                        Some(place) => self.store(place)?,
+                        None => self.drop_values(ttype),
+                    }
+                }
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value)?;
+                }
+                self.return_value()?;
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.expression(condition)?;
+                self.code.push(Instruction::If(BlockType::Empty));
+                self.labels.push(Label::Other);
+                self.block(then_body)?;
+                if !else_body.is_empty() {
+                    self.code.push(Instruction::Else);
+                    self.block(else_body)?;
+                }
+                self.code.push(Instruction::End);
+                self.labels.pop();
+            }
+            Statement::While {
+                condition,
+                body,
+                update,
+            } => {
+                self.code.push(Instruction::Block(BlockType::Empty));
+                self.labels.push(Label::Break);
+                self.code.push(Instruction::Loop(BlockType::Empty));
+                self.labels.push(Label::Other);
+
+                self.expression(condition)?;
+                self.code.push(Instruction::I32Eqz);
+                self.code.push(Instruction::BrIf(1));
+                self.code.push(Instruction::Block(BlockType::Empty));
+                self.labels.push(Label::Continue);
+                self.loops.push(self.defers.len());
+                self.block(body)?;
+                self.loops.pop();
+                self.code.push(Instruction::End);
+                self.labels.pop();
+                self.block(update)?;
+                self.code.push(Instruction::Br(0));
+
+                self.code.push(Instruction::End);
+                self.labels.pop();
+                self.code.push(Instruction::End);
+                self.labels.pop();
+            }
+            Statement::Break => self.branch(Label::Break)?,
+            Statement::Continue => self.branch(Label::Continue)?,
+            Statement::Defer(e) => {
+                if let Some(block) = self.defers.last_mut() {
+                    block.push(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// branch jumps to the innermost label of kind `label`, after running the expressions
+    /// deferred in the body of the loop it leaves.
+    fn branch(&mut self, label: Label) -> Result<()> {
+        if let Some(&first) = self.loops.last() {
+            self.run_defers(first)?;
+        }
+        match self.labels.iter().rev().position(|l| *l == label) {
+            Some(depth) => {
+                self.code.push(Instruction::Br(depth as u32));
+                Ok(())
+            }
+            None => Err(self.error("`break` or `continue` outside of a loop".into())),
+        }
+    }
+
+    /// drop_values drops the value of type `ttype` on the stack, releasing its references.
+    fn drop_values(&mut self, ttype: &Type) {
+        if self.ctx.types.references(ttype).is_empty() {
+            for _ in self.ctx.types.repr(ttype) {
+                self.code.push(Instruction::Drop);
+            }
+            return;
+        }
+        let slots = self.stash(ttype);
+        self.release(slots, ttype, 0..slots.len());
+    }
+
+    /// retain adds a reference to the texts and the lists of the value of type `ttype` in `slots`
+    /// whose position in the value is in `range`.
+    fn retain(&mut self, slots: Slots, ttype: &Type, range: Range<usize>) {
+        self.count_references(slots, ttype, range, true);
+    }
+
+    /// release removes a reference from the texts and the lists of the value of type `ttype` in
+    /// `slots` whose position in the value is in `range`.
+    fn release(&mut self, slots: Slots, ttype: &Type, range: Range<usize>) {
+        self.count_references(slots, ttype, range, false);
+    }
+
+    fn count_references(&mut self, slots: Slots, ttype: &Type, range: Range<usize>, retain: bool) {
+        for (i, reference) in self.ctx.types.references(ttype) {
+            if range.contains(&i) {
+                let f = match retain {
+                    true => self.ctx.runtime.retain,
+                    false => self.ctx.release_function(&reference),
+                };
+                self.code.push(slots.get(i));
+                self.code.push(Instruction::Call(f));
+            }
+        }
+    }
+
+    fn release_locals(&mut self) {
+        let locals = self.locals;
+        for (id, local) in locals.iter().enumerate() {
+            let len = self.ctx.types.repr(&local.ttype).len();
+            self.release(Slots::Local(self.slots[id], len), &local.ttype, 0..len);
+        }
+    }
+
+    /// slots returns where the value of `expr` is stored, if it is a local, a global, or a field
+    /// of one.
+    fn slots(&self, expr: &Expression) -> Option<Slots> {
+        let types = &self.ctx.types;
+        match &expr.kind {
+            ExpressionKind::Local(id) => Some(Slots::Local(
+                self.slots[*id],
+                types.repr(&self.locals[*id].ttype).len(),
+            )),
+            ExpressionKind::Global(id) => Some(Slots::Global(
+                self.ctx.globals[*id],
+                types.repr(&self.ctx.module.globals[*id].ttype).len(),
+            )),
+            ExpressionKind::Field(object, index) => {
+                let (offset, repr) = types.field(&object.ttype, *index);
+                Some(self.slots(object)?.part(offset, repr.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// store stores the value on the stack in `place`, releasing the value it replaces. Elements
+    /// of lists and their fields are stored by `store_element` instead.
+    fn store(&mut self, place: &Place) -> Result<()> {
+        let types = &self.ctx.types;
+        // The variable the place is part of, and the position of the place in it
+        let (whole, ttype, offset, len) = match place {
+            Place::Local(id) => {
+                let ttype = &self.locals[*id].ttype;
+                let len = types.repr(ttype).len();
+                (Slots::Local(self.slots[*id], len), ttype, 0, len)
+            }
+            Place::Global(id) => {
+                let ttype = &self.ctx.module.globals[*id].ttype;
+                let len = types.repr(ttype).len();
+                (Slots::Global(self.ctx.globals[*id], len), ttype, 0, len)
+            }
+            Place::Field(object, index) => {
+                let (offset, repr) = types.field(&object.ttype, *index);
+                match self.slots(object) {
+                    Some(slots) => (slots, &object.ttype, offset, repr.len()),
+                    None => return Err(self.error("assignment to a temporary value".into())),
+                }
+            }
+            Place::Index(_, _) => return Err(self.error("element stored as a variable".into())),
+        };
+        self.release(whole, ttype, offset..offset + len);
+        let slots = whole.part(offset, len);
+        for i in (0..slots.len()).rev() {
+            self.code.push(slots.set(i));
+        }
+        Ok(())
+    }
+
+    /// stash moves the value of type `ttype` on the stack to new locals.
+    fn stash(&mut self, ttype: &Type) -> Slots {
+        let repr = self.ctx.types.repr(ttype);
+        let locals = self.new_locals(&repr);
+        let slots = Slots::Local(locals.first().copied().unwrap_or(0), locals.len());
+        for i in (0..slots.len()).rev() {
+            self.code.push(slots.set(i));
+        }
+        slots
+    }
+
+    /// project pushes `len` of the WASM values making up the value of `expr`, starting at
+    /// `offset`. The texts of the rest of the value are released if it is a temporary value.
+    fn project(&mut self, expr: &'a Expression, offset: usize, len: usize) -> Result<()> {
+        let range = offset..offset + len;
+        match self.slots(expr) {
+            Some(slots) => {
+                for i in range.clone() {
+                    self.code.push(slots.get(i));
+                }
+                self.retain(slots, &expr.ttype, range);
+            }
+            None => {
+                self.expression(expr)?;
+                let slots = self.stash(&expr.ttype);
+                for i in range.clone() {
+                    self.code.push(slots.get(i));
+                }
+                self.release(slots, &expr.ttype, 0..offset);
+                self.release(slots, &expr.ttype, range.end..slots.len());
+            }
+        }
+        Ok(())
+    }
+
+    /// intrinsic calls `intrinsic`, a function of the `wasi` target returning a `ttype`. Like
+    /// imported functions, intrinsics borrow their arguments.
+    fn intrinsic(
+        &mut self,
+        intrinsic: Intrinsic,
+        args: &'a [Expression],
+        ttype: &Type,
+    ) -> Result<()> {
+        let wasi = self.ctx.wasi.as_ref().unwrap();
+        let (write, proc_exit) = (wasi.write, wasi.proc_exit);
+        let (arg_count, arg, env) = (wasi.arg_count, wasi.arg, wasi.env);
+        let expected = match intrinsic {
+            Intrinsic::Write | Intrinsic::Exit => Type::Void,
+            Intrinsic::ArgCount => Type::Int,
+            Intrinsic::Arg => Type::Text,
+            Intrinsic::Env => Type::Optional(Box::new(Type::Text)),
+        };
+        let arity = match intrinsic {
+            Intrinsic::ArgCount => 0,
+            _ => 1,
+        };
+        if *ttype != expected || args.len() != arity {
+            let message = format!("{:?} is called with the wrong signature", intrinsic);
+            return Err(self.error(message));
+        }
+
+        match intrinsic {
+            Intrinsic::Write => {
+                self.expression(&args[0])?;
+                let slots = self.stash(&Type::Text);
+                self.code
+                    .extend([slots.get(0), slots.get(1), Instruction::Call(write)]);
+                self.release(slots, &Type::Text, 0..2);
+            }
+            Intrinsic::Exit => {
+                self.expression(&args[0])?;
+                if self.ctx.types.repr(&args[0].ttype) == [ValType::I64] {
+                    self.code.push(Instruction::I32WrapI64);
+                }
+                self.code.push(Instruction::Call(proc_exit));
+            }
+            Intrinsic::ArgCount => self.code.push(Instruction::Call(arg_count)),
+            Intrinsic::Arg => {
+                self.expression(&args[0])?;
+                self.code.push(Instruction::Call(arg));
+            }
+            Intrinsic::Env => {
+                self.expression(&args[0])?;
+                let slots = self.stash(&Type::Text);
+                self.code
+                    .extend([slots.get(0), slots.get(1), Instruction::Call(env)]);
+                self.release(slots, &Type::Text, 0..2);
+            }
+        }
+        Ok(())
+    }
+
+    fn zero(&mut self, ttype: &Type) {
+        for t in self.ctx.types.repr(ttype) {
+            self.code.push(match t {
+                ValType::I64 => Instruction::I64Const(0),
+                ValType::F32 => Instruction::F32Const(0.0),
+                ValType::F64 => Instruction::F64Const(0.0),
+                _ => Instruction::I32Const(0),
+            });
+        }
+    }
+
+    fn block_type(&mut self, ttype: &Type) -> BlockType {
+        let repr = self.ctx.types.repr(ttype);
+        match repr.as_slice() {
+            [] => BlockType::Empty,
+            [t] => BlockType::Result(*t),
+            _ => BlockType::FunctionType(self.ctx.signatures.get(vec![], repr)),
+        }
+    }
+
+    fn expression(&mut self, expr: &'a Expression) -> Result<()> {
+        match &expr.kind {
+            ExpressionKind::Literal(literal) => self.literal(literal, &expr.ttype)?,
+            ExpressionKind::Local(_) | ExpressionKind::Global(_) => {
+                let len = self.ctx.types.repr(&expr.ttype).len();
+                self.project(expr, 0, len)?;
+            }
+            ExpressionKind::Call(callee, args) => {
+                if let (Callee::Import(id), Some(_)) = (callee, &self.ctx.wasi) {
+                    let path = &self.ctx.module.imports[*id].path;
+                    if let Some(intrinsic) = Intrinsic::from_path(path) {
+                        return self.intrinsic(intrinsic, args, &expr.ttype);
+                    }
+                }
+                // Imported functions only borrow their arguments, which are released after the
+                // call
+                let mut borrowed = vec![];
+                for arg in args {
+                    self.expression(arg)?;
+                    let imported = matches!(callee, Callee::Import(_));
+                    if imported && !self.ctx.types.references(&arg.ttype).is_empty() {
+                        let slots = self.stash(&arg.ttype);
+                        for i in 0..slots.len() {
+                            self.code.push(slots.get(i));
+                        }
+                        borrowed.push((slots, &arg.ttype));
+                    }
+                }
+                let index = match callee {
+                    Callee::Function(id) => self.ctx.first_function + *id as u32,
+                    Callee::Import(id) => {
+                        let params: Vec<Type> = args.iter().map(|a| a.ttype.clone()).collect();
+                        self.ctx.import_index(*id, &params, &expr.ttype)
+                    }
+                };
+                self.code.push(Instruction::Call(index));
+                for (slots, ttype) in borrowed {
+                    self.release(slots, ttype, 0..slots.len());
+                }
+                // The receiver is returned last, and goes back where it came from
+                if let Callee::Function(id) = callee {
+                    if self.ctx.module.functions[*id].mutable_receiver {
+                        let receiver = &args[0];
+                        match self.indexed(receiver) {
+                            Some((list, index, range)) => {
+                                self.store_element(list, index, range, &receiver.ttype)?
+                            }
+                            None => match Place::of(receiver) {
+                                Some(place) => self.store(&place)?,
+                                None => return Err(self.error("receiver is a temporary".into())),
+                            },
+                        }
+                    }
+                }
+            }
+            ExpressionKind::Field(object, index) => {
+                let (offset, repr) = self.ctx.types.field(&object.ttype, *index);
+                self.project(object, offset, repr.len())?;
+            }
+            ExpressionKind::Struct(values) | ExpressionKind::Tuple(values) => {
+                for value in values {
+                    self.expression(value)?;
+                }
+            }
+            ExpressionKind::List(elements) => self.list(&expr.ttype, elements)?,
+            ExpressionKind::Index(list, index) => {
+                let (slots, address) = self.element(list, index)?;
+                self.load(&expr.ttype, address);
+                self.release(slots, &list.ttype, 0..1);
+            }
+            ExpressionKind::Append(list, value) => self.append(list, value)?,
+            ExpressionKind::Length(list) => {
+                self.expression(list)?;
+                let slots = self.stash(&list.ttype);
+                self.code.extend([
+                    slots.get(0),
+                    Instruction::I32Load(memarg(ValType::I32, LIST_LENGTH)),
+                    Instruction::I64ExtendI32U,
+                ]);
+                self.release(slots, &list.ttype, 0..1);
+            }
+            ExpressionKind::Chars(text) => {
+                self.expression(text)?;
+                let slots = self.stash(&text.ttype);
+                let text_chars = self.ctx.runtime.text_chars;
+                self.code
+                    .extend([slots.get(0), slots.get(1), Instruction::Call(text_chars)]);
+                self.release(slots, &text.ttype, 0..2);
+            }
+            ExpressionKind::Unary(op, operand) => self.unary(op, operand)?,
+            ExpressionKind::Binary(op, lhs, rhs) => self.binary(op, lhs, rhs)?,
+            ExpressionKind::Coalesce(value, default) => {
+                let (slots, temporary) = match self.slots(value) {
+                    Some(slots) => (slots, false),
+                    None => {
+                        self.expression(value)?;
+                        (self.stash(&value.ttype), true)
+                    }
+                };
+                let block = self.block_type(&expr.ttype);
+                self.code.push(slots.get(0));
+                self.code.push(Instruction::If(block));
+                self.labels.push(Label::Other);
+                // The result is optional if the default is
+                let first = match expr.ttype {
+                    Type::Optional(_) => 0,
+                    _ => 1,
+                };
+                for i in first..slots.len() {
+                    self.code.push(slots.get(i));
+                }
+                if !temporary {
+                    self.retain(slots, &value.ttype, first..slots.len());
+                }
+                // An empty optional holds no texts
+                self.code.push(Instruction::Else);
+                self.expression(default)?;
+                self.code.push(Instruction::End);
+                self.labels.pop();
+            }
+            ExpressionKind::IsSome(operand) => self.project(operand, 0, 1)?,
+            ExpressionKind::Wrap(value) => {
+                self.code.push(Instruction::I32Const(1));
+                self.expression(value)?;
+            }
+            ExpressionKind::Unwrap(operand) => {
+                let len = self.ctx.types.repr(&expr.ttype).len();
+                self.project(operand, 1, len)?;
+            }
+            ExpressionKind::Convert(operand) => {
+                self.expression(operand)?;
+                self.convert(&operand.ttype, &expr.ttype)?;
+            }
+            ExpressionKind::Ok(value) | ExpressionKind::Err(value) => {
+                let (ok, err) = match &expr.ttype {
+                    Type::Result(ok, err) => (ok, err),
+                    t => return Err(self.error(format!("`ok` or `err` of type {}", t))),
+                };
+                match expr.kind {
+                    ExpressionKind::Ok(_) => {
+                        self.code.push(Instruction::I32Const(0));
+                        self.expression(value)?;
+                        self.zero(err);
+                    }
+                    _ => {
+                        self.code.push(Instruction::I32Const(1));
+                        self.zero(ok);
+                        self.expression(value)?;
+                    }
+                }
+            }
+            ExpressionKind::Propagate(operand) => self.propagate(operand)?,
+        }
+        Ok(())
+    }
+
+    /// list builds a list of type `ttype` holding `elements`, with room for no more.
+    fn list(&mut self, ttype: &Type, elements: &'a [Expression]) -> Result<()> {
+        let element = self.element_type(ttype)?;
+        let size = layout(&self.ctx.types, element).size;
+        let locals = self.new_locals(&[ValType::I32, ValType::I32]);
+        let (list, data) = (locals[0], locals[1]);
+        self.code.extend([
+            Instruction::I32Const(elements.len() as i32),
+            Instruction::I32Const(size as i32),
+            Instruction::Call(self.ctx.runtime.list_new),
+            Instruction::LocalTee(list),
+            Instruction::I32Load(memarg(ValType::I32, LIST_DATA)),
+            Instruction::LocalSet(data),
+        ]);
+        for (i, value) in elements.iter().enumerate() {
+            self.expression(value)?;
+            let slots = self.stash(element);
+            self.store_at(slots, element, data, i as u32 * size);
+        }
+        self.code.extend([
+            Instruction::LocalGet(list),
+            Instruction::I32Const(elements.len() as i32),
+            Instruction::I32Store(memarg(ValType::I32, LIST_LENGTH)),
+            Instruction::LocalGet(list),
+        ]);
+        Ok(())
+    }
+
+    /// append adds `value` at the end of `list`, making room for it first.
+    fn append(&mut self, list: &'a Expression, value: &'a Expression) -> Result<()> {
+        let element = self.element_type(&list.ttype)?;
+        let size = layout(&self.ctx.types, element).size;
+        self.expression(list)?;
+        let list_slots = self.stash(&list.ttype);
+        let address = self.new_locals(&[ValType::I32])[0];
+        self.expression(value)?;
+        let slots = self.stash(element);
+
+        let length = memarg(ValType::I32, LIST_LENGTH);
+        self.code.extend([
+            list_slots.get(0),
+            Instruction::I32Const(1),
+            Instruction::I32Const(size as i32),
+            Instruction::Call(self.ctx.runtime.list_reserve),
+            list_slots.get(0),
+            Instruction::I32Load(memarg(ValType::I32, LIST_DATA)),
+            list_slots.get(0),
+            Instruction::I32Load(length),
+            Instruction::I32Const(size as i32),
+            Instruction::I32Mul,
+            Instruction::I32Add,
+            Instruction::LocalSet(address),
+        ]);
+        self.store_at(slots, element, address, 0);
+        self.code.extend([
+            list_slots.get(0),
+            list_slots.get(0),
+            Instruction::I32Load(length),
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::I32Store(length),
+        ]);
+        self.release(list_slots, &list.ttype, 0..1);
+        Ok(())
+    }
+
+    /// element evaluates `list` into new slots, which hold a reference to the list for the caller
+    /// to release once done with the element, and returns them with a new local holding the
+    /// address of the element at `index`, trapping if the list has no such element.
+    fn element(&mut self, list: &'a Expression, index: &'a Expression) -> Result<(Slots, u32)> {
+        let element = self.element_type(&list.ttype)?;
+        let size = layout(&self.ctx.types, element).size;
+        self.expression(list)?;
+        let slots = self.stash(&list.ttype);
+        let locals = self.new_locals(&[ValType::I64, ValType::I32]);
+        let (index_local, address) = (locals[0], locals[1]);
+        self.expression(index)?;
+        // Negative positions are larger than any length once taken as unsigned
+        self.code.extend([
+            Instruction::LocalTee(index_local),
+            slots.get(0),
+            Instruction::I32Load(memarg(ValType::I32, LIST_LENGTH)),
+            Instruction::I64ExtendI32U,
+            Instruction::I64GeU,
+            Instruction::If(BlockType::Empty),
+            Instruction::Unreachable,
+            Instruction::End,
+            slots.get(0),
+            Instruction::I32Load(memarg(ValType::I32, LIST_DATA)),
+            Instruction::LocalGet(index_local),
+            Instruction::I32WrapI64,
+            Instruction::I32Const(size as i32),
+            Instruction::I32Mul,
+            Instruction::I32Add,
+            Instruction::LocalSet(address),
+        ]);
+        Ok((slots, address))
+    }
+
+    /// indexed returns the list and the position of the element that `expr` is, or is a field
+    /// of, and the positions of the values of `expr` in the representation of the element.
+    fn indexed(
+        &self,
+        expr: &'a Expression,
+    ) -> Option<(&'a Expression, &'a Expression, Range<usize>)> {
+        match &expr.kind {
+            ExpressionKind::Index(list, index) => {
+                let len = self.ctx.types.repr(&expr.ttype).len();
+                Some((list, index, 0..len))
+            }
+            ExpressionKind::Field(object, index) => {
+                let (list, position, range) = self.indexed(object)?;
+                let (offset, repr) = self.ctx.types.field(&object.ttype, *index);
+                let start = range.start + offset;
+                Some((list, position, start..start + repr.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// store_element stores the value of type `ttype` on the stack in the part of the element
+    /// of `list` at `index` whose values are at `range`, releasing the value it replaces.
+    fn store_element(
+        &mut self,
+        list: &'a Expression,
+        index: &'a Expression,
+        range: Range<usize>,
+        ttype: &Type,
+    ) -> Result<()> {
+        let element = self.element_type(&list.ttype)?;
+        let slots = self.stash(ttype);
+        let (list_slots, address) = self.element(list, index)?;
+        let values = layout(&self.ctx.types, element).values;
+        for (i, reference) in self.ctx.types.references(element) {
+            if range.contains(&i) {
+                self.code.extend([
+                    Instruction::LocalGet(address),
+                    Instruction::I32Load(memarg(ValType::I32, values[i].0)),
+                    Instruction::Call(self.ctx.release_function(&reference)),
+                ]);
+            }
+        }
+        for (i, (offset, t)) in values[range].iter().copied().enumerate() {
+            self.code.push(Instruction::LocalGet(address));
+            self.code.push(slots.get(i));
+            self.code.push(store_instruction(t, offset));
+        }
+        self.release(list_slots, &list.ttype, 0..1);
+        Ok(())
+    }
+
+    /// load pushes the value of type `ttype` stored at the address in the local `address`,
+    /// adding references to its texts and lists.
+    fn load(&mut self, ttype: &Type, address: u32) {
+        for (offset, t) in layout(&self.ctx.types, ttype).values {
+            self.code.push(Instruction::LocalGet(address));
+            self.code.push(load_instruction(t, offset));
+        }
+        if !self.ctx.types.references(ttype).is_empty() {
+            let slots = self.stash(ttype);
+            for i in 0..slots.len() {
+                self.code.push(slots.get(i));
+            }
+            self.retain(slots, ttype, 0..slots.len());
+        }
+    }
+
+    /// store_at stores the value of type `ttype` in `slots` at `offset` bytes past the address in
+    /// the local `address`. The stored value takes over the references of the one in `slots`.
+    fn store_at(&mut self, slots: Slots, ttype: &Type, address: u32, offset: u32) {
+        for (i, (field, t)) in layout(&self.ctx.types, ttype)
+            .values
+            .into_iter()
+            .enumerate()
+        {
+            self.code.push(Instruction::LocalGet(address));
+            self.code.push(slots.get(i));
+            self.code.push(store_instruction(t, offset + field));
+        }
+    }
+
+    /// element_type returns the type of the elements of the list type `ttype`.
+    fn element_type<'t>(&self, ttype: &'t Type) -> Result<&'t Type> {
+        match ttype {
+            Type::List(element) => Ok(element),
+            t => Err(self.error(format!("{} is not a list", t))),
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal, ttype: &Type) -> Result<()> {
+        let repr = self.ctx.types.repr(ttype);
+        let instruction = match (literal, repr.as_slice()) {
+            (Literal::Integer(i), [ValType::I32]) => Instruction::I32Const(*i as i32),
+            (Literal::Integer(i), [ValType::I64]) => Instruction::I64Const(*i as i64),
+            (Literal::Integer(i), [ValType::F32]) => Instruction::F32Const(*i as f32),
+            (Literal::Integer(i), [ValType::F64]) => Instruction::F64Const(*i as f64),
+            (Literal::Float(f), [ValType::F32]) => Instruction::F32Const(*f as f32),
+            (Literal::Float(f), [ValType::F64]) => Instruction::F64Const(*f),
+            (Literal::Char(c), _) => Instruction::I32Const(*c as i32),
+            (Literal::Bool(b), _) => Instruction::I32Const(*b as i32),
+            (Literal::String(s), _) => {
+                let address = self.ctx.data.add(s.as_bytes());
+                self.code.push(Instruction::I32Const(address as i32));
+                Instruction::I32Const(s.len() as i32)
+            }
+            (Literal::Nil, _) => {
+                self.zero(ttype);
+                return Ok(());
+            }
+            (l, _) => return Err(self.error(format!("literal {:?} of type {}", l, ttype))),
+        };
+        self.code.push(instruction);
+        Ok(())
+    }
+
+    fn unary(&mut self, op: &UnaryOperator, operand: &'a Expression) -> Result<()> {
+        let repr = self.ctx.types.repr(&operand.ttype);
+        match (op, repr.as_slice()) {
+            (UnaryOperator::Plus, _) => self.expression(operand)?,
+            (UnaryOperator::Not, _) => {
+                self.expression(operand)?;
+                self.code.push(Instruction::I32Eqz);
+            }
+            (UnaryOperator::Minus, [ValType::I32]) => {
+                self.code.push(Instruction::I32Const(0));
+                self.expression(operand)?;
+                self.code.push(Instruction::I32Sub);
+                self.normalize(&operand.ttype);
+            }
+            (UnaryOperator::Minus, [ValType::I64]) => {
+                self.code.push(Instruction::I64Const(0));
+                self.expression(operand)?;
+                self.code.push(Instruction::I64Sub);
+            }
+            (UnaryOperator::Minus, [ValType::F32]) => {
+                self.expression(operand)?;
+                self.code.push(Instruction::F32Neg);
+            }
+            (UnaryOperator::Minus, [ValType::F64]) => {
+                self.expression(operand)?;
+                self.code.push(Instruction::F64Neg);
+            }
+            _ => return Err(self.error(format!("cannot negate a {}", operand.ttype))),
+        }
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        op: &BinaryOperator,
+        lhs: &'a Expression,
+        rhs: &'a Expression,
+    ) -> Result<()> {
+        let ttype = &lhs.ttype;
+        match op {
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                self.expression(lhs)?;
+                self.code
+                    .push(Instruction::If(BlockType::Result(ValType::I32)));
+                self.labels.push(Label::Other);
+                if *op == BinaryOperator::LogicalAnd {
+                    self.expression(rhs)?;
+                    self.code.push(Instruction::Else);
+                    self.code.push(Instruction::I32Const(0));
+                } else {
+                    self.code.push(Instruction::I32Const(1));
+                    self.code.push(Instruction::Else);
+                    self.expression(rhs)?;
+                }
+                self.code.push(Instruction::End);
+                self.labels.pop();
+                return Ok(());
+            }
+            _ if *ttype == Type::Text => {
+                if *op != BinaryOperator::Add && !is_comparison(op) {
+                    return Err(self.error(format!("operator {:?} on text", op)));
+                }
+                self.expression(lhs)?;
+                let a = self.stash(ttype);
+                self.expression(rhs)?;
+                let b = self.stash(ttype);
+                for i in [a.get(0), a.get(1), b.get(0), b.get(1)] {
+                    self.code.push(i);
+                }
+                if *op == BinaryOperator::Add {
+                    self.code
+                        .push(Instruction::Call(self.ctx.runtime.text_concat));
+                } else {
+                    // Texts compare like the result of comparing them does with 0
+                    self.code
+                        .push(Instruction::Call(self.ctx.runtime.text_compare));
+                    self.code.push(Instruction::I32Const(0));
+                    self.code.push(arithmetic(op, &Type::Int32).unwrap());
+                }
+                self.release(a, ttype, 0..2);
+                self.release(b, ttype, 0..2);
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        let instruction = match arithmetic(op, ttype) {
+            Some(i) => i,
+            None => {
+                return Err(self.error(format!("operator {:?} on values of type {}", op, ttype)))
+            }
+        };
+        self.expression(lhs)?;
+        self.expression(rhs)?;
+        let narrow = ttype.is_integer() && ttype.bits().unwrap_or(64) < 32;
+        if narrow && matches!(op, BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight) {
+            // Shift counts are taken modulo the width of the type
+            self.code
+                .push(Instruction::I32Const(ttype.bits().unwrap() as i32 - 1));
+            self.code.push(Instruction::I32And);
+        }
+        self.code.push(instruction);
+        if matches!(
+            op,
+            BinaryOperator::Add
+                | BinaryOperator::Subtract
+                | BinaryOperator::Multiply
+                | BinaryOperator::Divide
+                | BinaryOperator::ShiftLeft
+        ) {
+            self.normalize(ttype);
+        }
+        Ok(())
+    }
+
+    /// normalize sign or zero extends the integer of type `ttype` on the stack from the width of
+    /// the type to 32 bits.
+    fn normalize(&mut self, ttype: &Type) {
+        match ttype {
+            Type::Int8 => self.code.push(Instruction::I32Extend8S),
+            Type::Int16 => self.code.push(Instruction::I32Extend16S),
+            Type::UInt8 | Type::UInt16 => {
+                self.code.push(Instruction::I32Const(match ttype {
+                    Type::UInt8 => 0xff,
+                    _ => 0xffff,
+                }));
+                self.code.push(Instruction::I32And);
+            }
+            _ => (),
+        }
+    }
+
+    /// convert converts the value of type `from` on the stack to type `to`, the same way as
+    /// `lang::Conversion`: integers wrap, floats are rounded towards zero and saturate when
+    /// converted to integers, and values converted to text become new texts.
+    fn convert(&mut self, from: &Type, to: &Type) -> Result<()> {
+        if *to == Type::Text && from != to {
+            return self.convert_to_text(from);
+        }
+        let (a, b) = (self.ctx.types.repr(from), self.ctx.types.repr(to));
+        let (a, b) = match (a.as_slice(), b.as_slice()) {
+            ([a], [b]) if from.is_numeric() && to.is_numeric() => (*a, *b),
+            _ if from == to => return Ok(()),
+            _ => return Err(self.error(format!("conversion from {} to {}", from, to))),
+        };
+        let signed = is_signed(from);
+        let instruction = match (a, b) {
+            (ValType::I32, ValType::I32) => None,
+            (ValType::I64, ValType::I64) => None,
+            (ValType::F32, ValType::F32) | (ValType::F64, ValType::F64) => None,
+            (ValType::I32, ValType::I64) if signed => Some(Instruction::I64ExtendI32S),
+            (ValType::I32, ValType::I64) => Some(Instruction::I64ExtendI32U),
+            (ValType::I64, ValType::I32) => Some(Instruction::I32WrapI64),
+            (ValType::I32, ValType::F32) if signed => Some(Instruction::F32ConvertI32S),
+            (ValType::I32, ValType::F32) => Some(Instruction::F32ConvertI32U),
+            (ValType::I32, ValType::F64) if signed => Some(Instruction::F64ConvertI32S),
+            (ValType::I32, ValType::F64) => Some(Instruction::F64ConvertI32U),
+            (ValType::I64, ValType::F32) if signed => Some(Instruction::F32ConvertI64S),
+            (ValType::I64, ValType::F32) => Some(Instruction::F32ConvertI64U),
+            (ValType::I64, ValType::F64) if signed => Some(Instruction::F64ConvertI64S),
+            (ValType::I64, ValType::F64) => Some(Instruction::F64ConvertI64U),
+            (ValType::F32, ValType::F64) => Some(Instruction::F64PromoteF32),
+            (ValType::F64, ValType::F32) => Some(Instruction::F32DemoteF64),
+            (ValType::F32 | ValType::F64, ValType::I32 | ValType::I64) => {
+                return self.truncate(a, to)
+            }
+            _ => return Err(self.error(format!("conversion from {} to {}", from, to))),
+        };
+        if let Some(i) = instruction {
+            self.code.push(i);
+        }
+        if to.is_integer() {
+            self.normalize(to);
+        }
+        Ok(())
+    }
+
+    /// convert_to_text converts the value of type `from` on the stack to a new text.
+    fn convert_to_text(&mut self, from: &Type) -> Result<()> {
+        let runtime = &self.ctx.runtime;
+        let function = match from {
+            Type::Bool => {
+                let value = self.new_locals(&[ValType::I32])[0];
+                let yes = self.ctx.data.add_runtime_text("true");
+                let no = self.ctx.data.add_runtime_text("false");
+                self.code.extend([
+                    Instruction::LocalSet(value),
+                    Instruction::I32Const(yes as i32),
+                    Instruction::I32Const(no as i32),
+                    Instruction::LocalGet(value),
+                    Instruction::Select,
+                    Instruction::I32Const(5),
+                    Instruction::LocalGet(value),
+                    Instruction::I32Sub,
+                ]);
+                return Ok(());
+            }
+            Type::Character => runtime.char_text,
+            Type::Float32 => {
+                self.code.push(Instruction::F64PromoteF32);
+                runtime.float_text
+            }
+            Type::Float | Type::Float64 => runtime.float_text,
+            t if t.is_integer() => {
+                let signed = is_signed(t);
+                if t.bits() != Some(64) {
+                    self.code.push(match signed {
+                        true => Instruction::I64ExtendI32S,
+                        false => Instruction::I64ExtendI32U,
+                    });
+                }
+                self.code.push(Instruction::I32Const(signed as i32));
+                runtime.int_text
+            }
+            t => return Err(self.error(format!("conversion from {} to text", t))),
+        };
+        self.code.push(Instruction::Call(function));
+        Ok(())
+    }
+
+    /// truncate converts the float of WASM type `from` on the stack to the integer type `to`.
+    fn truncate(&mut self, from: ValType, to: &Type) -> Result<()> {
+        let signed = is_signed(to);
+        let f32 = from == ValType::F32;
+        let instruction = match (to.bits(), signed, f32) {
+            (Some(64), true, true) => Instruction::I64TruncSatF32S,
+            (Some(64), true, false) => Instruction::I64TruncSatF64S,
+            (Some(64), false, true) => Instruction::I64TruncSatF32U,
+            (Some(64), false, false) => Instruction::I64TruncSatF64U,
+            (Some(32), false, true) => Instruction::I32TruncSatF32U,
+            (Some(32), false, false) => Instruction::I32TruncSatF64U,
+            (_, _, true) => Instruction::I32TruncSatF32S,
+            (_, _, false) => Instruction::I32TruncSatF64S,
+        };
+        self.code.push(instruction);
+
+        // Narrow integers saturate to their own range
+        if let (Some(bits), Some((min, max))) = (to.bits(), to.range()) {
+            if bits < 32 {
+                let value = self.new_locals(&[ValType::I32])[0];
+                for (bound, cmp) in [(max, Instruction::I32LtS), (min, Instruction::I32GtS)] {
+                    self.code.extend([
+                        Instruction::LocalTee(value),
+                        Instruction::I32Const(bound as i32),
+                        Instruction::LocalGet(value),
+                        Instruction::I32Const(bound as i32),
+                        cmp,
+                        Instruction::Select,
+                    ]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// propagate pushes the value of the result `operand`, or returns its error.
+    fn propagate(&mut self, operand: &'a Expression) -> Result<()> {
+        let (value, error) = match &operand.ttype {
+            Type::Result(value, error) => (value, error),
+            t => return Err(self.error(format!("`?` on a {}", t))),
+        };
+        let returned = match self.return_type {
+            Type::Result(value, e) if self.ctx.types.repr(e) == self.ctx.types.repr(error) => value,
+            t => return Err(self.error(format!("`?` in a function returning {}", t))),
+        };
+
+        self.expression(operand)?;
+        let slots = self.stash(&operand.ttype);
+        let value_len = self.ctx.types.repr(value).len();
+        self.code.push(slots.get(0));
+        self.code.push(Instruction::If(BlockType::Empty));
+        self.labels.push(Label::Other);
+        self.code.push(Instruction::I32Const(1));
+        self.zero(returned);
+        for i in 1 + value_len..slots.len() {
+            self.code.push(slots.get(i));
+        }
+        self.return_value()?;
+        self.code.push(Instruction::End);
+        self.labels.pop();
+        for i in 1..1 + value_len {
+            self.code.push(slots.get(i));
+        }
+        Ok(())
+    }
+}
+
+/// memarg returns the argument of a load or a store of a WASM value of type `t` at `offset`,
+/// which is aligned to its width.
+fn memarg(t: ValType, offset: u32) -> MemArg {
+    MemArg {
+        offset: offset as u64,
+        align: match t {
+            ValType::I64 | ValType::F64 => 3,
+            _ => 2,
+        },
+        memory_index: 0,
+    }
+}
+
+/// load_instruction returns the instruction loading a WASM value of type `t` from `offset` past
+/// the address on the stack.
+fn load_instruction(t: ValType, offset: u32) -> Instruction<'static> {
+    let arg = memarg(t, offset);
+    match t {
+        ValType::I64 => Instruction::I64Load(arg),
+        ValType::F32 => Instruction::F32Load(arg),
+        ValType::F64 => Instruction::F64Load(arg),
+        _ => Instruction::I32Load(arg),
+    }
+}
+
+/// store_instruction returns the instruction storing the WASM value of type `t` on the stack at
+/// `offset` past the address below it.
+fn store_instruction(t: ValType, offset: u32) -> Instruction<'static> {
+    let arg = memarg(t, offset);
+    match t {
+        ValType::I64 => Instruction::I64Store(arg),
+        ValType::F32 => Instruction::F32Store(arg),
+        ValType::F64 => Instruction::F64Store(arg),
+        _ => Instruction::I32Store(arg),
+    }
+}
+
+fn is_comparison(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Equals
+            | BinaryOperator::NotEquals
+            | BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEquals
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEquals
+    )
+}
+
+/// arithmetic returns the instruction applying `op` to two values of type `ttype`.
+fn arithmetic(op: &BinaryOperator, ttype: &Type) -> Option<Instruction<'static>> {
+    use BinaryOperator::*;
+    use Instruction as I;
+
+    let signed = is_signed(ttype);
+    Some(match ttype {
+        Type::Float32 => match op {
+            Add => I::F32Add,
+            Subtract => I::F32Sub,
+            Multiply => I::F32Mul,
+            Divide => I::F32Div,
+            Equals => I::F32Eq,
+            NotEquals => I::F32Ne,
+            LessThan => I::F32Lt,
+            LessThanOrEquals => I::F32Le,
+            GreaterThan => I::F32Gt,
+            GreaterThanOrEquals => I::F32Ge,
+            _ => return None,
+        },
+        Type::Float | Type::Float64 => match op {
+            Add => I::F64Add,
+            Subtract => I::F64Sub,
+            Multiply => I::F64Mul,
+            Divide => I::F64Div,
+            Equals => I::F64Eq,
+            NotEquals => I::F64Ne,
+            LessThan => I::F64Lt,
+            LessThanOrEquals => I::F64Le,
+            GreaterThan => I::F64Gt,
+            GreaterThanOrEquals => I::F64Ge,
+            _ => return None,
+        },
+        t if t.is_integer() && t.bits() == Some(64) => match (op, signed) {
+            (Add, _) => I::I64Add,
+            (Subtract, _) => I::I64Sub,
+            (Multiply, _) => I::I64Mul,
+            (Divide, true) => I::I64DivS,
+            (Divide, false) => I::I64DivU,
+            (Modulo, true) => I::I64RemS,
+            (Modulo, false) => I::I64RemU,
+            (BinaryAnd, _) => I::I64And,
+            (BinaryOr, _) => I::I64Or,
+            (Xor, _) => I::I64Xor,
+            (ShiftLeft, _) => I::I64Shl,
+            (ShiftRight, true) => I::I64ShrS,
+            (ShiftRight, false) => I::I64ShrU,
+            (Equals, _) => I::I64Eq,
+            (NotEquals, _) => I::I64Ne,
+            (LessThan, true) => I::I64LtS,
+            (LessThan, false) => I::I64LtU,
+            (LessThanOrEquals, true) => I::I64LeS,
+            (LessThanOrEquals, false) => I::I64LeU,
+            (GreaterThan, true) => I::I64GtS,
+            (GreaterThan, false) => I::I64GtU,
+            (GreaterThanOrEquals, true) => I::I64GeS,
+            (GreaterThanOrEquals, false) => I::I64GeU,
+            _ => return None,
+        },
+        // Bools and chars compare as unsigned integers
+        t if t.is_integer() || *t == Type::Bool || *t == Type::Character => match (op, signed) {
+            (Add, _) => I::I32Add,
+            (Subtract, _) => I::I32Sub,
+            (Multiply, _) => I::I32Mul,
+            (Divide, true) => I::I32DivS,
+            (Divide, false) => I::I32DivU,
+            (Modulo, true) => I::I32RemS,
+            (Modulo, false) => I::I32RemU,
+            (BinaryAnd, _) => I::I32And,
+            (BinaryOr, _) => I::I32Or,
+            (Xor, _) => I::I32Xor,
+            (ShiftLeft, _) => I::I32Shl,
+            (ShiftRight, true) => I::I32ShrS,
+            (ShiftRight, false) => I::I32ShrU,
+            (Equals, _) => I::I32Eq,
+            (NotEquals, _) => I::I32Ne,
+            (LessThan, true) => I::I32LtS,
+            (LessThan, false) => I::I32LtU,
+            (LessThanOrEquals, true) => I::I32LeS,
+            (LessThanOrEquals, false) => I::I32LeU,
+            (GreaterThan, true) => I::I32GtS,
+            (GreaterThan, false) => I::I32GtU,
+            (GreaterThanOrEquals, true) => I::I32GeS,
+            (GreaterThanOrEquals, false) => I::I32GeU,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
@@ -0,0 +1,106 @@
+//! The layout of linear memory, which hosts need to read and write Tiger values stored in it.
+//!
+//! - Addresses below `DATA_START` are never used, so that 0 is never the address of anything.
+//! - The data segment follows, holding the bytes of the text literals. It is not part of the
+//!   heap, and its contents are never freed.
+//! - The heap follows, up to the end of memory, which grows as needed. It starts with a table
+//!   of `FREE_LISTS_SIZE` bytes holding the address of the first free block of each size, or 0,
+//!   followed by the blocks. Blocks are 8 bytes or a larger power of two, and start with a header
+//!   of `HEADER_SIZE` bytes: the size of the block, not counting the header, and either the
+//!   number of references to the block or, once it is free, the address of the next free block
+//!   of its size. Blocks and their contents are 8-byte aligned. Blocks are handed out by the
+//!   runtime's `$alloc` with a single reference, and returned by `$release` once they have none
+//!   left, or directly with `$free`. Hosts can call them through the exports.
+//!
+//! Texts and lists are reference counted. A list holds the references of its elements, which it
+//! releases once it has no references left. Texts hold no references, and a list can only hold
+//! lists of other types, since structs cannot contain themselves through a list, so references
+//! cannot form cycles and every block is freed once nothing refers to it.
+//!
+//! A value stored in memory is the WASM values of its representation, in order, each at its
+//! natural alignment, as the fields of a C struct would be. In particular, a text is the
+//! address and the length of its UTF-8 bytes, which live elsewhere.
+//!
+//! A list is a heap block of `LIST_SIZE` bytes holding its length and capacity, in elements, and
+//! the address of another block holding `capacity` elements. The elements are laid out one after
+//! the other, each taking the size of the layout of the element type.
+
+use wasm_encoder::ValType;
+
+use crate::{hir, lang::Type};
+
+use super::types::Types;
+
+/// The size of the header of a heap block.
+pub const HEADER_SIZE: u32 = 8;
+
+/// The size of the table of free lists, which has room for every power of two.
+pub const FREE_LISTS_SIZE: u32 = 32 * 4;
+
+/// The offset of the length of a list in the list block.
+pub const LIST_LENGTH: u32 = 0;
+/// The offset of the capacity of a list in the list block.
+pub const LIST_CAPACITY: u32 = 4;
+/// The offset of the address of the elements of a list in the list block.
+pub const LIST_DATA: u32 = 8;
+/// The size of a list block.
+pub const LIST_SIZE: u32 = 12;
+
+/// Layout is how a value is stored in memory.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    /// The size of the value, padding included, which is also the distance between two values in
+    /// a list.
+    pub size: u32,
+    pub align: u32,
+    /// The offset and the type of each WASM value of the representation of the value.
+    pub values: Vec<(u32, ValType)>,
+}
+
+/// Layouts gives the layout of the types of a module.
+pub struct Layouts<'a> {
+    types: Types<'a>,
+}
+
+impl<'a> Layouts<'a> {
+    pub fn new(module: &'a hir::Module) -> Self {
+        Self {
+            types: Types::new(module),
+        }
+    }
+
+    pub fn layout(&self, ttype: &Type) -> Layout {
+        layout(&self.types, ttype)
+    }
+
+    /// field_offset returns the offset of field `index` in a struct of type `ttype`.
+    pub fn field_offset(&self, ttype: &Type, index: usize) -> u32 {
+        let layout = self.layout(ttype);
+        let (first, _) = self.types.field(ttype, index);
+        match layout.values.get(first) {
+            Some((offset, _)) => *offset,
+            None => layout.size,
+        }
+    }
+}
+
+/// layout returns how a value of type `ttype`, represented as `types` says, is stored in memory.
+pub(super) fn layout(types: &Types, ttype: &Type) -> Layout {
+    let (mut size, mut align) = (0u32, 1);
+    let mut values = vec![];
+    for t in types.repr(ttype) {
+        let width = match t {
+            ValType::I64 | ValType::F64 => 8,
+            _ => 4,
+        };
+        size = size.next_multiple_of(width);
+        values.push((size, t));
+        size += width;
+        align = align.max(width);
+    }
+    Layout {
+        size: size.next_multiple_of(align),
+        align,
+        values,
+    }
+}
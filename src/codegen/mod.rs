@@ -0,0 +1,728 @@
+//! Code generation turns the HIR of a module into a WebAssembly module.
+//!
+//! The WASM module is laid out as follows:
+//!
+//! - Functions: the imports, then the functions of the module in the order of the HIR, then the
+//!   runtime support functions, then a function releasing lists of each list type the module
+//!   uses, and last a function initializing the globals, which is the start function of the
+//!   module. For the `wasi` target, the WASI imports follow the others, the WASI
+//!   support functions follow the runtime's, and `_start` comes last, calling the initialization
+//!   instead of the start function.
+//! - Globals: those of the runtime, then the WASM values making up each global of the module.
+//! - Memory: a single memory, exported as `memory`, holding the text literals from
+//!   `DATA_START` on, each distinct literal once, and the scratch memory of the WASI support
+//!   functions, if any, followed by the heap. See `memory` for its layout.
+//!
+//! Imports are imported from the module named by their path, e.g. `io.write` is `write` of
+//! module `io`. `main`, the exported functions and the tests, as `test.<name>`, are exported,
+//! and so are the runtime support functions, under names starting with `$`. The Tiger
+//! signatures of `main` and the exported functions are described in a custom section, see
+//! `exports`, and a `name` section names the functions, their locals and the globals.
+//!
+//! Values cross the boundary with the host as the WASM values of their representation, in
+//! order, as for calls between Tiger functions. Texts are passed as their address and length:
+//! imported functions borrow the texts they are given for the duration of the call, and the
+//! texts they return belong to the caller, so they must either be allocated with `$alloc` or
+//! lie outside the heap. Lists, and values holding lists, cannot be passed yet: the checker
+//! rejects signatures using them.
+//!
+//! The binary can be checked with `validate`, which reports invalid code as an internal compiler
+//! error.
+
+use std::collections::HashMap;
+
+use wasm_encoder::{
+    CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection, Function,
+    FunctionSection, GlobalSection, GlobalType, ImportSection, IndirectNameMap, Instruction,
+    MemorySection, MemoryType, NameMap, NameSection, StartSection, TypeSection, ValType,
+};
+
+use crate::{
+    hir::{self, Callee, Expression, ExpressionKind, FunctionKind, ImportId, Place, Statement},
+    lang::Type,
+};
+
+pub mod exports;
+mod function;
+pub mod memory;
+mod runtime;
+mod types;
+mod validate;
+mod wasi;
+
+#[cfg(test)]
+mod test;
+
+use function::FunctionCompiler;
+use runtime::Runtime;
+use types::{Reference, Types};
+pub use validate::validate;
+use wasi::{Intrinsic, Wasi};
+
+/// The address of the first text literal. Nothing is stored below it, so that no text starts at
+/// address 0.
+const DATA_START: u32 = 16;
+
+const PAGE_SIZE: u32 = 1 << 16;
+
+/// Error is a construct of the HIR that cannot be compiled yet, or, if it has an offset, code
+/// generated by the compiler that is not valid WASM.
+#[derive(Debug)]
+pub struct Error {
+    pub message: String,
+    /// The function being compiled, if any.
+    pub function: Option<String>,
+    /// The offset in the binary of the invalid instruction or section.
+    pub offset: Option<usize>,
+}
+
+impl Error {
+    fn new(message: String) -> Self {
+        Self {
+            message,
+            function: None,
+            offset: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.function, self.offset) {
+            (Some(function), Some(offset)) => f.write_fmt(format_args!(
+                "internal compiler error: invalid code for `{}` at offset {:#x}: {}",
+                function, offset, self.message
+            )),
+            (None, Some(offset)) => f.write_fmt(format_args!(
+                "internal compiler error: invalid module at offset {:#x}: {}",
+                offset, self.message
+            )),
+            (Some(function), None) => f.write_fmt(format_args!(
+                "cannot compile `{}`: {}",
+                function, self.message
+            )),
+            (None, None) => f.write_fmt(format_args!("cannot compile module: {}", self.message)),
+        }
+    }
+}
+
+/// Target is the environment the binary runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Target {
+    /// A host embedding the binary, which provides the imports and calls the exports.
+    #[default]
+    Host,
+    /// A runtime implementing WASI preview 1, which runs the binary as a command. See `wasi`.
+    Wasi,
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "host" => Ok(Target::Host),
+            "wasi" => Ok(Target::Wasi),
+            _ => Err(format!("unknown target `{}`", s)),
+        }
+    }
+}
+
+/// compile returns the WASM binary of `module` for the host target.
+pub fn compile(module: &hir::Module) -> Result<Vec<u8>, Error> {
+    compile_for(module, Target::Host).map(|(wasm, _)| wasm)
+}
+
+/// compile_for returns the WASM binary of `module` for `target`, and what its data segment
+/// holds. The wasi target needs a `main` function to run.
+pub fn compile_for(module: &hir::Module, target: Target) -> Result<(Vec<u8>, DataStats), Error> {
+    let main = module.functions.iter().position(|f| f.name == "main");
+    if target == Target::Wasi && main.is_none() {
+        let message = "a program for target wasi needs a `main` function".into();
+        return Err(Error::new(message));
+    }
+    let mut ctx = Context::new(module, target)?;
+
+    let mut code = vec![];
+    let mut locals = vec![];
+    for func in &module.functions {
+        let params: Vec<ValType> = func
+            .params
+            .iter()
+            .flat_map(|p| ctx.types.repr(&func.locals[*p].ttype))
+            .collect();
+        let receiver = match func.mutable_receiver {
+            true => func.params.first().copied(),
+            false => None,
+        };
+        let mut results = ctx.types.repr(&func.return_type);
+        if let Some(receiver) = receiver {
+            results.extend(ctx.types.repr(&func.locals[receiver].ttype));
+        }
+        let signature = ctx.signatures.get(params, results);
+        let compiler = FunctionCompiler::new(
+            &mut ctx,
+            &func.name,
+            &func.params,
+            &func.locals,
+            &func.return_type,
+            receiver,
+        );
+        locals.push(compiler.local_names());
+        code.push((signature, compiler.compile(&func.body)?));
+    }
+    for ((params, results), f) in ctx.runtime.functions() {
+        code.push((ctx.signatures.get(params, results), f));
+    }
+    for ttype in ctx.lists.clone() {
+        let f = ctx.list_release_function(&ttype);
+        code.push((ctx.signatures.get(vec![ValType::I32], vec![]), f));
+    }
+    if let Some(wasi) = &ctx.wasi {
+        for ((params, results), f) in wasi.functions(&ctx.runtime) {
+            code.push((ctx.signatures.get(params, results), f));
+        }
+    }
+
+    // Globals are initialized by the start function, in order
+    let init: Vec<Statement> = (module.globals.iter().enumerate())
+        .map(|(id, g)| Statement::Assign(Place::Global(id), g.value.clone()))
+        .collect();
+    let start = match init.is_empty() {
+        true => None,
+        false => {
+            let signature = ctx.signatures.get(vec![], vec![]);
+            let compiler = FunctionCompiler::new(&mut ctx, "$init", &[], &[], &Type::Void, None);
+            code.push((signature, compiler.compile(&init)?));
+            Some(ctx.first_function + code.len() as u32 - 1)
+        }
+    };
+
+    // WASI runtimes don't let the start function use WASI, so `_start` initializes the globals
+    let start = match &ctx.wasi {
+        None => start,
+        Some(wasi) => {
+            let main = main.expect("wasi programs have a main function");
+            let f = start_function(&ctx, wasi, start, main);
+            code.push((ctx.signatures.get(vec![], vec![]), f));
+            None
+        }
+    };
+
+    let stats = ctx.data.stats.clone();
+    Ok((ctx.finish(code, start, locals), stats))
+}
+
+/// start_function returns `_start`, which calls `init`, if any, then `main` and exits with its
+/// result if it is an integer.
+fn start_function(ctx: &Context, wasi: &Wasi, init: Option<u32>, main: usize) -> Function {
+    let mut f = Function::new([]);
+    if let Some(init) = init {
+        f.instruction(&Instruction::Call(init));
+    }
+    f.instruction(&Instruction::Call(ctx.first_function + main as u32));
+    let return_type = &ctx.module.functions[main].return_type;
+    let repr = ctx.types.repr(return_type);
+    match repr.as_slice() {
+        [t] if return_type.is_integer() => {
+            if *t == ValType::I64 {
+                f.instruction(&Instruction::I32WrapI64);
+            }
+            f.instruction(&Instruction::Call(wasi.proc_exit));
+        }
+        // Exiting releases everything
+        _ => {
+            for _ in repr {
+                f.instruction(&Instruction::Drop);
+            }
+        }
+    }
+    f.instruction(&Instruction::End);
+    f
+}
+
+/// DataStats describes the data segment, which holds each distinct text literal once. The texts
+/// of the runtime, such as those it converts values to, are stored there too, but only count
+/// toward its size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DataStats {
+    /// The number of text literals compiled, empty ones excluded.
+    pub literals: usize,
+    /// The number of distinct text literals, stored in the data segment.
+    pub texts: usize,
+    /// The size of the data segment in bytes.
+    pub size: usize,
+    /// The number of bytes not stored because a literal was already in the data segment.
+    pub saved: usize,
+}
+
+impl std::fmt::Display for DataStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "data: {} bytes, {} texts for {} literals, {} bytes saved",
+            self.size, self.texts, self.literals, self.saved
+        ))
+    }
+}
+
+/// Signature is the types of the parameters and the results of a WASM function.
+type Signature = (Vec<ValType>, Vec<ValType>);
+
+/// Signatures holds the function types of the module, each once.
+struct Signatures {
+    section: TypeSection,
+    indices: HashMap<Signature, u32>,
+}
+
+impl Signatures {
+    fn get(&mut self, params: Vec<ValType>, results: Vec<ValType>) -> u32 {
+        let next = self.indices.len() as u32;
+        *self
+            .indices
+            .entry((params, results))
+            .or_insert_with_key(|(params, results)| {
+                self.section
+                    .function(params.iter().copied(), results.iter().copied());
+                next
+            })
+    }
+}
+
+/// Data is the initial contents of linear memory. Since the modules of a program are merged
+/// before they are compiled, a literal is stored once for the whole program.
+#[derive(Default)]
+struct Data {
+    bytes: Vec<u8>,
+    /// The address of the literals stored so far.
+    addresses: HashMap<Vec<u8>, u32>,
+    /// The address of the texts of the runtime stored so far.
+    runtime_addresses: HashMap<Vec<u8>, u32>,
+    stats: DataStats,
+}
+
+impl Data {
+    /// add stores `bytes`, unless they already are, and returns their address. Nothing is stored
+    /// for no bytes, whose address is 0, as for the zero value of a text, so that it is never
+    /// taken for the heap.
+    fn add(&mut self, bytes: &[u8]) -> u32 {
+        if bytes.is_empty() {
+            return 0;
+        }
+        self.stats.literals += 1;
+        if let Some(address) = self.addresses.get(bytes) {
+            self.stats.saved += bytes.len();
+            return *address;
+        }
+        let address = DATA_START + self.bytes.len() as u32;
+        self.bytes.extend(bytes);
+        self.addresses.insert(bytes.to_vec(), address);
+        self.stats.texts += 1;
+        self.stats.size = self.bytes.len();
+        address
+    }
+
+    /// add_runtime_text stores a text of the runtime, unless it already is, and returns its
+    /// address. Unlike `add`, it is not counted as a literal.
+    fn add_runtime_text(&mut self, text: &str) -> u32 {
+        if let Some(address) = self.runtime_addresses.get(text.as_bytes()) {
+            return *address;
+        }
+        let address = self.end();
+        self.bytes.extend(text.as_bytes());
+        self.runtime_addresses
+            .insert(text.as_bytes().to_vec(), address);
+        self.stats.size = self.bytes.len();
+        address
+    }
+
+    /// reserve stores `size` zero bytes, 8-byte aligned, for the runtime to work in, and returns
+    /// their address.
+    fn reserve(&mut self, size: u32) -> u32 {
+        let address = self.end().next_multiple_of(8);
+        self.bytes.resize((address + size - DATA_START) as usize, 0);
+        self.stats.size = self.bytes.len();
+        address
+    }
+
+    fn end(&self) -> u32 {
+        DATA_START + self.bytes.len() as u32
+    }
+}
+
+/// ImportedFunction is a WASM function import. Imports declared without a signature are
+/// imported once for each signature they are called with.
+struct ImportedFunction {
+    import: ImportId,
+    params: Vec<Type>,
+    result: Type,
+}
+
+struct Context<'a> {
+    module: &'a hir::Module,
+    types: Types<'a>,
+    signatures: Signatures,
+    data: Data,
+    imports: Vec<ImportedFunction>,
+    /// The index of the first function of the module
+    first_function: u32,
+    /// The first WASM global of each global
+    globals: Vec<u32>,
+    runtime: Runtime,
+    /// The list types of the module, each once, in the order of the functions releasing them
+    lists: Vec<Type>,
+    /// The support functions of the `wasi` target, when compiling for it.
+    wasi: Option<Wasi>,
+}
+
+impl<'a> Context<'a> {
+    fn new(module: &'a hir::Module, target: Target) -> Result<Self, Error> {
+        let types = Types::new(module);
+
+        // Intrinsics are not imported
+        let imported = |id: ImportId| {
+            target == Target::Host || Intrinsic::from_path(&module.imports[id].path).is_none()
+        };
+        let mut imports = vec![];
+        for (id, import) in module.imports.iter().enumerate() {
+            if !imported(id) {
+                continue;
+            }
+            if let Some((params, result)) = &import.signature {
+                imports.push(ImportedFunction {
+                    import: id,
+                    params: params.clone(),
+                    result: result.clone(),
+                });
+            }
+        }
+        let mut add_calls = |expr: &Expression| {
+            if let ExpressionKind::Call(Callee::Import(id), args) = &expr.kind {
+                if !imported(*id) {
+                    return;
+                }
+                let params: Vec<Type> = args.iter().map(|a| a.ttype.clone()).collect();
+                let known = imports.iter().any(|i| {
+                    i.import == *id
+                        && (module.imports[*id].signature.is_some()
+                            || (i.params == params && i.result == expr.ttype))
+                });
+                if !known {
+                    imports.push(ImportedFunction {
+                        import: *id,
+                        params,
+                        result: expr.ttype.clone(),
+                    });
+                }
+            }
+        };
+        for func in &module.functions {
+            walk_statements(&func.body, &mut add_calls);
+        }
+        for global in &module.globals {
+            walk_expression(&global.value, &mut add_calls);
+        }
+
+        let mut lists = vec![];
+        let mut add_lists = |expr: &Expression| types.add_lists(&expr.ttype, &mut lists);
+        for func in &module.functions {
+            walk_statements(&func.body, &mut add_lists);
+        }
+        for global in &module.globals {
+            walk_expression(&global.value, &mut add_lists);
+        }
+        let declared = (module.functions.iter())
+            .flat_map(|f| f.locals.iter().map(|l| &l.ttype).chain([&f.return_type]))
+            .chain(module.globals.iter().map(|g| &g.ttype))
+            .chain(
+                module
+                    .structs
+                    .iter()
+                    .flat_map(|s| s.fields.iter().map(|(_, t)| t)),
+            );
+        for ttype in declared {
+            types.add_lists(ttype, &mut lists);
+        }
+
+        let mut data = Data::default();
+        let mut first_function = imports.len() as u32;
+        let mut wasi = None;
+        if target == Target::Wasi {
+            let first_import = first_function;
+            first_function += Wasi::IMPORTS.len() as u32;
+            let runtime_end = first_function
+                + (module.functions.len() + Runtime::NAMES.len() + lists.len()) as u32;
+            let scratch = data.reserve(wasi::SCRATCH_SIZE);
+            wasi = Some(Wasi::new(first_import, runtime_end, scratch));
+        }
+        let mut globals = vec![];
+        let mut next = Runtime::GLOBALS;
+        for global in &module.globals {
+            globals.push(next);
+            next += types.repr(&global.ttype).len() as u32;
+        }
+
+        Ok(Self {
+            module,
+            types,
+            signatures: Signatures {
+                section: TypeSection::new(),
+                indices: HashMap::new(),
+            },
+            data,
+            imports,
+            first_function,
+            globals,
+            runtime: Runtime::new(first_function + module.functions.len() as u32, 0),
+            lists,
+            wasi,
+        })
+    }
+
+    /// import_index returns the index of the WASM function imported for calls to `import` with
+    /// arguments of types `params`.
+    fn import_index(&self, import: ImportId, params: &[Type], result: &Type) -> u32 {
+        let signed = self.module.imports[import].signature.is_some();
+        self.imports
+            .iter()
+            .position(|i| {
+                i.import == import && (signed || (i.params == params && i.result == *result))
+            })
+            .unwrap() as u32
+    }
+
+    /// release_function returns the index of the function removing a reference to what
+    /// `reference` refers to.
+    fn release_function(&self, reference: &Reference) -> u32 {
+        match reference {
+            Reference::Text => self.runtime.release,
+            Reference::List(ttype) => {
+                let position = self.lists.iter().position(|t| t == ttype).unwrap();
+                self.runtime.alloc + (Runtime::NAMES.len() + position) as u32
+            }
+        }
+    }
+
+    /// list_release_function returns the function releasing lists of type `ttype`.
+    fn list_release_function(&self, ttype: &Type) -> Function {
+        let element = match ttype {
+            Type::List(element) => element,
+            t => panic!("{} is not a list", t),
+        };
+        let layout = memory::layout(&self.types, element);
+        let references: Vec<(u32, u32)> = (self.types.references(element).iter())
+            .map(|(i, reference)| (layout.values[*i].0, self.release_function(reference)))
+            .collect();
+        self.runtime.list_release(layout.size, &references)
+    }
+
+    /// finish builds the binary from the code and the signature of every defined function, and
+    /// the names of the locals of the functions of the module.
+    fn finish(
+        mut self,
+        code: Vec<(u32, wasm_encoder::Function)>,
+        start: Option<u32>,
+        locals: Vec<Vec<(u32, String)>>,
+    ) -> Vec<u8> {
+        let mut function_names = vec![];
+        let mut imports = ImportSection::new();
+        for imported in &self.imports {
+            let import = &self.module.imports[imported.import];
+            let (module, name) = match import.path.rsplit_once('.') {
+                Some((module, name)) => (module, name),
+                None => ("env", import.path.as_str()),
+            };
+            // Imports called with several signatures need one name for each
+            let overloaded = (self.imports.iter())
+                .filter(|o| o.import == imported.import)
+                .count()
+                > 1;
+            let name = match overloaded {
+                true => format!(
+                    "{}({})",
+                    name,
+                    (imported.params.iter())
+                        .map(|t| t.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+                false => name.to_string(),
+            };
+            let params: Vec<ValType> = (imported.params.iter())
+                .flat_map(|t| self.types.repr(t))
+                .collect();
+            let results = self.types.repr(&imported.result);
+            let signature = self.signatures.get(params, results);
+            imports.import(module, &name, EntityType::Function(signature));
+            function_names.push(format!("{}.{}", module, name));
+        }
+        if let Some(wasi) = &self.wasi {
+            for (name, (params, results)) in wasi.imports() {
+                let signature = self.signatures.get(params, results);
+                imports.import(wasi::MODULE, name, EntityType::Function(signature));
+                function_names.push(format!("{}.{}", wasi::MODULE, name));
+            }
+        }
+
+        let mut functions = FunctionSection::new();
+        let mut codes = CodeSection::new();
+        for (signature, f) in &code {
+            functions.function(*signature);
+            codes.function(f);
+        }
+
+        let mut globals = GlobalSection::new();
+        let heap = (self.data.end() + 7) & !7;
+        for init in self.runtime.globals(heap) {
+            let global_type = GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+            };
+            globals.global(global_type, &init);
+        }
+        for global in &self.module.globals {
+            for t in self.types.repr(&global.ttype) {
+                let zero = match t {
+                    ValType::I64 => ConstExpr::i64_const(0),
+                    ValType::F32 => ConstExpr::f32_const(0.0),
+                    ValType::F64 => ConstExpr::f64_const(0.0),
+                    _ => ConstExpr::i32_const(0),
+                };
+                let global_type = GlobalType {
+                    val_type: t,
+                    mutable: true,
+                };
+                globals.global(global_type, &zero);
+            }
+        }
+
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: ((heap + memory::FREE_LISTS_SIZE) / PAGE_SIZE + 1) as u64,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+
+        let mut exports = ExportSection::new();
+        exports.export("memory", ExportKind::Memory, 0);
+        for (id, func) in self.module.functions.iter().enumerate() {
+            let name = match func.kind {
+                FunctionKind::Function if func.name == "main" || func.exported => func.name.clone(),
+                FunctionKind::Test => format!("test.{}", func.name),
+                _ => continue,
+            };
+            exports.export(&name, ExportKind::Func, self.first_function + id as u32);
+        }
+        for (i, name) in Runtime::NAMES.iter().enumerate() {
+            exports.export(name, ExportKind::Func, self.runtime.alloc + i as u32);
+        }
+        let has_main = self.module.functions.iter().any(|f| f.name == "main");
+        if self.wasi.is_some() && has_main {
+            let last = self.first_function + code.len() as u32 - 1;
+            exports.export("_start", ExportKind::Func, last);
+        }
+
+        let mut data = DataSection::new();
+        if !self.data.bytes.is_empty() {
+            data.active(
+                0,
+                &ConstExpr::i32_const(DATA_START as i32),
+                self.data.bytes.iter().copied(),
+            );
+        }
+
+        let mut res = wasm_encoder::Module::new();
+        res.section(&self.signatures.section)
+            .section(&imports)
+            .section(&functions)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports);
+        if let Some(start) = start {
+            res.section(&StartSection {
+                function_index: start,
+            });
+        }
+        res.section(&codes)
+            .section(&data)
+            .section(&self.names(function_names, locals))
+            .section(&exports::section(self.module));
+        res.finish()
+    }
+
+    /// names returns the `name` section, naming the functions and the globals after what they
+    /// implement, and the locals of the functions of the module after their Tiger names, for
+    /// debuggers and disassemblers. `imports` are the names of the imported functions.
+    fn names(&self, imports: Vec<String>, locals: Vec<Vec<(u32, String)>>) -> NameSection {
+        let mut functions = imports;
+        for func in &self.module.functions {
+            functions.push(match func.kind {
+                FunctionKind::Test => format!("test.{}", func.name),
+                _ => func.name.clone(),
+            });
+        }
+        functions.extend(Runtime::NAMES.iter().map(|n| n.to_string()));
+        functions.extend(self.lists.iter().map(|t| format!("$release_{}", t)));
+        if self.wasi.is_some() {
+            functions.extend(Wasi::NAMES.iter().map(|n| n.to_string()));
+        }
+        if !self.module.globals.is_empty() {
+            functions.push("$init".to_string());
+        }
+        if self.wasi.is_some() && self.module.functions.iter().any(|f| f.name == "main") {
+            functions.push("_start".to_string());
+        }
+
+        let mut function_map = NameMap::new();
+        for (i, name) in functions.iter().enumerate() {
+            function_map.append(i as u32, name);
+        }
+        let mut local_map = IndirectNameMap::new();
+        for (id, names) in locals.iter().enumerate() {
+            let mut map = NameMap::new();
+            for (index, name) in names {
+                map.append(*index, name);
+            }
+            local_map.append(self.first_function + id as u32, &map);
+        }
+        let mut global_map = NameMap::new();
+        for (i, name) in Runtime::GLOBAL_NAMES.iter().enumerate() {
+            global_map.append(i as u32, name);
+        }
+        for (id, global) in self.module.globals.iter().enumerate() {
+            let len = self.types.repr(&global.ttype).len() as u32;
+            for i in 0..len {
+                let name = match len {
+                    1 => global.name.clone(),
+                    _ => format!("{}.{}", global.name, i),
+                };
+                global_map.append(self.globals[id] + i, &name);
+            }
+        }
+
+        let mut res = NameSection::new();
+        res.module(&self.module.name);
+        res.functions(&function_map);
+        res.locals(&local_map);
+        res.globals(&global_map);
+        res
+    }
+}
+
+fn walk_statements(statements: &[Statement], f: &mut impl FnMut(&Expression)) {
+    for statement in statements {
+        for expr in statement.expressions() {
+            walk_expression(expr, f);
+        }
+        for body in statement.bodies() {
+            walk_statements(body, f);
+        }
+    }
+}
+
+fn walk_expression(expr: &Expression, f: &mut impl FnMut(&Expression)) {
+    f(expr);
+    for child in expr.kind.children() {
+        walk_expression(child, f);
+    }
+}
@@ -0,0 +1,1033 @@
+//! Runtime support functions that are added to every module: memory management and the
+//! operations on text and lists that don't fit in a few instructions, conversions to text
+//! included. See `memory` for the layout of the heap they manage.
+
+use wasm_encoder::{BlockType, ConstExpr, Function, Instruction, MemArg, ValType};
+
+use super::{
+    memory::{FREE_LISTS_SIZE, HEADER_SIZE, LIST_CAPACITY, LIST_DATA, LIST_LENGTH, LIST_SIZE},
+    Signature,
+};
+
+/// Runtime holds the indices of the support functions and globals.
+pub(super) struct Runtime {
+    pub alloc: u32,
+    pub free: u32,
+    pub text_concat: u32,
+    pub text_compare: u32,
+    pub list_new: u32,
+    pub list_reserve: u32,
+    pub retain: u32,
+    pub release: u32,
+    digits: u32,
+    pub int_text: u32,
+    pub float_text: u32,
+    pub char_text: u32,
+    pub text_chars: u32,
+    /// The global holding the address of the first byte of memory that was never allocated.
+    pub heap: u32,
+    /// The global holding the address where the heap starts, with the table of free lists.
+    /// Anything below is never freed.
+    pub heap_base: u32,
+}
+
+/// header pushes the address of the header of the block at the address in `local`.
+fn header(local: u32) -> [Instruction<'static>; 3] {
+    [
+        Instruction::LocalGet(local),
+        Instruction::I32Const(HEADER_SIZE as i32),
+        Instruction::I32Sub,
+    ]
+}
+
+/// word returns the argument of a load or a store of an `i32` at `offset`.
+pub(super) fn word(offset: u32) -> MemArg {
+    MemArg {
+        offset: offset as u64,
+        align: 2,
+        memory_index: 0,
+    }
+}
+
+/// byte returns the argument of a load or a store of a byte at `offset`.
+pub(super) fn byte(offset: u32) -> MemArg {
+    MemArg {
+        offset: offset as u64,
+        align: 0,
+        memory_index: 0,
+    }
+}
+
+/// function returns a function with one local of each type of `locals`, after its arguments,
+/// made of `code`.
+pub(super) fn function(locals: &[ValType], code: &[Instruction]) -> Function {
+    let mut f = Function::new(locals.iter().map(|t| (1, *t)));
+    for i in code {
+        f.instruction(i);
+    }
+    f
+}
+
+impl Runtime {
+    /// The names of the globals, in order.
+    pub const GLOBAL_NAMES: [&'static str; 2] = ["$heap", "$heap_base"];
+    pub const GLOBALS: u32 = Self::GLOBAL_NAMES.len() as u32;
+    /// The names of the support functions, in order, which are also the names they are exported
+    /// under.
+    pub const NAMES: [&'static str; 13] = [
+        "$alloc",
+        "$free",
+        "$text_concat",
+        "$text_compare",
+        "$list_new",
+        "$list_reserve",
+        "$retain",
+        "$release",
+        "$digits",
+        "$int_text",
+        "$float_text",
+        "$char_text",
+        "$text_chars",
+    ];
+
+    /// new places the support functions at `first_function` and their globals at `first_global`.
+    pub fn new(first_function: u32, first_global: u32) -> Self {
+        Self {
+            alloc: first_function,
+            free: first_function + 1,
+            text_concat: first_function + 2,
+            text_compare: first_function + 3,
+            list_new: first_function + 4,
+            list_reserve: first_function + 5,
+            retain: first_function + 6,
+            release: first_function + 7,
+            digits: first_function + 8,
+            int_text: first_function + 9,
+            float_text: first_function + 10,
+            char_text: first_function + 11,
+            text_chars: first_function + 12,
+            heap: first_global,
+            heap_base: first_global + 1,
+        }
+    }
+
+    /// globals returns the initial value of each global, in order, for a heap starting at
+    /// `heap_base`.
+    pub fn globals(&self, heap_base: u32) -> Vec<ConstExpr> {
+        vec![
+            ConstExpr::i32_const((heap_base + FREE_LISTS_SIZE) as i32),
+            ConstExpr::i32_const(heap_base as i32),
+        ]
+    }
+
+    /// functions returns the type and the code of each support function, in order.
+    pub fn functions(&self) -> Vec<(Signature, Function)> {
+        use ValType::{F64, I32, I64};
+        vec![
+            ((vec![I32], vec![I32]), self.alloc()),
+            ((vec![I32], vec![]), self.free()),
+            ((vec![I32; 4], vec![I32, I32]), self.text_concat()),
+            ((vec![I32; 4], vec![I32]), self.text_compare()),
+            ((vec![I32, I32], vec![I32]), self.list_new()),
+            ((vec![I32; 3], vec![]), self.list_reserve()),
+            ((vec![I32], vec![]), self.retain()),
+            ((vec![I32], vec![]), self.release()),
+            ((vec![I32, I64, I32], vec![I32]), self.digits()),
+            ((vec![I64, I32], vec![I32, I32]), self.int_text()),
+            ((vec![F64], vec![I32, I32]), self.float_text()),
+            ((vec![I32], vec![I32, I32]), self.char_text()),
+            ((vec![I32, I32], vec![I32]), self.text_chars()),
+        ]
+    }
+
+    /// alloc(size) returns the address of a block of at least `size` bytes, holding a single
+    /// reference. The block is taken from the free list of its size if there is one, and from the
+    /// end of the heap otherwise, growing memory as needed.
+    fn alloc(&self) -> Function {
+        let (size, class, list, block) = (0, 1, 2, 3);
+        let mut f = Function::new([(3, ValType::I32)]);
+        let mut code = vec![
+            // class = size > 8 ? 29 - clz(size - 1) : 0, which is log2(size) - 3 once the size
+            // is rounded up to a power of two
+            Instruction::I32Const(29),
+            Instruction::LocalGet(size),
+            Instruction::I32Const(1),
+            Instruction::I32Sub,
+            Instruction::I32Clz,
+            Instruction::I32Sub,
+            Instruction::I32Const(0),
+            Instruction::LocalGet(size),
+            Instruction::I32Const(8),
+            Instruction::I32GtU,
+            Instruction::Select,
+            Instruction::LocalTee(class),
+            // size = 8 << class
+            Instruction::I32Const(8),
+            Instruction::LocalGet(class),
+            Instruction::I32Shl,
+            Instruction::LocalSet(size),
+            // list = heap_base + class * 4
+            Instruction::I32Const(2),
+            Instruction::I32Shl,
+            Instruction::GlobalGet(self.heap_base),
+            Instruction::I32Add,
+            Instruction::LocalTee(list),
+            Instruction::I32Load(word(0)),
+            Instruction::LocalTee(block),
+            Instruction::If(BlockType::Empty),
+            Instruction::LocalGet(list),
+        ];
+        code.extend(header(block));
+        code.extend([
+            Instruction::I32Load(word(4)),
+            Instruction::I32Store(word(0)),
+        ]);
+        code.extend(header(block));
+        code.extend([
+            Instruction::I32Const(1),
+            Instruction::I32Store(word(4)),
+            Instruction::LocalGet(block),
+            Instruction::Return,
+            Instruction::End,
+            // block = heap + HEADER_SIZE, heap = block + size
+            Instruction::GlobalGet(self.heap),
+            Instruction::I32Const(HEADER_SIZE as i32),
+            Instruction::I32Add,
+            Instruction::LocalTee(block),
+            Instruction::LocalGet(size),
+            Instruction::I32Add,
+            Instruction::GlobalSet(self.heap),
+            // Grow memory by enough pages to hold everything below heap
+            Instruction::GlobalGet(self.heap),
+            Instruction::MemorySize(0),
+            Instruction::I32Const(16),
+            Instruction::I32Shl,
+            Instruction::I32GtU,
+            Instruction::If(BlockType::Empty),
+            Instruction::GlobalGet(self.heap),
+            Instruction::MemorySize(0),
+            Instruction::I32Const(16),
+            Instruction::I32Shl,
+            Instruction::I32Sub,
+            Instruction::I32Const(0xffff),
+            Instruction::I32Add,
+            Instruction::I32Const(16),
+            Instruction::I32ShrU,
+            Instruction::MemoryGrow(0),
+            Instruction::I32Const(-1),
+            Instruction::I32Eq,
+            Instruction::If(BlockType::Empty),
+            Instruction::Unreachable,
+            Instruction::End,
+            Instruction::End,
+        ]);
+        code.extend(header(block));
+        code.extend([Instruction::LocalGet(size), Instruction::I32Store(word(0))]);
+        code.extend(header(block));
+        code.extend([
+            Instruction::I32Const(1),
+            Instruction::I32Store(word(4)),
+            Instruction::LocalGet(block),
+            Instruction::End,
+        ]);
+        for i in &code {
+            f.instruction(i);
+        }
+        f
+    }
+
+    /// free(address) puts the block at `address` on the free list of its size. Freeing 0 does
+    /// nothing.
+    fn free(&self) -> Function {
+        let (address, list) = (0, 1);
+        let mut f = Function::new([(1, ValType::I32)]);
+        let mut code = vec![
+            Instruction::LocalGet(address),
+            Instruction::I32Eqz,
+            Instruction::BrIf(0),
+            // list = heap_base + (log2(size) - 3) * 4
+            Instruction::GlobalGet(self.heap_base),
+        ];
+        code.extend(header(address));
+        code.extend([
+            Instruction::I32Load(word(0)),
+            Instruction::I32Ctz,
+            Instruction::I32Const(3),
+            Instruction::I32Sub,
+            Instruction::I32Const(2),
+            Instruction::I32Shl,
+            Instruction::I32Add,
+            Instruction::LocalSet(list),
+        ]);
+        code.extend(header(address));
+        code.extend([
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(0)),
+            Instruction::I32Store(word(4)),
+            Instruction::LocalGet(list),
+            Instruction::LocalGet(address),
+            Instruction::I32Store(word(0)),
+            Instruction::End,
+        ]);
+        for i in &code {
+            f.instruction(i);
+        }
+        f
+    }
+
+    /// text_concat(address1, length1, address2, length2) returns a new text holding both texts.
+    fn text_concat(&self) -> Function {
+        let (a1, l1, a2, l2, res) = (0, 1, 2, 3, 4);
+        let copy = Instruction::MemoryCopy {
+            src_mem: 0,
+            dst_mem: 0,
+        };
+        let mut f = Function::new([(1, ValType::I32)]);
+        for i in [
+            Instruction::LocalGet(l1),
+            Instruction::LocalGet(l2),
+            Instruction::I32Add,
+            Instruction::Call(self.alloc),
+            Instruction::LocalTee(res),
+            Instruction::LocalGet(a1),
+            Instruction::LocalGet(l1),
+            copy.clone(),
+            Instruction::LocalGet(res),
+            Instruction::LocalGet(l1),
+            Instruction::I32Add,
+            Instruction::LocalGet(a2),
+            Instruction::LocalGet(l2),
+            copy,
+            Instruction::LocalGet(res),
+            Instruction::LocalGet(l1),
+            Instruction::LocalGet(l2),
+            Instruction::I32Add,
+            Instruction::End,
+        ] {
+            f.instruction(&i);
+        }
+        f
+    }
+
+    /// text_compare(address1, length1, address2, length2) returns -1, 0 or 1 if the first text
+    /// sorts before, the same as or after the second one. Texts are compared byte by byte, which
+    /// is the order of their code points.
+    fn text_compare(&self) -> Function {
+        let (a1, l1, a2, l2, i, n, b1, b2) = (0, 1, 2, 3, 4, 5, 6, 7);
+        let byte = MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: 0,
+        };
+        // Pushes (x > y) - (x < y)
+        let sign = |x, y| {
+            [
+                Instruction::LocalGet(x),
+                Instruction::LocalGet(y),
+                Instruction::I32GtU,
+                Instruction::LocalGet(x),
+                Instruction::LocalGet(y),
+                Instruction::I32LtU,
+                Instruction::I32Sub,
+            ]
+        };
+
+        let mut f = Function::new([(4, ValType::I32)]);
+        let mut code = vec![
+            // n = min(l1, l2)
+            Instruction::LocalGet(l1),
+            Instruction::LocalGet(l2),
+            Instruction::LocalGet(l1),
+            Instruction::LocalGet(l2),
+            Instruction::I32LtU,
+            Instruction::Select,
+            Instruction::LocalSet(n),
+            Instruction::Block(BlockType::Empty),
+            Instruction::Loop(BlockType::Empty),
+            Instruction::LocalGet(i),
+            Instruction::LocalGet(n),
+            Instruction::I32GeU,
+            Instruction::BrIf(1),
+            Instruction::LocalGet(a1),
+            Instruction::LocalGet(i),
+            Instruction::I32Add,
+            Instruction::I32Load8U(byte),
+            Instruction::LocalSet(b1),
+            Instruction::LocalGet(a2),
+            Instruction::LocalGet(i),
+            Instruction::I32Add,
+            Instruction::I32Load8U(byte),
+            Instruction::LocalSet(b2),
+            Instruction::LocalGet(b1),
+            Instruction::LocalGet(b2),
+            Instruction::I32Ne,
+            Instruction::If(BlockType::Empty),
+        ];
+        code.extend(sign(b1, b2));
+        code.extend([
+            Instruction::Return,
+            Instruction::End,
+            Instruction::LocalGet(i),
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::LocalSet(i),
+            Instruction::Br(0),
+            Instruction::End,
+            Instruction::End,
+        ]);
+        // All bytes of the shorter text match, so the shorter one sorts first
+        code.extend(sign(l1, l2));
+        code.push(Instruction::End);
+        for i in &code {
+            f.instruction(i);
+        }
+        f
+    }
+
+    /// list_new(capacity, element_size) returns a new empty list that can hold `capacity`
+    /// elements of `element_size` bytes.
+    fn list_new(&self) -> Function {
+        let (capacity, element_size, list) = (0, 1, 2);
+        let mut f = Function::new([(1, ValType::I32)]);
+        for i in [
+            Instruction::I32Const(LIST_SIZE as i32),
+            Instruction::Call(self.alloc),
+            Instruction::LocalTee(list),
+            Instruction::I32Const(0),
+            Instruction::I32Store(word(LIST_LENGTH)),
+            Instruction::LocalGet(list),
+            Instruction::LocalGet(capacity),
+            Instruction::I32Store(word(LIST_CAPACITY)),
+            Instruction::LocalGet(list),
+            Instruction::LocalGet(capacity),
+            Instruction::LocalGet(element_size),
+            Instruction::I32Mul,
+            Instruction::Call(self.alloc),
+            Instruction::I32Store(word(LIST_DATA)),
+            Instruction::LocalGet(list),
+            Instruction::End,
+        ] {
+            f.instruction(&i);
+        }
+        f
+    }
+
+    /// list_reserve(list, additional, element_size) makes room in `list` for `additional` more
+    /// elements of `element_size` bytes. When the elements don't fit, they are moved to a new
+    /// block, at least twice as large, and the old one is freed.
+    fn list_reserve(&self) -> Function {
+        let (list, additional, element_size, needed, capacity, data) = (0, 1, 2, 3, 4, 5);
+        let mut f = Function::new([(3, ValType::I32)]);
+        for i in [
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_LENGTH)),
+            Instruction::LocalGet(additional),
+            Instruction::I32Add,
+            Instruction::LocalTee(needed),
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_CAPACITY)),
+            Instruction::LocalTee(capacity),
+            Instruction::I32GtU,
+            Instruction::If(BlockType::Empty),
+            // capacity = max(capacity * 2, needed)
+            Instruction::LocalGet(capacity),
+            Instruction::I32Const(1),
+            Instruction::I32Shl,
+            Instruction::LocalTee(capacity),
+            Instruction::LocalGet(needed),
+            Instruction::LocalGet(capacity),
+            Instruction::LocalGet(needed),
+            Instruction::I32GtU,
+            Instruction::Select,
+            Instruction::LocalTee(capacity),
+            Instruction::LocalGet(element_size),
+            Instruction::I32Mul,
+            Instruction::Call(self.alloc),
+            Instruction::LocalTee(data),
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_DATA)),
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_LENGTH)),
+            Instruction::LocalGet(element_size),
+            Instruction::I32Mul,
+            Instruction::MemoryCopy {
+                src_mem: 0,
+                dst_mem: 0,
+            },
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_DATA)),
+            Instruction::Call(self.free),
+            Instruction::LocalGet(list),
+            Instruction::LocalGet(data),
+            Instruction::I32Store(word(LIST_DATA)),
+            Instruction::LocalGet(list),
+            Instruction::LocalGet(capacity),
+            Instruction::I32Store(word(LIST_CAPACITY)),
+            Instruction::End,
+            Instruction::End,
+        ] {
+            f.instruction(&i);
+        }
+        f
+    }
+
+    /// list_release(list) removes a reference to `list`, and once there are none left, releases
+    /// the references held by its elements and frees the list. It is not a support function, but
+    /// there is one for each list type, whose elements are `size` bytes and hold a reference at
+    /// each offset of `references`, released with the function next to it. Addresses below the
+    /// heap, such as 0 for the lists of empty optionals, are not counted.
+    pub fn list_release(&self, size: u32, references: &[(u32, u32)]) -> Function {
+        let (list, count, address, end) = (0, 1, 2, 3);
+        let mut f = Function::new([(3, ValType::I32)]);
+        let mut code = vec![
+            Instruction::LocalGet(list),
+            Instruction::GlobalGet(self.heap_base),
+            Instruction::I32LtU,
+            Instruction::BrIf(0),
+        ];
+        code.extend(header(list));
+        code.extend([
+            Instruction::I32Load(word(4)),
+            Instruction::I32Const(1),
+            Instruction::I32Sub,
+            Instruction::LocalTee(count),
+            Instruction::If(BlockType::Empty),
+        ]);
+        code.extend(header(list));
+        code.extend([
+            Instruction::LocalGet(count),
+            Instruction::I32Store(word(4)),
+            Instruction::Return,
+            Instruction::End,
+        ]);
+        if !references.is_empty() {
+            code.extend([
+                // end = data + length * size
+                Instruction::LocalGet(list),
+                Instruction::I32Load(word(LIST_DATA)),
+                Instruction::LocalTee(address),
+                Instruction::LocalGet(list),
+                Instruction::I32Load(word(LIST_LENGTH)),
+                Instruction::I32Const(size as i32),
+                Instruction::I32Mul,
+                Instruction::I32Add,
+                Instruction::LocalSet(end),
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(address),
+                Instruction::LocalGet(end),
+                Instruction::I32GeU,
+                Instruction::BrIf(1),
+            ]);
+            for (offset, release) in references {
+                code.extend([
+                    Instruction::LocalGet(address),
+                    Instruction::I32Load(word(*offset)),
+                    Instruction::Call(*release),
+                ]);
+            }
+            code.extend([
+                Instruction::LocalGet(address),
+                Instruction::I32Const(size as i32),
+                Instruction::I32Add,
+                Instruction::LocalSet(address),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+            ]);
+        }
+        code.extend([
+            Instruction::LocalGet(list),
+            Instruction::I32Load(word(LIST_DATA)),
+            Instruction::Call(self.free),
+            Instruction::LocalGet(list),
+            Instruction::Call(self.free),
+            Instruction::End,
+        ]);
+        for i in &code {
+            f.instruction(i);
+        }
+        f
+    }
+
+    /// retain(address) adds a reference to the block at `address`. Addresses below the heap,
+    /// such as those of text literals, are not counted.
+    fn retain(&self) -> Function {
+        let address = 0;
+        let mut f = Function::new([]);
+        for i in [
+            Instruction::LocalGet(address),
+            Instruction::GlobalGet(self.heap_base),
+            Instruction::I32LtU,
+            Instruction::BrIf(0),
+            Instruction::LocalGet(address),
+            Instruction::I32Const(HEADER_SIZE as i32),
+            Instruction::I32Sub,
+            Instruction::LocalGet(address),
+            Instruction::I32Const(HEADER_SIZE as i32),
+            Instruction::I32Sub,
+            Instruction::I32Load(word(4)),
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::I32Store(word(4)),
+            Instruction::End,
+        ] {
+            f.instruction(&i);
+        }
+        f
+    }
+
+    /// release(address) removes a reference to the block at `address`, and frees the block once
+    /// there are none left. Addresses below the heap are not counted.
+    fn release(&self) -> Function {
+        let (address, count) = (0, 1);
+        let mut f = Function::new([(1, ValType::I32)]);
+        for i in [
+            Instruction::LocalGet(address),
+            Instruction::GlobalGet(self.heap_base),
+            Instruction::I32LtU,
+            Instruction::BrIf(0),
+            Instruction::LocalGet(address),
+            Instruction::I32Const(HEADER_SIZE as i32),
+            Instruction::I32Sub,
+            Instruction::I32Load(word(4)),
+            Instruction::I32Const(1),
+            Instruction::I32Sub,
+            Instruction::LocalTee(count),
+            Instruction::I32Eqz,
+            Instruction::If(BlockType::Empty),
+            Instruction::LocalGet(address),
+            Instruction::Call(self.free),
+            Instruction::Else,
+            Instruction::LocalGet(address),
+            Instruction::I32Const(HEADER_SIZE as i32),
+            Instruction::I32Sub,
+            Instruction::LocalGet(count),
+            Instruction::I32Store(word(4)),
+            Instruction::End,
+            Instruction::End,
+        ] {
+            f.instruction(&i);
+        }
+        f
+    }
+
+    /// digits(address, value, count) writes the decimal digits of the unsigned integer `value`
+    /// at `address`, with leading zeros up to `count` digits, and returns the address after the
+    /// last one.
+    fn digits(&self) -> Function {
+        let (address, value, count, rest, length, end, position) = (0, 1, 2, 3, 4, 5, 6);
+        function(
+            &[ValType::I64, ValType::I32, ValType::I32, ValType::I32],
+            &[
+                Instruction::I32Const(1),
+                Instruction::LocalSet(length),
+                Instruction::LocalGet(value),
+                Instruction::LocalSet(rest),
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(rest),
+                Instruction::I64Const(10),
+                Instruction::I64DivU,
+                Instruction::LocalTee(rest),
+                Instruction::I64Eqz,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(length),
+                Instruction::I32Const(1),
+                Instruction::I32Add,
+                Instruction::LocalSet(length),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                // end = address + max(length, count)
+                Instruction::LocalGet(address),
+                Instruction::LocalGet(length),
+                Instruction::LocalGet(count),
+                Instruction::LocalGet(length),
+                Instruction::LocalGet(count),
+                Instruction::I32GtS,
+                Instruction::Select,
+                Instruction::I32Add,
+                Instruction::LocalTee(end),
+                Instruction::LocalSet(position),
+                // The digits are written from the last one
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(position),
+                Instruction::I32Const(1),
+                Instruction::I32Sub,
+                Instruction::LocalTee(position),
+                Instruction::LocalGet(value),
+                Instruction::I64Const(10),
+                Instruction::I64RemU,
+                Instruction::I32WrapI64,
+                Instruction::I32Const(b'0' as i32),
+                Instruction::I32Add,
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(value),
+                Instruction::I64Const(10),
+                Instruction::I64DivU,
+                Instruction::LocalSet(value),
+                Instruction::LocalGet(position),
+                Instruction::LocalGet(address),
+                Instruction::I32Ne,
+                Instruction::BrIf(0),
+                Instruction::End,
+                Instruction::LocalGet(end),
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// int_text(value, signed) returns a new text holding the decimal digits of `value`, which
+    /// is a signed integer if `signed` is 1 and an unsigned one otherwise.
+    fn int_text(&self) -> Function {
+        let (value, signed, negative, block) = (0, 1, 2, 3);
+        function(
+            &[ValType::I32, ValType::I32],
+            &[
+                Instruction::LocalGet(signed),
+                Instruction::LocalGet(value),
+                Instruction::I64Const(0),
+                Instruction::I64LtS,
+                Instruction::I32And,
+                Instruction::LocalTee(negative),
+                Instruction::If(BlockType::Empty),
+                // The magnitude of the smallest value only fits unsigned, which is how it is
+                // written
+                Instruction::I64Const(0),
+                Instruction::LocalGet(value),
+                Instruction::I64Sub,
+                Instruction::LocalSet(value),
+                Instruction::End,
+                // A sign and at most 20 digits
+                Instruction::I32Const(21),
+                Instruction::Call(self.alloc),
+                Instruction::LocalTee(block),
+                Instruction::I32Const(b'-' as i32),
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(negative),
+                Instruction::I32Add,
+                Instruction::LocalGet(value),
+                Instruction::I32Const(1),
+                Instruction::Call(self.digits),
+                Instruction::LocalGet(block),
+                Instruction::I32Sub,
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// float_text(value) returns a new text holding a float: its integer part and, unless they
+    /// are all zeros, a dot and its first six decimals, rounded, without trailing zeros. NaN and
+    /// the infinities are `NaN`, `inf` and `-inf`.
+    fn float_text(&self) -> Function {
+        let (value, negative, integer, fraction, places, block, end) = (0, 1, 2, 3, 4, 5, 6);
+        // The bytes of a text of at most 4 bytes, stored as a single word
+        let word_of = |text: &str| {
+            let mut bytes = [0; 4];
+            bytes[..text.len()].copy_from_slice(text.as_bytes());
+            i32::from_le_bytes(bytes)
+        };
+        function(
+            &[
+                ValType::I32,
+                ValType::I64,
+                ValType::I64,
+                ValType::I32,
+                ValType::I32,
+                ValType::I32,
+            ],
+            &[
+                // A sign, at most 20 digits, a dot and 6 decimals
+                Instruction::I32Const(28),
+                Instruction::Call(self.alloc),
+                Instruction::LocalTee(block),
+                Instruction::I32Const(b'-' as i32),
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(value),
+                Instruction::LocalGet(value),
+                Instruction::F64Ne,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(block),
+                Instruction::I32Const(word_of("NaN")),
+                Instruction::I32Store(word(0)),
+                Instruction::LocalGet(block),
+                Instruction::I32Const(3),
+                Instruction::Return,
+                Instruction::End,
+                // Negative values, -0 included, are written as their magnitude after the sign
+                Instruction::LocalGet(value),
+                Instruction::I64ReinterpretF64,
+                Instruction::I64Const(0),
+                Instruction::I64LtS,
+                Instruction::LocalTee(negative),
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(value),
+                Instruction::F64Neg,
+                Instruction::LocalSet(value),
+                Instruction::End,
+                Instruction::LocalGet(value),
+                Instruction::F64Const(f64::INFINITY),
+                Instruction::F64Eq,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(negative),
+                Instruction::I32Add,
+                Instruction::I32Const(word_of("inf")),
+                // Unaligned after a sign
+                Instruction::I32Store(byte(0)),
+                Instruction::LocalGet(block),
+                Instruction::I32Const(3),
+                Instruction::LocalGet(negative),
+                Instruction::I32Add,
+                Instruction::Return,
+                Instruction::End,
+                Instruction::LocalGet(value),
+                Instruction::I64TruncSatF64U,
+                Instruction::LocalSet(integer),
+                // fraction = nearest((value - trunc(value)) * 1e6), carried over to the integer
+                // part when it rounds up to 1
+                Instruction::LocalGet(value),
+                Instruction::LocalGet(value),
+                Instruction::F64Trunc,
+                Instruction::F64Sub,
+                Instruction::F64Const(1e6),
+                Instruction::F64Mul,
+                Instruction::F64Nearest,
+                Instruction::I64TruncSatF64U,
+                Instruction::LocalTee(fraction),
+                Instruction::I64Const(1_000_000),
+                Instruction::I64GeU,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(integer),
+                Instruction::I64Const(1),
+                Instruction::I64Add,
+                Instruction::LocalSet(integer),
+                Instruction::I64Const(0),
+                Instruction::LocalSet(fraction),
+                Instruction::End,
+                // Trailing zeros of the decimals are dropped
+                Instruction::I32Const(6),
+                Instruction::LocalSet(places),
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(fraction),
+                Instruction::I64Eqz,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(fraction),
+                Instruction::I64Const(10),
+                Instruction::I64RemU,
+                Instruction::I64Const(0),
+                Instruction::I64Ne,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(fraction),
+                Instruction::I64Const(10),
+                Instruction::I64DivU,
+                Instruction::LocalSet(fraction),
+                Instruction::LocalGet(places),
+                Instruction::I32Const(1),
+                Instruction::I32Sub,
+                Instruction::LocalSet(places),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(negative),
+                Instruction::I32Add,
+                Instruction::LocalGet(integer),
+                Instruction::I32Const(1),
+                Instruction::Call(self.digits),
+                Instruction::LocalSet(end),
+                Instruction::LocalGet(fraction),
+                Instruction::I64Const(0),
+                Instruction::I64Ne,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(end),
+                Instruction::I32Const(b'.' as i32),
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(end),
+                Instruction::I32Const(1),
+                Instruction::I32Add,
+                Instruction::LocalGet(fraction),
+                Instruction::LocalGet(places),
+                Instruction::Call(self.digits),
+                Instruction::LocalSet(end),
+                Instruction::End,
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(end),
+                Instruction::LocalGet(block),
+                Instruction::I32Sub,
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// char_text(code_point) returns a new text holding a character, encoded in UTF-8.
+    fn char_text(&self) -> Function {
+        let (c, length, i, block) = (0, 1, 2, 3);
+        function(
+            &[ValType::I32, ValType::I32, ValType::I32],
+            &[
+                // length = 1 + (c >= 0x80) + (c >= 0x800) + (c >= 0x10000)
+                Instruction::I32Const(1),
+                Instruction::LocalGet(c),
+                Instruction::I32Const(0x80),
+                Instruction::I32GeU,
+                Instruction::I32Add,
+                Instruction::LocalGet(c),
+                Instruction::I32Const(0x800),
+                Instruction::I32GeU,
+                Instruction::I32Add,
+                Instruction::LocalGet(c),
+                Instruction::I32Const(0x10000),
+                Instruction::I32GeU,
+                Instruction::I32Add,
+                Instruction::LocalTee(length),
+                Instruction::LocalSet(i),
+                Instruction::I32Const(4),
+                Instruction::Call(self.alloc),
+                Instruction::LocalSet(block),
+                // Continuation bytes hold 6 bits each, from the last one
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(i),
+                Instruction::I32Const(1),
+                Instruction::I32LeU,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(i),
+                Instruction::I32Const(1),
+                Instruction::I32Sub,
+                Instruction::LocalTee(i),
+                Instruction::I32Add,
+                Instruction::LocalGet(c),
+                Instruction::I32Const(0x3f),
+                Instruction::I32And,
+                Instruction::I32Const(0x80),
+                Instruction::I32Or,
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(c),
+                Instruction::I32Const(6),
+                Instruction::I32ShrU,
+                Instruction::LocalSet(c),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                // The first byte marks the length: 0xc0, 0xe0 or 0xf0 for 2, 3 or 4 bytes
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(c),
+                Instruction::I32Const(0xf00),
+                Instruction::LocalGet(length),
+                Instruction::I32ShrU,
+                Instruction::I32Const(0xf0),
+                Instruction::I32And,
+                Instruction::I32Const(0),
+                Instruction::LocalGet(length),
+                Instruction::I32Const(1),
+                Instruction::I32GtU,
+                Instruction::Select,
+                Instruction::I32Or,
+                Instruction::I32Store8(byte(0)),
+                Instruction::LocalGet(block),
+                Instruction::LocalGet(length),
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// text_chars(address, length) returns a new list holding the characters of a text, decoded
+    /// from UTF-8.
+    fn text_chars(&self) -> Function {
+        let (address, length, list, data, next, end, b, c) = (0, 1, 2, 3, 4, 5, 6, 7);
+        function(
+            &[ValType::I32; 6],
+            &[
+                // A text has at most as many characters as bytes
+                Instruction::LocalGet(length),
+                Instruction::I32Const(4),
+                Instruction::Call(self.list_new),
+                Instruction::LocalTee(list),
+                Instruction::I32Load(word(LIST_DATA)),
+                Instruction::LocalTee(data),
+                Instruction::LocalSet(next),
+                Instruction::LocalGet(address),
+                Instruction::LocalGet(length),
+                Instruction::I32Add,
+                Instruction::LocalSet(end),
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(address),
+                Instruction::LocalGet(end),
+                Instruction::I32GeU,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(address),
+                Instruction::I32Load8U(byte(0)),
+                Instruction::LocalSet(b),
+                Instruction::LocalGet(address),
+                Instruction::I32Const(1),
+                Instruction::I32Add,
+                Instruction::LocalSet(address),
+                // Continuation bytes add 6 bits to the character before them
+                Instruction::LocalGet(b),
+                Instruction::I32Const(0xc0),
+                Instruction::I32And,
+                Instruction::I32Const(0x80),
+                Instruction::I32Eq,
+                Instruction::LocalGet(next),
+                Instruction::LocalGet(data),
+                Instruction::I32Ne,
+                Instruction::I32And,
+                Instruction::If(BlockType::Empty),
+                Instruction::LocalGet(next),
+                Instruction::I32Const(4),
+                Instruction::I32Sub,
+                Instruction::LocalGet(c),
+                Instruction::I32Const(6),
+                Instruction::I32Shl,
+                Instruction::LocalGet(b),
+                Instruction::I32Const(0x3f),
+                Instruction::I32And,
+                Instruction::I32Or,
+                Instruction::LocalTee(c),
+                Instruction::I32Store(word(0)),
+                Instruction::Else,
+                // Other bytes start a character with the bits after their leading ones and the
+                // 0 that follows them: c = b & (0xff >> (leading ones + 1))
+                Instruction::LocalGet(next),
+                Instruction::LocalGet(b),
+                Instruction::I32Const(0xff),
+                Instruction::LocalGet(b),
+                Instruction::I32Const(-1),
+                Instruction::I32Xor,
+                Instruction::I32Const(24),
+                Instruction::I32Shl,
+                Instruction::I32Clz,
+                Instruction::I32Const(1),
+                Instruction::I32Add,
+                Instruction::I32ShrU,
+                Instruction::I32And,
+                Instruction::LocalTee(c),
+                Instruction::I32Store(word(0)),
+                Instruction::LocalGet(next),
+                Instruction::I32Const(4),
+                Instruction::I32Add,
+                Instruction::LocalSet(next),
+                Instruction::End,
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                Instruction::LocalGet(list),
+                Instruction::LocalGet(next),
+                Instruction::LocalGet(data),
+                Instruction::I32Sub,
+                Instruction::I32Const(2),
+                Instruction::I32ShrU,
+                Instruction::I32Store(word(LIST_LENGTH)),
+                Instruction::LocalGet(list),
+                Instruction::End,
+            ],
+        )
+    }
+}
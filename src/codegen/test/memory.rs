@@ -0,0 +1,329 @@
+use wasmtime::{Engine, Instance, Memory, Store, TypedFunc};
+
+use crate::{hir::interpreter, lang::Type, opt};
+
+use super::{
+    super::{
+        compile,
+        memory::{Layouts, HEADER_SIZE, LIST_CAPACITY, LIST_DATA, LIST_LENGTH},
+    },
+    lower_source,
+};
+
+const PAGE_SIZE: usize = 1 << 16;
+
+struct Runtime {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: TypedFunc<i32, ()>,
+    list_new: TypedFunc<(i32, i32), i32>,
+    list_reserve: TypedFunc<(i32, i32, i32), ()>,
+}
+
+impl Runtime {
+    /// new instantiates a module holding nothing but the runtime.
+    fn new() -> Self {
+        let module = lower_source("func main() {\n}\n");
+        let engine = Engine::default();
+        let wasm = wasmtime::Module::new(&engine, compile(&module).unwrap()).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &wasm, &[]).unwrap();
+        Self {
+            memory: instance.get_memory(&mut store, "memory").unwrap(),
+            alloc: instance.get_typed_func(&mut store, "$alloc").unwrap(),
+            free: instance.get_typed_func(&mut store, "$free").unwrap(),
+            list_new: instance.get_typed_func(&mut store, "$list_new").unwrap(),
+            list_reserve: instance
+                .get_typed_func(&mut store, "$list_reserve")
+                .unwrap(),
+            store,
+        }
+    }
+
+    fn alloc(&mut self, size: i32) -> i32 {
+        self.alloc.call(&mut self.store, size).unwrap()
+    }
+
+    fn free(&mut self, address: i32) {
+        self.free.call(&mut self.store, address).unwrap()
+    }
+
+    fn read(&self, address: i32) -> i32 {
+        let mut bytes = [0; 4];
+        (self.memory.read(&self.store, address as usize, &mut bytes)).unwrap();
+        i32::from_le_bytes(bytes)
+    }
+
+    fn write(&mut self, address: i32, value: i32) {
+        (self.memory)
+            .write(&mut self.store, address as usize, &value.to_le_bytes())
+            .unwrap()
+    }
+}
+
+#[test]
+fn blocks_are_aligned_and_disjoint() {
+    let mut rt = Runtime::new();
+    let a = rt.alloc(3);
+    let b = rt.alloc(12);
+    let c = rt.alloc(0);
+    assert!(a > 0);
+    assert_eq!([a % 8, b % 8, c % 8], [0, 0, 0]);
+    assert!(b >= a + 8 + HEADER_SIZE as i32);
+    assert!(c >= b + 16 + HEADER_SIZE as i32);
+    // Block sizes are rounded up and stored in the header
+    assert_eq!(rt.read(b - HEADER_SIZE as i32), 16);
+}
+
+#[test]
+fn freed_blocks_are_reused() {
+    let mut rt = Runtime::new();
+    let a = rt.alloc(16);
+    let b = rt.alloc(16);
+    rt.free(a);
+    assert_eq!(rt.alloc(16), a);
+
+    // Blocks are reused for sizes that round up to the same power of two
+    let large = rt.alloc(64);
+    assert_eq!(rt.read(large - HEADER_SIZE as i32), 64);
+    rt.free(large);
+    rt.free(b);
+    assert_eq!(rt.alloc(9), b);
+    let small = rt.alloc(8);
+    assert_ne!(small, large);
+    assert_eq!(rt.alloc(33), large);
+
+    // Freeing 0 does nothing
+    rt.free(0);
+    assert!(rt.alloc(64) > small);
+}
+
+#[test]
+fn memory_grows_as_needed() {
+    let mut rt = Runtime::new();
+    let pages = rt.memory.size(&rt.store);
+    let size = 3 * PAGE_SIZE as i32;
+    let a = rt.alloc(size);
+    assert!(rt.memory.size(&rt.store) >= pages + 3);
+    rt.write(a + size - 4, 42);
+    assert_eq!(rt.read(a + size - 4), 42);
+}
+
+#[test]
+fn lists_grow() {
+    let mut rt = Runtime::new();
+    let list = rt.list_new.call(&mut rt.store, (2, 4)).unwrap();
+    assert_eq!(rt.read(list + LIST_LENGTH as i32), 0);
+    assert_eq!(rt.read(list + LIST_CAPACITY as i32), 2);
+
+    let data = rt.read(list + LIST_DATA as i32);
+    rt.write(data, 7);
+    rt.write(data + 4, 8);
+    rt.write(list + LIST_LENGTH as i32, 2);
+
+    // Room for one more doubles the capacity, room for more grows it to what is needed
+    rt.list_reserve.call(&mut rt.store, (list, 1, 4)).unwrap();
+    assert_eq!(rt.read(list + LIST_CAPACITY as i32), 4);
+    rt.list_reserve.call(&mut rt.store, (list, 7, 4)).unwrap();
+    assert_eq!(rt.read(list + LIST_CAPACITY as i32), 9);
+    let moved = rt.read(list + LIST_DATA as i32);
+    assert_ne!(moved, data);
+    assert_eq!([rt.read(moved), rt.read(moved + 4)], [7, 8]);
+
+    // There is room already
+    rt.list_reserve.call(&mut rt.store, (list, 7, 4)).unwrap();
+    assert_eq!(rt.read(list + LIST_DATA as i32), moved);
+}
+
+#[test]
+fn struct_layouts() {
+    let module = lower_source(
+        "
+struct Item {
+	visible bool
+	count int
+	label text
+	weight float32
+}
+
+func main() {
+}
+",
+    );
+    let layouts = Layouts::new(&module);
+    let item = Type::Struct("Item".into());
+    let layout = layouts.layout(&item);
+    let offsets: Vec<u32> = layout.values.iter().map(|(offset, _)| *offset).collect();
+    assert_eq!(offsets, [0, 8, 16, 20, 24]);
+    assert_eq!((layout.size, layout.align), (32, 8));
+    assert_eq!(layouts.field_offset(&item, 2), 16);
+    assert_eq!(layouts.field_offset(&item, 3), 24);
+
+    let text = layouts.layout(&Type::Text);
+    assert_eq!((text.size, text.align), (8, 4));
+}
+
+/// STRESS builds and drops texts in loops, through every way a text can be stored.
+const STRESS: &str = "
+var {
+	latest text = \"\"
+}
+
+struct Entry {
+	name text
+	count int
+}
+
+func label(i int) text {
+	if i % 2 == 0 {
+		return \"even\"
+	}
+	return \"odd\"
+}
+
+func join(a text, b text) text {
+	return a + \", \" + b
+}
+
+func checked(s text) result[text, text] {
+	if s == \"\" {
+		return err(\"empty \" + s)
+	}
+	return ok(s + \".\")
+}
+
+func describe(i int) result[text, text] {
+	defer join(\"done\", latest)
+	var {
+		s text = checked(label(i))?
+	}
+	latest = s
+	return ok(s + s)
+}
+
+func main() int {
+	var {
+		total int = 0
+		e Entry = Entry{name = \"\", count = 0}
+		last ?text = nil
+		s text = \"\"
+		pair (text, int) = (\"\", 0)
+		first text = \"\"
+	}
+	for i in range(20000) {
+		s = join(label(i), label(i + 1))
+		e.name = s + \"!\"
+		e.count += 1
+		last = e.name
+		pair = (s + s, i)
+		if s == \"even, odd\" && (last ?? \"\") == \"even, odd!\" {
+			total += 1
+		}
+		describe(i)
+		first, _ = pair
+		join(first, Entry{name = s, count = i}.name)
+	}
+	return total + e.count
+}
+";
+
+#[test]
+fn texts_are_freed() {
+    assert_runs_in_constant_memory(STRESS, 30000);
+}
+
+/// LIST_STRESS does the work of `examples/05_lists_loops.tiger` in a loop, builds and drops
+/// lists through every way a list can be stored, and iterates over the characters of a text.
+const LIST_STRESS: &str = "
+struct Inventory {
+	name text
+	items list[text]
+}
+
+func fizzbuzz(i int) text {
+	if i % 3 == 0 && i % 5 == 0 {
+		return \"fizzbuzz\"
+	} else if i % 3 == 0 {
+		return \"fizz\"
+	} else if i % 5 == 0 {
+		return \"buzz\"
+	} else {
+		return \"\"
+	}
+}
+
+func answers(n int) list[text] {
+	var {
+		answers list[text] = list[text]{}
+	}
+	for i in range(1, n + 1) {
+		append(answers, fizzbuzz(i))
+	}
+	return answers
+}
+
+func main() int {
+	var {
+		total int = 0
+		answers list[text] = list[text]{}
+		grid list[list[text]] = list[list[text]]{}
+		inventory Inventory = Inventory{name = \"\", items = list[text]{}}
+	}
+	for round in range(5000) {
+		answers = answers(15)
+		for num, response in answers {
+			if response != \"\" {
+				total += num + 1
+			}
+		}
+		for i in range(len(answers)) {
+			if answers[i] == \"\" {
+				answers[i] = \"number \" + fizzbuzz(3)
+			}
+		}
+		grid = list[list[text]]{answers, list[text]{answers[0] + \"!\"}}
+		append(grid[1], answers[2])
+		inventory = Inventory{name = grid[1][0], items = grid[1]}
+		append(inventory.items, inventory.name)
+		total += len(answers(3)) + len(inventory.items)
+		for i, c in inventory.name {
+			if c == '!' {
+				total += i
+			}
+		}
+	}
+	return total
+}
+";
+
+#[test]
+fn lists_are_freed() {
+    assert_runs_in_constant_memory(LIST_STRESS, 385000);
+}
+
+/// assert_runs_in_constant_memory checks that `main` of `source` returns `expected` in the
+/// interpreter and in the compiled module, at every optimization level, without growing the
+/// memory of the module.
+fn assert_runs_in_constant_memory(source: &str, expected: i64) {
+    let module = lower_source(source);
+    let result = format!("main -> Ok(Int({}))", expected);
+    assert_eq!(interpreter::run(&module), [result]);
+    for level in [opt::Level::O0, opt::Level::O2] {
+        let mut module = lower_source(source);
+        opt::optimize(&mut module, level);
+        let engine = Engine::default();
+        let wasm = wasmtime::Module::new(&engine, compile(&module).unwrap()).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &wasm, &[]).unwrap();
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let pages = memory.size(&store);
+
+        let main = instance
+            .get_typed_func::<(), i64>(&mut store, "main")
+            .unwrap();
+        assert_eq!(main.call(&mut store, ()).unwrap(), expected);
+        // Without freeing, the loops of the stress tests allocate megabytes
+        assert_eq!(memory.size(&store), pages, "at {:?}", level);
+    }
+}
@@ -0,0 +1,815 @@
+use std::collections::HashMap;
+
+use wasmtime::{Engine, Linker, Store, Val, ValType};
+
+use crate::{
+    hir::{
+        self,
+        interpreter::{self, Value},
+        test::{lower_examples, lower_source},
+        FunctionKind,
+    },
+    lang::Type,
+    opt,
+};
+
+use super::{compile, compile_for, exports, validate, Context, DataStats, Target};
+
+mod memory;
+
+/// run compiles `module`, runs `main` and the tests under wasmtime, and returns what they
+/// printed and returned, in the same form as `interpreter::run`.
+fn run(module: &hir::Module) -> Vec<String> {
+    let binary = compile(module).unwrap();
+    if let Err(e) = validate(&binary) {
+        panic!("{}", e);
+    }
+    let imports = Context::new(module, Target::Host).unwrap().imports;
+    let structs: HashMap<String, Vec<Type>> = (module.structs.iter())
+        .map(|s| {
+            (
+                s.name.clone(),
+                s.fields.iter().map(|(_, t)| t.clone()).collect(),
+            )
+        })
+        .collect();
+
+    let engine = Engine::default();
+    let wasm = wasmtime::Module::new(&engine, &binary).unwrap();
+    let mut store = Store::new(&engine, vec![]);
+    let mut linker: Linker<Vec<String>> = Linker::new(&engine);
+    for (import, imported) in wasm.imports().zip(imports) {
+        let path = module.imports[imported.import].path.clone();
+        let structs = structs.clone();
+        let ty = import.ty().unwrap_func().clone();
+        let result_types: Vec<ValType> = ty.results().collect();
+        linker
+            .func_new(
+                import.module(),
+                import.name(),
+                ty,
+                move |mut caller, args, results| {
+                    let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+                    let memory = memory.data(&caller).to_vec();
+                    let mut args = args.iter().cloned();
+                    let values: Vec<Value> = (imported.params.iter())
+                        .map(|t| decode(t, &mut args, &memory, &structs))
+                        .collect();
+                    caller.data_mut().push(format!("{}{:?}", path, values));
+                    // Imports return zeros, as they do in the interpreter
+                    for (result, ty) in results.iter_mut().zip(&result_types) {
+                        *result = match ty {
+                            ValType::I64 => Val::I64(0),
+                            ValType::F32 => Val::F32(0),
+                            ValType::F64 => Val::F64(0),
+                            _ => Val::I32(0),
+                        };
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+    }
+    let instance = linker.instantiate(&mut store, &wasm).unwrap();
+
+    let mut results = HashMap::new();
+    for func in &module.functions {
+        let name = match func.kind {
+            FunctionKind::Function if func.name == "main" => func.name.clone(),
+            FunctionKind::Test => format!("test.{}", func.name),
+            _ => continue,
+        };
+        let f = instance.get_func(&mut store, &name).unwrap();
+        let mut values = vec![Val::I32(0); f.ty(&store).results().len()];
+        let res = match f.call(&mut store, &[], &mut values) {
+            Ok(()) => {
+                let memory = instance.get_memory(&mut store, "memory").unwrap();
+                let memory = memory.data(&store);
+                let mut values = values.into_iter();
+                Ok(decode(&func.return_type, &mut values, memory, &structs))
+            }
+            Err(trap) => Err(trap.to_string()),
+        };
+        results.insert(func.name.clone(), res);
+    }
+
+    let mut output = store.into_data();
+    let mut names: Vec<&String> = results.keys().collect();
+    names.sort();
+    for name in names {
+        output.push(format!("{} -> {:?}", name, results[name]));
+    }
+    output
+}
+
+/// decode reads a value of type `ttype` from the WASM values representing it.
+fn decode(
+    ttype: &Type,
+    values: &mut impl Iterator<Item = Val>,
+    memory: &[u8],
+    structs: &HashMap<String, Vec<Type>>,
+) -> Value {
+    let decode_all = |types: &[Type], values: &mut dyn Iterator<Item = Val>| {
+        let mut values = values;
+        types
+            .iter()
+            .map(|t| decode(t, &mut values, memory, structs))
+            .collect()
+    };
+    match ttype {
+        Type::Void => Value::Void,
+        Type::Text => match (values.next(), values.next()) {
+            (Some(Val::I32(address)), Some(Val::I32(len))) => {
+                let bytes = &memory[address as usize..(address + len) as usize];
+                Value::Text(String::from_utf8(bytes.to_vec()).unwrap())
+            }
+            v => panic!("bad text {:?}", v),
+        },
+        Type::Struct(name) => Value::Struct(decode_all(&structs[name], values)),
+        Type::Tuple(types) => Value::Tuple(decode_all(types, values)),
+        Type::Optional(t) => {
+            let present = values.next().unwrap().unwrap_i32() != 0;
+            let value = decode(t, values, memory, structs);
+            match present {
+                true => value,
+                false => Value::Nil,
+            }
+        }
+        Type::Result(t, e) => {
+            let is_err = values.next().unwrap().unwrap_i32() != 0;
+            let value = decode(t, values, memory, structs);
+            let error = decode(e, values, memory, structs);
+            match is_err {
+                true => Value::Err(Box::new(error)),
+                false => Value::Ok(Box::new(value)),
+            }
+        }
+        t => {
+            let signed = super::types::is_signed(t);
+            match values.next().unwrap() {
+                Val::I32(i) if *t == Type::Bool => Value::Bool(i != 0),
+                Val::I32(i) if *t == Type::Character => {
+                    Value::Char(char::from_u32(i as u32).unwrap())
+                }
+                Val::I32(i) if signed => Value::Int(i as i128),
+                Val::I32(i) => Value::Int(i as u32 as i128),
+                Val::I64(i) if signed => Value::Int(i as i128),
+                Val::I64(i) => Value::Int(i as u64 as i128),
+                Val::F32(bits) => Value::Float(f32::from_bits(bits) as f64),
+                Val::F64(bits) => Value::Float(f64::from_bits(bits)),
+                v => panic!("bad {} {:?}", t, v),
+            }
+        }
+    }
+}
+
+const FEATURES: &str = "
+use {
+	io.print_line
+}
+
+var {
+	origin Point = Point{x = 1, y = 2}
+	calls int32 = 0
+}
+
+struct Point {
+	x int
+	y int
+}
+
+struct Segment {
+	start Point
+	end Point
+}
+
+func (p Point) sum() int {
+	return p.x + p.y
+}
+
+func (var p Point) shift(d int) {
+	p.x += d
+	p.y += d
+}
+
+func (var s Segment) stretch(d int) int {
+	s.end.shift(d)
+	return s.end.sum()
+}
+
+func divmod(a int, b int) (int, int) {
+	calls += 1
+	return a / b, a % b
+}
+
+func parse(s text) result[int, text] {
+	if s == \"one\" {
+		return ok(1)
+	}
+	return err(\"unknown \" + s)
+}
+
+func twice(s text) result[int, text] {
+	defer print_line(\"leaving \" + s)
+	var {
+		n int = parse(s)?
+	}
+	print_line(n * 2)
+	return ok(n * 2)
+}
+
+func countdown(n int) {
+	for i in range(n) {
+		defer print_line(i)
+		if i == 2 {
+			break
+		}
+		defer print_line(\"tick\")
+	}
+}
+
+func find(x int) ?int {
+	if x > 2 {
+		return x * 10
+	}
+	return nil
+}
+
+func lists() {
+	var {
+		names list[text] = list[text]{\"a\", \"b\"}
+		points list[Point] = list[Point]{origin}
+		total int = 0
+	}
+	for i in range(10) {
+		append(points, Point{x = i, y = i * 2})
+	}
+	points[0].shift(100)
+	points[1].y = 7
+	points[2] = points[3]
+	for i, point in points {
+		total += point.sum() * i
+	}
+	print_line(total)
+	print_line(len(points), points[0].x, points[1].y)
+	names[0] = names[1] + \"!\"
+	append(names, names[0])
+	for name in names {
+		print_line(name)
+	}
+}
+
+func main() {
+	var {
+		q int = 0
+		r int = 0
+		p Point = origin
+		small int8 = 100
+		byte uint8 = 250
+		ratio float32 = 2.75
+		name text = \"tiger\"
+		segment Segment = Segment{start = origin, end = origin}
+	}
+	q, r = divmod(17, 5)
+	print_line(q)
+	print_line(r)
+	p.y = p.sum()
+	print_line(p.y)
+	p.shift(10)
+	print_line(p.sum())
+	print_line(segment.stretch(5))
+	print_line(segment.end.x)
+	small += 100
+	byte += 10
+	print_line(small)
+	print_line(byte)
+	print_line(ratio as int8)
+	print_line(-1000.5 as int8)
+	print_line(small as uint)
+	print_line(name + \"!\")
+	print_line(name < \"tie\")
+	print_line(find(3) ?? 0)
+	print_line(find(1) ?? -1)
+	twice(\"one\")
+	twice(\"two\")
+	countdown(5)
+	print_line(calls)
+	lists()
+}
+
+test points() bool {
+	return origin.sum() == 3 && \"a\" != \"b\"
+}
+";
+
+#[test]
+fn features_run_like_the_interpreter() {
+    let module = lower_source(FEATURES);
+    let expected = interpreter::run(&module);
+    assert_eq!(
+        expected,
+        [
+            "io.print_line[Int(3)]",
+            "io.print_line[Int(2)]",
+            "io.print_line[Int(3)]",
+            "io.print_line[Int(24)]",
+            "io.print_line[Int(13)]",
+            "io.print_line[Int(6)]",
+            "io.print_line[Int(-56)]",
+            "io.print_line[Int(4)]",
+            "io.print_line[Int(2)]",
+            "io.print_line[Int(-128)]",
+            "io.print_line[Int(18446744073709551560)]",
+            "io.print_line[Text(\"tiger!\")]",
+            "io.print_line[Bool(false)]",
+            "io.print_line[Int(30)]",
+            "io.print_line[Int(-1)]",
+            "io.print_line[Int(2)]",
+            "io.print_line[Text(\"leaving one\")]",
+            "io.print_line[Text(\"leaving two\")]",
+            "io.print_line[Text(\"tick\")]",
+            "io.print_line[Int(0)]",
+            "io.print_line[Text(\"tick\")]",
+            "io.print_line[Int(1)]",
+            "io.print_line[Int(2)]",
+            "io.print_line[Int(1)]",
+            "io.print_line[Int(1003)]",
+            "io.print_line[Int(11), Int(101), Int(7)]",
+            "io.print_line[Text(\"b!\")]",
+            "io.print_line[Text(\"b\")]",
+            "io.print_line[Text(\"b!\")]",
+            "main -> Ok(Void)",
+            "points -> Ok(Bool(true))",
+        ]
+    );
+    assert_eq!(run(&module), expected);
+
+    let mut optimized = lower_source(FEATURES);
+    opt::optimize(&mut optimized, opt::Level::O2);
+    assert_eq!(run(&optimized), expected);
+}
+
+#[test]
+fn values_convert_to_text() {
+    let source = "
+use {
+	io.print_line
+}
+
+func join(separator text, values ...text) text {
+	var {
+		res text = \"\"
+	}
+	for i, value in values {
+		if i > 0 {
+			res += separator
+		}
+		res += value
+	}
+	return res
+}
+
+func ratio(a float, b float) float {
+	return a / b
+}
+
+func integers(i int64, u uint64, small int8, tiny uint8) text {
+	return join(\" \", i, u, small, tiny)
+}
+
+func floats(f float, g float32) text {
+	return join(\" \", f, g, f * 1000000, -f)
+}
+
+func main() {
+	print_line(integers(-9223372036854775808, 18446744073709551615, -128, 255))
+	print_line(integers(0, 7, 127, 0))
+	print_line(floats(1.5, 0.25))
+	print_line(floats(-0.0000004, -2.5))
+	print_line(floats(0.9999996, 3.1415926))
+	print_line(join(\" \", ratio(1, 0), ratio(-1, 0), ratio(0, 0), ratio(2, 3)))
+	print_line(join(\", \", 'a', 'é', '€', '🐯', true, false))
+	print_line(join(\"-\") + 42 as text + true as text + 0.5 as text)
+}
+";
+    let module = lower_source(source);
+    let expected = interpreter::run(&module);
+    assert_eq!(
+        expected,
+        [
+            "io.print_line[Text(\"-9223372036854775808 18446744073709551615 -128 255\")]",
+            "io.print_line[Text(\"0 7 127 0\")]",
+            "io.print_line[Text(\"1.5 0.25 1500000 -1.5\")]",
+            "io.print_line[Text(\"-0 -2.5 -0.4 0\")]",
+            "io.print_line[Text(\"1 3.141593 999999.6 -1\")]",
+            "io.print_line[Text(\"inf -inf NaN 0.666667\")]",
+            "io.print_line[Text(\"a, é, €, 🐯, true, false\")]",
+            "io.print_line[Text(\"42true0.5\")]",
+            "main -> Ok(Void)",
+        ]
+    );
+    assert_eq!(run(&module), expected);
+
+    let mut optimized = lower_source(source);
+    opt::optimize(&mut optimized, opt::Level::O2);
+    assert_eq!(run(&optimized), expected);
+}
+
+#[test]
+fn texts_iterate_over_their_characters() {
+    let source = "
+use {
+	io.print_line
+}
+
+func reversed(s text) text {
+	var {
+		res text = \"\"
+	}
+	for c in s {
+		res = c as text + res
+	}
+	return res
+}
+
+func positions(s text, wanted char) list[int] {
+	var {
+		res list[int] = list[int]{}
+	}
+	for i, c in s {
+		if c == wanted {
+			append(res, i)
+		}
+	}
+	return res
+}
+
+func main() int {
+	print_line(reversed(\"tiger 🐯, é€\"))
+	print_line(reversed(\"\"))
+	return len(positions(\"a€a🐯a\", 'a')) * 100 + positions(\"a€a🐯a\", 'a')[2]
+}
+";
+    let module = lower_source(source);
+    let expected = interpreter::run(&module);
+    assert_eq!(
+        expected,
+        [
+            "io.print_line[Text(\"€é ,🐯 regit\")]",
+            "io.print_line[Text(\"\")]",
+            "main -> Ok(Int(304))",
+        ]
+    );
+    assert_eq!(run(&module), expected);
+}
+
+#[test]
+fn compound_assignments_evaluate_their_target_once() {
+    let source = "
+use {
+	io.print_line
+}
+
+struct Point {
+	x int
+}
+
+func next(log list[int]) int {
+	append(log, len(log))
+	return 0
+}
+
+func main() int {
+	var {
+		log list[int] = list[int]{}
+		xs list[int] = list[int]{1, 2}
+		points list[Point] = list[Point]{Point{x = 1}}
+	}
+	xs[next(log)] += 5
+	points[next(log)].x *= 3
+	print_line(xs[0], points[0].x)
+	return len(log)
+}
+";
+    let module = lower_source(source);
+    let expected = interpreter::run(&module);
+    assert_eq!(
+        expected,
+        ["io.print_line[Int(6), Int(3)]", "main -> Ok(Int(2))",]
+    );
+    assert_eq!(run(&module), expected);
+
+    let mut optimized = lower_source(source);
+    opt::optimize(&mut optimized, opt::Level::O2);
+    assert_eq!(run(&optimized), expected);
+}
+
+#[test]
+fn examples_run_like_the_interpreter() {
+    for (path, module) in lower_examples() {
+        assert_eq!(
+            run(&module),
+            interpreter::run(&module),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn indexing_out_of_range_traps() {
+    let module = lower_source(
+        "
+func values() list[int] {
+	return list[int]{1, 2, 3}
+}
+
+test last() bool {
+	return values()[2] == 3
+}
+
+test past_the_end() bool {
+	return values()[3] == 0
+}
+
+test negative() bool {
+	return values()[-1] == 0
+}
+",
+    );
+    let interpreted = interpreter::run(&module);
+    let compiled = run(&module);
+    assert_eq!(interpreted[0], "last -> Ok(Bool(true))");
+    assert_eq!(compiled[0], interpreted[0]);
+    for (compiled, interpreted) in compiled[1..].iter().zip(&interpreted[1..]) {
+        assert!(interpreted.contains("-> Err("), "{}", interpreted);
+        assert!(compiled.contains("-> Err("), "{}", compiled);
+    }
+}
+
+#[test]
+fn wasi_programs_need_main() {
+    let module = lower_source(
+        "
+func answer() int {
+	return 42
+}
+",
+    );
+    assert!(compile(&module).is_ok());
+    let e = compile_for(&module, Target::Wasi).unwrap_err();
+    assert_eq!(
+        e.message,
+        "a program for target wasi needs a `main` function"
+    );
+}
+
+#[test]
+fn invalid_code_is_an_internal_error() {
+    use wasm_encoder::{
+        CodeSection, Function, FunctionSection, Instruction, NameMap, NameSection, TypeSection,
+        ValType,
+    };
+
+    let module = lower_source(
+        "
+func main() int {
+	return 1
+}
+",
+    );
+    // What a broken backend could emit for `main`: an addition of mismatched operands
+    let mut types = TypeSection::new();
+    types.function([], [ValType::I32]);
+    let mut functions = FunctionSection::new();
+    functions.function(0);
+    let mut f = Function::new([]);
+    f.instruction(&Instruction::I32Const(1));
+    f.instruction(&Instruction::I64Const(2));
+    f.instruction(&Instruction::I32Add);
+    f.instruction(&Instruction::End);
+    let mut code = CodeSection::new();
+    code.function(&f);
+    let mut function_names = NameMap::new();
+    function_names.append(0, "main");
+    let mut names = NameSection::new();
+    names.functions(&function_names);
+    let mut binary = wasm_encoder::Module::new();
+    // Custom sections can go anywhere, and this one leaves `i32.add` at the end of the binary
+    binary.section(&types).section(&names).section(&functions);
+    binary.section(&code);
+    let binary = binary.finish();
+
+    let e = validate(&binary).unwrap_err();
+    assert_eq!(e.function.as_deref(), Some("main"));
+    // The offset is that of `i32.add`, the last instruction before `end`
+    assert_eq!(e.offset, Some(binary.len() - 2));
+    assert!(e
+        .to_string()
+        .starts_with("internal compiler error: invalid code for `main`"));
+
+    // Without a name section, functions are known by their index
+    let mut unnamed = wasm_encoder::Module::new();
+    unnamed.section(&types).section(&functions).section(&code);
+    let e = validate(&unnamed.finish()).unwrap_err();
+    assert_eq!(e.function.as_deref(), Some("function 0"));
+
+    assert!(validate(&compile(&module).unwrap()).is_ok());
+}
+
+#[test]
+fn literals_are_stored_once() {
+    let module = lower_source(
+        "
+use {
+	io.print_line
+}
+
+const {
+	greeting text = \"hel\" + \"lo\"
+}
+
+func main() {
+	print_line(\"hello\")
+	print_line(greeting)
+	print_line(\"\")
+}
+
+export func greet() text {
+	return greeting
+}
+",
+    );
+    // The constant is evaluated by the checker, so it is a literal like any other
+    let (_, stats) = compile_for(&module, Target::Host).unwrap();
+    assert_eq!(
+        stats,
+        DataStats {
+            literals: 3,
+            texts: 1,
+            size: 5,
+            saved: 10,
+        }
+    );
+    assert_eq!(run(&module), interpreter::run(&module));
+
+    // The texts that the wasi runtime prints take room, but are not literals
+    let (_, stats) = compile_for(&module, Target::Wasi).unwrap();
+    assert_eq!((stats.literals, stats.texts, stats.saved), (3, 1, 10));
+    assert!(stats.size > 5);
+}
+
+#[test]
+fn extern_functions_exchange_texts_with_the_host() {
+    use wasmtime::{Caller, Extern, ValType};
+
+    let module = lower_source(
+        "
+extern \"host\" func shout(s text) text
+
+export func greet() text {
+	var {
+		greeting text = \"hi\"
+	}
+	return shout(greeting + \" there\") + \"!\"
+}
+",
+    );
+    let engine = Engine::default();
+    let wasm = wasmtime::Module::new(&engine, compile(&module).unwrap()).unwrap();
+    let import = wasm.imports().next().unwrap();
+    assert_eq!((import.module(), import.name()), ("host", "shout"));
+    let ty = import.ty().unwrap_func().clone();
+    assert!(ty.params().eq([ValType::I32, ValType::I32]));
+    assert!(ty.results().eq([ValType::I32, ValType::I32]));
+
+    // The host returns a text of its own, allocated in the module's memory
+    let mut linker: Linker<()> = Linker::new(&engine);
+    linker
+        .func_wrap(
+            "host",
+            "shout",
+            |mut caller: Caller<'_, ()>, address: i32, length: i32| {
+                let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+                    panic!("no memory");
+                };
+                let mut bytes = vec![0; length as usize];
+                memory.read(&caller, address as usize, &mut bytes).unwrap();
+                bytes.make_ascii_uppercase();
+                let alloc = caller.get_export("$alloc").unwrap().into_func().unwrap();
+                let alloc = alloc.typed::<i32, i32>(&caller).unwrap();
+                let res = alloc.call(&mut caller, length).unwrap();
+                memory.write(&mut caller, res as usize, &bytes).unwrap();
+                (res, length)
+            },
+        )
+        .unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = linker.instantiate(&mut store, &wasm).unwrap();
+    let greet = instance.get_typed_func::<(), (i32, i32)>(&mut store, "greet");
+    let (address, length) = greet.unwrap().call(&mut store, ()).unwrap();
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let text = &memory.data(&store)[address as usize..(address + length) as usize];
+    assert_eq!(text, b"HI THERE!");
+}
+
+#[test]
+fn exported_functions_are_described() {
+    let module = lower_source(
+        "
+struct Point {
+	x int
+	y int
+}
+
+export func distance(a Point, b Point) int {
+	return manhattan(a.x - b.x, a.y - b.y)
+}
+
+func manhattan(dx int, dy int) int {
+	return abs(dx) + abs(dy)
+}
+
+func abs(x int) int {
+	if x < 0 {
+		return -x
+	}
+	return x
+}
+",
+    );
+    let binary = compile(&module).unwrap();
+    let engine = Engine::default();
+    let wasm = wasmtime::Module::new(&engine, &binary).unwrap();
+    assert!(wasm.get_export("distance").is_some());
+    assert!(wasm.get_export("manhattan").is_none());
+
+    let exports = exports::read(&binary).unwrap().unwrap();
+    let names: Vec<&String> = exports.imports.keys().collect();
+    assert_eq!(names, ["distance"]);
+    let signature = exports.imports["distance"].signature.as_ref().unwrap();
+    let point = Type::Struct("Point".into());
+    let args: Vec<(&str, &Type)> = signature
+        .args
+        .iter()
+        .map(|a| (a.ident.as_str(), &a.ttype))
+        .collect();
+    assert_eq!(args, [("a", &point), ("b", &point)]);
+    assert_eq!(signature.return_value, Type::Int);
+    let fields = &exports.types["Point"].fields;
+    assert_eq!(fields, &[("x".into(), Type::Int), ("y".into(), Type::Int)]);
+
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::new(&engine).instantiate(&mut store, &wasm).unwrap();
+    let distance = instance.get_typed_func::<(i64, i64, i64, i64), i64>(&mut store, "distance");
+    assert_eq!(distance.unwrap().call(&mut store, (1, 5, 4, 1)).unwrap(), 7);
+}
+
+#[test]
+fn functions_locals_and_globals_are_named() {
+    let module = lower_source(
+        "
+use {
+	io.print_line
+}
+
+var {
+	greeting text = \"hello\"
+}
+
+func greet(name text, times int) {
+	var {
+		i int = 0
+	}
+	while i < times {
+		print_line(greeting + \", \" + name)
+		i += 1
+	}
+}
+
+func main() {
+	greet(\"world\", 2)
+}
+",
+    );
+    let wat = wasmprinter::print_bytes(compile(&module).unwrap()).unwrap();
+    for expected in [
+        "(import \"io\" \"print_line\" (func $io.print_line ",
+        "(func $greet (;1;) (type 0) (param $name.0 i32) (param $name.1 i32) (param $times i64)",
+        "(local $i i64)",
+        "call $io.print_line",
+        "(func $main ",
+        "(func $$alloc ",
+        "(func $$init ",
+        "(global $$heap ",
+        "(global $greeting.0 ",
+        "(global $greeting.1 ",
+    ] {
+        assert!(
+            wat.contains(expected),
+            "`{}` not found in:\n{}",
+            expected,
+            wat
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! The representation of Tiger values as WASM values. Values are not boxed: a value is a
+//! sequence of WASM values, so that it can live in locals and globals, and be passed to and
+//! returned from functions as it is.
+//!
+//! - `bool`, `char` and integers of up to 32 bits are an `i32`. Narrow integers are always kept
+//!   sign or zero extended to 32 bits.
+//! - `int`, `uint` and the other 64 bit integers are an `i64`.
+//! - `float32` is an `f32`, `float` and `float64` are an `f64`.
+//! - `text` is the address and the length in bytes of its UTF-8 encoding in linear memory.
+//! - A struct or a tuple is its fields, in order.
+//! - An optional is an `i32` that is 1 if it holds a value, followed by the value.
+//! - A result is an `i32` that is 1 if it holds an error, followed by the value and the error.
+//! - A list is the `i32` address of its list block in linear memory.
+
+use std::collections::HashMap;
+
+use wasm_encoder::ValType;
+
+use crate::{hir, lang::Type};
+
+/// Reference is what a WASM value holding a reference to the heap refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Reference {
+    /// The bytes of a text.
+    Text,
+    /// The list block of a list of the given type.
+    List(Type),
+}
+
+pub(super) struct Types<'a> {
+    structs: HashMap<&'a str, &'a hir::Struct>,
+}
+
+impl<'a> Types<'a> {
+    /// new returns the representation of the types of `module`, whose structs must not contain
+    /// themselves, as the checker makes sure of.
+    pub fn new(module: &'a hir::Module) -> Self {
+        let structs = module
+            .structs
+            .iter()
+            .map(|s| (s.name.as_str(), s))
+            .collect();
+        Self { structs }
+    }
+
+    /// repr returns the WASM values a value of type `ttype` is made of.
+    pub fn repr(&self, ttype: &Type) -> Vec<ValType> {
+        let mut res = vec![];
+        self.append_repr(ttype, &mut res);
+        res
+    }
+
+    fn append_repr(&self, ttype: &Type, res: &mut Vec<ValType>) {
+        match ttype {
+            Type::Void => (),
+            Type::Text => res.extend([ValType::I32, ValType::I32]),
+            Type::List(_) => res.push(ValType::I32),
+            Type::Struct(name) => {
+                for (_, field) in &self.structs[name.as_str()].fields {
+                    self.append_repr(field, res);
+                }
+            }
+            Type::Tuple(types) => {
+                for t in types {
+                    self.append_repr(t, res);
+                }
+            }
+            Type::Optional(t) => {
+                res.push(ValType::I32);
+                self.append_repr(t, res);
+            }
+            Type::Result(t, e) => {
+                res.push(ValType::I32);
+                self.append_repr(t, res);
+                self.append_repr(e, res);
+            }
+            t => res.push(scalar(t).unwrap()),
+        }
+    }
+
+    /// references returns the positions of the values in the representation of `ttype` that
+    /// hold references to the heap, and what they refer to.
+    pub fn references(&self, ttype: &Type) -> Vec<(usize, Reference)> {
+        let mut res = vec![];
+        self.append_references(ttype, 0, &mut res);
+        res
+    }
+
+    fn append_references(
+        &self,
+        ttype: &Type,
+        mut offset: usize,
+        res: &mut Vec<(usize, Reference)>,
+    ) -> usize {
+        match ttype {
+            Type::Text => res.push((offset, Reference::Text)),
+            Type::List(_) => res.push((offset, Reference::List(ttype.clone()))),
+            Type::Struct(name) => {
+                for (_, field) in &self.structs[name.as_str()].fields {
+                    offset = self.append_references(field, offset, res);
+                }
+                return offset;
+            }
+            Type::Tuple(types) => {
+                for t in types {
+                    offset = self.append_references(t, offset, res);
+                }
+                return offset;
+            }
+            Type::Optional(t) => return self.append_references(t, offset + 1, res),
+            Type::Result(t, e) => {
+                offset = self.append_references(t, offset + 1, res);
+                return self.append_references(e, offset, res);
+            }
+            _ => (),
+        }
+        offset + self.repr(ttype).len()
+    }
+
+    /// add_lists adds the list types that values of type `ttype` can hold, `ttype` included,
+    /// to `lists`, unless they are there already.
+    pub fn add_lists(&self, ttype: &Type, lists: &mut Vec<Type>) {
+        match ttype {
+            Type::List(element) if !lists.contains(ttype) => {
+                lists.push(ttype.clone());
+                self.add_lists(element, lists);
+            }
+            Type::Struct(name) => {
+                if let Some(s) = self.structs.get(name.as_str()) {
+                    for (_, field) in &s.fields {
+                        self.add_lists(field, lists);
+                    }
+                }
+            }
+            Type::Tuple(types) => types.iter().for_each(|t| self.add_lists(t, lists)),
+            Type::Optional(t) => self.add_lists(t, lists),
+            Type::Result(t, e) => {
+                self.add_lists(t, lists);
+                self.add_lists(e, lists);
+            }
+            _ => (),
+        }
+    }
+
+    /// field returns the position of field `index` of struct `ttype` in its representation,
+    /// and the representation of the field.
+    pub fn field(&self, ttype: &Type, index: usize) -> (usize, Vec<ValType>) {
+        let fields = match ttype {
+            Type::Struct(name) => &self.structs[name.as_str()].fields,
+            t => panic!("{} is not a struct", t),
+        };
+        let offset = fields[..index]
+            .iter()
+            .map(|(_, t)| self.repr(t).len())
+            .sum();
+        (offset, self.repr(&fields[index].1))
+    }
+}
+
+/// scalar returns the WASM type of a value that is represented by a single WASM value.
+fn scalar(ttype: &Type) -> Option<ValType> {
+    Some(match ttype {
+        Type::Bool | Type::Character => ValType::I32,
+        Type::Float32 => ValType::F32,
+        Type::Float | Type::Float64 => ValType::F64,
+        t if t.is_integer() && t.bits() == Some(64) => ValType::I64,
+        t if t.is_integer() => ValType::I32,
+        _ => return None,
+    })
+}
+
+/// is_signed returns true for the signed integer types.
+pub(super) fn is_signed(ttype: &Type) -> bool {
+    matches!(ttype.range(), Some((min, _)) if min < 0)
+}
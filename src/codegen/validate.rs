@@ -0,0 +1,57 @@
+//! Validation of the binaries produced by code generation. A binary that does not validate is a
+//! bug of the compiler, which is easier to track down from the function and the instruction it
+//! was found in than from the error of the runtime that refuses to load it.
+
+use wasmparser::{
+    BinaryReaderError, FuncValidatorAllocations, Name, NameSectionReader, Parser, Payload,
+    ValidPayload,
+};
+
+use super::Error;
+
+/// validate checks that `wasm` is a valid WASM module. Functions with invalid code are reported
+/// by the name the name section of the module gives them.
+pub fn validate(wasm: &[u8]) -> Result<(), Error> {
+    let mut validator = wasmparser::Validator::new();
+    let mut allocs = FuncValidatorAllocations::default();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload.map_err(|e| invalid(e, None))?;
+        if let ValidPayload::Func(func, body) =
+            validator.payload(&payload).map_err(|e| invalid(e, None))?
+        {
+            let mut func = func.into_validator(allocs);
+            let index = func.index();
+            func.validate(&body)
+                .map_err(|e| invalid(e, Some(function_name(wasm, index))))?;
+            allocs = func.into_allocations();
+        }
+    }
+    Ok(())
+}
+
+/// function_name returns the name of function `index` of `wasm` from its name section, or its
+/// index if it has none.
+fn function_name(wasm: &[u8], index: u32) -> String {
+    for payload in Parser::new(0).parse_all(wasm).flatten() {
+        let reader = match payload {
+            Payload::CustomSection(reader) if reader.name() == "name" => reader,
+            _ => continue,
+        };
+        for name in NameSectionReader::new(reader.data(), reader.data_offset()).flatten() {
+            if let Name::Function(map) = name {
+                if let Some(naming) = map.into_iter().flatten().find(|n| n.index == index) {
+                    return naming.name.to_string();
+                }
+            }
+        }
+    }
+    format!("function {}", index)
+}
+
+fn invalid(e: BinaryReaderError, function: Option<String>) -> Error {
+    Error {
+        message: e.message().to_string(),
+        function,
+        offset: Some(e.offset()),
+    }
+}
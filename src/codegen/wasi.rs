@@ -0,0 +1,384 @@
+//! Support for the `wasi` target, which makes command-line programs that run under any runtime
+//! implementing WASI preview 1.
+//!
+//! The binary imports what it needs from `wasi_snapshot_preview1` and exports `_start`, which
+//! initializes the globals, runs `main` and, if `main` returns an `int`, exits with it as the
+//! exit code. The checker makes sure that `main` takes no arguments and returns nothing or an
+//! `int`. Calls to the following functions are compiled to support functions calling WASI
+//! instead of being imported:
+//!
+//! - `io.write(s text)` writes a text to the standard output.
+//! - `os.exit(code int)` exits with `code`.
+//! - `os.arg_count() int` and `os.arg(index int) text` return the command-line arguments, the
+//!   first of which is the name of the program. Arguments out of range are empty.
+//! - `os.env(name text) ?text` returns the value of an environment variable, if it is set.
+
+use wasm_encoder::{BlockType, Function, Instruction, ValType};
+
+use super::{
+    runtime::{byte, function, word, Runtime},
+    Signature,
+};
+
+/// The module WASI functions are imported from.
+pub(super) const MODULE: &str = "wasi_snapshot_preview1";
+
+/// The size of the memory the support functions work in, reserved in the data segment.
+pub(super) const SCRATCH_SIZE: u32 = 16;
+
+const STDOUT: i32 = 1;
+
+/// Intrinsic is a function that is part of the target rather than imported from the host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Intrinsic {
+    Write,
+    Exit,
+    ArgCount,
+    Arg,
+    Env,
+}
+
+impl Intrinsic {
+    pub fn from_path(path: &str) -> Option<Self> {
+        Some(match path {
+            "io.write" => Intrinsic::Write,
+            "os.exit" => Intrinsic::Exit,
+            "os.arg_count" => Intrinsic::ArgCount,
+            "os.arg" => Intrinsic::Arg,
+            "os.env" => Intrinsic::Env,
+            _ => return None,
+        })
+    }
+}
+
+/// Wasi holds the indices of the imported WASI functions and of the support functions.
+pub(super) struct Wasi {
+    fd_write: u32,
+    pub proc_exit: u32,
+    args_sizes_get: u32,
+    args_get: u32,
+    environ_sizes_get: u32,
+    environ_get: u32,
+    pub write: u32,
+    c_length: u32,
+    pub arg_count: u32,
+    pub arg: u32,
+    pub env: u32,
+    /// The address of the scratch memory.
+    scratch: u32,
+}
+
+impl Wasi {
+    /// The names of the imported WASI functions, in order.
+    pub const IMPORTS: [&'static str; 6] = [
+        "fd_write",
+        "proc_exit",
+        "args_sizes_get",
+        "args_get",
+        "environ_sizes_get",
+        "environ_get",
+    ];
+
+    /// The names of the support functions, in order.
+    pub const NAMES: [&'static str; 5] = ["$write", "$c_length", "$arg_count", "$arg", "$env"];
+
+    /// new places the imports at `first_import` and the support functions at `first_function`.
+    /// The support functions use the memory at `scratch`.
+    pub fn new(first_import: u32, first_function: u32, scratch: u32) -> Self {
+        let f = first_function;
+        Self {
+            fd_write: first_import,
+            proc_exit: first_import + 1,
+            args_sizes_get: first_import + 2,
+            args_get: first_import + 3,
+            environ_sizes_get: first_import + 4,
+            environ_get: first_import + 5,
+            write: f,
+            c_length: f + 1,
+            arg_count: f + 2,
+            arg: f + 3,
+            env: f + 4,
+            scratch,
+        }
+    }
+
+    /// imports returns the name and the type of each imported function, in order.
+    pub fn imports(&self) -> Vec<(&'static str, Signature)> {
+        use ValType::I32;
+        let types = [
+            (vec![I32; 4], vec![I32]),
+            (vec![I32], vec![]),
+            (vec![I32; 2], vec![I32]),
+            (vec![I32; 2], vec![I32]),
+            (vec![I32; 2], vec![I32]),
+            (vec![I32; 2], vec![I32]),
+        ];
+        Self::IMPORTS.into_iter().zip(types).collect()
+    }
+
+    /// functions returns the type and the code of each support function, in order.
+    pub fn functions(&self, runtime: &Runtime) -> Vec<(Signature, Function)> {
+        use ValType::{I32, I64};
+        vec![
+            ((vec![I32, I32], vec![]), self.write_function()),
+            ((vec![I32], vec![I32]), self.c_length_function()),
+            ((vec![], vec![I64]), self.arg_count_function()),
+            ((vec![I64], vec![I32, I32]), self.arg_function(runtime)),
+            ((vec![I32, I32], vec![I32; 3]), self.env_function(runtime)),
+        ]
+    }
+
+    /// write(address, length) writes a text to the standard output. Errors are ignored.
+    fn write_function(&self) -> Function {
+        let (address, length, written) = (0, 1, 2);
+        function(
+            &[ValType::I32],
+            &[
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(length),
+                Instruction::I32Eqz,
+                Instruction::BrIf(1),
+                // The standard output may take part of the text at a time
+                Instruction::I32Const(0),
+                Instruction::LocalGet(address),
+                Instruction::I32Store(word(self.scratch)),
+                Instruction::I32Const(0),
+                Instruction::LocalGet(length),
+                Instruction::I32Store(word(self.scratch + 4)),
+                Instruction::I32Const(STDOUT),
+                Instruction::I32Const(self.scratch as i32),
+                Instruction::I32Const(1),
+                Instruction::I32Const(self.scratch as i32 + 8),
+                Instruction::Call(self.fd_write),
+                Instruction::BrIf(1),
+                Instruction::I32Const(0),
+                Instruction::I32Load(word(self.scratch + 8)),
+                Instruction::LocalTee(written),
+                Instruction::LocalGet(address),
+                Instruction::I32Add,
+                Instruction::LocalSet(address),
+                Instruction::LocalGet(length),
+                Instruction::LocalGet(written),
+                Instruction::I32Sub,
+                Instruction::LocalSet(length),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// c_length(address) returns the length of the text at `address`, which ends with a 0 byte.
+    fn c_length_function(&self) -> Function {
+        let (address, end) = (0, 1);
+        function(
+            &[ValType::I32],
+            &[
+                Instruction::LocalGet(address),
+                Instruction::LocalSet(end),
+                Instruction::Block(BlockType::Empty),
+                Instruction::Loop(BlockType::Empty),
+                Instruction::LocalGet(end),
+                Instruction::I32Load8U(byte(0)),
+                Instruction::I32Eqz,
+                Instruction::BrIf(1),
+                Instruction::LocalGet(end),
+                Instruction::I32Const(1),
+                Instruction::I32Add,
+                Instruction::LocalSet(end),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+                Instruction::LocalGet(end),
+                Instruction::LocalGet(address),
+                Instruction::I32Sub,
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// sizes returns the code calling `f`, which is `args_sizes_get` or `environ_sizes_get`,
+    /// and allocating blocks for the addresses and the contents of the texts it returns, stored
+    /// in locals `addresses` and `buffer`. The number of texts is left on the stack.
+    fn sizes(
+        &self,
+        runtime: &Runtime,
+        f: u32,
+        addresses: u32,
+        buffer: u32,
+    ) -> Vec<Instruction<'static>> {
+        vec![
+            Instruction::I32Const(self.scratch as i32 + 8),
+            Instruction::I32Const(self.scratch as i32 + 12),
+            Instruction::Call(f),
+            Instruction::Drop,
+            Instruction::I32Const(0),
+            Instruction::I32Load(word(self.scratch + 8)),
+            Instruction::I32Const(2),
+            Instruction::I32Shl,
+            Instruction::Call(runtime.alloc),
+            Instruction::LocalSet(addresses),
+            Instruction::I32Const(0),
+            Instruction::I32Load(word(self.scratch + 12)),
+            Instruction::Call(runtime.alloc),
+            Instruction::LocalSet(buffer),
+            Instruction::I32Const(0),
+            Instruction::I32Load(word(self.scratch + 8)),
+        ]
+    }
+
+    /// arg_count() returns the number of command-line arguments.
+    fn arg_count_function(&self) -> Function {
+        function(
+            &[],
+            &[
+                Instruction::I32Const(self.scratch as i32 + 8),
+                Instruction::I32Const(self.scratch as i32 + 12),
+                Instruction::Call(self.args_sizes_get),
+                Instruction::Drop,
+                Instruction::I32Const(0),
+                Instruction::I32Load(word(self.scratch + 8)),
+                Instruction::I64ExtendI32U,
+                Instruction::End,
+            ],
+        )
+    }
+
+    /// arg(index) returns a new text holding the command-line argument at `index`, or an empty
+    /// text if there is none.
+    fn arg_function(&self, runtime: &Runtime) -> Function {
+        let (index, count, addresses, buffer, address, length, res) = (0, 1, 2, 3, 4, 5, 6);
+        let mut code = self.sizes(runtime, self.args_sizes_get, addresses, buffer);
+        code.extend([
+            Instruction::LocalSet(count),
+            Instruction::LocalGet(addresses),
+            Instruction::LocalGet(buffer),
+            Instruction::Call(self.args_get),
+            Instruction::Drop,
+            Instruction::LocalGet(index),
+            Instruction::LocalGet(count),
+            Instruction::I64ExtendI32U,
+            Instruction::I64LtU,
+            Instruction::If(BlockType::Empty),
+            Instruction::LocalGet(addresses),
+            Instruction::LocalGet(index),
+            Instruction::I32WrapI64,
+            Instruction::I32Const(2),
+            Instruction::I32Shl,
+            Instruction::I32Add,
+            Instruction::I32Load(word(0)),
+            Instruction::LocalTee(address),
+            Instruction::Call(self.c_length),
+            Instruction::LocalTee(length),
+            Instruction::Call(runtime.alloc),
+            Instruction::LocalTee(res),
+            Instruction::LocalGet(address),
+            Instruction::LocalGet(length),
+            Instruction::MemoryCopy {
+                src_mem: 0,
+                dst_mem: 0,
+            },
+            Instruction::End,
+            Instruction::LocalGet(addresses),
+            Instruction::Call(runtime.free),
+            Instruction::LocalGet(buffer),
+            Instruction::Call(runtime.free),
+            Instruction::LocalGet(res),
+            Instruction::LocalGet(length),
+            Instruction::End,
+        ]);
+        function(&[ValType::I32; 6], &code)
+    }
+
+    /// env(name_address, name_length) returns a new text holding the value of the environment
+    /// variable `name`, as an optional.
+    fn env_function(&self, runtime: &Runtime) -> Function {
+        let (name, name_length) = (0, 1);
+        let (count, addresses, buffer, i, entry, length, res) = (2, 3, 4, 5, 6, 7, 8);
+        let mut code = self.sizes(runtime, self.environ_sizes_get, addresses, buffer);
+        code.extend([
+            Instruction::LocalSet(count),
+            Instruction::LocalGet(addresses),
+            Instruction::LocalGet(buffer),
+            Instruction::Call(self.environ_get),
+            Instruction::Drop,
+            // Variables are `name=value`
+            Instruction::Block(BlockType::Empty),
+            Instruction::Loop(BlockType::Empty),
+            Instruction::LocalGet(i),
+            Instruction::LocalGet(count),
+            Instruction::I32GeU,
+            Instruction::BrIf(1),
+            Instruction::LocalGet(addresses),
+            Instruction::LocalGet(i),
+            Instruction::I32Const(2),
+            Instruction::I32Shl,
+            Instruction::I32Add,
+            Instruction::I32Load(word(0)),
+            Instruction::LocalTee(entry),
+            Instruction::Call(self.c_length),
+            Instruction::LocalSet(length),
+            Instruction::LocalGet(i),
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::LocalSet(i),
+            Instruction::LocalGet(length),
+            Instruction::LocalGet(name_length),
+            Instruction::I32LeU,
+            Instruction::BrIf(0),
+            Instruction::LocalGet(entry),
+            Instruction::LocalGet(name_length),
+            Instruction::I32Add,
+            Instruction::I32Load8U(byte(0)),
+            Instruction::I32Const(b'=' as i32),
+            Instruction::I32Ne,
+            Instruction::BrIf(0),
+            Instruction::LocalGet(entry),
+            Instruction::LocalGet(name_length),
+            Instruction::LocalGet(name),
+            Instruction::LocalGet(name_length),
+            Instruction::Call(runtime.text_compare),
+            Instruction::BrIf(0),
+            // Found: copy what follows `=`
+            Instruction::LocalGet(length),
+            Instruction::LocalGet(name_length),
+            Instruction::I32Sub,
+            Instruction::I32Const(1),
+            Instruction::I32Sub,
+            Instruction::LocalTee(length),
+            Instruction::Call(runtime.alloc),
+            Instruction::LocalTee(res),
+            Instruction::LocalGet(entry),
+            Instruction::LocalGet(name_length),
+            Instruction::I32Add,
+            Instruction::I32Const(1),
+            Instruction::I32Add,
+            Instruction::LocalGet(length),
+            Instruction::MemoryCopy {
+                src_mem: 0,
+                dst_mem: 0,
+            },
+            Instruction::LocalGet(addresses),
+            Instruction::Call(runtime.free),
+            Instruction::LocalGet(buffer),
+            Instruction::Call(runtime.free),
+            Instruction::I32Const(1),
+            Instruction::LocalGet(res),
+            Instruction::LocalGet(length),
+            Instruction::Return,
+            Instruction::End,
+            Instruction::End,
+            Instruction::LocalGet(addresses),
+            Instruction::Call(runtime.free),
+            Instruction::LocalGet(buffer),
+            Instruction::Call(runtime.free),
+            Instruction::I32Const(0),
+            Instruction::I32Const(0),
+            Instruction::I32Const(0),
+            Instruction::End,
+        ]);
+        function(&[ValType::I32; 7], &code)
+    }
+}
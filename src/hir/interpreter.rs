@@ -0,0 +1,530 @@
+//! A small interpreter for the HIR, used by tests as the reference for what a module does: the
+//! optimizer and the backend must not change it. Imports are not linked: calling one records its
+//! arguments in the output, and returns the zero value of its type.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{Callee, Expression, ExpressionKind, Literal, Module, Place, Statement};
+
+use crate::{
+    lang::{float_text, Type},
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    Char(char),
+    /// An optional without a value. Optionals holding a value are represented by the value.
+    Nil,
+    Struct(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// Lists are shared by every copy of the value.
+    List(List),
+    Ok(Box<Value>),
+    Err(Box<Value>),
+    Void,
+}
+
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+/// Exit is why the evaluation of an expression stopped early.
+#[derive(Debug)]
+enum Exit {
+    Return(Value),
+    Trap(String),
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+pub struct Interpreter<'a> {
+    module: &'a Module,
+    globals: Vec<Value>,
+    pub output: Vec<String>,
+}
+
+struct Frame {
+    locals: Vec<Value>,
+    /// The expressions deferred in each block being run, the innermost block last
+    defers: Vec<Vec<Expression>>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(module: &'a Module) -> Result<Self, String> {
+        let mut res = Self {
+            module,
+            globals: vec![],
+            output: vec![],
+        };
+        let mut frame = Frame {
+            locals: vec![],
+            defers: vec![],
+        };
+        for global in &module.globals {
+            let value = res.eval(&global.value, &mut frame).map_err(exit_message)?;
+            res.globals.push(value);
+        }
+        Ok(res)
+    }
+
+    /// call runs the function called `name` and returns its result, or the reason it trapped.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let id = match self.module.functions.iter().position(|f| f.name == name) {
+            Some(id) => id,
+            None => return Err(format!("no function `{}`", name)),
+        };
+        self.call_function(id, args)
+            .map(|(value, _)| value)
+            .map_err(exit_message)
+    }
+
+    /// call_function calls function `id`, and returns its result and, for methods with a
+    /// mutable receiver, the value of the receiver when it returned.
+    fn call_function(&mut self, id: usize, args: Vec<Value>) -> Result<(Value, Value), Exit> {
+        let func = &self.module.functions[id];
+        let mut frame = Frame {
+            locals: func.locals.iter().map(|l| zero(&l.ttype)).collect(),
+            defers: vec![],
+        };
+        for (param, arg) in func.params.iter().zip(args) {
+            frame.locals[*param] = arg;
+        }
+
+        let res = match self.run(&func.body, &mut frame) {
+            Ok(Flow::Return(v)) | Err(Exit::Return(v)) => v,
+            Ok(_) => Value::Void,
+            Err(trap) => return Err(trap),
+        };
+        let receiver = match func.mutable_receiver {
+            true => std::mem::replace(&mut frame.locals[func.params[0]], Value::Void),
+            false => Value::Void,
+        };
+        Ok((res, receiver))
+    }
+
+    /// run runs a block of statements, and then the expressions deferred in it, however the
+    /// block is left. A trap stops everything, deferred expressions included.
+    fn run(&mut self, statements: &[Statement], frame: &mut Frame) -> Result<Flow, Exit> {
+        frame.defers.push(vec![]);
+        let mut res = Ok(Flow::Normal);
+        for statement in statements {
+            match self.execute(statement, frame) {
+                Ok(Flow::Normal) => (),
+                flow => {
+                    res = flow;
+                    break;
+                }
+            }
+        }
+        let deferred = frame.defers.pop().unwrap_or_default();
+        if let Err(Exit::Trap(_)) = res {
+            return res;
+        }
+        for e in deferred.iter().rev() {
+            self.eval(e, frame)?;
+        }
+        res
+    }
+
+    fn execute(&mut self, statement: &Statement, frame: &mut Frame) -> Result<Flow, Exit> {
+        match statement {
+            Statement::Expression(e) => {
+                self.eval(e, frame)?;
+            }
+            Statement::Assign(place, value) => {
+                let value = self.eval(value, frame)?;
+                self.store(place, value, frame)?;
+            }
+            Statement::AssignTuple(places, value) => {
+                let values = match self.eval(value, frame)? {
+                    Value::Tuple(values) => values,
+                    v => return Err(Exit::Trap(format!("{:?} is not a tuple", v))),
+                };
+                for (place, value) in places.iter().zip(values) {
+                    if let Some(place) = place {
+                        self.store(place, value, frame)?;
+                    }
+                }
+            }
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, frame)?,
+                    None => Value::Void,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let body = match self.eval(condition, frame)? == Value::Bool(true) {
+                    true => then_body,
+                    false => else_body,
+                };
+                return self.run(body, frame);
+            }
+            Statement::While {
+                condition,
+                body,
+                update,
+            } => {
+                while self.eval(condition, frame)? == Value::Bool(true) {
+                    match self.run(body, frame)? {
+                        Flow::Break => break,
+                        Flow::Return(v) => return Ok(Flow::Return(v)),
+                        Flow::Normal | Flow::Continue => (),
+                    }
+                    self.run(update, frame)?;
+                }
+            }
+            Statement::Break => return Ok(Flow::Break),
+            Statement::Continue => return Ok(Flow::Continue),
+            Statement::Defer(e) => {
+                if let Some(block) = frame.defers.last_mut() {
+                    block.push(e.clone());
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn store(&mut self, place: &Place, value: Value, frame: &mut Frame) -> Result<(), Exit> {
+        match place {
+            Place::Local(id) => frame.locals[*id] = value,
+            Place::Global(id) => self.globals[*id] = value,
+            Place::Field(object, index) => {
+                let mut path = vec![*index];
+                let mut object = &**object;
+                while let ExpressionKind::Field(inner, index) = &object.kind {
+                    path.push(*index);
+                    object = inner;
+                }
+                if let ExpressionKind::Index(list, index) = &object.kind {
+                    let (list, index) = self.element(list, index, frame)?;
+                    let mut elements = list.borrow_mut();
+                    return set_field(&mut elements[index], path, value);
+                }
+                let target = match object.kind {
+                    ExpressionKind::Local(id) => &mut frame.locals[id],
+                    ExpressionKind::Global(id) => &mut self.globals[id],
+                    _ => return Err(Exit::Trap("assignment to a temporary".into())),
+                };
+                set_field(target, path, value)?;
+            }
+            Place::Index(list, index) => {
+                let (list, index) = self.element(list, index, frame)?;
+                list.borrow_mut()[index] = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// element evaluates `list` and `index`, and returns the list and the position of the
+    /// element, which it holds.
+    fn element(
+        &mut self,
+        list: &Expression,
+        index: &Expression,
+        frame: &mut Frame,
+    ) -> Result<(List, usize), Exit> {
+        let list = match self.eval(list, frame)? {
+            Value::List(list) => list,
+            v => return Err(Exit::Trap(format!("{:?} is not a list", v))),
+        };
+        let len = list.borrow().len();
+        match self.eval(index, frame)? {
+            Value::Int(i) if i >= 0 && (i as usize) < len => Ok((list, i as usize)),
+            v => Err(Exit::Trap(format!("index {:?} out of range", v))),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expression, frame: &mut Frame) -> Result<Value, Exit> {
+        Ok(match &expr.kind {
+            ExpressionKind::Literal(l) => match l {
+                Literal::Integer(i) => Value::Int(*i),
+                Literal::Float(f) => Value::Float(*f),
+                Literal::String(s) => Value::Text(s.clone()),
+                Literal::Char(c) => Value::Char(*c),
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::Nil => Value::Nil,
+            },
+            ExpressionKind::Local(id) => frame.locals[*id].clone(),
+            ExpressionKind::Global(id) => self.globals[*id].clone(),
+            ExpressionKind::Call(callee, args) => {
+                let mut values = vec![];
+                for arg in args {
+                    values.push(self.eval(arg, frame)?);
+                }
+                match callee {
+                    Callee::Function(id) => {
+                        let (value, receiver) = self.call_function(*id, values)?;
+                        if self.module.functions[*id].mutable_receiver {
+                            match args.first().and_then(Place::of) {
+                                Some(place) => self.store(&place, receiver, frame)?,
+                                None => return Err(Exit::Trap("receiver is a temporary".into())),
+                            }
+                        }
+                        value
+                    }
+                    Callee::Import(id) => {
+                        let name = &self.module.imports[*id].path;
+                        self.output.push(format!("{}{:?}", name, values));
+                        zero(&expr.ttype)
+                    }
+                }
+            }
+            ExpressionKind::Field(object, index) => match self.eval(object, frame)? {
+                Value::Struct(mut fields) => fields.swap_remove(*index),
+                v => return Err(Exit::Trap(format!("{:?} is not a struct", v))),
+            },
+            ExpressionKind::Struct(values) | ExpressionKind::Tuple(values) => {
+                let mut res = vec![];
+                for value in values {
+                    res.push(self.eval(value, frame)?);
+                }
+                match expr.kind {
+                    ExpressionKind::Struct(_) => Value::Struct(res),
+                    _ => Value::Tuple(res),
+                }
+            }
+            ExpressionKind::List(elements) => {
+                let mut res = vec![];
+                for element in elements {
+                    res.push(self.eval(element, frame)?);
+                }
+                Value::List(Rc::new(RefCell::new(res)))
+            }
+            ExpressionKind::Index(list, index) => {
+                let (list, index) = self.element(list, index, frame)?;
+                let value = list.borrow()[index].clone();
+                value
+            }
+            ExpressionKind::Append(list, value) => {
+                let list = self.eval(list, frame)?;
+                let value = self.eval(value, frame)?;
+                match list {
+                    Value::List(list) => list.borrow_mut().push(value),
+                    v => return Err(Exit::Trap(format!("{:?} is not a list", v))),
+                }
+                Value::Void
+            }
+            ExpressionKind::Length(list) => match self.eval(list, frame)? {
+                Value::List(list) => Value::Int(list.borrow().len() as i128),
+                v => return Err(Exit::Trap(format!("{:?} is not a list", v))),
+            },
+            ExpressionKind::Chars(text) => match self.eval(text, frame)? {
+                Value::Text(t) => {
+                    let chars = t.chars().map(Value::Char).collect();
+                    Value::List(Rc::new(RefCell::new(chars)))
+                }
+                v => return Err(Exit::Trap(format!("{:?} is not a text", v))),
+            },
+            ExpressionKind::Unary(op, operand) => match (op, self.eval(operand, frame)?) {
+                (UnaryOperator::Not, Value::Bool(b)) => Value::Bool(!b),
+                (UnaryOperator::Minus, Value::Int(i)) => Value::Int(expr.ttype.wrap(-i)),
+                (UnaryOperator::Minus, Value::Float(f)) => Value::Float(-f),
+                (UnaryOperator::Plus, v) => v,
+                (_, v) => return Err(Exit::Trap(format!("bad operand {:?}", v))),
+            },
+            ExpressionKind::Binary(BinaryOperator::LogicalAnd, lhs, rhs) => {
+                match self.eval(lhs, frame)? {
+                    Value::Bool(true) => self.eval(rhs, frame)?,
+                    v => v,
+                }
+            }
+            ExpressionKind::Binary(BinaryOperator::LogicalOr, lhs, rhs) => {
+                match self.eval(lhs, frame)? {
+                    Value::Bool(false) => self.eval(rhs, frame)?,
+                    v => v,
+                }
+            }
+            ExpressionKind::Binary(op, lhs, rhs) => {
+                let (a, b) = (self.eval(lhs, frame)?, self.eval(rhs, frame)?);
+                binary(op, a, b, &lhs.ttype)?
+            }
+            ExpressionKind::Coalesce(value, default) => match self.eval(value, frame)? {
+                Value::Nil => self.eval(default, frame)?,
+                v => v,
+            },
+            ExpressionKind::IsSome(operand) => {
+                Value::Bool(self.eval(operand, frame)? != Value::Nil)
+            }
+            ExpressionKind::Wrap(operand) | ExpressionKind::Unwrap(operand) => {
+                self.eval(operand, frame)?
+            }
+            ExpressionKind::Convert(operand) if expr.ttype == Type::Text => {
+                Value::Text(match self.eval(operand, frame)? {
+                    Value::Int(i) => i.to_string(),
+                    Value::Float(f) => float_text(f),
+                    Value::Bool(b) => b.to_string(),
+                    Value::Char(c) => c.to_string(),
+                    Value::Text(t) => t,
+                    v => return Err(Exit::Trap(format!("{:?} converted to text", v))),
+                })
+            }
+            ExpressionKind::Convert(operand) => match self.eval(operand, frame)? {
+                Value::Int(i) if expr.ttype.is_float() => {
+                    Value::Float(round(i as f64, &expr.ttype))
+                }
+                Value::Int(i) => Value::Int(expr.ttype.wrap(i)),
+                Value::Float(f) => match expr.ttype.range() {
+                    Some((min, max)) => Value::Int((f as i128).clamp(min, max)),
+                    None => Value::Float(round(f, &expr.ttype)),
+                },
+                v => v,
+            },
+            ExpressionKind::Ok(value) => Value::Ok(Box::new(self.eval(value, frame)?)),
+            ExpressionKind::Err(value) => Value::Err(Box::new(self.eval(value, frame)?)),
+            ExpressionKind::Propagate(operand) => match self.eval(operand, frame)? {
+                Value::Ok(v) => *v,
+                err => return Err(Exit::Return(err)),
+            },
+        })
+    }
+}
+
+fn binary(op: &BinaryOperator, a: Value, b: Value, ttype: &Type) -> Result<Value, Exit> {
+    use std::cmp::Ordering;
+
+    let ordering = match (&a, &b) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    let compared = match op {
+        BinaryOperator::Equals => Some(a == b),
+        BinaryOperator::NotEquals => Some(a != b),
+        BinaryOperator::LessThan => Some(ordering == Some(Ordering::Less)),
+        BinaryOperator::LessThanOrEquals => {
+            Some(matches!(ordering, Some(Ordering::Less | Ordering::Equal)))
+        }
+        BinaryOperator::GreaterThan => Some(ordering == Some(Ordering::Greater)),
+        BinaryOperator::GreaterThanOrEquals => Some(matches!(
+            ordering,
+            Some(Ordering::Greater | Ordering::Equal)
+        )),
+        _ => None,
+    };
+    if let Some(res) = compared {
+        return Ok(Value::Bool(res));
+    }
+
+    let trap = |msg: &str| Err(Exit::Trap(msg.to_string()));
+    Ok(match (a, b) {
+        (Value::Int(a), Value::Int(b)) => {
+            let bits = ttype.bits().unwrap_or(64) as i128;
+            let res = match op {
+                BinaryOperator::Add => a + b,
+                BinaryOperator::Subtract => a - b,
+                BinaryOperator::Multiply => ttype.wrap(a).wrapping_mul(ttype.wrap(b)),
+                BinaryOperator::Divide | BinaryOperator::Modulo if b == 0 => {
+                    return trap("division by zero")
+                }
+                BinaryOperator::Divide | BinaryOperator::Modulo
+                    if b == -1 && ttype.range().map(|(min, _)| min) == Some(a) =>
+                {
+                    return trap("integer overflow")
+                }
+                BinaryOperator::Divide => a / b,
+                BinaryOperator::Modulo => a % b,
+                BinaryOperator::BinaryAnd => a & b,
+                BinaryOperator::BinaryOr => a | b,
+                BinaryOperator::Xor => a ^ b,
+                BinaryOperator::ShiftLeft => a << b.rem_euclid(bits),
+                BinaryOperator::ShiftRight => a >> b.rem_euclid(bits),
+                _ => return trap("bad integer operator"),
+            };
+            Value::Int(ttype.wrap(res))
+        }
+        (Value::Float(a), Value::Float(b)) => Value::Float(round(
+            match op {
+                BinaryOperator::Add => a + b,
+                BinaryOperator::Subtract => a - b,
+                BinaryOperator::Multiply => a * b,
+                BinaryOperator::Divide => a / b,
+                _ => return trap("bad float operator"),
+            },
+            ttype,
+        )),
+        (Value::Text(a), Value::Text(b)) if *op == BinaryOperator::Add => Value::Text(a + &b),
+        (a, b) => return trap(&format!("bad operands {:?} and {:?}", a, b)),
+    })
+}
+
+/// set_field assigns `value` to the field of `target` at `path`, the index of the outermost field
+/// last.
+fn set_field(mut target: &mut Value, path: Vec<usize>, value: Value) -> Result<(), Exit> {
+    for index in path.into_iter().rev() {
+        target = match target {
+            Value::Struct(fields) => &mut fields[index],
+            v => return Err(Exit::Trap(format!("{:?} is not a struct", v))),
+        };
+    }
+    *target = value;
+    Ok(())
+}
+
+fn round(f: f64, ttype: &Type) -> f64 {
+    match ttype.bits() {
+        Some(32) => f as f32 as f64,
+        _ => f,
+    }
+}
+
+/// zero is the value of a local before it is assigned.
+fn zero(ttype: &Type) -> Value {
+    match ttype {
+        t if t.is_integer() => Value::Int(0),
+        t if t.is_float() => Value::Float(0.0),
+        Type::Bool => Value::Bool(false),
+        Type::Text => Value::Text(String::new()),
+        Type::Character => Value::Char('\0'),
+        Type::Optional(_) => Value::Nil,
+        Type::Tuple(types) => Value::Tuple(types.iter().map(zero).collect()),
+        Type::List(_) => Value::List(Rc::default()),
+        _ => Value::Void,
+    }
+}
+
+fn exit_message(exit: Exit) -> String {
+    match exit {
+        Exit::Return(v) => format!("returned {:?} outside of a function", v),
+        Exit::Trap(msg) => msg,
+    }
+}
+
+/// run runs `main` if there is one, and then every test, and returns everything the module
+/// printed and returned.
+pub fn run(module: &Module) -> Vec<String> {
+    let mut interpreter = match Interpreter::new(module) {
+        Ok(interpreter) => interpreter,
+        Err(msg) => return vec![format!("trap: {}", msg)],
+    };
+    let mut results = HashMap::new();
+    for func in &module.functions {
+        let is_entry = func.name == "main" || func.kind == crate::hir::FunctionKind::Test;
+        if is_entry {
+            let res = interpreter.call(&func.name, vec![]);
+            results.insert(func.name.clone(), res);
+        }
+    }
+    let mut output = interpreter.output;
+    let mut names: Vec<&String> = results.keys().collect();
+    names.sort();
+    for name in names {
+        output.push(format!("{} -> {:?}", name, results[name]));
+    }
+    output
+}
@@ -0,0 +1,1167 @@
+use std::collections::HashMap;
+
+use crate::{
+    lang::{
+        self, DeclarationId, DeclarationKind, ExpressionValue, ScopeId, ScopeKind, ScopeTree,
+        StatementValue, Type,
+    },
+    tokenizer::{AssignOperator, BinaryOperator, Token, UnaryOperator},
+};
+
+use super::{
+    Callee, Expression, ExpressionKind, Function, FunctionId, FunctionKind, Global, GlobalId,
+    Import, Literal, Local, LocalId, Module, Place, Statement, Struct,
+};
+
+/// Error is a construct that the checker accepts but that cannot be lowered yet.
+#[derive(Debug)]
+pub struct Error {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub source: String,
+}
+
+impl Error {
+    fn at_token(t: &Token, message: String) -> Self {
+        Self {
+            message,
+            line: t.line,
+            column: t.column,
+            source: t.path.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}:{}:{}: {}",
+            self.source, self.line, self.column, self.message
+        ))
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+struct Lowering<'a> {
+    module: &'a lang::Module,
+    functions: HashMap<String, FunctionId>,
+    methods: HashMap<(String, String), FunctionId>,
+    signatures: Vec<&'a lang::FuncSignature>,
+    globals: HashMap<String, (GlobalId, Type)>,
+    imports: Vec<Import>,
+    tree: ScopeTree,
+    scope: ScopeId,
+
+    // The function being lowered
+    locals: Vec<Local>,
+    // The locals of the local values of the function, made when they are first used.
+    local_ids: HashMap<DeclarationId, LocalId>,
+    constants: Option<&'a HashMap<String, lang::Const>>,
+    return_type: Type,
+}
+
+/// lower builds the HIR of `module`, which must have been accepted by `check::check_module`.
+pub fn lower(module: &lang::Module) -> Result<Module> {
+    let mut funcs: Vec<(String, FunctionKind, &lang::Func)> = vec![];
+    let mut names: Vec<&String> = module.functions.keys().collect();
+    names.sort();
+    funcs.extend(names.into_iter().map(|n| {
+        let kind = FunctionKind::Function;
+        (n.clone(), kind, &module.functions[n])
+    }));
+    let mut names: Vec<&String> = module.tests.keys().collect();
+    names.sort();
+    funcs.extend(
+        names
+            .into_iter()
+            .map(|n| (n.clone(), FunctionKind::Test, &module.tests[n])),
+    );
+    let mut types: Vec<&String> = module.types.keys().collect();
+    types.sort();
+    for ttype in &types {
+        let methods = &module.types[*ttype].methods;
+        let mut names: Vec<&String> = methods.keys().collect();
+        names.sort();
+        funcs.extend(names.into_iter().map(|n| {
+            let name = format!("{}.{}", ttype, n);
+            (name, FunctionKind::Method, &methods[n])
+        }));
+    }
+
+    let mut lowering = Lowering {
+        module,
+        functions: HashMap::new(),
+        methods: HashMap::new(),
+        signatures: funcs.iter().map(|(_, _, f)| &f.signature).collect(),
+        globals: HashMap::new(),
+        imports: vec![],
+        tree: ScopeTree::with_module(module),
+        scope: ScopeTree::MODULE,
+        locals: vec![],
+        local_ids: HashMap::new(),
+        constants: None,
+        return_type: Type::Void,
+    };
+    for (id, (name, kind, _)) in funcs.iter().enumerate() {
+        match kind {
+            FunctionKind::Function => _ = lowering.functions.insert(name.clone(), id),
+            FunctionKind::Test => (),
+            FunctionKind::Method => {
+                let (ttype, method) = name.split_once('.').unwrap();
+                let key = (ttype.to_string(), method.to_string());
+                lowering.methods.insert(key, id);
+            }
+        }
+    }
+
+    let mut names: Vec<&String> = module.imports.keys().collect();
+    names.sort();
+    for name in names {
+        let import = &module.imports[name];
+        lowering.imports.push(Import {
+            name: name.clone(),
+            path: import.path.clone(),
+            signature: import.signature.as_ref().map(|s| {
+                let args = s.args.iter().map(|a| a.ttype.clone()).collect();
+                (args, s.return_value.clone())
+            }),
+        });
+    }
+
+    let mut names: Vec<&String> = module.variables.keys().collect();
+    names.sort();
+    for (id, name) in names.iter().enumerate() {
+        let ttype = module.variables[*name].ttype.clone();
+        lowering.globals.insert(name.to_string(), (id, ttype));
+    }
+    let mut globals = vec![];
+    for name in names {
+        let var = &module.variables[name];
+        globals.push(Global {
+            name: name.clone(),
+            ttype: var.ttype.clone(),
+            value: lowering.expression(&var.initial_value, Some(&var.ttype))?,
+        });
+    }
+
+    let mut functions = vec![];
+    for (name, kind, func) in funcs {
+        functions.push(lowering.function(name, kind, func)?);
+    }
+
+    Ok(Module {
+        name: module.identifier.clone(),
+        structs: types
+            .into_iter()
+            .map(|t| Struct {
+                name: t.clone(),
+                fields: module.types[t].fields.clone(),
+            })
+            .collect(),
+        globals,
+        imports: lowering.imports,
+        functions,
+    })
+}
+
+impl<'a> Lowering<'a> {
+    fn function(
+        &mut self,
+        name: String,
+        kind: FunctionKind,
+        func: &'a lang::Func,
+    ) -> Result<Function> {
+        self.locals = vec![];
+        self.local_ids.clear();
+        self.scope = self.tree.push_function(func);
+        self.constants = Some(&func.constants);
+        self.return_type = func.signature.return_value.clone();
+
+        let signature = &func.signature;
+        let params = signature
+            .receiver
+            .iter()
+            .map(|r| &r.ident)
+            .chain(signature.args.iter().map(|a| &a.ident))
+            .map(|ident| self.lookup(ident))
+            .collect::<Option<Vec<LocalId>>>()
+            .expect("parameters are declared in the function scope");
+
+        let body = self.block(&func.statements)?;
+        self.scope = ScopeTree::MODULE;
+        Ok(Function {
+            exported: kind == FunctionKind::Function && self.module.exports.contains_key(&name),
+            name,
+            kind,
+            params,
+            mutable_receiver: signature.receiver.as_ref().is_some_and(|r| r.mutable),
+            return_type: self.return_type.clone(),
+            locals: std::mem::take(&mut self.locals),
+            body,
+        })
+    }
+
+    fn new_local(&mut self, ident: &str, ttype: Type) -> LocalId {
+        self.locals.push(Local {
+            name: ident.into(),
+            ttype,
+        });
+        self.locals.len() - 1
+    }
+
+    fn block(&mut self, statements: &[lang::Statement]) -> Result<Vec<Statement>> {
+        let mut res = vec![];
+        for statement in statements {
+            self.statement(statement, &mut res)?;
+        }
+        Ok(res)
+    }
+
+    /// scoped_block lowers the statements of a block that opens a scope of `kind`.
+    fn scoped_block(
+        &mut self,
+        statements: &[lang::Statement],
+        kind: ScopeKind,
+        first_token: &Token,
+    ) -> Result<Vec<Statement>> {
+        let parent = self.scope;
+        self.scope = self.tree.push_block(parent, kind, first_token, statements);
+        let res = self.block(statements);
+        self.scope = parent;
+        res
+    }
+
+    fn statement(&mut self, statement: &lang::Statement, out: &mut Vec<Statement>) -> Result<()> {
+        let t = &statement.first_token;
+        let lowered = match &statement.value {
+            StatementValue::Expression(e) => Statement::Expression(self.expression(e, None)?),
+            StatementValue::Defer(e) => Statement::Defer(self.expression(e, None)?),
+            StatementValue::VarDeclaration(decl) => {
+                let (place, ttype) = self.variable(&decl.ident, t)?;
+                Statement::Assign(place, self.expression(&decl.value, Some(&ttype))?)
+            }
+            StatementValue::Assignment(assignment) => {
+                let (mut place, ttype) = self.place(&assignment.target)?;
+                let value = match &assignment.operator {
+                    AssignOperator::Assign => self.expression(&assignment.value, Some(&ttype))?,
+                    AssignOperator::AssignAfter(op) => {
+                        // The place is both read and written, but its operands are evaluated
+                        // once
+                        self.bind_place(&mut place, out);
+                        let current = unwrap(read(&place, ttype.clone()));
+                        let value = self.operand(&assignment.value, Some(&current.ttype))?;
+                        let res = self.binary(op.clone(), current, value);
+                        coerce(res, Some(&ttype))
+                    }
+                };
+                Statement::Assign(place, value)
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                let mut places = vec![];
+                for binding in &assignment.bindings {
+                    match binding.as_str() {
+                        "_" => places.push(None),
+                        b => places.push(Some(self.variable(b, t)?.0)),
+                    }
+                }
+                Statement::AssignTuple(places, self.expression(&assignment.value, None)?)
+            }
+            StatementValue::Return(value) => {
+                let ttype = self.return_type.clone();
+                match value {
+                    Some(value) => Statement::Return(Some(self.expression(value, Some(&ttype))?)),
+                    None => Statement::Return(None),
+                }
+            }
+            StatementValue::Break => Statement::Break,
+            StatementValue::Continue => Statement::Continue,
+            StatementValue::If(stmt) => {
+                let mut branches = vec![];
+                for (condition, body) in &stmt.branches {
+                    let first_token = body
+                        .first()
+                        .map_or(&condition.first_token, |s| &s.first_token);
+                    let condition = self.expression(condition, Some(&Type::Bool))?;
+                    branches.push((
+                        condition,
+                        self.scoped_block(body, ScopeKind::Block, first_token)?,
+                    ));
+                }
+                let mut else_body = match &stmt.else_body {
+                    Some(body) => {
+                        let first_token = body.first().map_or(t, |s| &s.first_token);
+                        self.scoped_block(body, ScopeKind::Block, first_token)?
+                    }
+                    None => vec![],
+                };
+                // `if a {} else if b {} else {}` is `if a {} else { if b {} else {} }`
+                while let Some((condition, then_body)) = branches.pop() {
+                    else_body = vec![Statement::If {
+                        condition,
+                        then_body,
+                        else_body,
+                    }];
+                }
+                out.append(&mut else_body);
+                return Ok(());
+            }
+            StatementValue::For(stmt) => match self.range_arguments(&stmt.iterable) {
+                Some(_) => return self.range_loop(statement, stmt, out),
+                None => return self.list_loop(statement, stmt, out),
+            },
+            StatementValue::While(stmt) => Statement::While {
+                condition: self.expression(&stmt.condition, Some(&Type::Bool))?,
+                body: self.scoped_block(&stmt.body, ScopeKind::Loop, t)?,
+                update: vec![],
+            },
+            StatementValue::Loop(body) => Statement::While {
+                condition: Expression::literal(Literal::Bool(true), Type::Bool),
+                body: self.scoped_block(body, ScopeKind::Loop, t)?,
+                update: vec![],
+            },
+        };
+        out.push(lowered);
+        Ok(())
+    }
+
+    /// range_loop lowers `for i in range(start, end, step)` to
+    ///
+    /// ```text
+    /// i = start
+    /// $end = end
+    /// $step = step
+    /// while (step > 0 && i < $end) || (step < 0 && i > $end) { ...; i = i + $step }
+    /// ```
+    ///
+    /// The condition is simplified when the step is a literal, as is the case for the default
+    /// of 1.
+    fn range_loop(
+        &mut self,
+        statement: &lang::Statement,
+        stmt: &lang::For,
+        out: &mut Vec<Statement>,
+    ) -> Result<()> {
+        let args = match self.range_arguments(&stmt.iterable) {
+            Some(args) if !args.is_empty() && args.len() <= 3 => args,
+            _ => {
+                return Err(Error::at_token(
+                    &stmt.iterable.first_token,
+                    "only `range` can be iterated over".into(),
+                ))
+            }
+        };
+        if stmt.bindings.len() != 1 {
+            return Err(Error::at_token(
+                &statement.first_token,
+                "a range is iterated over with a single loop variable".into(),
+            ));
+        }
+
+        let int = Some(&Type::Int);
+        let zero = Expression::literal(Literal::Integer(0), Type::Int);
+        let (start, end_value) = match args.len() {
+            1 => (zero.clone(), self.expression(&args[0], int)?),
+            _ => (
+                self.expression(&args[0], int)?,
+                self.expression(&args[1], int)?,
+            ),
+        };
+        let step = match args.get(2) {
+            Some(step) => self.expression(step, int)?,
+            None => Expression::literal(Literal::Integer(1), Type::Int),
+        };
+
+        let parent = self.scope;
+        let t = &statement.first_token;
+        self.scope = self.tree.push_for(parent, stmt, &[Type::Int], t);
+        let counter = self.lookup(&stmt.bindings[0]).unwrap();
+        let end_local = self.new_local("$end", Type::Int);
+        let i = Expression::local(counter, Type::Int);
+        let end = Expression::local(end_local, Type::Int);
+        out.push(Statement::Assign(Place::Local(counter), start));
+        out.push(Statement::Assign(Place::Local(end_local), end_value));
+
+        let compare = |op, lhs: &Expression, rhs: &Expression| {
+            Expression::binary(op, lhs.clone(), rhs.clone(), Type::Bool)
+        };
+        let (condition, step) = match &step.kind {
+            ExpressionKind::Literal(Literal::Integer(s)) => {
+                let op = match *s < 0 {
+                    true => BinaryOperator::GreaterThan,
+                    false => BinaryOperator::LessThan,
+                };
+                (compare(op, &i, &end), step)
+            }
+            _ => {
+                let step_local = self.new_local("$step", Type::Int);
+                out.push(Statement::Assign(Place::Local(step_local), step));
+                let step = Expression::local(step_local, Type::Int);
+                let up = compare(
+                    BinaryOperator::LogicalAnd,
+                    &compare(BinaryOperator::GreaterThan, &step, &zero),
+                    &compare(BinaryOperator::LessThan, &i, &end),
+                );
+                let down = compare(
+                    BinaryOperator::LogicalAnd,
+                    &compare(BinaryOperator::LessThan, &step, &zero),
+                    &compare(BinaryOperator::GreaterThan, &i, &end),
+                );
+                (compare(BinaryOperator::LogicalOr, &up, &down), step)
+            }
+        };
+
+        let body = self.block(&stmt.body)?;
+        self.scope = parent;
+
+        let next = Expression::binary(BinaryOperator::Add, i, step, Type::Int);
+        out.push(Statement::While {
+            condition,
+            body,
+            update: vec![Statement::Assign(Place::Local(counter), next)],
+        });
+        Ok(())
+    }
+
+    /// list_loop lowers `for i, x in xs` to
+    ///
+    /// ```text
+    /// $list = xs
+    /// $i = 0
+    /// while $i < len($list) { i = $i; x = $list[$i]; ...; $i = $i + 1 }
+    /// ```
+    ///
+    /// The position is counted in a local of its own, so that the body cannot change which
+    /// element comes next. Elements appended to the list by the body are iterated over too.
+    /// Loops over a text iterate over a list of its characters.
+    fn list_loop(
+        &mut self,
+        statement: &lang::Statement,
+        stmt: &lang::For,
+        out: &mut Vec<Statement>,
+    ) -> Result<()> {
+        let mut list = self.operand(&stmt.iterable, None)?;
+        let element = match list.ttype.element() {
+            Some(element) => element,
+            None => {
+                return Err(Error::at_token(
+                    &stmt.iterable.first_token,
+                    "only `range`, lists and texts can be iterated over".into(),
+                ))
+            }
+        };
+        if list.ttype == Type::Text {
+            let ttype = Type::List(Box::new(element.clone()));
+            list = Expression::new(ExpressionKind::Chars(Box::new(list)), ttype);
+        }
+
+        let list_local = self.new_local("$list", list.ttype.clone());
+        let counter = self.new_local("$i", Type::Int);
+        let list_ttype = list.ttype.clone();
+        out.push(Statement::Assign(Place::Local(list_local), list));
+        out.push(Statement::Assign(
+            Place::Local(counter),
+            Expression::literal(Literal::Integer(0), Type::Int),
+        ));
+
+        let parent = self.scope;
+        let t = &statement.first_token;
+        let types = stmt.list_bindings(&element);
+        self.scope = self.tree.push_for(parent, stmt, &types, t);
+        let list = Expression::local(list_local, list_ttype);
+        let i = Expression::local(counter, Type::Int);
+        let mut body = vec![];
+        let bindings = stmt.bindings.iter().rev();
+        let values = [
+            Expression::new(
+                ExpressionKind::Index(Box::new(list.clone()), Box::new(i.clone())),
+                element,
+            ),
+            i.clone(),
+        ];
+        for (binding, value) in bindings.zip(values) {
+            if binding != "_" {
+                let id = self.lookup(binding).unwrap();
+                body.push(Statement::Assign(Place::Local(id), value));
+            }
+        }
+        body.reverse();
+        body.extend(self.block(&stmt.body)?);
+        self.scope = parent;
+
+        let length = Expression::new(ExpressionKind::Length(Box::new(list)), Type::Int);
+        let one = Expression::literal(Literal::Integer(1), Type::Int);
+        let next = Expression::binary(BinaryOperator::Add, i.clone(), one, Type::Int);
+        out.push(Statement::While {
+            condition: Expression::binary(BinaryOperator::LessThan, i, length, Type::Bool),
+            body,
+            update: vec![Statement::Assign(Place::Local(counter), next)],
+        });
+        Ok(())
+    }
+
+    fn range_arguments<'e>(&self, expr: &'e lang::Expression) -> Option<&'e [lang::Expression]> {
+        match &expr.value {
+            ExpressionValue::FunctionCall(call) => match &call.function.value {
+                ExpressionValue::Identifier(ident)
+                    if ident.namespace.is_empty()
+                        && ident.name == "range"
+                        && !self.functions.contains_key("range") =>
+                {
+                    Some(&call.args)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// variable returns the place of the local or global variable `ident`, and its type.
+    fn variable(&mut self, ident: &str, t: &Token) -> Result<(Place, Type)> {
+        if let Some(id) = self.lookup(ident) {
+            return Ok((Place::Local(id), self.locals[id].ttype.clone()));
+        }
+        match self.globals.get(ident) {
+            Some((id, ttype)) => Ok((Place::Global(*id), ttype.clone())),
+            None => Err(Error::at_token(t, format!("`{}` is not a variable", ident))),
+        }
+    }
+
+    /// place returns the place an assignment to `target` stores to, and its type.
+    fn place(&mut self, target: &lang::Expression) -> Result<(Place, Type)> {
+        match &target.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                self.variable(&ident.name, &target.first_token)
+            }
+            ExpressionValue::MemberAccess(access) => {
+                let object = self.operand(&access.object, None)?;
+                let (index, ttype) = self.field(&object, &access.member, &target.first_token)?;
+                Ok((Place::Field(Box::new(object), index), ttype))
+            }
+            ExpressionValue::Index(index) => {
+                let (list, index, ttype) = self.index(index, &target.first_token)?;
+                Ok((Place::Index(Box::new(list), Box::new(index)), ttype))
+            }
+            _ => Err(Error::at_token(
+                &target.first_token,
+                "cannot assign to this expression".into(),
+            )),
+        }
+    }
+
+    /// bind_place stores the list and index operands of `place` in locals, unless they are
+    /// locals or literals already, so that the place can be read and written without evaluating
+    /// them twice.
+    fn bind_place(&mut self, place: &mut Place, out: &mut Vec<Statement>) {
+        match place {
+            Place::Field(object, _) => self.bind_element(object, out),
+            Place::Index(list, index) => {
+                self.bind(list, out);
+                self.bind(index, out);
+            }
+            Place::Local(_) | Place::Global(_) => (),
+        }
+    }
+
+    /// bind_element binds the operands of the list element a chain of field accesses starts
+    /// from, if it starts from one.
+    fn bind_element(&mut self, object: &mut Expression, out: &mut Vec<Statement>) {
+        match &mut object.kind {
+            ExpressionKind::Field(inner, _) => self.bind_element(inner, out),
+            ExpressionKind::Index(list, index) => {
+                self.bind(list, out);
+                self.bind(index, out);
+            }
+            _ => (),
+        }
+    }
+
+    /// bind stores the value of `expr` in a new local and replaces `expr` with that local.
+    fn bind(&mut self, expr: &mut Expression, out: &mut Vec<Statement>) {
+        if let ExpressionKind::Local(_) | ExpressionKind::Global(_) | ExpressionKind::Literal(_) =
+            expr.kind
+        {
+            return;
+        }
+        let local = self.new_local("$operand", expr.ttype.clone());
+        let value = std::mem::replace(expr, Expression::local(local, expr.ttype.clone()));
+        out.push(Statement::Assign(Place::Local(local), value));
+    }
+
+    /// lookup returns the local of `ident` if it is a local value other than a constant, and
+    /// makes it the first time it is used.
+    fn lookup(&mut self, ident: &str) -> Option<LocalId> {
+        let id = self.tree.lookup(self.scope, ident)?;
+        let decl = self.tree.declaration(id);
+        if decl.scope == ScopeTree::MODULE || decl.kind == DeclarationKind::Constant {
+            return None;
+        }
+        if let Some(local) = self.local_ids.get(&id) {
+            return Some(*local);
+        }
+        let (name, ttype) = (decl.name.clone(), decl.ttype.clone()?);
+        let local = self.new_local(&name, ttype);
+        self.local_ids.insert(id, local);
+        Some(local)
+    }
+
+    /// expression lowers `expr`, converting it to `expected` if a type is expected.
+    fn expression(
+        &mut self,
+        expr: &lang::Expression,
+        expected: Option<&Type>,
+    ) -> Result<Expression> {
+        let value = self.value(expr, expected)?;
+        Ok(coerce(value, expected))
+    }
+
+    /// operand lowers an operand of an operator or the object of a member access. Optionals are
+    /// unwrapped, since the checker only lets them be used this way once they are known to hold
+    /// a value.
+    fn operand(&mut self, expr: &lang::Expression, hint: Option<&Type>) -> Result<Expression> {
+        Ok(unwrap(self.value(expr, hint)?))
+    }
+
+    /// value lowers `expr` without converting it. Literals take the type of `expected` if they
+    /// can hold a value of that type.
+    fn value(&mut self, expr: &lang::Expression, expected: Option<&Type>) -> Result<Expression> {
+        let t = &expr.first_token;
+        let target = expected.map(|t| match t {
+            Type::Optional(inner) => &**inner,
+            t => t,
+        });
+
+        match &expr.value {
+            ExpressionValue::Literal(literal) => Ok(match literal {
+                lang::Literal::Integer(i) => match target {
+                    Some(f) if f.is_float() => {
+                        Expression::literal(Literal::Float(*i as f64), f.clone())
+                    }
+                    Some(t) if t.is_integer() => {
+                        Expression::literal(Literal::Integer(*i), t.clone())
+                    }
+                    _ => Expression::literal(Literal::Integer(*i), Type::Int),
+                },
+                lang::Literal::Float(f) => match target {
+                    Some(t) if t.is_float() => Expression::literal(Literal::Float(*f), t.clone()),
+                    _ => Expression::literal(Literal::Float(*f), Type::Float),
+                },
+                lang::Literal::String(s) => {
+                    Expression::literal(Literal::String(s.clone()), Type::Text)
+                }
+                lang::Literal::Char(c) => Expression::literal(Literal::Char(*c), Type::Character),
+                lang::Literal::Bool(b) => Expression::literal(Literal::Bool(*b), Type::Bool),
+                lang::Literal::Nil => {
+                    let ttype = match expected {
+                        Some(t @ Type::Optional(_)) => t.clone(),
+                        _ => Type::Optional(Box::new(Type::Void)),
+                    };
+                    Expression::literal(Literal::Nil, ttype)
+                }
+            }),
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                let name = &ident.name;
+                if let Some(id) = self.lookup(name) {
+                    return Ok(Expression::local(id, self.locals[id].ttype.clone()));
+                }
+                // Constants have been evaluated by the checker
+                let constant = self
+                    .constants
+                    .and_then(|c| c.get(name))
+                    .or_else(|| self.module.constants.get(name));
+                if let Some(cst) = constant {
+                    return self.expression(&cst.value, Some(&cst.ttype));
+                }
+                if let Some((id, ttype)) = self.globals.get(name) {
+                    return Ok(Expression::new(ExpressionKind::Global(*id), ttype.clone()));
+                }
+                Err(Error::at_token(
+                    t,
+                    format!("`{}` cannot be used as a value", name),
+                ))
+            }
+            ExpressionValue::Identifier(_) => Err(Error::at_token(
+                t,
+                "names of other modules can only be called".into(),
+            )),
+            ExpressionValue::FunctionCall(call) => self.call(expr, call, expected),
+            ExpressionValue::MethodCall(call) => {
+                let receiver = self.operand(&call.receiver, None)?;
+                let id = match &receiver.ttype {
+                    Type::Struct(s) => self.methods.get(&(s.clone(), call.method.clone())),
+                    _ => None,
+                };
+                let id = match id {
+                    Some(id) => *id,
+                    None => {
+                        let message = format!("unknown method `{}`", call.method);
+                        return Err(Error::at_token(t, message));
+                    }
+                };
+                let mutable = self.signatures[id]
+                    .receiver
+                    .as_ref()
+                    .is_some_and(|r| r.mutable);
+                if mutable && Place::of(&receiver).is_none() {
+                    let message = format!("`{}` cannot change its receiver", call.method);
+                    return Err(Error::at_token(t, message));
+                }
+                let mut args = vec![receiver];
+                args.append(&mut self.arguments(&call.args, Some(self.signatures[id]))?);
+                let ttype = self.signatures[id].return_value.clone();
+                Ok(Expression::new(
+                    ExpressionKind::Call(Callee::Function(id), args),
+                    ttype,
+                ))
+            }
+            ExpressionValue::MemberAccess(access) => {
+                let object = self.operand(&access.object, None)?;
+                let (index, ttype) = self.field(&object, &access.member, t)?;
+                Ok(Expression::new(
+                    ExpressionKind::Field(Box::new(object), index),
+                    ttype,
+                ))
+            }
+            ExpressionValue::StructLiteral(literal) => {
+                let ttype = match self.module.types.get(&literal.ttype) {
+                    Some(ttype) => ttype,
+                    None => {
+                        let message = format!("unknown type `{}`", literal.ttype);
+                        return Err(Error::at_token(t, message));
+                    }
+                };
+                let mut fields = vec![];
+                for (name, field_type) in &ttype.fields {
+                    // Fields left out are zero
+                    let value = match literal.fields.iter().find(|(f, _)| f == name) {
+                        Some((_, value)) => self.expression(value, Some(field_type))?,
+                        None => match self.zero(field_type) {
+                            Some(zero) => zero,
+                            None => {
+                                let message = format!("field `{}` needs a value", name);
+                                return Err(Error::at_token(t, message));
+                            }
+                        },
+                    };
+                    fields.push(value);
+                }
+                let ttype = Type::Struct(literal.ttype.clone());
+                Ok(Expression::new(ExpressionKind::Struct(fields), ttype))
+            }
+            ExpressionValue::ListLiteral(literal) => {
+                let mut elements = vec![];
+                for element in &literal.elements {
+                    elements.push(self.expression(element, Some(&literal.ttype))?);
+                }
+                let ttype = Type::List(Box::new(literal.ttype.clone()));
+                Ok(Expression::new(ExpressionKind::List(elements), ttype))
+            }
+            ExpressionValue::Index(index) => {
+                let (list, index, ttype) = self.index(index, t)?;
+                Ok(Expression::new(
+                    ExpressionKind::Index(Box::new(list), Box::new(index)),
+                    ttype,
+                ))
+            }
+            ExpressionValue::Tuple(elements) => {
+                let types = match target {
+                    Some(Type::Tuple(types)) if types.len() == elements.len() => Some(types),
+                    _ => None,
+                };
+                let mut res = vec![];
+                for (i, element) in elements.iter().enumerate() {
+                    res.push(self.expression(element, types.map(|t| &t[i]))?);
+                }
+                let ttype = Type::Tuple(res.iter().map(|e| e.ttype.clone()).collect());
+                Ok(Expression::new(ExpressionKind::Tuple(res), ttype))
+            }
+            ExpressionValue::OptionCheck(check) => {
+                let operand = self.value(&check.operand, None)?;
+                let is_some =
+                    Expression::new(ExpressionKind::IsSome(Box::new(operand)), Type::Bool);
+                Ok(match check.is_some {
+                    true => is_some,
+                    false => Expression::new(
+                        ExpressionKind::Unary(UnaryOperator::Not, Box::new(is_some)),
+                        Type::Bool,
+                    ),
+                })
+            }
+            ExpressionValue::Propagate(operand) => {
+                let operand = self.operand(operand, None)?;
+                let ttype = match &operand.ttype {
+                    Type::Result(value, _) => *value.clone(),
+                    _ => return Err(Error::at_token(t, "`?` needs a result".into())),
+                };
+                Ok(Expression::new(
+                    ExpressionKind::Propagate(Box::new(operand)),
+                    ttype,
+                ))
+            }
+            ExpressionValue::Conversion(conversion) => {
+                self.conversion(&conversion.operand, &conversion.ttype)
+            }
+            ExpressionValue::BinaryOperation(op) => {
+                let (lhs, rhs) = (&op.operands[0], &op.operands[1]);
+                match op.operator {
+                    BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => {
+                        let bool = Some(&Type::Bool);
+                        let lhs = self.expression(lhs, bool)?;
+                        let rhs = self.expression(rhs, bool)?;
+                        Ok(Expression::binary(
+                            op.operator.clone(),
+                            lhs,
+                            rhs,
+                            Type::Bool,
+                        ))
+                    }
+                    BinaryOperator::NilCoalesce => {
+                        let lhs = self.value(lhs, None)?;
+                        let inner = match &lhs.ttype {
+                            Type::Optional(inner) => *inner.clone(),
+                            _ => return Ok(lhs),
+                        };
+                        // The result is only optional if the default is
+                        let rhs = self.value(rhs, Some(&inner))?;
+                        let ttype = match rhs.ttype {
+                            Type::Optional(_) => lhs.ttype.clone(),
+                            _ => inner,
+                        };
+                        let rhs = coerce(rhs, Some(&ttype));
+                        Ok(Expression::new(
+                            ExpressionKind::Coalesce(Box::new(lhs), Box::new(rhs)),
+                            ttype,
+                        ))
+                    }
+                    _ => {
+                        let hint = match is_comparison(&op.operator) {
+                            true => None,
+                            false => target.filter(|t| t.is_numeric()),
+                        };
+                        // Untyped literals take the type of the other operand
+                        let (lhs, rhs) = if is_untyped(lhs) && !is_untyped(rhs) {
+                            let rhs = self.operand(rhs, hint)?;
+                            (self.operand(lhs, Some(&rhs.ttype))?, rhs)
+                        } else {
+                            let lhs = self.operand(lhs, hint)?;
+                            let rhs = self.operand(rhs, Some(&lhs.ttype))?;
+                            (lhs, rhs)
+                        };
+                        Ok(self.binary(op.operator.clone(), lhs, rhs))
+                    }
+                }
+            }
+            ExpressionValue::UnaryOperation(op) => {
+                let operand = match op.operator {
+                    UnaryOperator::Not => self.expression(&op.operand, Some(&Type::Bool))?,
+                    _ => self.operand(&op.operand, target.filter(|t| t.is_numeric()))?,
+                };
+                let ttype = operand.ttype.clone();
+                Ok(Expression::new(
+                    ExpressionKind::Unary(op.operator.clone(), Box::new(operand)),
+                    ttype,
+                ))
+            }
+        }
+    }
+
+    fn call(
+        &mut self,
+        expr: &lang::Expression,
+        call: &lang::FunctionCall,
+        expected: Option<&Type>,
+    ) -> Result<Expression> {
+        let t = &expr.first_token;
+        let ident = match &call.function.value {
+            ExpressionValue::Identifier(ident) => ident,
+            _ => return Err(Error::at_token(t, "only functions can be called".into())),
+        };
+
+        if ident.namespace.is_empty() {
+            if let Some(id) = self.functions.get(&ident.name).copied() {
+                let args = self.arguments(&call.args, Some(self.signatures[id]))?;
+                let ttype = self.signatures[id].return_value.clone();
+                return Ok(Expression::new(
+                    ExpressionKind::Call(Callee::Function(id), args),
+                    ttype,
+                ));
+            }
+            match (ident.name.as_str(), expected, call.args.as_slice()) {
+                ("ok", Some(t @ Type::Result(value, _)), [arg]) => {
+                    let value = self.expression(arg, Some(value))?;
+                    return Ok(Expression::new(
+                        ExpressionKind::Ok(Box::new(value)),
+                        t.clone(),
+                    ));
+                }
+                ("err", Some(t @ Type::Result(_, error)), [arg]) => {
+                    let error = self.expression(arg, Some(error))?;
+                    return Ok(Expression::new(
+                        ExpressionKind::Err(Box::new(error)),
+                        t.clone(),
+                    ));
+                }
+                ("ok" | "err", _, _) => {
+                    let message = format!("the result type of `{}` is not known", ident.name);
+                    return Err(Error::at_token(t, message));
+                }
+                ("append", _, [list, value]) => {
+                    let list = self.operand(list, None)?;
+                    let element = match &list.ttype {
+                        Type::List(element) => *element.clone(),
+                        _ => return Err(Error::at_token(t, "`append` needs a list".into())),
+                    };
+                    let value = self.expression(value, Some(&element))?;
+                    return Ok(Expression::new(
+                        ExpressionKind::Append(Box::new(list), Box::new(value)),
+                        Type::Void,
+                    ));
+                }
+                ("len", _, [list]) => {
+                    let list = self.operand(list, None)?;
+                    if !matches!(list.ttype, Type::List(_)) {
+                        return Err(Error::at_token(t, "`len` needs a list".into()));
+                    }
+                    return Ok(Expression::new(
+                        ExpressionKind::Length(Box::new(list)),
+                        Type::Int,
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        // Calls to other modules, either imported by name or through an imported module
+        let root = ident.namespace.first().unwrap_or(&ident.name);
+        let import = match self.module.imports.get(root) {
+            Some(import) => import,
+            None => {
+                let message = format!("`{}` cannot be called", ident.name);
+                return Err(Error::at_token(t, message));
+            }
+        };
+        let mut path = import.path.clone();
+        let mut name = root.clone();
+        if !ident.namespace.is_empty() {
+            for word in ident.namespace.iter().skip(1).chain([&ident.name]) {
+                path = format!("{}.{}", path, word);
+                name = format!("{}.{}", name, word);
+            }
+        }
+        let id = match self.imports.iter().position(|i| i.path == path) {
+            Some(id) => id,
+            None => {
+                self.imports.push(Import {
+                    name,
+                    path,
+                    signature: None,
+                });
+                self.imports.len() - 1
+            }
+        };
+
+        // Imports called through an imported module have no signature
+        let declared = self.module.imports.get(&self.imports[id].name);
+        let args = self.arguments(&call.args, declared.and_then(|i| i.signature.as_ref()))?;
+        // Imports without a signature return what the call is expected to return, if anything
+        let ttype = match &self.imports[id].signature {
+            Some((_, ret)) => ret.clone(),
+            None => expected.cloned().unwrap_or(Type::Void),
+        };
+        Ok(Expression::new(
+            ExpressionKind::Call(Callee::Import(id), args),
+            ttype,
+        ))
+    }
+
+    /// arguments lowers the arguments of a call to a function with `signature`, if it is known.
+    /// The values given to a variadic argument are converted to its element type and passed as
+    /// a list.
+    fn arguments(
+        &mut self,
+        args: &[lang::Expression],
+        signature: Option<&'a lang::FuncSignature>,
+    ) -> Result<Vec<Expression>> {
+        let variadic = signature.filter(|s| s.variadic);
+        let fixed = variadic.map_or(args.len(), |s| s.args.len() - 1);
+        let mut res = vec![];
+        for (i, arg) in args.iter().enumerate().take(fixed) {
+            let ttype = signature.and_then(|s| s.args.get(i)).map(|a| &a.ttype);
+            res.push(self.expression(arg, ttype)?);
+        }
+        if let Some(Type::List(element)) = variadic.map(|s| &s.args[fixed].ttype) {
+            let mut values = vec![];
+            for arg in &args[fixed..] {
+                values.push(self.conversion(arg, element)?);
+            }
+            res.push(Expression::new(
+                ExpressionKind::List(values),
+                Type::List(element.clone()),
+            ));
+        }
+        Ok(res)
+    }
+
+    /// conversion lowers the conversion of `operand` to `ttype`.
+    fn conversion(&mut self, operand: &lang::Expression, ttype: &Type) -> Result<Expression> {
+        let operand = self.operand(operand, None)?;
+        if operand.ttype == *ttype {
+            return Ok(operand);
+        }
+        Ok(Expression::new(
+            ExpressionKind::Convert(Box::new(operand)),
+            ttype.clone(),
+        ))
+    }
+
+    /// binary builds a binary operation, widening the operand of the narrower type.
+    fn binary(&self, op: BinaryOperator, lhs: Expression, rhs: Expression) -> Expression {
+        let (lhs, rhs) = match op {
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => (lhs, rhs),
+            _ if rhs.ttype.widens_to(&lhs.ttype) => {
+                let ttype = lhs.ttype.clone();
+                (lhs, coerce(rhs, Some(&ttype)))
+            }
+            _ if lhs.ttype.widens_to(&rhs.ttype) => {
+                let ttype = rhs.ttype.clone();
+                (coerce(lhs, Some(&ttype)), rhs)
+            }
+            _ => (lhs, rhs),
+        };
+        let ttype = match is_comparison(&op) {
+            true => Type::Bool,
+            false => lhs.ttype.clone(),
+        };
+        Expression::binary(op, lhs, rhs, ttype)
+    }
+
+    /// index lowers the list and the position of `index`, and returns them with the type of
+    /// the element.
+    fn index(&mut self, index: &lang::Index, t: &Token) -> Result<(Expression, Expression, Type)> {
+        let list = self.operand(&index.object, None)?;
+        let ttype = match &list.ttype {
+            Type::List(element) => *element.clone(),
+            _ => return Err(Error::at_token(t, "only lists can be indexed".into())),
+        };
+        let position = self.expression(&index.index, Some(&Type::Int))?;
+        Ok((list, position, ttype))
+    }
+
+    fn field(&self, object: &Expression, member: &str, t: &Token) -> Result<(usize, Type)> {
+        let fields = match &object.ttype {
+            Type::Struct(s) => self.module.types.get(s).map(|t| &t.fields),
+            _ => None,
+        };
+        fields
+            .and_then(|f| {
+                f.iter()
+                    .position(|(name, _)| name == member)
+                    .map(|i| (i, f[i].1.clone()))
+            })
+            .ok_or_else(|| Error::at_token(t, format!("unknown field `{}`", member)))
+    }
+
+    /// zero returns the value of type `ttype` that struct fields without a value start with.
+    fn zero(&self, ttype: &Type) -> Option<Expression> {
+        let literal = |l| Some(Expression::literal(l, ttype.clone()));
+        match ttype {
+            t if t.is_integer() => literal(Literal::Integer(0)),
+            t if t.is_float() => literal(Literal::Float(0.0)),
+            Type::Bool => literal(Literal::Bool(false)),
+            Type::Text => literal(Literal::String("".into())),
+            Type::Character => literal(Literal::Char('\0')),
+            Type::Optional(_) => literal(Literal::Nil),
+            Type::List(_) => Some(Expression::new(ExpressionKind::List(vec![]), ttype.clone())),
+            Type::Tuple(types) => {
+                let elements = types.iter().map(|t| self.zero(t)).collect::<Option<_>>()?;
+                Some(Expression::new(
+                    ExpressionKind::Tuple(elements),
+                    ttype.clone(),
+                ))
+            }
+            Type::Struct(s) => {
+                let fields = self.module.types.get(s)?.fields.iter();
+                let fields = fields.map(|(_, t)| self.zero(t)).collect::<Option<_>>()?;
+                Some(Expression::new(
+                    ExpressionKind::Struct(fields),
+                    ttype.clone(),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// unwrap unwraps `value` if it is an optional.
+fn unwrap(value: Expression) -> Expression {
+    match value.ttype.clone() {
+        Type::Optional(inner) => Expression::new(ExpressionKind::Unwrap(Box::new(value)), *inner),
+        _ => value,
+    }
+}
+
+/// read returns an expression reading the value of type `ttype` stored at `place`.
+fn read(place: &Place, ttype: Type) -> Expression {
+    let kind = match place {
+        Place::Local(id) => ExpressionKind::Local(*id),
+        Place::Global(id) => ExpressionKind::Global(*id),
+        Place::Field(object, index) => ExpressionKind::Field(object.clone(), *index),
+        Place::Index(list, index) => ExpressionKind::Index(list.clone(), index.clone()),
+    };
+    Expression::new(kind, ttype)
+}
+
+/// coerce converts `value` to `expected`, if a type is expected and `value` needs converting.
+fn coerce(value: Expression, expected: Option<&Type>) -> Expression {
+    let expected = match expected {
+        Some(t) if *t != value.ttype => t,
+        _ => return value,
+    };
+
+    match (&value.ttype, expected) {
+        (_, Type::Optional(_)) if value.kind == ExpressionKind::Literal(Literal::Nil) => {
+            Expression::literal(Literal::Nil, expected.clone())
+        }
+        (Type::Optional(inner), _) => {
+            let inner = *inner.clone();
+            let unwrapped = Expression::new(ExpressionKind::Unwrap(Box::new(value)), inner);
+            coerce(unwrapped, Some(expected))
+        }
+        (_, Type::Optional(inner)) => {
+            let value = coerce(value, Some(inner));
+            Expression::new(ExpressionKind::Wrap(Box::new(value)), expected.clone())
+        }
+        (t, _) if t.widens_to(expected) => {
+            Expression::new(ExpressionKind::Convert(Box::new(value)), expected.clone())
+        }
+        _ => value,
+    }
+}
+
+fn is_comparison(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Equals
+            | BinaryOperator::NotEquals
+            | BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEquals
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEquals
+    )
+}
+
+/// is_untyped returns true for numeric literals and arithmetic on them only, whose type is
+/// decided by where they are used.
+fn is_untyped(expr: &lang::Expression) -> bool {
+    match &expr.value {
+        ExpressionValue::Literal(lang::Literal::Integer(_) | lang::Literal::Float(_)) => true,
+        ExpressionValue::UnaryOperation(op) => {
+            op.operator != UnaryOperator::Not && is_untyped(&op.operand)
+        }
+        ExpressionValue::BinaryOperation(op) => {
+            !is_comparison(&op.operator) && op.operands.iter().all(is_untyped)
+        }
+        _ => false,
+    }
+}
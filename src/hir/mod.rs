@@ -0,0 +1,389 @@
+//! The high-level intermediate representation sits between the checked `lang` AST and code
+//! generation. Every expression carries its type, names are replaced by the IDs of what they
+//! refer to, constants are replaced by their values and the following constructs are desugared:
+//!
+//! - compound assignments such as `x += 1` become `x = x + 1`,
+//! - `for i in range(start, end, step)` becomes a `while` loop over a counter,
+//! - `for i, x in xs` becomes a `while` loop over the positions of the elements of `xs`,
+//! - `else if` chains become nested `if` statements,
+//! - `loop` becomes `while true`.
+//!
+//! Implicit conversions are made explicit: integers widened by an assignment or an operator are
+//! wrapped in a `Convert`, values stored as optionals in a `Wrap`, and optional variables that a
+//! nil check has shown to hold a value in an `Unwrap`.
+//!
+//! The HIR of a module is built by `lower`, from a module that `check::check_module` accepted.
+
+use crate::{
+    lang::Type,
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+mod lower;
+
+#[cfg(test)]
+pub(crate) mod interpreter;
+#[cfg(test)]
+pub(crate) mod test;
+
+pub use lower::{lower, Error};
+
+pub type FunctionId = usize;
+pub type GlobalId = usize;
+pub type ImportId = usize;
+pub type LocalId = usize;
+
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub structs: Vec<Struct>,
+    pub globals: Vec<Global>,
+    pub imports: Vec<Import>,
+    /// Functions, then tests, then methods, each sorted by name.
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+#[derive(Debug)]
+pub struct Global {
+    pub name: String,
+    pub ttype: Type,
+    pub value: Expression,
+}
+
+/// Import is a function of another module, called as `name`. `path` is the full name of the
+/// function, e.g. `io.print_line`.
+#[derive(Debug)]
+pub struct Import {
+    pub name: String,
+    pub path: String,
+    /// The signature of the function, if it is declared in this module.
+    pub signature: Option<(Vec<Type>, Type)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FunctionKind {
+    Function,
+    Test,
+    /// A method is called with its receiver as the first argument.
+    Method,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    /// The name of the function, `Type.method` for methods.
+    pub name: String,
+    pub kind: FunctionKind,
+    /// True if the function can be called from outside the module.
+    pub exported: bool,
+    /// The locals holding the receiver and the arguments, in order.
+    pub params: Vec<LocalId>,
+    /// True for methods with a mutable receiver, declared as `func (var p T)`. Calls store the
+    /// value the receiver has when the method returns back into the receiver they were made on.
+    pub mutable_receiver: bool,
+    pub return_type: Type,
+    pub locals: Vec<Local>,
+    pub body: Vec<Statement>,
+}
+
+/// Local is a value stored in a function: an argument, the receiver, a variable, a loop counter,
+/// or a value introduced by desugaring, whose name starts with `$`.
+#[derive(Debug)]
+pub struct Local {
+    pub name: String,
+    pub ttype: Type,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Place {
+    Local(LocalId),
+    Global(GlobalId),
+    /// A field of a struct value, by index.
+    Field(Box<Expression>, usize),
+    /// An element of a list, at the position given by the int of the second expression.
+    Index(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Expression(Expression),
+    Assign(Place, Expression),
+    /// Assigns the elements of a tuple. Elements bound to `_` are discarded.
+    AssignTuple(Vec<Option<Place>>, Expression),
+    Return(Option<Expression>),
+    If {
+        condition: Expression,
+        then_body: Vec<Statement>,
+        else_body: Vec<Statement>,
+    },
+    /// While runs `body` and then `update` for as long as `condition` holds. `continue` skips
+    /// the rest of `body`, but still runs `update`.
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+        update: Vec<Statement>,
+    },
+    Break,
+    Continue,
+    /// Defer evaluates the expression when the block holding the statement is left, before the
+    /// expressions deferred earlier in that block.
+    Defer(Expression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub ttype: Type,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Char(char),
+    Bool(bool),
+    Nil,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Callee {
+    Function(FunctionId),
+    Import(ImportId),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpressionKind {
+    Literal(Literal),
+    Local(LocalId),
+    Global(GlobalId),
+    /// Calls a function. The receiver of a method with a mutable receiver, its first argument, is
+    /// a local, a global or a field of one, which the call assigns.
+    Call(Callee, Vec<Expression>),
+    /// Reads a field of a struct value, by index.
+    Field(Box<Expression>, usize),
+    /// Builds a struct, with the fields in the order of its declaration.
+    Struct(Vec<Expression>),
+    Tuple(Vec<Expression>),
+    /// Builds a list holding the elements.
+    List(Vec<Expression>),
+    /// Reads the element of a list at the position given by the int of the second operand,
+    /// trapping if there is none.
+    Index(Box<Expression>, Box<Expression>),
+    /// Adds the second operand at the end of the list of the first.
+    Append(Box<Expression>, Box<Expression>),
+    /// The number of elements of a list, as an int.
+    Length(Box<Expression>),
+    /// A new list holding the characters of a text.
+    Chars(Box<Expression>),
+    Unary(UnaryOperator, Box<Expression>),
+    /// A binary operation on two operands of the same type. `&&` and `||` only evaluate their
+    /// second operand if needed.
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    /// The value of an optional if it holds one, the second operand otherwise.
+    Coalesce(Box<Expression>, Box<Expression>),
+    IsSome(Box<Expression>),
+    Wrap(Box<Expression>),
+    Unwrap(Box<Expression>),
+    Convert(Box<Expression>),
+    Ok(Box<Expression>),
+    Err(Box<Expression>),
+    /// The value of a result, returning its error from the function if it holds one.
+    Propagate(Box<Expression>),
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, ttype: Type) -> Self {
+        Self { kind, ttype }
+    }
+
+    pub fn literal(literal: Literal, ttype: Type) -> Self {
+        Self::new(ExpressionKind::Literal(literal), ttype)
+    }
+
+    pub fn local(id: LocalId, ttype: Type) -> Self {
+        Self::new(ExpressionKind::Local(id), ttype)
+    }
+
+    pub fn binary(operator: BinaryOperator, lhs: Expression, rhs: Expression, ttype: Type) -> Self {
+        Self::new(
+            ExpressionKind::Binary(operator, Box::new(lhs), Box::new(rhs)),
+            ttype,
+        )
+    }
+}
+
+impl Statement {
+    /// expressions returns the expressions of the statement itself, leaving out those of the
+    /// statements nested in it.
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Statement::Expression(e) | Statement::Defer(e) => vec![e],
+            Statement::Assign(place, value) => {
+                let mut res = place.expressions();
+                res.push(value);
+                res
+            }
+            Statement::AssignTuple(places, value) => {
+                let mut res: Vec<&Expression> = places
+                    .iter()
+                    .flatten()
+                    .flat_map(|p| p.expressions())
+                    .collect();
+                res.push(value);
+                res
+            }
+            Statement::Return(value) => value.iter().collect(),
+            Statement::If { condition, .. } | Statement::While { condition, .. } => {
+                vec![condition]
+            }
+            Statement::Break | Statement::Continue => vec![],
+        }
+    }
+
+    pub fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Statement::Expression(e) | Statement::Defer(e) => vec![e],
+            Statement::Assign(place, value) => {
+                let mut res = place.expressions_mut();
+                res.push(value);
+                res
+            }
+            Statement::AssignTuple(places, value) => {
+                let mut res: Vec<&mut Expression> = places
+                    .iter_mut()
+                    .flatten()
+                    .flat_map(|p| p.expressions_mut())
+                    .collect();
+                res.push(value);
+                res
+            }
+            Statement::Return(value) => value.iter_mut().collect(),
+            Statement::If { condition, .. } | Statement::While { condition, .. } => {
+                vec![condition]
+            }
+            Statement::Break | Statement::Continue => vec![],
+        }
+    }
+
+    /// bodies returns the blocks of statements nested in the statement.
+    pub fn bodies(&self) -> Vec<&Vec<Statement>> {
+        match self {
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => vec![then_body, else_body],
+            Statement::While { body, update, .. } => vec![body, update],
+            _ => vec![],
+        }
+    }
+
+    pub fn bodies_mut(&mut self) -> Vec<&mut Vec<Statement>> {
+        match self {
+            Statement::If {
+                then_body,
+                else_body,
+                ..
+            } => vec![then_body, else_body],
+            Statement::While { body, update, .. } => vec![body, update],
+            _ => vec![],
+        }
+    }
+}
+
+impl Place {
+    /// of returns the place that `expr` reads, if it is a local, a global, a field of one or an
+    /// element of a list.
+    pub fn of(expr: &Expression) -> Option<Place> {
+        match &expr.kind {
+            ExpressionKind::Local(id) => Some(Place::Local(*id)),
+            ExpressionKind::Global(id) => Some(Place::Global(*id)),
+            ExpressionKind::Field(object, index) => {
+                Place::of(object)?;
+                Some(Place::Field(object.clone(), *index))
+            }
+            // Lists are shared, so an element of any list is a place
+            ExpressionKind::Index(list, index) => Some(Place::Index(list.clone(), index.clone())),
+            _ => None,
+        }
+    }
+
+    fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Place::Field(object, _) => vec![object],
+            Place::Index(list, index) => vec![list, index],
+            _ => vec![],
+        }
+    }
+
+    fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Place::Field(object, _) => vec![object],
+            Place::Index(list, index) => vec![list, index],
+            _ => vec![],
+        }
+    }
+}
+
+impl ExpressionKind {
+    /// children returns the operands of an expression.
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            ExpressionKind::Literal(_) | ExpressionKind::Local(_) | ExpressionKind::Global(_) => {
+                vec![]
+            }
+            ExpressionKind::Call(_, args)
+            | ExpressionKind::Struct(args)
+            | ExpressionKind::Tuple(args)
+            | ExpressionKind::List(args) => args.iter().collect(),
+            ExpressionKind::Binary(_, lhs, rhs)
+            | ExpressionKind::Coalesce(lhs, rhs)
+            | ExpressionKind::Index(lhs, rhs)
+            | ExpressionKind::Append(lhs, rhs) => vec![lhs, rhs],
+            ExpressionKind::Field(e, _)
+            | ExpressionKind::Length(e)
+            | ExpressionKind::Chars(e)
+            | ExpressionKind::Unary(_, e)
+            | ExpressionKind::IsSome(e)
+            | ExpressionKind::Wrap(e)
+            | ExpressionKind::Unwrap(e)
+            | ExpressionKind::Convert(e)
+            | ExpressionKind::Ok(e)
+            | ExpressionKind::Err(e)
+            | ExpressionKind::Propagate(e) => vec![e],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            ExpressionKind::Literal(_) | ExpressionKind::Local(_) | ExpressionKind::Global(_) => {
+                vec![]
+            }
+            ExpressionKind::Call(_, args)
+            | ExpressionKind::Struct(args)
+            | ExpressionKind::Tuple(args)
+            | ExpressionKind::List(args) => args.iter_mut().collect(),
+            ExpressionKind::Binary(_, lhs, rhs)
+            | ExpressionKind::Coalesce(lhs, rhs)
+            | ExpressionKind::Index(lhs, rhs)
+            | ExpressionKind::Append(lhs, rhs) => vec![lhs, rhs],
+            ExpressionKind::Field(e, _)
+            | ExpressionKind::Length(e)
+            | ExpressionKind::Chars(e)
+            | ExpressionKind::Unary(_, e)
+            | ExpressionKind::IsSome(e)
+            | ExpressionKind::Wrap(e)
+            | ExpressionKind::Unwrap(e)
+            | ExpressionKind::Convert(e)
+            | ExpressionKind::Ok(e)
+            | ExpressionKind::Err(e)
+            | ExpressionKind::Propagate(e) => vec![e],
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    check::{check_module, Severity},
+    lang::Type,
+    link,
+    parser::Parser,
+    tokenizer::BinaryOperator,
+};
+
+use super::{
+    interpreter, lower, Expression, ExpressionKind, Function, Literal, Module, Place, Statement,
+};
+
+/// lower_source parses, checks and lowers a module made of `src`, which must have no errors.
+pub(crate) fn lower_source(src: &str) -> Module {
+    let mut parser = Parser::new("test".into());
+    parser.add_source(src.as_bytes(), None).unwrap();
+    let mut module = parser.finalize().unwrap();
+    let errors = check_module(&mut module);
+    if let Some(e) = errors.iter().find(|e| e.severity == Severity::Error) {
+        panic!("{}", e);
+    }
+    lower(&module).unwrap()
+}
+
+/// lower_examples loads every program of `examples`, with the modules it imports, and returns
+/// it checked, lowered and merged, along with its path. Every example must build.
+pub(crate) fn lower_examples() -> Vec<(PathBuf, Module)> {
+    let mut paths: Vec<PathBuf> = (fs::read_dir("examples").unwrap())
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    let mut res = vec![];
+    for path in paths {
+        let assembly = match link::load(&path, &["examples".into()]) {
+            Ok(assembly) => assembly,
+            Err(errors) => panic!("{}", errors[0]),
+        };
+        let mut modules = vec![];
+        for mut module in assembly.modules {
+            let errors = check_module(&mut module);
+            if let Some(e) = errors.iter().find(|e| e.severity == Severity::Error) {
+                panic!("{}", e);
+            }
+            modules.push(lower(&module).unwrap_or_else(|e| panic!("{}", e)));
+        }
+        res.push((path, link::merge(modules)));
+    }
+    res
+}
+
+fn local(function: &Function, name: &str) -> Expression {
+    let id = function.locals.iter().position(|l| l.name == name).unwrap();
+    Expression::local(id, function.locals[id].ttype.clone())
+}
+
+fn int(i: i128) -> Expression {
+    Expression::literal(Literal::Integer(i), Type::Int)
+}
+
+#[test]
+fn compound_assignment() {
+    let module = lower_source(
+        "
+func count() int {
+	var {
+		total int = 0
+	}
+	total += 2
+	return total
+}
+",
+    );
+
+    let f = &module.functions[0];
+    let total = local(f, "total");
+    assert_eq!(
+        f.body[1],
+        Statement::Assign(
+            Place::Local(0),
+            Expression::binary(BinaryOperator::Add, total, int(2), Type::Int)
+        )
+    );
+}
+
+#[test]
+fn range_loop() {
+    let module = lower_source(
+        "
+func sum() int {
+	var {
+		total int = 0
+	}
+	for i in range(2, 10) {
+		total += i
+	}
+	return total
+}
+",
+    );
+
+    let f = &module.functions[0];
+    let (i, end) = (local(f, "i"), local(f, "$end"));
+    assert_eq!(f.body[1], Statement::Assign(Place::Local(1), int(2)));
+    assert_eq!(f.body[2], Statement::Assign(Place::Local(2), int(10)));
+    match &f.body[3] {
+        Statement::While {
+            condition, update, ..
+        } => {
+            let less = BinaryOperator::LessThan;
+            let condition_expected = Expression::binary(less, i.clone(), end, Type::Bool);
+            assert_eq!(*condition, condition_expected);
+            let next = Expression::binary(BinaryOperator::Add, i, int(1), Type::Int);
+            assert_eq!(*update, vec![Statement::Assign(Place::Local(1), next)]);
+        }
+        s => panic!("expected a while loop, found {:?}", s),
+    }
+}
+
+#[test]
+fn else_if_chain_and_conversions() {
+    let module = lower_source(
+        "
+func sign(a int8) int {
+	if a > 0 {
+		return 1
+	} else if a < 0 {
+		return -1
+	} else {
+		return a
+	}
+}
+",
+    );
+
+    let f = &module.functions[0];
+    assert_eq!(f.body.len(), 1);
+    let inner = match &f.body[0] {
+        Statement::If {
+            condition,
+            else_body,
+            ..
+        } => {
+            // The literal takes the type of the other operand
+            let zero = Expression::literal(Literal::Integer(0), Type::Int8);
+            let a = local(f, "a");
+            let condition_expected =
+                Expression::binary(BinaryOperator::GreaterThan, a, zero, Type::Bool);
+            assert_eq!(*condition, condition_expected);
+            assert_eq!(else_body.len(), 1);
+            &else_body[0]
+        }
+        s => panic!("expected an if statement, found {:?}", s),
+    };
+    match inner {
+        Statement::If { else_body, .. } => {
+            let a = Box::new(local(f, "a"));
+            let converted = Expression::new(ExpressionKind::Convert(a), Type::Int);
+            assert_eq!(*else_body, vec![Statement::Return(Some(converted))]);
+        }
+        s => panic!("expected an if statement, found {:?}", s),
+    }
+}
+
+#[test]
+fn deferred_calls_run_when_their_block_is_left() {
+    let module = lower_source(
+        "
+use {
+	io.print_line
+}
+
+func parse(s text) result[int, text] {
+	if s == \"one\" {
+		return ok(1)
+	}
+	return err(s)
+}
+
+func loops() {
+	defer print_line(\"function\")
+	for i in range(3) {
+		defer print_line(i)
+		if i == 1 {
+			continue
+		}
+		defer print_line(\"iteration\")
+	}
+	loop {
+		defer print_line(\"break\")
+		break
+	}
+}
+
+func propagates(s text) result[int, text] {
+	defer print_line(\"outer\")
+	while s != \"\" {
+		defer print_line(\"inner\")
+		var {
+			n int = parse(s)?
+		}
+		return ok(n)
+	}
+	return err(\"empty\")
+}
+
+func main() {
+	loops()
+	propagates(\"one\")
+	propagates(\"two\")
+}
+",
+    );
+    assert_eq!(
+        interpreter::run(&module),
+        [
+            "io.print_line[Text(\"iteration\")]",
+            "io.print_line[Int(0)]",
+            "io.print_line[Int(1)]",
+            "io.print_line[Text(\"iteration\")]",
+            "io.print_line[Int(2)]",
+            "io.print_line[Text(\"break\")]",
+            "io.print_line[Text(\"function\")]",
+            "io.print_line[Text(\"inner\")]",
+            "io.print_line[Text(\"outer\")]",
+            "io.print_line[Text(\"inner\")]",
+            "io.print_line[Text(\"outer\")]",
+            "main -> Ok(Void)",
+        ]
+    );
+}
@@ -1 +1,29 @@
-pub struct Assembly {}
\ No newline at end of file
+use super::{Func, Module};
+
+/// Assembly is a whole program: the module holding its entry point, and every module it imports
+/// functions from, directly or not. Imports of functions of other modules of the assembly have
+/// been resolved, and carry the signature of the function they refer to, or of the `extern`
+/// declaration they refer to. The host provides the functions declared `extern`.
+pub struct Assembly {
+    /// The modules of the program, the one holding the entry point first.
+    pub modules: Vec<Module>,
+}
+
+impl Assembly {
+    /// entry returns `main` of the first module, if the program has one.
+    pub fn entry(&self) -> Option<&Func> {
+        self.modules.first()?.functions.get("main")
+    }
+
+    pub fn module(&self, identifier: &str) -> Option<&Module> {
+        self.modules.iter().find(|m| m.identifier == identifier)
+    }
+
+    /// resolve returns the module and the function an import path such as `io.print_line`
+    /// refers to, if it is a function of a module of the assembly.
+    pub fn resolve(&self, path: &str) -> Option<(&Module, &Func)> {
+        let (module, name) = path.rsplit_once('.')?;
+        let module = self.module(module)?;
+        Some((module, module.functions.get(name)?))
+    }
+}
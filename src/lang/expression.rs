@@ -1,48 +1,156 @@
 use crate::tokenizer::{BinaryOperator, Token, UnaryOperator};
 
-#[derive(Clone)]
+use super::Type;
+
+#[derive(Clone, Debug)]
 pub struct Expression {
     pub value: ExpressionValue,
-    first_token: Token,
+    pub first_token: Token,
 }
 
-#[derive(Clone)]
+/// Identifier is a plain or module-qualified name, such as `print_line` or `io.print_line`.
+///
+/// The parser cannot tell `p.x` (a field of the value `p`) apart from `io.print_line` (a name
+/// from the module `io`), so both are parsed as identifiers with a namespace. Once the module
+/// has been parsed, identifiers whose first word refers to a value are rewritten into
+/// MemberAccess expressions.
+#[derive(Clone, Debug)]
 pub struct Identifier {
-    namespace: Vec<String>,
-    name: String,
+    pub namespace: Vec<String>,
+    pub name: String,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct FunctionCall {
-    function: Box<Expression>,
-    args: Vec<Expression>,
+    pub function: Box<Expression>,
+    pub args: Vec<Expression>,
+}
+
+/// MemberAccess reads the field `member` of the struct value `object`.
+#[derive(Clone, Debug)]
+pub struct MemberAccess {
+    pub object: Box<Expression>,
+    pub member: String,
+}
+
+/// MethodCall calls the method `method` from the method set of the struct type of `receiver`.
+#[derive(Clone, Debug)]
+pub struct MethodCall {
+    pub receiver: Box<Expression>,
+    pub method: String,
+    pub args: Vec<Expression>,
+}
+
+/// StructLiteral builds a new struct value, e.g. `Point{x: 1, y: 2}`.
+#[derive(Clone, Debug)]
+pub struct StructLiteral {
+    pub ttype: String,
+    pub fields: Vec<(String, Expression)>,
+}
+
+/// ListLiteral builds a new list of elements of type `ttype`, e.g. `list[int]{1, 2, 3}`.
+#[derive(Clone, Debug)]
+pub struct ListLiteral {
+    pub ttype: Type,
+    pub elements: Vec<Expression>,
+}
+
+/// Index reads the element at position `index` of the list `object`, e.g. `answers[i]`.
+/// Positions start at 0, and reading past the end of the list stops the program.
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
+/// OptionCheck tests whether an optional value holds a value (`x is some`) or not (`x is nil`).
+#[derive(Clone, Debug)]
+pub struct OptionCheck {
+    pub operand: Box<Expression>,
+    pub is_some: bool,
+}
+
+/// Conversion turns a numeric value into another numeric type, written as `int32(x)` or
+/// `x as int32`. `as` applies to the whole operand on its left, signs included: `-x as int8` is
+/// `(-x) as int8`.
+///
+/// Conversions never fail at runtime. Converting to a narrower integer type keeps the low bits of
+/// the value, converting a float to an integer truncates towards zero and saturates at the bounds
+/// of the integer type, and converting to a narrower float type rounds to the nearest value.
+///
+/// Numbers, booleans and characters also convert to text, as in `x as text`: integers are
+/// written in decimal, floats as `float_text` says, booleans as `true` or `false`, and characters
+/// as themselves.
+#[derive(Clone, Debug)]
+pub struct Conversion {
+    pub operand: Box<Expression>,
+    pub ttype: Type,
+}
+
+/// float_text returns the text a float converts to: its integer part and, unless they are all
+/// zeros, a dot and its first six decimals, rounded, without trailing zeros. NaN and the
+/// infinities are `NaN`, `inf` and `-inf`.
+pub fn float_text(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".into();
+    }
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let value = value.abs();
+    if value.is_infinite() {
+        return format!("{}inf", sign);
+    }
+    let mut integer = value as u64;
+    let mut fraction = ((value - value.trunc()) * 1e6).round_ties_even() as u64;
+    if fraction >= 1_000_000 {
+        integer = integer.wrapping_add(1);
+        fraction = 0;
+    }
+    match fraction {
+        0 => format!("{}{}", sign, integer),
+        _ => {
+            let decimals = format!("{:06}", fraction);
+            format!("{}{}.{}", sign, integer, decimals.trim_end_matches('0'))
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct BinOp {
-    operator: BinaryOperator,
-    operands: Vec<Expression>,
+    pub operator: BinaryOperator,
+    pub operands: Vec<Expression>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct UnOp {
-    operator: UnaryOperator,
-    operand: Box<Expression>,
+    pub operator: UnaryOperator,
+    pub operand: Box<Expression>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Literal {
     Integer(i128),
     Float(f64),
     String(String),
     Char(char),
     Bool(bool),
+    Nil,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum ExpressionValue {
     Identifier(Identifier),
     FunctionCall(FunctionCall),
+    MemberAccess(MemberAccess),
+    MethodCall(MethodCall),
+    StructLiteral(StructLiteral),
+    ListLiteral(ListLiteral),
+    Index(Index),
+    Tuple(Vec<Expression>),
+    OptionCheck(OptionCheck),
+    /// Propagate is `expr?`: the value of a result if it holds one, otherwise the enclosing
+    /// function returns the error.
+    Propagate(Box<Expression>),
+    Conversion(Conversion),
     BinaryOperation(BinOp),
     UnaryOperation(UnOp),
     Literal(Literal),
@@ -96,6 +204,43 @@ impl Expression {
         }
     }
 
+    pub fn literal_nil(first_token: Token) -> Self {
+        Self {
+            value: ExpressionValue::Literal(Literal::Nil),
+            first_token,
+        }
+    }
+
+    pub fn option_check(operand: Expression, is_some: bool) -> Self {
+        let first_token = operand.first_token.clone();
+        Self {
+            value: ExpressionValue::OptionCheck(OptionCheck {
+                operand: Box::new(operand),
+                is_some,
+            }),
+            first_token,
+        }
+    }
+
+    pub fn propagate(operand: Expression) -> Self {
+        let first_token = operand.first_token.clone();
+        Self {
+            value: ExpressionValue::Propagate(Box::new(operand)),
+            first_token,
+        }
+    }
+
+    pub fn conversion(operand: Expression, ttype: Type) -> Self {
+        let first_token = operand.first_token.clone();
+        Self {
+            value: ExpressionValue::Conversion(Conversion {
+                operand: Box::new(operand),
+                ttype,
+            }),
+            first_token,
+        }
+    }
+
     pub fn unary_plus(operand: Expression, first_token: Token) -> Self {
         Self {
             value: ExpressionValue::UnaryOperation(UnOp {
@@ -136,4 +281,74 @@ impl Expression {
             first_token,
         }
     }
+
+    pub fn binary_operation(operator: BinaryOperator, lhs: Expression, rhs: Expression) -> Self {
+        let first_token = lhs.first_token.clone();
+        Self {
+            value: ExpressionValue::BinaryOperation(BinOp {
+                operator,
+                operands: vec![lhs, rhs],
+            }),
+            first_token,
+        }
+    }
+
+    pub fn member_access(object: Expression, member: String) -> Self {
+        let first_token = object.first_token.clone();
+        Self {
+            value: ExpressionValue::MemberAccess(MemberAccess {
+                object: Box::new(object),
+                member,
+            }),
+            first_token,
+        }
+    }
+
+    pub fn method_call(receiver: Expression, method: String, args: Vec<Expression>) -> Self {
+        let first_token = receiver.first_token.clone();
+        Self {
+            value: ExpressionValue::MethodCall(MethodCall {
+                receiver: Box::new(receiver),
+                method,
+                args,
+            }),
+            first_token,
+        }
+    }
+
+    pub fn struct_literal(
+        ttype: String,
+        fields: Vec<(String, Expression)>,
+        first_token: Token,
+    ) -> Self {
+        Self {
+            value: ExpressionValue::StructLiteral(StructLiteral { ttype, fields }),
+            first_token,
+        }
+    }
+
+    pub fn list_literal(ttype: Type, elements: Vec<Expression>, first_token: Token) -> Self {
+        Self {
+            value: ExpressionValue::ListLiteral(ListLiteral { ttype, elements }),
+            first_token,
+        }
+    }
+
+    pub fn index(object: Expression, index: Expression) -> Self {
+        let first_token = object.first_token.clone();
+        Self {
+            value: ExpressionValue::Index(Index {
+                object: Box::new(object),
+                index: Box::new(index),
+            }),
+            first_token,
+        }
+    }
+
+    pub fn tuple(elements: Vec<Expression>, first_token: Token) -> Self {
+        Self {
+            value: ExpressionValue::Tuple(elements),
+            first_token,
+        }
+    }
 }
@@ -2,11 +2,20 @@ use std::collections::HashMap;
 
 mod assembly;
 mod module;
+mod scope;
+mod statement;
 mod symbol;
 mod ttype;
 
+#[cfg(test)]
+mod test;
+
 pub use assembly::Assembly;
 pub use module::Module;
+pub use scope::{
+    Declaration, DeclarationId, DeclarationKind, Reference, Scope, ScopeId, ScopeKind, ScopeTree,
+    Shadowing, Span,
+};
 
 use crate::tokenizer::Token;
 
@@ -26,13 +35,18 @@ pub enum SymbolRef<'a> {
     Type(&'a StructType),
 }
 
+#[derive(Debug)]
 pub struct Import {
     pub path: String,
     pub signature: Option<FuncSignature>,
-    first_token: Token,
+    /// True for functions declared with `extern`, which the host provides.
+    pub external: bool,
+    pub first_token: Token,
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum Type {
+    Void,
     Text,
     Character,
     Bool,
@@ -50,46 +64,87 @@ pub enum Type {
     Float32,
     Float64,
     Struct(String), // Value is the name of the struct type
+    Tuple(Vec<Type>),
+    /// Optional holds either a value of the inner type or `nil`. It is written as `?T` or
+    /// `option[T]`.
+    Optional(Box<Type>),
+    /// Result holds either a value of the first type or an error of the second one. It is
+    /// written as `result[T, E]`, and its values are built with `ok(value)` and `err(error)`.
+    Result(Box<Type>, Box<Type>),
+    /// List is a growable sequence of values of the inner type, written as `list[T]`. Lists are
+    /// shared: assigning a list or passing it to a function does not copy it, so `append` on one
+    /// name for it shows through all others.
+    List(Box<Type>),
 }
 
+#[derive(Debug)]
 pub struct StructType {
-    ident: String,
-    first_token: Token,
-    fields: Vec<(String, Type)>,
+    pub ident: String,
+    pub first_token: Token,
+    pub fields: Vec<(String, Type)>,
+    /// The method set of the type, keyed by method name. Every function in here has a receiver.
+    pub methods: HashMap<String, Func>,
 }
 
+#[derive(Debug)]
 pub struct Func {
-    signature: FuncSignature,
-    constants: HashMap<String, Const>,
-    variables: HashMap<String, Type>,
-    statements: Vec<Statement>,
-    first_token: Token,
+    pub signature: FuncSignature,
+    pub constants: HashMap<String, Const>,
+    pub statements: Vec<Statement>,
+    pub first_token: Token,
 }
 
+#[derive(Clone, Debug)]
 pub struct FuncSignature {
-    args: Vec<Type>,
-    return_value: Type,
-    first_token: Token,
+    pub receiver: Option<Receiver>,
+    pub args: Vec<Argument>,
+    /// True if the last argument is declared as `name ...T`, which takes any number of values.
+    /// The function sees them as a `list[T]`, the type of the argument.
+    pub variadic: bool,
+    pub return_value: Type,
+    pub first_token: Token,
+}
+
+/// Argument is a parameter of a function, declared as `name type`. Its token is its name.
+#[derive(Clone, Debug)]
+pub struct Argument {
+    pub ident: String,
+    pub ttype: Type,
+    pub first_token: Token,
 }
 
-pub struct Statement {
-    value: StatementValue,
-    first_token: Token,
+/// Receiver is the value a method is called on, declared as `func (p Point) ...`.
+///
+/// A mutable receiver, declared as `func (var p Point) ...`, writes any changes the method makes
+/// to it back to the caller's value, so it can only be called on values that can be assigned to.
+#[derive(Clone, Debug)]
+pub struct Receiver {
+    pub ident: String,
+    pub ttype: Type,
+    pub mutable: bool,
+    pub first_token: Token,
 }
 
-pub enum StatementValue {}
+pub use statement::{
+    Assignment, For, If, MultiAssignment, Statement, StatementValue, VarDeclaration, While,
+};
 
 mod expression;
-pub use expression::{Expression, ExpressionValue, Identifier};
+pub use expression::{
+    float_text, BinOp, Conversion, Expression, ExpressionValue, FunctionCall, Identifier, Index,
+    ListLiteral, Literal, MemberAccess, MethodCall, OptionCheck, StructLiteral, UnOp,
+};
 
+#[derive(Debug)]
 pub struct Const {
-    ttype: Type,
-    value: Expression,
-    first_token: Token,
+    pub ttype: Type,
+    pub value: Expression,
+    pub first_token: Token,
 }
 
+#[derive(Debug)]
 pub struct Variable {
-    ttype: Type,
-    initial_value: Expression,
-    first_token: Token,
+    pub ttype: Type,
+    pub initial_value: Expression,
+    pub first_token: Token,
 }
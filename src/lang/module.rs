@@ -3,15 +3,17 @@ use std::collections::HashMap;
 use super::{Const, Func, FuncSignature, Import, StructType, Symbol, SymbolRef, Variable};
 
 pub struct Module {
-    identifier: String,
+    pub identifier: String,
 
-    functions: HashMap<String, Func>,
-    constants: HashMap<String, Const>,
-    variables: HashMap<String, Variable>,
-    types: HashMap<String, StructType>,
+    pub functions: HashMap<String, Func>,
+    pub constants: HashMap<String, Const>,
+    pub variables: HashMap<String, Variable>,
+    pub types: HashMap<String, StructType>,
+    pub tests: HashMap<String, Func>,
 
-    imports: HashMap<String, Import>,
-    exports: HashMap<String, FuncSignature>,
+    pub imports: HashMap<String, Import>,
+    /// The functions declared `export`, which the host can call.
+    pub exports: HashMap<String, FuncSignature>,
 }
 
 impl Module {
@@ -22,12 +24,13 @@ impl Module {
             variables: HashMap::new(),
             functions: HashMap::new(),
             types: HashMap::new(),
+            tests: HashMap::new(),
             imports: HashMap::new(),
             exports: HashMap::new(),
         }
     }
 
-    pub fn lookup(&self, ident: &str) -> Option<SymbolRef> {
+    pub fn lookup(&self, ident: &str) -> Option<SymbolRef<'_>> {
         if let Some(func) = self.functions.get(ident) {
             return Some(SymbolRef::Function(func));
         }
@@ -75,8 +78,9 @@ impl Module {
         None
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn define(&mut self, ident: String, symb: Symbol) -> Result<(), ()> {
-        if let Some(_) = self.lookup(&ident) {
+        if self.lookup(&ident).is_some() {
             return Err(()); // Already defined
         }
 
@@ -102,8 +106,8 @@ impl Module {
     }
 
     pub fn redefine(&mut self, ident: String, symb: Symbol) -> Option<Symbol> {
-        let res = self.undefine(&ident).map(|s| s);
-        self.define(ident, symb);
+        let res = self.undefine(&ident);
+        _ = self.define(ident, symb); // Cannot fail, the symbol was just removed
         res
     }
 }
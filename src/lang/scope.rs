@@ -0,0 +1,645 @@
+//! The scope tree is the hierarchical symbol table of a module. The module scope holds the
+//! functions, types, constants, variables and imports of the module. Every function has a scope
+//! for its receiver, arguments and constants, and every block and loop body opens another scope
+//! for the variables declared in it.
+//!
+//! Looking up a name walks outward from the innermost scope. Local names may shadow names of the
+//! module, but not other local names of the same function. Variables are visible in the whole
+//! block that declares them, before the declaration as well, so that reading them early can be
+//! reported as a use before assignment rather than as an undefined name.
+//!
+//! Name resolution builds the whole tree up front. The passes that walk a module in order, from
+//! member resolution to lowering, grow a tree as they go with `with_module` and the `push_`
+//! functions, and look names up in the scope they are in.
+
+use std::collections::HashMap;
+
+use crate::tokenizer::Token;
+
+use super::{Expression, ExpressionValue, For, Func, Module, Statement, StatementValue, Type};
+
+pub type ScopeId = usize;
+pub type DeclarationId = usize;
+
+/// Span is a range of a source file. `start` and `end` are (line, column) pairs, `end` being the
+/// position just after the range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub path: String,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Span {
+    pub fn contains(&self, path: &str, line: usize, column: usize) -> bool {
+        self.path == path && self.start <= (line, column) && (line, column) < self.end
+    }
+
+    fn extend(&mut self, other: &Span) {
+        self.end = self.end.max(other.end);
+    }
+}
+
+impl From<&Token> for Span {
+    fn from(t: &Token) -> Self {
+        Self {
+            path: t.path.clone(),
+            start: (t.line, t.column),
+            end: (t.line, t.column + t.length.max(1)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScopeKind {
+    Module,
+    Function,
+    Block,
+    Loop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeclarationKind {
+    Function,
+    Type,
+    Constant,
+    Variable,
+    Import,
+    Receiver,
+    Argument,
+    LoopVariable,
+}
+
+#[derive(Debug)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// None for functions and imports, whose type is not a single value type, and for loop
+    /// variables whose type is not known.
+    pub ttype: Option<Type>,
+    /// True for the values that can be assigned to: variables and mutable receivers.
+    pub mutable: bool,
+    pub span: Span,
+    pub scope: ScopeId,
+}
+
+impl Declaration {
+    /// is_value returns true if the declaration is a value, which everything but functions,
+    /// types and imports is.
+    pub fn is_value(&self) -> bool {
+        !matches!(
+            self.kind,
+            DeclarationKind::Function | DeclarationKind::Type | DeclarationKind::Import
+        )
+    }
+}
+
+/// Scope is a node of the scope tree. The span of a scope starts at the token opening it and
+/// ends after the last token seen inside it; the span of the module scope is empty.
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
+    pub span: Option<Span>,
+    symbols: HashMap<String, DeclarationId>,
+}
+
+/// Reference is a use of a name, together with the declaration it resolves to.
+#[derive(Debug)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub scope: ScopeId,
+    /// True if the name is used as a type rather than as a value.
+    pub is_type: bool,
+    pub declaration: Option<DeclarationId>,
+}
+
+/// Shadowing is a local declaration that hides another local declaration of the same function.
+#[derive(Debug)]
+pub struct Shadowing {
+    pub declaration: DeclarationId,
+    pub shadowed: DeclarationId,
+}
+
+pub struct ScopeTree {
+    scopes: Vec<Scope>,
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<Reference>,
+    pub shadowings: Vec<Shadowing>,
+}
+
+impl ScopeTree {
+    pub const MODULE: ScopeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope {
+                kind: ScopeKind::Module,
+                parent: None,
+                children: vec![],
+                span: None,
+                symbols: HashMap::new(),
+            }],
+            declarations: vec![],
+            references: vec![],
+            shadowings: vec![],
+        }
+    }
+
+    /// build returns the scope tree of `module`, with every name used in it resolved.
+    pub fn build(module: &Module) -> Self {
+        let mut builder = Builder {
+            tree: Self::with_module(module),
+            current: Self::MODULE,
+        };
+        builder.module(module);
+        builder.tree
+    }
+
+    /// with_module returns a tree of only the module scope, holding the functions, types,
+    /// constants, variables and imports of `module`. Passes that walk the module add the scopes
+    /// of functions and blocks as they reach them.
+    pub fn with_module(module: &Module) -> Self {
+        use DeclarationKind as Kind;
+
+        let mut tree = Self::new();
+        let scope = Self::MODULE;
+        for (ident, func) in &module.functions {
+            tree.declare_at(scope, ident, Kind::Function, None, &func.first_token);
+        }
+        for (ident, ttype) in &module.types {
+            let t = Some(Type::Struct(ident.clone()));
+            tree.declare_at(scope, ident, Kind::Type, t, &ttype.first_token);
+        }
+        for (ident, cst) in &module.constants {
+            let t = Some(cst.ttype.clone());
+            tree.declare_at(scope, ident, Kind::Constant, t, &cst.first_token);
+        }
+        for (ident, var) in &module.variables {
+            let t = Some(var.ttype.clone());
+            tree.declare_at(scope, ident, Kind::Variable, t, &var.first_token);
+        }
+        for (ident, import) in &module.imports {
+            // Functions declared `extern` are functions of the module, which others can import
+            let kind = match import.external {
+                true => Kind::Function,
+                false => Kind::Import,
+            };
+            tree.declare_at(scope, ident, kind, None, &import.first_token);
+        }
+        tree
+    }
+
+    /// push_function adds the scope of `func` to the module scope and returns it. The receiver,
+    /// arguments and constants of `func` are declared in it, as are the variables declared at
+    /// the top level of its body.
+    pub fn push_function(&mut self, func: &Func) -> ScopeId {
+        use DeclarationKind as Kind;
+
+        let scope = self.push_scope(Self::MODULE, ScopeKind::Function, &func.first_token);
+        if let Some(receiver) = &func.signature.receiver {
+            let t = Some(receiver.ttype.clone());
+            let id = self.declare_at(
+                scope,
+                &receiver.ident,
+                Kind::Receiver,
+                t,
+                &receiver.first_token,
+            );
+            if let Some(id) = id {
+                self.declarations[id].mutable = receiver.mutable;
+            }
+        }
+        for arg in &func.signature.args {
+            let t = Some(arg.ttype.clone());
+            self.declare_at(scope, &arg.ident, Kind::Argument, t, &arg.first_token);
+        }
+        for (ident, cst) in &func.constants {
+            let t = Some(cst.ttype.clone());
+            self.declare_at(scope, ident, Kind::Constant, t, &cst.first_token);
+        }
+        self.declare_variables(scope, &func.statements);
+        scope
+    }
+
+    /// push_block adds a scope of `kind` for the block `statements` inside `parent`, and
+    /// declares the variables of the block in it.
+    pub fn push_block(
+        &mut self,
+        parent: ScopeId,
+        kind: ScopeKind,
+        first_token: &Token,
+        statements: &[Statement],
+    ) -> ScopeId {
+        let scope = self.push_scope(parent, kind, first_token);
+        self.declare_variables(scope, statements);
+        scope
+    }
+
+    /// push_for adds the scope of the `for` loop `stmt` inside `parent`. Each loop variable is
+    /// declared in it with the type at its position in `types`, if there is one, together with
+    /// the variables of the body.
+    pub fn push_for(
+        &mut self,
+        parent: ScopeId,
+        stmt: &For,
+        types: &[Type],
+        first_token: &Token,
+    ) -> ScopeId {
+        let scope = self.push_scope(parent, ScopeKind::Loop, first_token);
+        for (i, binding) in stmt.bindings.iter().enumerate() {
+            let kind = DeclarationKind::LoopVariable;
+            self.declare_at(scope, binding, kind, types.get(i).cloned(), first_token);
+        }
+        self.declare_variables(scope, &stmt.body);
+        scope
+    }
+
+    /// parent returns the scope enclosing `scope`, which must not be the module scope.
+    pub fn parent(&self, scope: ScopeId) -> ScopeId {
+        self.scopes[scope]
+            .parent
+            .expect("the module scope has no parent")
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id]
+    }
+
+    pub fn declaration(&self, id: DeclarationId) -> &Declaration {
+        &self.declarations[id]
+    }
+
+    /// push_scope adds a new scope inside `parent` and returns it.
+    pub fn push_scope(&mut self, parent: ScopeId, kind: ScopeKind, first_token: &Token) -> ScopeId {
+        let id = self.scopes.len();
+        self.scopes.push(Scope {
+            kind,
+            parent: Some(parent),
+            children: vec![],
+            span: Some(first_token.into()),
+            symbols: HashMap::new(),
+        });
+        self.scopes[parent].children.push(id);
+        id
+    }
+
+    /// declare adds a declaration to `scope`. A name can only be declared once per scope, the
+    /// declaration already there is returned if there is one.
+    ///
+    /// Declarations that shadow a local name of an enclosing scope are added to `shadowings`.
+    pub fn declare(
+        &mut self,
+        scope: ScopeId,
+        name: String,
+        kind: DeclarationKind,
+        ttype: Option<Type>,
+        span: Span,
+    ) -> Result<DeclarationId, DeclarationId> {
+        if let Some(previous) = self.scopes[scope].symbols.get(&name) {
+            return Err(*previous);
+        }
+
+        let id = self.declarations.len();
+        if let Some(shadowed) = self.lookup(scope, &name) {
+            if self.declarations[shadowed].scope != Self::MODULE {
+                self.shadowings.push(Shadowing {
+                    declaration: id,
+                    shadowed,
+                });
+            }
+        }
+        self.declarations.push(Declaration {
+            name: name.clone(),
+            kind,
+            ttype,
+            mutable: kind == DeclarationKind::Variable,
+            span,
+            scope,
+        });
+        self.scopes[scope].symbols.insert(name, id);
+        Ok(id)
+    }
+
+    /// lookup returns the declaration `name` refers to in `scope`, walking outward through the
+    /// enclosing scopes.
+    pub fn lookup(&self, scope: ScopeId, name: &str) -> Option<DeclarationId> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &self.scopes[id];
+            if let Some(decl) = scope.symbols.get(name) {
+                return Some(*decl);
+            }
+            current = scope.parent;
+        }
+        None
+    }
+
+    /// lookup_value is lookup for names used as values. Functions, types and imports are not
+    /// found.
+    pub fn lookup_value(&self, scope: ScopeId, name: &str) -> Option<&Declaration> {
+        let decl = &self.declarations[self.lookup(scope, name)?];
+        decl.is_value().then_some(decl)
+    }
+
+    /// declare_at is declare for a name declared at `t`. Redeclarations in the same scope are
+    /// rejected by the parser, None is returned for them.
+    fn declare_at(
+        &mut self,
+        scope: ScopeId,
+        name: &str,
+        kind: DeclarationKind,
+        ttype: Option<Type>,
+        t: &Token,
+    ) -> Option<DeclarationId> {
+        self.declare(scope, name.into(), kind, ttype, t.into()).ok()
+    }
+
+    /// declare_variables declares the variables of a block in `scope`. Variables are visible in
+    /// the whole block.
+    fn declare_variables(&mut self, scope: ScopeId, statements: &[Statement]) {
+        for statement in statements {
+            if let StatementValue::VarDeclaration(decl) = &statement.value {
+                let ttype = Some(decl.ttype.clone());
+                let kind = DeclarationKind::Variable;
+                self.declare_at(scope, &decl.ident, kind, ttype, &statement.first_token);
+            }
+        }
+    }
+
+    /// visible_names returns every name that can be used in `scope`.
+    pub fn visible_names(&self, scope: ScopeId) -> Vec<&str> {
+        let mut res = vec![];
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            res.extend(self.scopes[id].symbols.keys().map(|s| s.as_str()));
+            current = self.scopes[id].parent;
+        }
+        res
+    }
+
+    /// scope_at returns the innermost scope at a position of a source file.
+    pub fn scope_at(&self, path: &str, line: usize, column: usize) -> ScopeId {
+        let mut scope = Self::MODULE;
+        'descend: loop {
+            for child in &self.scopes[scope].children {
+                let span = self.scopes[*child].span.as_ref();
+                if span.is_some_and(|s| s.contains(path, line, column)) {
+                    scope = *child;
+                    continue 'descend;
+                }
+            }
+            return scope;
+        }
+    }
+
+    /// definition_at returns the declaration of the name at a position of a source file, which
+    /// is either a use of the name or its declaration.
+    pub fn definition_at(&self, path: &str, line: usize, column: usize) -> Option<&Declaration> {
+        let reference = self
+            .references
+            .iter()
+            .find(|r| r.span.contains(path, line, column));
+        match reference {
+            Some(r) => r.declaration.map(|d| &self.declarations[d]),
+            None => self
+                .declarations
+                .iter()
+                .find(|d| d.span.contains(path, line, column)),
+        }
+    }
+}
+
+impl Default for ScopeTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Builder {
+    tree: ScopeTree,
+    current: ScopeId,
+}
+
+impl Builder {
+    fn module(&mut self, module: &Module) {
+        for var in module.variables.values() {
+            self.ttype(&var.ttype, &var.first_token);
+            self.expression(&var.initial_value);
+        }
+        for cst in module.constants.values() {
+            self.ttype(&cst.ttype, &cst.first_token);
+            self.expression(&cst.value);
+        }
+        for ttype in module.types.values() {
+            for (_, field) in &ttype.fields {
+                self.ttype(field, &ttype.first_token);
+            }
+        }
+        for func in module.functions.values().chain(module.tests.values()) {
+            self.func(func);
+        }
+        for ttype in module.types.values() {
+            for method in ttype.methods.values() {
+                self.func(method);
+            }
+        }
+    }
+
+    fn func(&mut self, func: &Func) {
+        let signature = &func.signature;
+        for arg in &signature.args {
+            self.ttype(&arg.ttype, &arg.first_token);
+        }
+        self.ttype(&signature.return_value, &signature.first_token);
+
+        self.current = self.tree.push_function(func);
+        if let Some(receiver) = &signature.receiver {
+            self.ttype(&receiver.ttype, &receiver.first_token);
+        }
+        for cst in func.constants.values() {
+            self.ttype(&cst.ttype, &cst.first_token);
+        }
+        for cst in func.constants.values() {
+            self.expression(&cst.value);
+        }
+
+        self.statements(&func.statements);
+        self.current = ScopeTree::MODULE;
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    /// block adds the statements of a block to a new scope of `kind` opened by `first_token`.
+    fn block(&mut self, statements: &[Statement], kind: ScopeKind, first_token: &Token) {
+        let parent = self.current;
+        self.current = self.tree.push_block(parent, kind, first_token, statements);
+        self.statements(statements);
+        self.close(parent);
+    }
+
+    /// close leaves the current scope for `parent`, whose span grows to include it.
+    fn close(&mut self, parent: ScopeId) {
+        let span = self.tree.scopes[self.current].span.clone();
+        if let (Some(outer), Some(span)) = (self.tree.scopes[parent].span.as_mut(), span) {
+            outer.extend(&span);
+        }
+        self.current = parent;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        self.seen(&statement.first_token);
+        let t = &statement.first_token;
+        match &statement.value {
+            StatementValue::Expression(e) | StatementValue::Defer(e) => self.expression(e),
+            StatementValue::VarDeclaration(decl) => {
+                self.ttype(&decl.ttype, t);
+                self.expression(&decl.value);
+            }
+            StatementValue::Assignment(assignment) => {
+                self.expression(&assignment.target);
+                self.expression(&assignment.value);
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                for binding in assignment.bindings.iter().filter(|b| *b != "_") {
+                    self.reference(binding, t, false);
+                }
+                self.expression(&assignment.value);
+            }
+            StatementValue::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StatementValue::Break | StatementValue::Continue => (),
+            StatementValue::If(stmt) => {
+                for (condition, body) in &stmt.branches {
+                    self.expression(condition);
+                    let first_token = body
+                        .first()
+                        .map_or(&condition.first_token, |s| &s.first_token);
+                    self.block(body, ScopeKind::Block, first_token);
+                }
+                if let Some(body) = &stmt.else_body {
+                    let first_token = body.first().map_or(t, |s| &s.first_token);
+                    self.block(body, ScopeKind::Block, first_token);
+                }
+            }
+            StatementValue::For(stmt) => {
+                self.expression(&stmt.iterable);
+                let parent = self.current;
+                self.current = self.tree.push_for(parent, stmt, &[], t);
+                self.statements(&stmt.body);
+                self.close(parent);
+            }
+            StatementValue::While(stmt) => {
+                self.expression(&stmt.condition);
+                self.block(&stmt.body, ScopeKind::Loop, t);
+            }
+            StatementValue::Loop(body) => self.block(body, ScopeKind::Loop, t),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        self.seen(&expr.first_token);
+        match &expr.value {
+            ExpressionValue::Identifier(ident) => {
+                // Only the first word of a qualified name is declared in this module
+                let root = ident.namespace.first().unwrap_or(&ident.name);
+                self.reference(root, &expr.first_token, false);
+            }
+            ExpressionValue::FunctionCall(call) => {
+                self.expression(&call.function);
+                for arg in &call.args {
+                    self.expression(arg);
+                }
+            }
+            ExpressionValue::MethodCall(call) => {
+                self.expression(&call.receiver);
+                for arg in &call.args {
+                    self.expression(arg);
+                }
+            }
+            ExpressionValue::MemberAccess(access) => self.expression(&access.object),
+            ExpressionValue::StructLiteral(literal) => {
+                self.ttype(&Type::Struct(literal.ttype.clone()), &expr.first_token);
+                for (_, value) in &literal.fields {
+                    self.expression(value);
+                }
+            }
+            ExpressionValue::ListLiteral(literal) => {
+                self.ttype(&literal.ttype, &expr.first_token);
+                for element in &literal.elements {
+                    self.expression(element);
+                }
+            }
+            ExpressionValue::Index(index) => {
+                self.expression(&index.object);
+                self.expression(&index.index);
+            }
+            ExpressionValue::Tuple(elements) => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            ExpressionValue::OptionCheck(check) => self.expression(&check.operand),
+            ExpressionValue::Propagate(operand) => self.expression(operand),
+            ExpressionValue::Conversion(conversion) => {
+                self.ttype(&conversion.ttype, &expr.first_token);
+                self.expression(&conversion.operand);
+            }
+            ExpressionValue::BinaryOperation(op) => {
+                for operand in &op.operands {
+                    self.expression(operand);
+                }
+            }
+            ExpressionValue::UnaryOperation(op) => self.expression(&op.operand),
+            ExpressionValue::Literal(_) => (),
+        }
+    }
+
+    /// ttype adds a reference for every struct type used by `ttype`.
+    fn ttype(&mut self, ttype: &Type, first_token: &Token) {
+        match ttype {
+            Type::Struct(s) => self.reference(s, first_token, true),
+            Type::Tuple(types) => {
+                for t in types {
+                    self.ttype(t, first_token);
+                }
+            }
+            Type::Optional(t) | Type::List(t) => self.ttype(t, first_token),
+            Type::Result(t, e) => {
+                self.ttype(t, first_token);
+                self.ttype(e, first_token);
+            }
+            _ => (),
+        }
+    }
+
+    fn reference(&mut self, ident: &str, t: &Token, is_type: bool) {
+        let scope = if is_type {
+            ScopeTree::MODULE
+        } else {
+            self.current
+        };
+        self.tree.references.push(Reference {
+            name: ident.into(),
+            span: t.into(),
+            scope: self.current,
+            is_type,
+            declaration: self.tree.lookup(scope, ident),
+        });
+    }
+
+    /// seen extends the span of the current scope to include `t`.
+    fn seen(&mut self, t: &Token) {
+        if let Some(span) = self.tree.scopes[self.current].span.as_mut() {
+            span.extend(&t.into());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::tokenizer::{AssignOperator, Token};
+
+use super::{Expression, Type};
+
+#[derive(Debug)]
+pub struct Statement {
+    pub value: StatementValue,
+    pub first_token: Token,
+}
+
+#[derive(Debug)]
+pub enum StatementValue {
+    Expression(Expression),
+    VarDeclaration(VarDeclaration),
+    Assignment(Assignment),
+    MultiAssignment(MultiAssignment),
+    Return(Option<Expression>),
+    If(If),
+    For(For),
+    While(While),
+    Loop(Vec<Statement>),
+    Break,
+    Continue,
+    /// Defer runs a call when the block holding the statement is left, whichever way that
+    /// happens: by reaching its end, or through `return`, `break`, `continue` or `?`. A `defer`
+    /// in a loop body runs once per iteration. Deferred calls run in the reverse order of the
+    /// `defer` statements that were reached, innermost blocks first.
+    Defer(Expression),
+}
+
+/// VarDeclaration declares a variable of a `var` block inside a function body. The variable
+/// belongs to the block holding the declaration.
+#[derive(Debug)]
+pub struct VarDeclaration {
+    pub ident: String,
+    pub ttype: Type,
+    pub value: Expression,
+}
+
+#[derive(Debug)]
+pub struct Assignment {
+    pub target: Expression,
+    pub operator: AssignOperator,
+    pub value: Expression,
+}
+
+/// MultiAssignment destructures a tuple into several variables, as in `q, r = divmod(7, 2)`.
+/// Elements bound to `_` are discarded.
+#[derive(Debug)]
+pub struct MultiAssignment {
+    pub bindings: Vec<String>,
+    pub value: Expression,
+}
+
+/// If is a whole `if ... else if ... else` chain. Each branch is a condition and the statements
+/// executed when it is the first condition to hold.
+#[derive(Debug)]
+pub struct If {
+    pub branches: Vec<(Expression, Vec<Statement>)>,
+    pub else_body: Option<Vec<Statement>>,
+}
+
+/// For iterates over `iterable`, binding each element to the identifiers in `bindings`, as in
+/// `for i in range(10)` or `for num, response in answers`.
+///
+/// A range is iterated over with a single variable. A list is iterated over with either one
+/// variable, bound to each element, or two, bound to the position of each element and the
+/// element.
+#[derive(Debug)]
+pub struct For {
+    pub bindings: Vec<String>,
+    pub iterable: Expression,
+    pub body: Vec<Statement>,
+}
+
+impl For {
+    /// list_bindings returns the types of the loop variables for a loop over a list of
+    /// elements of type `element`.
+    pub fn list_bindings(&self, element: &Type) -> Vec<Type> {
+        match self.bindings.len() {
+            1 => vec![element.clone()],
+            _ => vec![Type::Int, element.clone()],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct While {
+    pub condition: Expression,
+    pub body: Vec<Statement>,
+}
+
+impl Statement {
+    pub fn new(value: StatementValue, first_token: Token) -> Self {
+        Self { value, first_token }
+    }
+}
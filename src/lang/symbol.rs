@@ -1,12 +1,15 @@
 use crate::tokenizer::Token;
 
-use super::{Const, Expression, Import, Symbol, Type, Variable};
+use std::collections::HashMap;
+
+use super::{Const, Expression, Func, FuncSignature, Import, StructType, Symbol, Type, Variable};
 
 impl Symbol {
     pub fn new_import(import: String, first_token: Token) -> Self {
         Self::Import(Import {
             path: import,
             signature: None,
+            external: false,
             first_token,
         })
     }
@@ -26,4 +29,24 @@ impl Symbol {
             first_token,
         })
     }
+
+    pub fn new_struct(ident: String, fields: Vec<(String, Type)>, first_token: Token) -> Self {
+        Self::Type(StructType {
+            ident,
+            first_token,
+            fields,
+            methods: HashMap::new(),
+        })
+    }
+}
+
+impl Func {
+    pub fn new(signature: FuncSignature, first_token: Token) -> Self {
+        Self {
+            signature,
+            constants: HashMap::new(),
+            statements: vec![],
+            first_token,
+        }
+    }
 }
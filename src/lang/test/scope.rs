@@ -0,0 +1,87 @@
+use crate::{
+    lang::{DeclarationKind, ScopeKind, ScopeTree, StatementValue},
+    parser::Parser,
+};
+
+const SRC: &str = "
+const {
+	limit int = 10
+}
+
+func count(step int) int {
+	var {
+		total int = 0
+	}
+	while total < limit {
+		var {
+			next int = total + step
+		}
+		total = next
+	}
+	return total
+}
+";
+
+fn build() -> ScopeTree {
+    let mut parser = Parser::new("test".into());
+    parser.add_source(SRC.as_bytes(), None).unwrap();
+    let module = parser.finalize().unwrap();
+    ScopeTree::build(&module)
+}
+
+#[test]
+fn lookup_walks_outward() {
+    let tree = build();
+
+    // Inside the loop body, on `next`
+    let body = tree.scope_at("-", 12, 4);
+    assert_eq!(tree.scope(body).kind, ScopeKind::Loop);
+    let function = tree.scope(body).parent.unwrap();
+    assert_eq!(tree.scope(function).kind, ScopeKind::Function);
+
+    let kind = |scope, name| tree.lookup(scope, name).map(|d| tree.declaration(d).kind);
+    assert_eq!(kind(body, "next"), Some(DeclarationKind::Variable));
+    assert_eq!(kind(body, "step"), Some(DeclarationKind::Argument));
+    assert_eq!(kind(body, "limit"), Some(DeclarationKind::Constant));
+    assert_eq!(kind(body, "count"), Some(DeclarationKind::Function));
+    assert_eq!(kind(function, "next"), None);
+}
+
+#[test]
+fn definitions() {
+    let tree = build();
+
+    // `next` in `total = next`
+    let decl = tree.definition_at("-", 14, 11).unwrap();
+    assert_eq!(decl.name, "next");
+    assert_eq!(decl.span.start, (12, 4));
+
+    // `limit` in the loop condition
+    let decl = tree.definition_at("-", 10, 17).unwrap();
+    assert_eq!(decl.name, "limit");
+    assert_eq!(tree.scope(decl.scope).kind, ScopeKind::Module);
+}
+
+#[test]
+fn scopes_added_while_walking() {
+    let mut parser = Parser::new("test".into());
+    parser.add_source(SRC.as_bytes(), None).unwrap();
+    let module = parser.finalize().unwrap();
+    let func = &module.functions["count"];
+
+    let mut tree = ScopeTree::with_module(&module);
+    let scope = tree.push_function(func);
+    let StatementValue::While(stmt) = &func.statements[1].value else {
+        panic!("expected a while loop");
+    };
+    let t = &func.statements[1].first_token;
+    let body = tree.push_block(scope, ScopeKind::Loop, t, &stmt.body);
+
+    assert_eq!(tree.parent(body), scope);
+    assert!(tree.lookup_value(body, "next").is_some_and(|d| d.mutable));
+    assert!(tree.lookup_value(body, "step").is_some_and(|d| !d.mutable));
+    assert!(tree.lookup_value(scope, "next").is_none());
+    // Functions are declared, but are not values
+    assert!(tree.lookup(body, "count").is_some());
+    assert!(tree.lookup_value(body, "count").is_none());
+}
@@ -29,3 +29,134 @@ impl From<String> for Type {
         Self::from(value.as_str())
     }
 }
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Type::Void => "void",
+            Type::Text => "text",
+            Type::Character => "char",
+            Type::Bool => "bool",
+            Type::Int => "int",
+            Type::Int8 => "int8",
+            Type::Int16 => "int16",
+            Type::Int32 => "int32",
+            Type::Int64 => "int64",
+            Type::UInt => "uint",
+            Type::UInt8 => "uint8",
+            Type::UInt16 => "uint16",
+            Type::UInt32 => "uint32",
+            Type::UInt64 => "uint64",
+            Type::Float => "float",
+            Type::Float32 => "float32",
+            Type::Float64 => "float64",
+            Type::Struct(s) => s,
+            Type::Optional(t) => return f.write_fmt(format_args!("?{}", t)),
+            Type::Result(t, e) => return f.write_fmt(format_args!("result[{}, {}]", t, e)),
+            Type::List(t) => return f.write_fmt(format_args!("list[{}]", t)),
+            Type::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                return f.write_fmt(format_args!("({})", types.join(", ")));
+            }
+        };
+        f.write_str(s)
+    }
+}
+
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Int
+                | Type::Int8
+                | Type::Int16
+                | Type::Int32
+                | Type::Int64
+                | Type::UInt
+                | Type::UInt8
+                | Type::UInt16
+                | Type::UInt32
+                | Type::UInt64
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::Float | Type::Float32 | Type::Float64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// bits returns the size of a numeric type in bits. `int`, `uint` and `float` are 64 bits wide.
+    pub fn bits(&self) -> Option<u32> {
+        match self {
+            Type::Int8 | Type::UInt8 => Some(8),
+            Type::Int16 | Type::UInt16 => Some(16),
+            Type::Int32 | Type::UInt32 | Type::Float32 => Some(32),
+            Type::Int | Type::Int64 | Type::UInt | Type::UInt64 | Type::Float | Type::Float64 => {
+                Some(64)
+            }
+            _ => None,
+        }
+    }
+
+    /// range returns the smallest and largest value of an integer type.
+    pub fn range(&self) -> Option<(i128, i128)> {
+        let bits = self.bits()?;
+        match self {
+            Type::Int | Type::Int8 | Type::Int16 | Type::Int32 | Type::Int64 => {
+                Some((-(1 << (bits - 1)), (1 << (bits - 1)) - 1))
+            }
+            Type::UInt | Type::UInt8 | Type::UInt16 | Type::UInt32 | Type::UInt64 => {
+                Some((0, (1 << bits) - 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// wrap reduces an integer to the range of this integer type, keeping its low bits
+    /// reinterpreted in the type. This is how integer arithmetic overflows at runtime.
+    pub fn wrap(&self, v: i128) -> i128 {
+        let (bits, (min, max)) = match (self.bits(), self.range()) {
+            (Some(bits), Some(range)) => (bits, range),
+            _ => return v,
+        };
+        let mut v = v & ((1i128 << bits) - 1);
+        if min < 0 && v > max {
+            v -= 1i128 << bits;
+        }
+        v
+    }
+
+    /// widens_to returns true if every value of this type can be represented exactly in `to`,
+    /// in which case it is converted implicitly. All other conversions between numeric types
+    /// have to be written out.
+    pub fn widens_to(&self, to: &Type) -> bool {
+        let (from_bits, to_bits) = match (self.bits(), to.bits()) {
+            (Some(f), Some(t)) => (f, t),
+            _ => return false,
+        };
+        if self.is_float() {
+            return to.is_float() && from_bits <= to_bits;
+        }
+        if to.is_float() {
+            // The integer has to fit into the mantissa
+            let mantissa = if to_bits == 32 { 24 } else { 53 };
+            return from_bits < mantissa;
+        }
+
+        let (from, to) = (self.range().unwrap(), to.range().unwrap());
+        to.0 <= from.0 && from.1 <= to.1
+    }
+
+    /// element returns the type of what a `for` loop over a value of this type iterates over:
+    /// the elements of a list, or the characters of a text.
+    pub fn element(&self) -> Option<Type> {
+        match self {
+            Type::List(element) => Some(*element.clone()),
+            Type::Text => Some(Type::Character),
+            _ => None,
+        }
+    }
+}
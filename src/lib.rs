@@ -1,4 +1,9 @@
+pub mod check;
 pub mod cmd;
+pub mod codegen;
+pub mod hir;
 pub mod lang;
+pub mod link;
+pub mod opt;
 pub mod parser;
 pub mod tokenizer;
@@ -0,0 +1,417 @@
+//! Linking puts the modules of a program together. `load` parses a module and the modules it
+//! imports from, and `link` resolves the imports between them into an `lang::Assembly`. Once
+//! each module has been checked and lowered, `merge` turns their HIR into a single module, which
+//! is compiled into a single WASM binary.
+//!
+//! A module is a file, named after the file without its `.tiger` extension. An import such as
+//! `util.clamp` refers to function `clamp` of module `util`, which is looked up in the
+//! directories given to `load`, and then in the standard library built into the compiler, from
+//! the `stdlib` directory. Importing from a module that cannot be found is an error.
+//!
+//! Functions declared `extern`, which have a signature of their own, are left to the host. A
+//! module importing an `extern` function of another module, as in `os.exit`, imports it from the
+//! host as well.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hir::{self, Callee, ExpressionKind, FunctionKind, Place, Statement},
+    lang::{Assembly, Module},
+    parser::{self, Parser},
+    tokenizer::Token,
+};
+
+/// The modules of the standard library, by name, with their source.
+pub const STDLIB: [(&str, &str); 3] = [
+    ("format", include_str!("../../stdlib/format.tiger")),
+    ("io", include_str!("../../stdlib/io.tiger")),
+    ("os", include_str!("../../stdlib/os.tiger")),
+];
+
+#[cfg(test)]
+mod test;
+
+#[derive(Debug)]
+pub struct Error {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub source: String,
+}
+
+impl Error {
+    fn at_token(t: &Token, message: String) -> Self {
+        Self {
+            message,
+            line: t.line,
+            column: t.column,
+            source: t.path.clone(),
+        }
+    }
+
+    /// in_module is an error about a whole module.
+    fn in_module(module: &str, message: String) -> Self {
+        Self {
+            message,
+            line: 0,
+            column: 0,
+            source: module.to_string(),
+        }
+    }
+}
+
+impl From<parser::Error> for Error {
+    fn from(e: parser::Error) -> Self {
+        // The error of the tokenizer has the same position, and tells more
+        let (kind, message) = match &e.kind {
+            parser::ErrorKind::TokenizerError(t) => (t.kind.to_string(), &t.message),
+            kind => (kind.to_string(), &e.message),
+        };
+        let message = match message.is_empty() {
+            true => kind,
+            false => format!("{} ({})", kind, message),
+        };
+        Self {
+            message,
+            line: e.line,
+            column: e.column,
+            source: e.source,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            return f.write_fmt(format_args!("{}: {}", self.source, self.message));
+        }
+        f.write_fmt(format_args!(
+            "{}:{}:{}: {}",
+            self.source, self.line, self.column, self.message
+        ))
+    }
+}
+
+/// load parses the module at `root` and every module it imports from, directly or not, looking
+/// them up in `search` and then in `STDLIB`, and links them.
+pub fn load(root: &Path, search: &[PathBuf]) -> Result<Assembly, Vec<Error>> {
+    let mut modules = vec![parse_file(root)?];
+    let mut next = 0;
+    while next < modules.len() {
+        // Functions declared `extern` come from the host
+        let mut wanted: Vec<String> = (modules[next].imports.values())
+            .filter(|i| i.signature.is_none())
+            .filter_map(|i| Some(i.path.rsplit_once('.')?.0.to_string()))
+            .collect();
+        wanted.sort();
+        wanted.dedup();
+        for name in wanted {
+            if modules.iter().any(|m| m.identifier == name) {
+                continue;
+            }
+            let path = search.iter().map(|dir| dir.join(format!("{}.tiger", name)));
+            if let Some(path) = path.into_iter().find(|p| p.is_file()) {
+                modules.push(parse_file(&path)?);
+            } else if let Some((_, src)) = STDLIB.iter().find(|(module, _)| *module == name) {
+                let source = format!("stdlib/{}.tiger", name);
+                modules.push(parse(name, src.as_bytes(), source)?);
+            }
+        }
+        next += 1;
+    }
+    link(modules)
+}
+
+fn parse_file(path: &Path) -> Result<Module, Vec<Error>> {
+    let name = path.to_string_lossy().to_string();
+    let identifier = match path.file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => return Err(vec![Error::in_module(&name, "not a module".into())]),
+    };
+    let file = File::open(path).map_err(|e| {
+        vec![Error::in_module(
+            &name,
+            format!("cannot read module: {}", e),
+        )]
+    })?;
+
+    parse(identifier, file, name)
+}
+
+/// parse parses module `identifier` from `source`, which is named `name` in errors.
+fn parse(identifier: String, source: impl Read, name: String) -> Result<Module, Vec<Error>> {
+    let mut parser = Parser::new(identifier);
+    let res = parser
+        .add_source(source, Some(name))
+        .and_then(|_| parser.finalize());
+    res.map_err(|e| vec![e.into()])
+}
+
+/// link builds the assembly made of `modules`, the first of which holds the entry point. Each
+/// import of a function of another module gets the signature of that function, and an import of
+/// an `extern` function of another module becomes the same import from the host.
+pub fn link(mut modules: Vec<Module>) -> Result<Assembly, Vec<Error>> {
+    let mut errors = vec![];
+
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for (i, module) in modules.iter().enumerate() {
+        if modules[..i]
+            .iter()
+            .any(|m| m.identifier == module.identifier)
+        {
+            errors.push(Error::in_module(
+                &module.identifier,
+                "module is part of the program twice".into(),
+            ));
+        }
+        if i > 0 {
+            if let Some(main) = module.functions.get("main") {
+                errors.push(Error::at_token(
+                    &main.first_token,
+                    format!(
+                        "`main` must be declared in module `{}`, the entry point of the program",
+                        modules[0].identifier
+                    ),
+                ));
+            }
+        }
+        // Types are not namespaced once modules are merged
+        let mut types: Vec<&String> = module.types.keys().collect();
+        types.sort();
+        for name in types {
+            match seen.get(name.as_str()) {
+                Some(other) => errors.push(Error::at_token(
+                    &module.types[name].first_token,
+                    format!("struct `{}` is also declared in module `{}`", name, other),
+                )),
+                None => _ = seen.insert(name, &module.identifier),
+            }
+        }
+    }
+
+    let mut resolved = vec![];
+    for (i, module) in modules.iter().enumerate() {
+        for (ident, import) in &module.imports {
+            if import.signature.is_some() {
+                continue; // Declared `extern`
+            }
+            let (target, name) = match import.path.rsplit_once('.') {
+                Some(path) => path,
+                None => continue,
+            };
+            let target = match modules.iter().position(|m| m.identifier == target) {
+                Some(target) => target,
+                None => {
+                    errors.push(Error::at_token(
+                        &import.first_token,
+                        format!("cannot find module `{}`", target),
+                    ));
+                    continue;
+                }
+            };
+            let declared = modules[target].imports.get(name).filter(|i| i.external);
+            match (modules[target].functions.get(name), declared) {
+                (Some(func), _) => resolved.push((
+                    i,
+                    ident.clone(),
+                    import.path.clone(),
+                    func.signature.clone(),
+                )),
+                (None, Some(declared)) => resolved.push((
+                    i,
+                    ident.clone(),
+                    declared.path.clone(),
+                    declared.signature.clone().unwrap(),
+                )),
+                (None, None) => errors.push(Error::at_token(
+                    &import.first_token,
+                    format!(
+                        "module `{}` has no function `{}`",
+                        modules[target].identifier, name
+                    ),
+                )),
+            }
+        }
+    }
+    for (i, ident, path, signature) in resolved {
+        let import = modules[i].imports.get_mut(&ident).unwrap();
+        import.path = path;
+        import.signature = Some(signature);
+    }
+
+    if !errors.is_empty() {
+        errors.sort_by(|a, b| (&a.source, a.line, a.column).cmp(&(&b.source, b.line, b.column)));
+        return Err(errors);
+    }
+    Ok(Assembly { modules })
+}
+
+/// merge merges the HIR of the modules of an assembly, in the same order, into a single module.
+/// Calls to imported functions of the assembly become calls to the functions themselves. The
+/// functions and globals of all but the first module are renamed to `module.name`, and only the
+/// first module keeps its tests and exports. The imports of the other modules that they never call,
+/// such as the `extern` functions of the standard library a program does not use, are left out.
+pub fn merge(modules: Vec<hir::Module>) -> hir::Module {
+    let mut first_function = vec![];
+    let mut first_global = vec![];
+    let (mut functions, mut globals) = (0, 0);
+    for module in &modules {
+        first_function.push(functions);
+        first_global.push(globals);
+        functions += module.functions.len();
+        globals += module.globals.len();
+    }
+
+    // Where each import of each module goes
+    let mut res = hir::Module {
+        name: modules[0].name.clone(),
+        structs: vec![],
+        globals: vec![],
+        imports: vec![],
+        functions: vec![],
+    };
+    let mut callees: Vec<Vec<Option<Callee>>> = vec![];
+    for (i, module) in modules.iter().enumerate() {
+        let called = called_imports(module);
+        let mut module_callees = vec![];
+        for (id, import) in module.imports.iter().enumerate() {
+            if i > 0 && !called[id] {
+                module_callees.push(None);
+                continue;
+            }
+            let resolved = import.path.rsplit_once('.').and_then(|(target, name)| {
+                let target = modules.iter().position(|m| m.name == target)?;
+                let id = modules[target]
+                    .functions
+                    .iter()
+                    .position(|f| f.kind == FunctionKind::Function && f.name == name)?;
+                Some(Callee::Function(first_function[target] + id))
+            });
+            let callee = match resolved {
+                Some(callee) => callee,
+                None => {
+                    let existing = res
+                        .imports
+                        .iter()
+                        .position(|i| i.path == import.path && i.signature == import.signature);
+                    Callee::Import(existing.unwrap_or_else(|| {
+                        res.imports.push(hir::Import {
+                            name: import.name.clone(),
+                            path: import.path.clone(),
+                            signature: import.signature.clone(),
+                        });
+                        res.imports.len() - 1
+                    }))
+                }
+            };
+            module_callees.push(Some(callee));
+        }
+        callees.push(module_callees);
+    }
+
+    for (i, module) in modules.into_iter().enumerate() {
+        let renumber = |expr: &mut hir::Expression| match &mut expr.kind {
+            ExpressionKind::Call(callee, _) => {
+                *callee = match callee {
+                    Callee::Function(id) => Callee::Function(first_function[i] + *id),
+                    Callee::Import(id) => callees[i][*id].clone().unwrap(),
+                }
+            }
+            ExpressionKind::Global(id) => *id += first_global[i],
+            _ => (),
+        };
+        let rename = |name: String| match i {
+            0 => name,
+            _ => format!("{}.{}", module.name, name),
+        };
+
+        res.structs.extend(module.structs);
+        for mut global in module.globals {
+            walk_expression(&mut global.value, &renumber);
+            global.name = rename(global.name);
+            res.globals.push(global);
+        }
+        for mut func in module.functions {
+            walk_statements(&mut func.body, &renumber, first_global[i]);
+            func.name = rename(func.name);
+            if i > 0 {
+                func.exported = false;
+                // Keep the test so that function IDs stay the same, but make it a plain function
+                if func.kind == FunctionKind::Test {
+                    func.kind = FunctionKind::Function;
+                }
+            }
+            res.functions.push(func);
+        }
+    }
+    res
+}
+
+/// called_imports returns whether each import of `module` is called by its functions or globals.
+fn called_imports(module: &hir::Module) -> Vec<bool> {
+    fn visit(expr: &hir::Expression, called: &mut [bool]) {
+        if let ExpressionKind::Call(Callee::Import(id), _) = &expr.kind {
+            called[*id] = true;
+        }
+        for child in expr.kind.children() {
+            visit(child, called);
+        }
+    }
+    fn visit_statements(statements: &[Statement], called: &mut [bool]) {
+        for statement in statements {
+            for expr in statement.expressions() {
+                visit(expr, called);
+            }
+            for body in statement.bodies() {
+                visit_statements(body, called);
+            }
+        }
+    }
+
+    let mut called = vec![false; module.imports.len()];
+    for func in &module.functions {
+        visit_statements(&func.body, &mut called);
+    }
+    for global in &module.globals {
+        visit(&global.value, &mut called);
+    }
+    called
+}
+
+fn walk_statements(
+    statements: &mut [Statement],
+    f: &impl Fn(&mut hir::Expression),
+    first_global: usize,
+) {
+    for statement in statements {
+        match statement {
+            Statement::Assign(Place::Global(id), _) => *id += first_global,
+            Statement::AssignTuple(places, _) => {
+                for place in places.iter_mut().flatten() {
+                    if let Place::Global(id) = place {
+                        *id += first_global;
+                    }
+                }
+            }
+            _ => (),
+        }
+        for expr in statement.expressions_mut() {
+            walk_expression(expr, f);
+        }
+        for body in statement.bodies_mut() {
+            walk_statements(body, f, first_global);
+        }
+    }
+}
+
+fn walk_expression(expr: &mut hir::Expression, f: &impl Fn(&mut hir::Expression)) {
+    f(expr);
+    for child in expr.kind.children_mut() {
+        walk_expression(child, f);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    check::{check_module, Severity},
+    hir::{self, interpreter},
+    lang::{Assembly, Module, Type},
+    parser::Parser,
+};
+
+use super::{link, load, merge, STDLIB};
+
+fn parse(name: &str, src: &str) -> Module {
+    let mut parser = Parser::new(name.into());
+    parser
+        .add_source(src.as_bytes(), Some(format!("{}.tiger", name)))
+        .unwrap();
+    parser.finalize().unwrap()
+}
+
+/// stdlib parses module `name` of the standard library.
+fn stdlib(name: &str) -> Module {
+    let (_, src) = STDLIB.iter().find(|(module, _)| *module == name).unwrap();
+    parse(name, src)
+}
+
+fn link_errors(modules: Vec<Module>) -> Vec<String> {
+    match link(modules) {
+        Ok(_) => vec![],
+        Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+    }
+}
+
+/// lower checks and lowers every module of `assembly`, and merges them.
+fn lower(assembly: Assembly) -> Result<hir::Module, Vec<String>> {
+    let mut res = vec![];
+    let mut messages = vec![];
+    for mut module in assembly.modules {
+        let errors = check_module(&mut module);
+        (errors.iter())
+            .filter(|e| e.severity == Severity::Error)
+            .for_each(|e| messages.push(e.to_string()));
+        if messages.is_empty() {
+            res.push(hir::lower(&module).unwrap());
+        }
+    }
+    match messages.is_empty() {
+        true => Ok(merge(res)),
+        false => Err(messages),
+    }
+}
+
+const UTIL: &str = "
+var {
+	calls int = 0
+}
+
+func triple(x int) int {
+	calls = calls + 1
+	return helper(x) + x
+}
+
+func helper(x int) int {
+	return x * 2
+}
+
+func count() int {
+	return calls
+}
+";
+
+#[test]
+fn calls_between_modules() {
+    let main = parse(
+        "main",
+        "
+use {
+	util.triple
+	util.count
+	io.print_line
+}
+
+func helper() int {
+	return 1
+}
+
+func main() int {
+	print_line(\"start\")
+	return triple(5) + triple(helper()) + count()
+}
+",
+    );
+    let assembly = link(vec![main, parse("util", UTIL), stdlib("io")]).unwrap();
+    assert!(assembly.resolve("util.triple").is_some());
+    assert!(assembly.entry().is_some());
+
+    let module = lower(assembly).unwrap();
+    // Names of other modules do not collide with the entry module's
+    let mut names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "helper",
+            "io.join",
+            "io.print",
+            "io.print_line",
+            "main",
+            "util.count",
+            "util.helper",
+            "util.triple"
+        ]
+    );
+    assert_eq!(module.imports.len(), 1);
+    assert_eq!(
+        interpreter::run(&module),
+        ["io.write[Text(\"start\\n\")]", "main -> Ok(Int(20))"]
+    );
+}
+
+#[test]
+fn imports_are_checked_against_the_module() {
+    let main = parse(
+        "main",
+        "
+use {
+	util.triple
+}
+
+func main() {
+	var {
+		x text = triple(\"a\")
+	}
+}
+",
+    );
+    let res = lower(link(vec![main, parse("util", UTIL)]).unwrap());
+    assert!(matches!(res, Err(errors) if !errors.is_empty()));
+}
+
+#[test]
+fn missing_functions_are_reported_where_imported() {
+    let main = parse(
+        "main",
+        "
+use {
+	util.triple
+	util.quadruple
+}
+
+func main() {
+}
+",
+    );
+    assert_eq!(
+        link_errors(vec![main, parse("util", UTIL)]),
+        ["main.tiger:4:2: module `util` has no function `quadruple`"]
+    );
+}
+
+#[test]
+fn collisions_are_reported() {
+    let point = "
+struct Point {
+	x int
+}
+";
+    let main = parse("main", &format!("{}\nfunc main() {{\n}}\n", point));
+    let other = parse("other", &format!("{}\nfunc main() {{\n}}\n", point));
+    assert_eq!(
+        link_errors(vec![main, other]),
+        [
+            "other.tiger:2:8: struct `Point` is also declared in module `main`",
+            "other.tiger:6:1: `main` must be declared in module `main`, the entry point of the program",
+        ]
+    );
+}
+
+#[test]
+fn extern_functions_of_other_modules_come_from_the_host() {
+    let main = parse(
+        "main",
+        "
+use {
+	os.arg
+	os.env
+	os.fork
+}
+
+func main() {
+}
+",
+    );
+    assert_eq!(
+        link_errors(vec![main, stdlib("os")]),
+        ["main.tiger:5:2: module `os` has no function `fork`"]
+    );
+
+    let main = parse(
+        "main",
+        "
+use {
+	os.arg
+}
+
+func first_argument() text {
+	return arg(1)
+}
+",
+    );
+    let assembly = link(vec![main, stdlib("os")]).unwrap();
+    let module = lower(assembly).unwrap();
+    // The functions of `os` the program does not call are not imported
+    assert_eq!(module.imports.len(), 1);
+    assert_eq!(module.imports[0].path, "os.arg");
+    assert_eq!(
+        module.imports[0].signature,
+        Some((vec![Type::Int], Type::Text))
+    );
+}
+
+#[test]
+fn format_substitutes_values_in_order() {
+    let main = parse(
+        "main",
+        "
+use {
+	format.format_text
+	format.print_line
+}
+
+func main() {
+	print_line(\"%d: %s\", 1, true)
+	print_line(\"%d%%\\n\", 2.5)
+	print_line(\"\")
+	print_line(\"%s\", format_text(\"%s and %s, %\", 'a'))
+}
+",
+    );
+    let module = lower(link(vec![main, stdlib("format"), stdlib("io")]).unwrap()).unwrap();
+    assert_eq!(
+        interpreter::run(&module),
+        [
+            "io.write[Text(\"1: true\\n\")]",
+            "io.write[Text(\"2.5%\\n\")]",
+            "io.write[Text(\"\\n\")]",
+            "io.write[Text(\"a and %s, %\\n\")]",
+            "main -> Ok(Void)",
+        ]
+    );
+}
+
+#[test]
+fn missing_modules_are_reported_where_imported() {
+    let main = parse(
+        "main",
+        "
+use {
+	io.print_line
+	nosuch.thing
+}
+
+extern \"host\" func shout(s text)
+
+func main() {
+}
+",
+    );
+    assert_eq!(
+        link_errors(vec![main, stdlib("io")]),
+        ["main.tiger:4:2: cannot find module `nosuch`"]
+    );
+}
+
+#[test]
+fn errors_in_imported_modules_keep_their_position() {
+    let dir = std::env::temp_dir().join(format!("tiger-link-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("main.tiger");
+    std::fs::write(&main, "use {\n\tutil.triple\n}\n\nfunc main() {\n}\n").unwrap();
+    std::fs::write(
+        dir.join("util.tiger"),
+        "func triple(x int) int {\n\treturn )\n}\n",
+    )
+    .unwrap();
+    let res = load(&main, std::slice::from_ref(&dir));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let errors = res.err().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].source.ends_with("util.tiger"));
+    assert_eq!((errors[0].line, errors[0].column), (2, 9));
+    assert!(errors[0].message.starts_with("unexpected token `)`"));
+}
+
+#[test]
+fn standard_library_modules_check_cleanly() {
+    let mut checked = vec![];
+    for entry in std::fs::read_dir("stdlib").unwrap() {
+        let path = entry.unwrap().path();
+        let assembly = match load(&path, &["stdlib".into()]) {
+            Ok(assembly) => assembly,
+            Err(errors) => panic!("{}: {:?}", path.display(), errors),
+        };
+        for mut module in assembly.modules {
+            let errors = check_module(&mut module);
+            assert!(errors.is_empty(), "{}", errors[0]);
+        }
+        checked.push(path.file_stem().unwrap().to_string_lossy().to_string());
+    }
+    // Every module of the directory is built into the compiler
+    checked.sort();
+    let builtin: Vec<&str> = STDLIB.iter().map(|(name, _)| *name).collect();
+    assert_eq!(checked, builtin);
+}
@@ -0,0 +1,137 @@
+//! Dead code elimination: branches that are never taken, statements that can never run,
+//! expressions whose value is thrown away without any effect and stores to locals that are never
+//! read.
+
+use crate::hir::{Expression, ExpressionKind, Function, Literal, Place, Statement};
+
+use super::is_pure;
+
+/// eliminate_dead_code removes dead code from `func` and returns true if anything was removed.
+pub(super) fn eliminate_dead_code(func: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let mut read = vec![false; func.locals.len()];
+        for param in &func.params {
+            read[*param] = true;
+        }
+        mark_reads(&func.body, &mut read);
+
+        let mut removed = false;
+        simplify(&mut func.body, &read, &mut removed);
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+fn mark_reads(statements: &[Statement], read: &mut [bool]) {
+    for statement in statements {
+        for expr in statement.expressions() {
+            mark_expression(expr, read);
+        }
+        for body in statement.bodies() {
+            mark_reads(body, read);
+        }
+    }
+}
+
+fn mark_expression(expr: &Expression, read: &mut [bool]) {
+    if let ExpressionKind::Local(id) = expr.kind {
+        read[id] = true;
+    }
+    for child in expr.kind.children() {
+        mark_expression(child, read);
+    }
+}
+
+/// simplify removes dead code from a block, given the locals that are read anywhere in the
+/// function.
+fn simplify(statements: &mut Vec<Statement>, read: &[bool], removed: &mut bool) {
+    let mut res = vec![];
+    let mut remaining = std::mem::take(statements).into_iter();
+    while let Some(mut statement) = remaining.next() {
+        for body in statement.bodies_mut() {
+            simplify(body, read, removed);
+        }
+
+        let diverges = matches!(
+            statement,
+            Statement::Return(_) | Statement::Break | Statement::Continue
+        );
+        match statement {
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => match condition.kind {
+                // The calls deferred in a branch run when the branch is left, so such a branch
+                // has to stay a block of its own
+                ExpressionKind::Literal(Literal::Bool(taken))
+                    if !defers(if taken { &then_body } else { &else_body }) =>
+                {
+                    res.extend(if taken { then_body } else { else_body });
+                    *removed = true;
+                }
+                _ if then_body.is_empty() && else_body.is_empty() => {
+                    discard(condition, &mut res);
+                    *removed = true;
+                }
+                _ => res.push(Statement::If {
+                    condition,
+                    then_body,
+                    else_body,
+                }),
+            },
+            Statement::While { condition, .. }
+                if condition.kind == ExpressionKind::Literal(Literal::Bool(false)) =>
+            {
+                *removed = true
+            }
+            Statement::Expression(e) => {
+                let pure = is_pure(&e);
+                discard(e, &mut res);
+                *removed |= pure;
+            }
+            Statement::Assign(Place::Local(id), value) if !read[id] => {
+                discard(value, &mut res);
+                *removed = true;
+            }
+            Statement::AssignTuple(places, value) => {
+                let places: Vec<Option<Place>> = places
+                    .into_iter()
+                    .map(|p| match p {
+                        Some(Place::Local(id)) if !read[id] => {
+                            *removed = true;
+                            None
+                        }
+                        p => p,
+                    })
+                    .collect();
+                match places.iter().all(|p| p.is_none()) {
+                    true => discard(value, &mut res),
+                    false => res.push(Statement::AssignTuple(places, value)),
+                }
+            }
+            statement => res.push(statement),
+        }
+
+        // Nothing after a return, break or continue runs
+        if diverges {
+            *removed |= remaining.next().is_some();
+            break;
+        }
+    }
+    *statements = res;
+}
+
+fn defers(statements: &[Statement]) -> bool {
+    statements.iter().any(|s| matches!(s, Statement::Defer(_)))
+}
+
+/// discard keeps the effects of evaluating `expr`, whose value is not used.
+fn discard(expr: Expression, statements: &mut Vec<Statement>) {
+    if !is_pure(&expr) {
+        statements.push(Statement::Expression(expr));
+    }
+}
@@ -0,0 +1,231 @@
+//! Constant folding and propagation. Operations on literals are replaced by their result, and
+//! locals that are assigned a literal exactly once, at the top level of the function, are
+//! replaced by that literal wherever they are read afterwards.
+
+use std::collections::HashMap;
+
+use crate::{
+    hir::{Expression, ExpressionKind, Function, Literal, LocalId, Place, Statement},
+    lang::{float_text, Type},
+    tokenizer::{BinaryOperator, UnaryOperator},
+};
+
+use super::{is_pure, walk_expression, walk_expressions};
+
+pub(super) fn fold_function(func: &mut Function) {
+    let mut assignments = vec![0; func.locals.len()];
+    count_assignments(&func.body, &mut assignments);
+    for param in &func.params {
+        assignments[*param] += 1;
+    }
+
+    let mut constants: HashMap<LocalId, Expression> = HashMap::new();
+    for statement in &mut func.body {
+        walk_expressions(std::slice::from_mut(statement), &mut |expr| {
+            if let ExpressionKind::Local(id) = expr.kind {
+                if let Some(value) = constants.get(&id) {
+                    *expr = value.clone();
+                }
+            }
+            fold(expr);
+        });
+
+        if let Statement::Assign(Place::Local(id), value) = statement {
+            if assignments[*id] == 1 && matches!(value.kind, ExpressionKind::Literal(_)) {
+                constants.insert(*id, value.clone());
+            }
+        }
+    }
+}
+
+pub(super) fn fold_expression(expr: &mut Expression) {
+    walk_expression(expr, &mut fold);
+}
+
+fn count_assignments(statements: &[Statement], res: &mut [usize]) {
+    for statement in statements {
+        match statement {
+            Statement::Assign(Place::Local(id), _) => res[*id] += 1,
+            Statement::AssignTuple(places, _) => {
+                for place in places.iter().flatten() {
+                    if let Place::Local(id) = place {
+                        res[*id] += 1;
+                    }
+                }
+            }
+            _ => (),
+        }
+        for body in statement.bodies() {
+            count_assignments(body, res);
+        }
+    }
+}
+
+/// fold replaces `expr` by a simpler expression with the same value, assuming its operands have
+/// already been folded.
+fn fold(expr: &mut Expression) {
+    let ttype = expr.ttype.clone();
+    let folded = match &mut expr.kind {
+        ExpressionKind::Binary(op, lhs, rhs) => binary(op, lhs, rhs, &ttype),
+        ExpressionKind::Unary(op, operand) => match (op, literal(operand)) {
+            (UnaryOperator::Plus, _) => Some((**operand).clone()),
+            (UnaryOperator::Not, Some(Literal::Bool(b))) => Some(bool(!b)),
+            (UnaryOperator::Minus, Some(Literal::Integer(i))) => {
+                Some(Expression::literal(Literal::Integer(ttype.wrap(-i)), ttype))
+            }
+            (UnaryOperator::Minus, Some(Literal::Float(f))) => {
+                Some(Expression::literal(Literal::Float(-f), ttype))
+            }
+            _ => None,
+        },
+        ExpressionKind::Convert(operand) => match literal(operand) {
+            Some(l) => convert(l, &operand.ttype, &ttype).map(|l| Expression::literal(l, ttype)),
+            None => None,
+        },
+        ExpressionKind::IsSome(operand) => match &operand.kind {
+            ExpressionKind::Literal(Literal::Nil) => Some(bool(false)),
+            ExpressionKind::Wrap(_) if is_pure(operand) => Some(bool(true)),
+            _ => None,
+        },
+        ExpressionKind::Unwrap(operand) => match &operand.kind {
+            ExpressionKind::Wrap(value) => Some((**value).clone()),
+            _ => None,
+        },
+        ExpressionKind::Coalesce(value, default) => match &value.kind {
+            ExpressionKind::Literal(Literal::Nil) => Some((**default).clone()),
+            ExpressionKind::Wrap(inner) if is_pure(default) => Some(match ttype {
+                Type::Optional(_) => (**value).clone(),
+                _ => (**inner).clone(),
+            }),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(folded) = folded {
+        *expr = folded;
+    }
+}
+
+fn binary(
+    op: &BinaryOperator,
+    lhs: &Expression,
+    rhs: &Expression,
+    ttype: &Type,
+) -> Option<Expression> {
+    // `&&` and `||` are folded as soon as the result is decided by one operand
+    match (op, literal(lhs), literal(rhs)) {
+        (BinaryOperator::LogicalAnd, Some(Literal::Bool(true)), _)
+        | (BinaryOperator::LogicalOr, Some(Literal::Bool(false)), _) => return Some(rhs.clone()),
+        (BinaryOperator::LogicalAnd, Some(Literal::Bool(false)), _)
+        | (BinaryOperator::LogicalOr, Some(Literal::Bool(true)), _) => return Some(lhs.clone()),
+        (BinaryOperator::LogicalAnd, _, Some(Literal::Bool(true)))
+        | (BinaryOperator::LogicalOr, _, Some(Literal::Bool(false))) => return Some(lhs.clone()),
+        _ => (),
+    }
+
+    let (a, b) = (literal(lhs)?, literal(rhs)?);
+    let operands = &lhs.ttype;
+    let res = match (a, b) {
+        (Literal::Integer(a), Literal::Integer(b)) => match compare(op, a.cmp(&b)) {
+            Some(res) => Literal::Bool(res),
+            None => Literal::Integer(integer(op, a, b, operands)?),
+        },
+        (Literal::Float(a), Literal::Float(b)) => match compare(op, a.partial_cmp(&b)?) {
+            Some(res) => Literal::Bool(res),
+            None => Literal::Float(round(float(op, a, b)?, operands)),
+        },
+        (Literal::String(a), Literal::String(b)) => match (op, compare(op, a.cmp(&b))) {
+            (_, Some(res)) => Literal::Bool(res),
+            (BinaryOperator::Add, None) => Literal::String(a + &b),
+            _ => return None,
+        },
+        (Literal::Char(a), Literal::Char(b)) => Literal::Bool(compare(op, a.cmp(&b))?),
+        (Literal::Bool(a), Literal::Bool(b)) => Literal::Bool(compare(op, a.cmp(&b))?),
+        _ => return None,
+    };
+    Some(Expression::literal(res, ttype.clone()))
+}
+
+fn compare(op: &BinaryOperator, ordering: std::cmp::Ordering) -> Option<bool> {
+    use std::cmp::Ordering::*;
+    Some(match op {
+        BinaryOperator::Equals => ordering == Equal,
+        BinaryOperator::NotEquals => ordering != Equal,
+        BinaryOperator::LessThan => ordering == Less,
+        BinaryOperator::LessThanOrEquals => ordering != Greater,
+        BinaryOperator::GreaterThan => ordering == Greater,
+        BinaryOperator::GreaterThanOrEquals => ordering != Less,
+        _ => return None,
+    })
+}
+
+/// integer applies an arithmetic operator to two integers of type `ttype`. Operations that trap
+/// at runtime are not folded.
+fn integer(op: &BinaryOperator, a: i128, b: i128, ttype: &Type) -> Option<i128> {
+    let bits = ttype.bits()? as i128;
+    let res = match op {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a.checked_mul(b)?,
+        BinaryOperator::Divide if b != 0 && b != -1 => a / b,
+        BinaryOperator::Modulo if b != 0 && b != -1 => a % b,
+        BinaryOperator::BinaryAnd => a & b,
+        BinaryOperator::BinaryOr => a | b,
+        BinaryOperator::Xor => a ^ b,
+        BinaryOperator::ShiftLeft if (0..bits).contains(&b) => a << b,
+        BinaryOperator::ShiftRight if (0..bits).contains(&b) => a >> b,
+        _ => return None,
+    };
+    Some(ttype.wrap(res))
+}
+
+fn float(op: &BinaryOperator, a: f64, b: f64) -> Option<f64> {
+    Some(match op {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        BinaryOperator::Divide => a / b,
+        _ => return None,
+    })
+}
+
+/// convert applies a conversion of a literal of type `from` to `ttype`, following the rules of
+/// `Conversion`.
+fn convert(value: Literal, from: &Type, ttype: &Type) -> Option<Literal> {
+    if *ttype == Type::Text {
+        return Some(Literal::String(match value {
+            Literal::Integer(i) => i.to_string(),
+            Literal::Float(f) => float_text(round(f, from)),
+            Literal::Bool(b) => b.to_string(),
+            Literal::Char(c) => c.to_string(),
+            Literal::String(s) => s,
+            Literal::Nil => return None,
+        }));
+    }
+    Some(match (value, ttype.range()) {
+        (Literal::Integer(i), _) if ttype.is_float() => Literal::Float(round(i as f64, ttype)),
+        (Literal::Integer(i), Some(_)) => Literal::Integer(ttype.wrap(i)),
+        (Literal::Float(f), _) if ttype.is_float() => Literal::Float(round(f, ttype)),
+        (Literal::Float(f), Some((min, max))) => Literal::Integer((f as i128).clamp(min, max)),
+        _ => return None,
+    })
+}
+
+/// round rounds `f` to the precision of the float type `ttype`.
+fn round(f: f64, ttype: &Type) -> f64 {
+    match ttype.bits() {
+        Some(32) => f as f32 as f64,
+        _ => f,
+    }
+}
+
+fn literal(expr: &Expression) -> Option<Literal> {
+    match &expr.kind {
+        ExpressionKind::Literal(l) => Some(l.clone()),
+        _ => None,
+    }
+}
+
+fn bool(b: bool) -> Expression {
+    Expression::literal(Literal::Bool(b), Type::Bool)
+}
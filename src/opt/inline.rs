@@ -0,0 +1,117 @@
+//! Inlining of small functions. A function whose body is a single `return` of a small
+//! expression is inlined into its callers, with its arguments substituted for its parameters.
+//!
+//! Arguments are only substituted if that can't change what the program does: they must be
+//! pure, an argument that is used more than once must be cheap to evaluate again, and if the
+//! body has effects of its own, arguments must not read globals that those effects may change.
+
+use std::collections::HashMap;
+
+use crate::hir::{Callee, Expression, ExpressionKind, FunctionId, Module, Statement};
+
+use super::{callees, is_pure, walk_expression, walk_expressions};
+
+/// Inlined bodies are limited to this many expression nodes.
+const MAX_SIZE: usize = 12;
+
+struct Inlinable {
+    params: Vec<usize>,
+    body: Expression,
+}
+
+pub(super) fn inline_functions(module: &mut Module) {
+    let mut inlinable = HashMap::new();
+    for (id, func) in module.functions.iter().enumerate() {
+        // Calls to these also store the receiver, which an inlined body would not
+        if func.mutable_receiver {
+            continue;
+        }
+        let body = match func.body.as_slice() {
+            [Statement::Return(Some(e))] => e,
+            _ => continue,
+        };
+        let mut recursive = false;
+        callees(body, &mut |callee| recursive |= callee == id);
+        if recursive || size(body) > MAX_SIZE || contains_propagate(body) {
+            continue;
+        }
+        inlinable.insert(
+            id,
+            Inlinable {
+                params: func.params.clone(),
+                body: body.clone(),
+            },
+        );
+    }
+
+    for func in &mut module.functions {
+        walk_expressions(&mut func.body, &mut |expr| inline_call(expr, &inlinable));
+    }
+    for global in &mut module.globals {
+        walk_expression(&mut global.value, &mut |expr| inline_call(expr, &inlinable));
+    }
+}
+
+fn inline_call(expr: &mut Expression, inlinable: &HashMap<FunctionId, Inlinable>) {
+    let (callee, args) = match &expr.kind {
+        ExpressionKind::Call(Callee::Function(id), args) => match inlinable.get(id) {
+            Some(callee) => (callee, args),
+            None => return,
+        },
+        _ => return,
+    };
+    let effects = !is_pure(&callee.body);
+    for (param, arg) in callee.params.iter().zip(args) {
+        let uses = count_uses(&callee.body, *param);
+        if !is_pure(arg) || (uses > 1 && !is_cheap(arg)) || (effects && reads_globals(arg)) {
+            return;
+        }
+    }
+
+    let mut body = callee.body.clone();
+    let args: HashMap<usize, &Expression> = callee.params.iter().copied().zip(args).collect();
+    walk_expression(&mut body, &mut |e| {
+        if let ExpressionKind::Local(id) = e.kind {
+            if let Some(arg) = args.get(&id) {
+                *e = (*arg).clone();
+            }
+        }
+    });
+    *expr = body;
+}
+
+fn reads_globals(expr: &Expression) -> bool {
+    matches!(expr.kind, ExpressionKind::Global(_))
+        || expr.kind.children().into_iter().any(reads_globals)
+}
+
+fn size(expr: &Expression) -> usize {
+    1 + expr.kind.children().into_iter().map(size).sum::<usize>()
+}
+
+fn contains_propagate(expr: &Expression) -> bool {
+    matches!(expr.kind, ExpressionKind::Propagate(_))
+        || expr.kind.children().into_iter().any(contains_propagate)
+}
+
+fn count_uses(expr: &Expression, local: usize) -> usize {
+    let own = match expr.kind {
+        ExpressionKind::Local(id) if id == local => 1,
+        _ => 0,
+    };
+    own + expr
+        .kind
+        .children()
+        .into_iter()
+        .map(|c| count_uses(c, local))
+        .sum::<usize>()
+}
+
+/// is_cheap returns true for expressions that are no more expensive to evaluate twice than to
+/// store in a local.
+fn is_cheap(expr: &Expression) -> bool {
+    matches!(
+        expr.kind,
+        ExpressionKind::Literal(_) | ExpressionKind::Local(_) | ExpressionKind::Global(_)
+    )
+}
@@ -0,0 +1,122 @@
+//! The optimizer rewrites the HIR of a module into a smaller one that behaves the same.
+//!
+//! - `-O0` leaves the module as it is.
+//! - `-O1` folds constant expressions, propagates constant locals, removes dead code and stores,
+//!   and removes functions that cannot be reached from `main`, the tests and the exports.
+//! - `-O2` also inlines small functions before doing all of the above.
+
+use crate::{
+    hir::{Callee, Expression, ExpressionKind, Module, Statement},
+    tokenizer::BinaryOperator,
+};
+
+mod dce;
+mod fold;
+mod inline;
+mod prune;
+
+#[cfg(test)]
+mod test;
+
+/// Level is how hard the optimizer tries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub enum Level {
+    O0,
+    #[default]
+    O1,
+    O2,
+}
+
+impl std::str::FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Level::O0),
+            "1" => Ok(Level::O1),
+            "2" => Ok(Level::O2),
+            _ => Err(format!("invalid optimization level `{}`", s)),
+        }
+    }
+}
+
+/// optimize optimizes `module` at `level`.
+pub fn optimize(module: &mut Module, level: Level) {
+    if level == Level::O0 {
+        return;
+    }
+    if level >= Level::O2 {
+        inline::inline_functions(module);
+    }
+
+    for global in &mut module.globals {
+        fold::fold_expression(&mut global.value);
+    }
+    for func in &mut module.functions {
+        // Removing dead code can leave more constants to propagate, and the other way around
+        loop {
+            fold::fold_function(func);
+            if !dce::eliminate_dead_code(func) {
+                break;
+            }
+        }
+    }
+    prune::remove_unused_functions(module);
+}
+
+/// is_pure returns true if evaluating `expr` has no effect besides producing its value, and
+/// cannot fail.
+fn is_pure(expr: &Expression) -> bool {
+    let own = match &expr.kind {
+        ExpressionKind::Call(_, _) | ExpressionKind::Propagate(_) => false,
+        // Lists are shared, so what their elements and length are depends on when they are
+        // read. Indexing also traps when out of bounds
+        ExpressionKind::Append(_, _) | ExpressionKind::Index(_, _) | ExpressionKind::Length(_) => {
+            false
+        }
+        // Integer division traps on a zero divisor, and on overflow
+        ExpressionKind::Binary(BinaryOperator::Divide | BinaryOperator::Modulo, _, rhs) => {
+            !rhs.ttype.is_integer() || is_safe_divisor(rhs)
+        }
+        _ => true,
+    };
+    own && expr.kind.children().into_iter().all(is_pure)
+}
+
+/// is_safe_divisor returns true if integer division by `expr` can never trap.
+fn is_safe_divisor(expr: &Expression) -> bool {
+    match expr.kind {
+        ExpressionKind::Literal(crate::hir::Literal::Integer(i)) => i != 0 && i != -1,
+        _ => false,
+    }
+}
+
+/// walk_expressions calls `f` on every expression in `statements`, including nested ones,
+/// operands before the expressions using them.
+fn walk_expressions(statements: &mut [Statement], f: &mut impl FnMut(&mut Expression)) {
+    for statement in statements {
+        for expr in statement.expressions_mut() {
+            walk_expression(expr, f);
+        }
+        for body in statement.bodies_mut() {
+            walk_expressions(body, f);
+        }
+    }
+}
+
+fn walk_expression(expr: &mut Expression, f: &mut impl FnMut(&mut Expression)) {
+    for child in expr.kind.children_mut() {
+        walk_expression(child, f);
+    }
+    f(expr);
+}
+
+/// callees calls `f` with every function called in `expr`.
+fn callees(expr: &Expression, f: &mut impl FnMut(usize)) {
+    if let ExpressionKind::Call(Callee::Function(id), _) = &expr.kind {
+        f(*id);
+    }
+    for child in expr.kind.children() {
+        callees(child, f);
+    }
+}
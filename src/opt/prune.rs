@@ -0,0 +1,66 @@
+//! Removal of functions that are never called. Functions are kept if they can be reached from
+//! `main`, a test or an exported function.
+
+use crate::hir::{Callee, ExpressionKind, FunctionKind, Module, Statement};
+
+use super::{callees, walk_expression, walk_expressions};
+
+pub(super) fn remove_unused_functions(module: &mut Module) {
+    let mut used = vec![false; module.functions.len()];
+    let mut todo: Vec<usize> = module
+        .functions
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.name == "main" || f.kind == FunctionKind::Test || f.exported)
+        .map(|(id, _)| id)
+        .collect();
+    for global in &module.globals {
+        callees(&global.value, &mut |id| todo.push(id));
+    }
+
+    while let Some(id) = todo.pop() {
+        if used[id] {
+            continue;
+        }
+        used[id] = true;
+        for_each_callee(&module.functions[id].body, &mut |callee| todo.push(callee));
+    }
+
+    // Functions keep their order, so IDs only move down
+    let mut ids = vec![None; used.len()];
+    let mut next = 0;
+    for (id, used) in used.iter().enumerate() {
+        if *used {
+            ids[id] = Some(next);
+            next += 1;
+        }
+    }
+    let mut id = 0;
+    module.functions.retain(|_| {
+        id += 1;
+        used[id - 1]
+    });
+
+    let mut renumber = |expr: &mut crate::hir::Expression| {
+        if let ExpressionKind::Call(Callee::Function(id), _) = &mut expr.kind {
+            *id = ids[*id].unwrap();
+        }
+    };
+    for func in &mut module.functions {
+        walk_expressions(&mut func.body, &mut renumber);
+    }
+    for global in &mut module.globals {
+        walk_expression(&mut global.value, &mut renumber);
+    }
+}
+
+fn for_each_callee(statements: &[Statement], f: &mut impl FnMut(usize)) {
+    for statement in statements {
+        for expr in statement.expressions() {
+            callees(expr, f);
+        }
+        for body in statement.bodies() {
+            for_each_callee(body, f);
+        }
+    }
+}
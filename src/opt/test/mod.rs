@@ -0,0 +1,119 @@
+use crate::hir::{
+    self, interpreter,
+    test::{lower_examples, lower_source},
+    ExpressionKind, Literal, Statement,
+};
+
+use super::{optimize, Level};
+
+#[test]
+fn folding_and_dead_code() {
+    let mut module = lower_source(
+        "
+const {
+	verbose bool = false
+}
+
+func main() int {
+	var {
+		x int = 6
+		y int = x * 7
+		unused int = 3
+	}
+	if verbose {
+		return 0
+	}
+	return y
+	x = 1
+}
+",
+    );
+    optimize(&mut module, Level::O1);
+
+    let body = &module.functions[0].body;
+    assert_eq!(body.len(), 1);
+    match &body[0] {
+        Statement::Return(Some(e)) => {
+            assert_eq!(e.kind, ExpressionKind::Literal(Literal::Integer(42)))
+        }
+        s => panic!("unexpected {:?}", s),
+    }
+}
+
+#[test]
+fn inlining_and_unused_functions() {
+    let src = "
+func double(x int) int {
+	return x + x
+}
+
+func unused() int {
+	return 1
+}
+
+func main() int {
+	return double(21)
+}
+";
+    let mut module = lower_source(src);
+    optimize(&mut module, Level::O1);
+    let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["double", "main"]);
+
+    let mut module = lower_source(src);
+    optimize(&mut module, Level::O2);
+    let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["main"]);
+    assert_eq!(
+        module.functions[0].body,
+        [Statement::Return(Some(hir::Expression::literal(
+            Literal::Integer(42),
+            crate::lang::Type::Int
+        )))]
+    );
+}
+
+#[test]
+fn branches_with_deferred_calls_stay_blocks() {
+    let src = "
+use {
+	io.print_line
+}
+
+const {
+	verbose bool = true
+}
+
+func main() {
+	if verbose {
+		defer print_line(\"branch\")
+	}
+	print_line(\"after\")
+}
+";
+    let mut module = lower_source(src);
+    let expected = interpreter::run(&module);
+    assert_eq!(expected[0], "io.print_line[Text(\"branch\")]");
+    optimize(&mut module, Level::O1);
+    assert_eq!(interpreter::run(&module), expected);
+}
+
+/// Every example must print and return the same at every optimization level.
+#[test]
+fn examples_behave_the_same() {
+    let expected: Vec<Vec<String>> = (lower_examples().iter())
+        .map(|(_, module)| interpreter::run(module))
+        .collect();
+    for level in [Level::O1, Level::O2] {
+        for ((path, mut module), expected) in lower_examples().into_iter().zip(&expected) {
+            optimize(&mut module, level);
+            assert_eq!(
+                interpreter::run(&module),
+                *expected,
+                "{} at {:?}",
+                path.display(),
+                level
+            );
+        }
+    }
+}
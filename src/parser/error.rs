@@ -1,6 +1,6 @@
 use std::io::Read;
 
-use crate::tokenizer::{self, TokenStream, Token};
+use crate::tokenizer::{self, Token, TokenStream};
 
 #[derive(Debug)]
 pub struct Error {
@@ -25,23 +25,26 @@ impl Error {
     }
 
     pub(super) fn redefined_symbol<R: Read>(ts: &TokenStream<R>, ident: &str) -> Self {
-	let (path, line, col) = ts.position();
+        Self::new(ts, ErrorKind::SymbolRedefined(ident.into()), "".into())
+    }
+
+    pub(super) fn at_token(t: &Token, kind: ErrorKind, message: String) -> Self {
         Self {
-            message: format!("`{}` already defined", ident),
-            kind: ErrorKind::SymbolRedefined(ident.into()),
-            line,
-            column: col,
-            source: path,
+            message,
+            kind,
+            line: t.line,
+            column: t.column,
+            source: t.path.clone(),
         }
     }
 
     pub(super) fn unexpected_token(t: Token, message: String) -> Self {
         Self {
-            message: message,
+            message,
             line: t.line,
             column: t.column,
             source: t.path.clone(),
-            kind: ErrorKind::UnexpectedToken(t),
+            kind: ErrorKind::UnexpectedToken(Box::new(t)),
         }
     }
 }
@@ -49,27 +52,31 @@ impl Error {
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let ErrorKind::TokenizerError(e) = &self.kind {
-	    return e.fmt(f);
-	}
+            return e.fmt(f);
+        }
         f.write_fmt(format_args!(
             "{}:{}:{}: {}",
             self.source, self.line, self.column, self.kind
         ))?;
-	if self.message.is_empty() {
-	    return Ok(());
-	}
-	f.write_fmt(format_args!(" ({})", self.message))
+        if self.message.is_empty() {
+            return Ok(());
+        }
+        f.write_fmt(format_args!(" ({})", self.message))
     }
 }
 
 #[derive(Debug)]
 pub enum ErrorKind {
     Nop,
-    TokenizerError(tokenizer::Error),
-    UnexpectedToken(Token),
+    TokenizerError(Box<tokenizer::Error>),
+    UnexpectedToken(Box<Token>),
     UnexpectedEOF,
     InvalidImport(String),
     SymbolRedefined(String),
+    UndefinedType(String),
+    InvalidReceiver(String),
+    UnknownMember(String, String),
+    ImmutableReceiver(String),
 }
 
 impl std::fmt::Display for ErrorKind {
@@ -77,10 +84,25 @@ impl std::fmt::Display for ErrorKind {
         match self {
             ErrorKind::Nop => f.write_str("no error"),
             ErrorKind::TokenizerError(e) => e.fmt(f),
-            ErrorKind::UnexpectedToken(t) => f.write_fmt(format_args!("unexpected token `{}`", t.text)),
+            ErrorKind::UnexpectedToken(t) => {
+                f.write_fmt(format_args!("unexpected token `{}`", t.text))
+            }
             ErrorKind::UnexpectedEOF => f.write_str("unexpected EOF"),
             ErrorKind::InvalidImport(imp) => f.write_fmt(format_args!("invalid import `{}`", imp)),
-            ErrorKind::SymbolRedefined(s) => f.write_fmt(format_args!("`{}` is already defined", s)),
+            ErrorKind::SymbolRedefined(s) => {
+                f.write_fmt(format_args!("`{}` is already defined", s))
+            }
+            ErrorKind::UndefinedType(t) => f.write_fmt(format_args!("undefined type `{}`", t)),
+            ErrorKind::InvalidReceiver(t) => {
+                f.write_fmt(format_args!("invalid receiver type `{}`", t))
+            }
+            ErrorKind::UnknownMember(t, m) => {
+                f.write_fmt(format_args!("type `{}` has no field or method `{}`", t, m))
+            }
+            ErrorKind::ImmutableReceiver(m) => f.write_fmt(format_args!(
+                "cannot call method `{}` with a mutable receiver on an immutable value",
+                m
+            )),
         }
     }
 }
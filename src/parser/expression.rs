@@ -2,14 +2,104 @@ use std::io::Read;
 
 use crate::{
     lang::{Expression, ExpressionValue},
-    tokenizer::{BinaryOperator, Token, TokenStream, TokenValue, UnaryOperator},
+    tokenizer::{AssignOperator, BinaryOperator, Token, TokenStream, TokenValue, UnaryOperator},
 };
 
-use super::{token_matcher, Error, ErrorKind};
+use super::{
+    consume_token, next_token_is, parse_type, skip_while,
+    token_matcher::{self, OperatorPrecedence},
+    Error, ErrorKind,
+};
 
 pub fn parse<R: Read, F>(ts: &mut TokenStream<R>, terminator: &F) -> Result<Expression, Error>
 where
-    F: Fn(&Token) -> bool,
+    F: Fn(&Token) -> bool + ?Sized,
+{
+    parse_binary(ts, terminator, None)
+}
+
+/// parse_binary parses a sequence of operands joined by binary operators.
+///
+/// Operators that bind as loosely as `limit` or looser end the sequence, so that the caller can
+/// apply them to the whole sub-expression parsed so far. Operators of equal precedence are thus
+/// left-associative.
+fn parse_binary<R: Read, F>(
+    ts: &mut TokenStream<R>,
+    terminator: &F,
+    limit: Option<&OperatorPrecedence>,
+) -> Result<Expression, Error>
+where
+    F: Fn(&Token) -> bool + ?Sized,
+{
+    let mut lhs = parse_operand(ts, terminator)?;
+
+    while let Some(t) = ts.peek() {
+        let t = t?;
+        if terminator(&t) {
+            break;
+        }
+        let operator = match &t.value {
+            TokenValue::BinaryOperator(op) => op.clone(),
+            TokenValue::KeywordIs => {
+                if limit.is_some_and(|l| OperatorPrecedence::from(&t) >= *l) {
+                    break;
+                }
+                _ = ts.next_token(); // Pop `is`
+                let t = consume_token(
+                    ts,
+                    |t| t.value == TokenValue::KeywordSome || t.value == TokenValue::NilLiteral,
+                    "expected `some` or `nil` after `is`".into(),
+                )?;
+                lhs = Expression::option_check(lhs, t.value == TokenValue::KeywordSome);
+                continue;
+            }
+            _ => {
+                return Err(Error::unexpected_token(
+                    t,
+                    "while parsing expression".into(),
+                ))
+            }
+        };
+
+        let precedence = OperatorPrecedence::from(&t);
+        if limit.is_some_and(|l| precedence >= *l) {
+            break;
+        }
+
+        _ = ts.next_token(); // Pop operator
+        let rhs = if operator == BinaryOperator::NilCoalesce {
+            // `a ?? b ?? c` is `a ?? (b ?? c)`
+            parse_binary(ts, terminator, Some(&OperatorPrecedence::Comparison))?
+        } else {
+            parse_binary(ts, terminator, Some(&precedence))?
+        };
+        lhs = Expression::binary_operation(operator, lhs, rhs);
+    }
+
+    Ok(lhs)
+}
+
+/// parse_operand parses a single operand of a binary operation: a unary operation followed by
+/// any `as` conversions. Conversions apply to the whole unary operation, so that `-x as int8`
+/// is `(-x) as int8`.
+fn parse_operand<R: Read, F>(ts: &mut TokenStream<R>, terminator: &F) -> Result<Expression, Error>
+where
+    F: Fn(&Token) -> bool + ?Sized,
+{
+    let mut operand = parse_unary(ts, terminator)?;
+    while next_token_is(ts, |t| t.value == TokenValue::KeywordAs && !terminator(t))? {
+        _ = ts.next_token(); // Pop `as`
+        let ttype = parse_type(ts)?;
+        operand = Expression::conversion(operand, ttype);
+    }
+    Ok(operand)
+}
+
+/// parse_unary parses a literal, identifier or parenthesized expression with any unary
+/// operators in front of it and any function calls, member accesses or `?` after it.
+fn parse_unary<R: Read, F>(ts: &mut TokenStream<R>, terminator: &F) -> Result<Expression, Error>
+where
+    F: Fn(&Token) -> bool + ?Sized,
 {
     let first_token = match ts.peek() {
         Some(t) => t?,
@@ -22,215 +112,321 @@ where
         }
     };
 
-    let mut first_subexp = match &first_token.value {
-        TokenValue::Identifier(_) => parse_identifier(ts, terminator),
-        TokenValue::IntegerLiteral(v) => Ok(Expression::literal_int(
-            v.to_owned(),
-            ts.next_token().unwrap()?,
-        )),
-        TokenValue::FloatingPointLiteral(v) => Ok(Expression::literal_float(
-            v.to_owned(),
-            ts.next_token().unwrap()?,
-        )),
-        TokenValue::StringLiteral(v) => Ok(Expression::literal_string(
-            v.to_owned(),
-            ts.next_token().unwrap()?,
-        )),
-        TokenValue::CharLiteral(v) => Ok(Expression::literal_char(
-            v.to_owned(),
-            ts.next_token().unwrap()?,
-        )),
-        TokenValue::BoolLiteral(v) => Ok(Expression::literal_bool(
-            v.to_owned(),
-            ts.next_token().unwrap()?,
-        )),
+    match &first_token.value {
         TokenValue::UnaryOperator(v) => {
             _ = ts.next_token(); // Pop operator
-            let sub_expression = parse(ts, terminator)?;
-            match v {
-                // Note: the Minus and Plus case currently can't be reached, because the
-                // tokenizer converts all '+' and '-' signs into BinaryOperator tokens.
-                UnaryOperator::Not => {
-                    Ok(Expression::unary_not(sub_expression, first_token.clone()))
-                }
-                UnaryOperator::Minus => {
-                    Ok(Expression::unary_minus(sub_expression, first_token.clone()))
-                }
-                UnaryOperator::Plus => {
-                    Ok(Expression::unary_plus(sub_expression, first_token.clone()))
-                }
-            }
+            let operand = parse_unary(ts, terminator)?;
+            // Note: the Minus and Plus case currently can't be reached, because the
+            // tokenizer converts all '+' and '-' signs into BinaryOperator tokens.
+            return match v {
+                UnaryOperator::Not => Ok(Expression::unary_not(operand, first_token.clone())),
+                UnaryOperator::Minus => Ok(Expression::unary_minus(operand, first_token.clone())),
+                UnaryOperator::Plus => Ok(Expression::unary_plus(operand, first_token.clone())),
+            };
         }
         TokenValue::BinaryOperator(BinaryOperator::Subtract) => {
             _ = ts.next_token(); // Pop operator
-            let sub_expression = parse(ts, terminator)?;
-            Ok(Expression::unary_minus(sub_expression, first_token.clone()))
+            let operand = parse_unary(ts, terminator)?;
+            return Ok(Expression::unary_minus(operand, first_token));
         }
         TokenValue::BinaryOperator(BinaryOperator::Add) => {
             _ = ts.next_token(); // Pop operator
-            let sub_expression = parse(ts, terminator)?;
-            Ok(Expression::unary_plus(sub_expression, first_token.clone()))
+            let operand = parse_unary(ts, terminator)?;
+            return Ok(Expression::unary_plus(operand, first_token));
         }
-        TokenValue::OpenParen => {
-            _ = ts.next_token(); // Pop '('
-            let sub_expression = parse(ts, &token_matcher::close_paren)?;
-            super::consume_token(ts, token_matcher::close_paren, "".into())?;
-            Ok(sub_expression)
-        }
-        _ => {
-            return Err(Error::unexpected_token(
-                first_token,
-                "expecting an expression".into(),
-            ))
-        }
-    }?;
+        _ => (),
+    }
+
+    let mut operand = parse_primary(ts, terminator)?;
 
     while let Some(t) = ts.peek() {
         let t = t?;
         if terminator(&t) {
-            return Ok(first_subexp);
-        }
-        match (&first_subexp.value, &t.value) {
-            (ExpressionValue::Identifier(_), TokenValue::OpenParen) => {
-                // Parsing a function call
-                _ = ts.next_token();
-                let mut args = Vec::new();
-                loop {
-                    let arg = parse(
-                        ts,
-                        &token_matcher::either(token_matcher::close_paren, token_matcher::comma),
-                    )?;
-                    args.push(arg);
-                    if let Some(t) = ts.peek() {
-                        let t = t?;
-                        match &t.value {
-                            TokenValue::CloseParen => {
-                                _ = ts.next_token();
-                                break;
-                            }
-                            TokenValue::Comma => {
-                                _ = ts.next_token();
-                            }
-                            _ => {
-                                return Err(Error::unexpected_token(
-                                    t,
-                                    "while parsing function call argument list".into(),
-                                ))
-                            }
-                        }
-                    } else {
-                        // Should not happen, parse above would have returned unexpected EOF error
-                        return Err(Error::new(
-                            ts,
-                            ErrorKind::UnexpectedEOF,
-                            "unexpected EOF, expression expected".into(),
-                        ));
-                    }
-                }
-                first_subexp = Expression::function_call(first_subexp, args);
-            } // Function call
-            (_, TokenValue::Dot) => {
-                // Struct member access
-                // TODO
-                return Err(Error::unexpected_token(
-                    t,
-                    "member access is only supported for identifier expressions".into(),
-                ));
+            break;
+        }
+        match &t.value {
+            TokenValue::OpenParen => {
+                // Function or method call. Which one it is gets decided once the whole module
+                // has been parsed.
+                _ = ts.next_token(); // Pop '('
+                let args = parse_call_arguments(ts)?;
+                operand = Expression::function_call(operand, args);
             }
-            (_, TokenValue::BinaryOperator(op)) => todo!(),
-            (_, TokenValue::OpenBracket) => {
-                // List member access
-                return Err(Error::unexpected_token(
-                    t,
-                    "list member access is not implemented yet".into(),
-                ));
+            TokenValue::Dot => {
+                // Member access on anything but a plain identifier, such as `a.b().c`.
+                _ = ts.next_token(); // Pop '.'
+                let member = consume_token(
+                    ts,
+                    token_matcher::identifier,
+                    "expected field or method name after `.`".into(),
+                )?;
+                operand = Expression::member_access(operand, member.text);
             }
-            (_, _) => {
-                return Err(Error::unexpected_token(
-                    t,
-                    "while parsing expression".into(),
-                ))
+            TokenValue::QuestionMark => {
+                // Error propagation
+                _ = ts.next_token(); // Pop '?'
+                operand = Expression::propagate(operand);
+            }
+            TokenValue::OpenBracket => {
+                // List element access
+                _ = ts.next_token(); // Pop '['
+                let index = parse(ts, &token_matcher::close_bracket)?;
+                consume_token(
+                    ts,
+                    token_matcher::close_bracket,
+                    "expected `]` after list index".into(),
+                )?;
+                operand = Expression::index(operand, index);
             }
+            _ => break,
         }
     }
-    return Ok(first_subexp);
+
+    Ok(operand)
 }
 
-fn parse_identifier<R: Read, F>(ts: &mut TokenStream<R>, terminator: F) -> Result<Expression, Error>
+fn parse_primary<R: Read, F>(ts: &mut TokenStream<R>, terminator: &F) -> Result<Expression, Error>
 where
-    F: Fn(&Token) -> bool,
+    F: Fn(&Token) -> bool + ?Sized,
 {
-    let first_token: Token = match ts.peek() {
+    let first_token = match ts.peek() {
         Some(t) => t?,
         None => {
             return Err(Error::new(
                 ts,
                 ErrorKind::UnexpectedEOF,
-                "unexpected EOF, identifier expected".into(),
-            ))
+                "unexpected EOF, expression expected".into(),
+            ));
         }
     };
 
-    let mut words = Vec::<String>::new();
-    let mut must_dot = false;
-    while let Some(t) = ts.peek() {
-        let t = t?;
-        match &t.value {
-            TokenValue::Identifier(i) => {
-                if !must_dot {
-                    _ = ts.next_token();
-                    words.push(i.clone());
-                    must_dot = true;
-                } else {
-                    Error::unexpected_token(t, "expected `.` or end of identifier".into());
-                }
+    match &first_token.value {
+        TokenValue::Identifier(_) => {
+            let ident = parse_identifier(ts)?;
+            if first_token.text == "list"
+                && matches!(&ident.value, ExpressionValue::Identifier(i) if i.namespace.is_empty())
+                && next_token_is(ts, |t| t.value == TokenValue::OpenBracket)?
+            {
+                // `list[T]{...}`, which is why a value called `list` cannot be indexed
+                parse_list_literal(ts, first_token)
+            } else if next_token_is(ts, |t| t.value == TokenValue::OpenBrace && !terminator(t))? {
+                parse_struct_literal(ts, first_token.text.clone(), first_token)
+            } else {
+                Ok(ident)
             }
-
-            TokenValue::Dot => {
-                if must_dot {
-                    _ = ts.next_token();
-                    must_dot = false;
-                } else {
-                    Error::unexpected_token(t, "while parsing identifier".into());
-                }
+        }
+        TokenValue::IntegerLiteral(v) => Ok(Expression::literal_int(
+            v.to_owned(),
+            ts.next_token().unwrap()?,
+        )),
+        TokenValue::FloatingPointLiteral(v) => Ok(Expression::literal_float(
+            v.to_owned(),
+            ts.next_token().unwrap()?,
+        )),
+        TokenValue::StringLiteral(v) => Ok(Expression::literal_string(
+            v.to_owned(),
+            ts.next_token().unwrap()?,
+        )),
+        TokenValue::CharLiteral(v) => Ok(Expression::literal_char(
+            v.to_owned(),
+            ts.next_token().unwrap()?,
+        )),
+        TokenValue::BoolLiteral(v) => Ok(Expression::literal_bool(
+            v.to_owned(),
+            ts.next_token().unwrap()?,
+        )),
+        TokenValue::NilLiteral => Ok(Expression::literal_nil(ts.next_token().unwrap()?)),
+        TokenValue::OpenParen => {
+            _ = ts.next_token(); // Pop '('
+            let elements = parse_list(ts, &token_matcher::close_paren)?;
+            consume_token(ts, token_matcher::close_paren, "".into())?;
+            if elements.len() == 1 {
+                Ok(elements.into_iter().next().unwrap())
+            } else {
+                Ok(Expression::tuple(elements, first_token))
             }
+        }
+        _ => Err(Error::unexpected_token(
+            first_token,
+            "expecting an expression".into(),
+        )),
+    }
+}
 
-            TokenValue::UnaryOperator(_)
-            | TokenValue::BinaryOperator(_)
-            | TokenValue::Assignment(_)
-            | TokenValue::Comma
-            | TokenValue::Newline
-            | TokenValue::OpenBracket => break,
+/// parse_list parses one or more comma separated expressions, up to but not including the token
+/// matched by `terminator`.
+pub fn parse_list<R: Read, F>(
+    ts: &mut TokenStream<R>,
+    terminator: &F,
+) -> Result<Vec<Expression>, Error>
+where
+    F: Fn(&Token) -> bool + ?Sized,
+{
+    let mut res = vec![];
+    loop {
+        res.push(parse(ts, &|t: &Token| {
+            terminator(t) || token_matcher::comma(t)
+        })?);
+        if !next_token_is(ts, token_matcher::comma)? {
+            return Ok(res);
+        }
+        _ = ts.next_token(); // Pop ','
+    }
+}
 
-            _ => {
-                if terminator(&t) {
-                    break;
-                } else {
+/// parse_call_arguments parses the argument list of a function call, after the opening `(` and
+/// up to and including the closing `)`.
+fn parse_call_arguments<R: Read>(ts: &mut TokenStream<R>) -> Result<Vec<Expression>, Error> {
+    let mut args = Vec::new();
+
+    if next_token_is(ts, token_matcher::close_paren)? {
+        _ = ts.next_token();
+        return Ok(args);
+    }
+
+    loop {
+        let arg = parse(
+            ts,
+            &token_matcher::either(token_matcher::close_paren, token_matcher::comma),
+        )?;
+        args.push(arg);
+        if let Some(t) = ts.next_token() {
+            let t = t?;
+            match &t.value {
+                TokenValue::CloseParen => return Ok(args),
+                TokenValue::Comma => (),
+                _ => {
                     return Err(Error::unexpected_token(
                         t,
-                        "while parsing identifier".into(),
-                    ));
+                        "while parsing function call argument list".into(),
+                    ))
                 }
             }
+        } else {
+            // Should not happen, parse above would have returned unexpected EOF error
+            return Err(Error::new(
+                ts,
+                ErrorKind::UnexpectedEOF,
+                "unexpected EOF, expression expected".into(),
+            ));
         }
     }
+}
 
-    if words.is_empty() {
-        if let Some(t) = ts.peek() {
-            let t = t?;
-            return Err(Error::unexpected_token(t, "expected identifier".into()));
-        } else {
+/// parse_struct_literal parses the `{field = value, ...}` part of a struct literal. The type name
+/// has already been consumed, `first_token` is its first token.
+fn parse_struct_literal<R: Read>(
+    ts: &mut TokenStream<R>,
+    ttype: String,
+    first_token: Token,
+) -> Result<Expression, Error> {
+    consume_token(ts, token_matcher::open_brace, "".into())?;
+
+    let mut fields = vec![];
+    loop {
+        skip_while(ts, token_matcher::newline)?;
+        let t = match ts.next_token() {
+            Some(t) => t?,
+            None => {
+                return Err(Error::new(
+                    ts,
+                    ErrorKind::UnexpectedEOF,
+                    "missing `}` after struct literal".into(),
+                ))
+            }
+        };
+        let field = match &t.value {
+            TokenValue::CloseBrace => break,
+            TokenValue::Identifier(i) => i.clone(),
+            _ => {
+                return Err(Error::unexpected_token(
+                    t,
+                    "expected field name or `}` in struct literal".into(),
+                ))
+            }
+        };
+        consume_token(
+            ts,
+            |t| t.value == TokenValue::Assignment(AssignOperator::Assign),
+            "expected `=` after field name in struct literal".into(),
+        )?;
+        let value = parse(
+            ts,
+            &token_matcher::either(token_matcher::comma, token_matcher::end_of_statement),
+        )?;
+        fields.push((field, value));
+
+        if next_token_is(ts, token_matcher::comma)? {
+            _ = ts.next_token();
+        }
+    }
+
+    Ok(Expression::struct_literal(ttype, fields, first_token))
+}
+
+/// parse_list_literal parses the `[T]{element, ...}` part of a list literal. The `list` keyword
+/// has already been consumed, `first_token` is its token.
+fn parse_list_literal<R: Read>(
+    ts: &mut TokenStream<R>,
+    first_token: Token,
+) -> Result<Expression, Error> {
+    consume_token(ts, |t| t.value == TokenValue::OpenBracket, "".into())?;
+    let ttype = parse_type(ts)?;
+    consume_token(
+        ts,
+        token_matcher::close_bracket,
+        "expected `]` after the element type of a list".into(),
+    )?;
+    consume_token(
+        ts,
+        token_matcher::open_brace,
+        "expected `{` after list type in list literal".into(),
+    )?;
+
+    let mut elements = vec![];
+    loop {
+        skip_while(ts, token_matcher::newline)?;
+        if next_token_is(ts, token_matcher::close_brace)? {
+            _ = ts.next_token();
+            break;
+        }
+        if ts.peek().is_none() {
             return Err(Error::new(
                 ts,
                 ErrorKind::UnexpectedEOF,
-                "unexpected EOF, identifier expected".into(),
+                "missing `}` after list literal".into(),
             ));
         }
+        elements.push(parse(
+            ts,
+            &token_matcher::either(token_matcher::comma, token_matcher::end_of_statement),
+        )?);
+
+        if next_token_is(ts, token_matcher::comma)? {
+            _ = ts.next_token();
+        }
     }
 
-    Ok(Expression::identifier(words, first_token))
+    Ok(Expression::list_literal(ttype, elements, first_token))
 }
 
-fn test() {
-    let function_call = parse!(func: identifier);
+/// parse_identifier parses a plain or dotted identifier such as `x`, `io.print_line` or `p.x`.
+///
+/// A dotted identifier is recorded as a namespace path, because whether `p.x` is the field of a
+/// value or a name from another module can only be told once all symbols are known.
+fn parse_identifier<R: Read>(ts: &mut TokenStream<R>) -> Result<Expression, Error> {
+    let first_token = consume_token(ts, token_matcher::identifier, "expected identifier".into())?;
+
+    let mut words = vec![first_token.text.clone()];
+    while next_token_is(ts, |t| t.value == TokenValue::Dot)? {
+        _ = ts.next_token(); // Pop '.'
+        let word = consume_token(
+            ts,
+            token_matcher::identifier,
+            "expected identifier after `.`".into(),
+        )?;
+        words.push(word.text);
+    }
+
+    Ok(Expression::identifier(words, first_token))
 }
@@ -0,0 +1,208 @@
+use std::io::Read;
+
+use crate::{
+    lang::{Argument, Func, FuncSignature, Receiver, Type},
+    tokenizer::{Token, TokenStream, TokenValue},
+};
+
+use super::{
+    consume_token, next_token_is, parse_type, statement, token_matcher, Error, ErrorKind, Result,
+};
+
+/// parse_func parses a function, method or test declaration, starting after the `func` or `test`
+/// keyword. It returns the identifier of the function together with the function itself.
+///
+/// Methods are declared with a receiver in front of the function name, as in
+/// `func (p Point) length() float`, or `func (var p Point) scale(f float)` for methods that modify
+/// the value they are called on.
+pub fn parse_func<R: Read>(
+    token_stream: &mut TokenStream<R>,
+    first_token: Token,
+    allow_receiver: bool,
+) -> Result<(String, Func)> {
+    let receiver = if next_token_is(token_stream, token_matcher::open_paren)? {
+        let t = token_stream.next_token().unwrap()?;
+        if !allow_receiver {
+            return Err(Error::unexpected_token(
+                t,
+                "only functions can have a receiver".into(),
+            ));
+        }
+        Some(parse_receiver(token_stream)?)
+    } else {
+        None
+    };
+
+    let ident = consume_token(
+        token_stream,
+        token_matcher::identifier,
+        "expected function name".into(),
+    )?;
+    consume_token(
+        token_stream,
+        token_matcher::open_paren,
+        "expected `(` after function name".into(),
+    )?;
+    let (args, variadic) = parse_arguments(token_stream)?;
+
+    let return_value = if next_token_is(token_stream, token_matcher::open_brace)? {
+        Type::Void
+    } else {
+        parse_type(token_stream)?
+    };
+
+    let signature = FuncSignature {
+        receiver,
+        args,
+        variadic,
+        return_value,
+        first_token: first_token.clone(),
+    };
+    let mut func = Func::new(signature, first_token);
+    func.statements = statement::parse_block(token_stream, &mut func)?;
+
+    Ok((ident.text, func))
+}
+
+/// parse_extern parses the declaration of a function provided by the host, starting after the
+/// `extern` keyword, as in `extern "env" func print(s text)`. The optional string names the WASM
+/// module the function is imported from, `env` by default. It returns the identifier of the
+/// function together with its path, `module.name`, and its signature.
+pub fn parse_extern<R: Read>(
+    token_stream: &mut TokenStream<R>,
+    first_token: Token,
+) -> Result<(String, String, FuncSignature)> {
+    let mut module = "env".to_string();
+    if let Some(t) = token_stream.peek() {
+        if let TokenValue::StringLiteral(s) = t?.value {
+            _ = token_stream.next_token();
+            if s.is_empty() || s.contains('.') {
+                return Err(Error::new(
+                    token_stream,
+                    ErrorKind::InvalidImport(s),
+                    "invalid module name".into(),
+                ));
+            }
+            module = s;
+        }
+    }
+    consume_token(
+        token_stream,
+        |t| t.value == TokenValue::KeywordFunc,
+        "expected `func` after `extern`".into(),
+    )?;
+
+    let ident = consume_token(
+        token_stream,
+        token_matcher::identifier,
+        "expected function name".into(),
+    )?;
+    consume_token(
+        token_stream,
+        token_matcher::open_paren,
+        "expected `(` after function name".into(),
+    )?;
+    let (args, variadic) = parse_arguments(token_stream)?;
+
+    // Declarations have no body, so they end with the line
+    let ends = token_stream.peek().is_none();
+    let return_value = if ends || next_token_is(token_stream, token_matcher::newline)? {
+        Type::Void
+    } else {
+        parse_type(token_stream)?
+    };
+
+    let signature = FuncSignature {
+        receiver: None,
+        args,
+        variadic,
+        return_value,
+        first_token,
+    };
+    let path = format!("{}.{}", module, ident.text);
+    Ok((ident.text, path, signature))
+}
+
+/// parse_receiver parses the receiver of a method, after the opening `(` and up to and including
+/// the closing `)`.
+fn parse_receiver<R: Read>(token_stream: &mut TokenStream<R>) -> Result<Receiver> {
+    let mutable = next_token_is(token_stream, |t| t.value == TokenValue::KeywordVar)?;
+    if mutable {
+        _ = token_stream.next_token(); // Pop `var`
+    }
+
+    let ident = consume_token(
+        token_stream,
+        token_matcher::identifier,
+        "expected receiver name".into(),
+    )?;
+    let ttype = parse_type(token_stream)?;
+    consume_token(
+        token_stream,
+        token_matcher::close_paren,
+        "expected `)` after method receiver".into(),
+    )?;
+
+    Ok(Receiver {
+        ident: ident.text.clone(),
+        ttype,
+        mutable,
+        first_token: ident,
+    })
+}
+
+/// parse_arguments parses the argument list of a function declaration, after the opening `(` and
+/// up to and including the closing `)`. It also returns whether the last argument is variadic,
+/// declared as `name ...T`, in which case its type is `list[T]`.
+fn parse_arguments<R: Read>(token_stream: &mut TokenStream<R>) -> Result<(Vec<Argument>, bool)> {
+    let mut res: Vec<Argument> = vec![];
+    if next_token_is(token_stream, token_matcher::close_paren)? {
+        _ = token_stream.next_token();
+        return Ok((res, false));
+    }
+
+    loop {
+        let arg = consume_token(
+            token_stream,
+            token_matcher::identifier,
+            "expected argument name".into(),
+        )?;
+        if res.iter().any(|a| a.ident == arg.text) {
+            return Err(Error::at_token(
+                &arg,
+                ErrorKind::SymbolRedefined(arg.text.clone()),
+                "duplicate argument name".into(),
+            ));
+        }
+        let variadic = next_token_is(token_stream, |t| t.value == TokenValue::Ellipsis)?;
+        if variadic {
+            _ = token_stream.next_token(); // Pop `...`
+        }
+        let ttype = parse_type(token_stream)?;
+        res.push(Argument {
+            ident: arg.text.clone(),
+            ttype: match variadic {
+                true => Type::List(Box::new(ttype)),
+                false => ttype,
+            },
+            first_token: arg,
+        });
+
+        if variadic {
+            consume_token(
+                token_stream,
+                token_matcher::close_paren,
+                "only the last argument can be variadic".into(),
+            )?;
+            return Ok((res, true));
+        }
+        let t = consume_token(
+            token_stream,
+            |t| token_matcher::comma(t) || token_matcher::close_paren(t),
+            "expected `,` or `)` in argument list".into(),
+        )?;
+        if token_matcher::close_paren(&t) {
+            return Ok((res, false));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+
+use crate::{
+    lang::{
+        Declaration, Expression, ExpressionValue, Func, Identifier, Literal, Module, ScopeId,
+        ScopeKind, ScopeTree, Statement, StatementValue, Type,
+    },
+    tokenizer::Token,
+};
+
+use super::{Error, ErrorKind, Result};
+
+/// StructInfo holds what is needed about a struct type to resolve member accesses on its values.
+struct StructInfo {
+    fields: HashMap<String, Type>,
+    // Method name to return type and whether the method has a mutable receiver.
+    methods: HashMap<String, (Type, bool)>,
+}
+
+/// Resolver tells member accesses and method calls apart from module-qualified identifiers.
+///
+/// The parser records both `p.x` and `io.print_line` as identifiers with a namespace. When the
+/// first word of such an identifier refers to a value, it is rewritten into a chain of member
+/// accesses, and a call to the last member of the chain becomes a method call. Identifiers whose
+/// first word does not refer to a value are left as module-qualified names.
+struct Resolver {
+    functions: HashMap<String, Type>,
+    types: HashMap<String, StructInfo>,
+    tree: ScopeTree,
+    scope: ScopeId,
+}
+
+pub fn resolve(module: &mut Module) -> Result<()> {
+    let mut resolver = Resolver::new(module);
+
+    for cst in module.constants.values_mut() {
+        resolver.resolve_expression(&mut cst.value)?;
+    }
+    for var in module.variables.values_mut() {
+        resolver.resolve_expression(&mut var.initial_value)?;
+    }
+    for func in module.functions.values_mut() {
+        resolver.resolve_func(func)?;
+    }
+    for func in module.tests.values_mut() {
+        resolver.resolve_func(func)?;
+    }
+    for ttype in module.types.values_mut() {
+        for method in ttype.methods.values_mut() {
+            resolver.resolve_func(method)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Resolver {
+    fn new(module: &Module) -> Self {
+        let functions = module
+            .functions
+            .iter()
+            .map(|(ident, f)| (ident.clone(), f.signature.return_value.clone()))
+            .collect();
+
+        let types = module
+            .types
+            .iter()
+            .map(|(ident, t)| {
+                let info = StructInfo {
+                    fields: t.fields.iter().cloned().collect(),
+                    methods: t
+                        .methods
+                        .iter()
+                        .map(|(m, f)| {
+                            let mutable = f.signature.receiver.as_ref().is_some_and(|r| r.mutable);
+                            (m.clone(), (f.signature.return_value.clone(), mutable))
+                        })
+                        .collect(),
+                };
+                (ident.clone(), info)
+            })
+            .collect();
+
+        Self {
+            functions,
+            types,
+            tree: ScopeTree::with_module(module),
+            scope: ScopeTree::MODULE,
+        }
+    }
+
+    fn resolve_func(&mut self, func: &mut Func) -> Result<()> {
+        self.scope = self.tree.push_function(func);
+        let mut res = Ok(());
+        for cst in func.constants.values_mut() {
+            res = res.and_then(|_| self.resolve_expression(&mut cst.value));
+        }
+        res = res.and_then(|_| self.resolve_statements(&mut func.statements));
+        self.scope = ScopeTree::MODULE;
+        res
+    }
+
+    fn resolve_statements(&mut self, statements: &mut [Statement]) -> Result<()> {
+        for statement in statements {
+            self.resolve_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    /// resolve_block resolves the statements of a block in a new scope of `kind`.
+    fn resolve_block(
+        &mut self,
+        statements: &mut [Statement],
+        kind: ScopeKind,
+        first_token: &Token,
+    ) -> Result<()> {
+        let parent = self.scope;
+        self.scope = self.tree.push_block(parent, kind, first_token, statements);
+        let res = self.resolve_statements(statements);
+        self.scope = parent;
+        res
+    }
+
+    fn resolve_statement(&mut self, statement: &mut Statement) -> Result<()> {
+        let t = &statement.first_token;
+        match &mut statement.value {
+            StatementValue::Expression(e) | StatementValue::Defer(e) => self.resolve_expression(e),
+            StatementValue::VarDeclaration(decl) => self.resolve_expression(&mut decl.value),
+            StatementValue::Assignment(assignment) => {
+                self.resolve_expression(&mut assignment.target)?;
+                self.resolve_expression(&mut assignment.value)
+            }
+            StatementValue::MultiAssignment(assignment) => {
+                self.resolve_expression(&mut assignment.value)
+            }
+            StatementValue::Return(Some(e)) => self.resolve_expression(e),
+            StatementValue::Return(None) | StatementValue::Break | StatementValue::Continue => {
+                Ok(())
+            }
+            StatementValue::If(stmt) => {
+                for (condition, body) in &mut stmt.branches {
+                    self.resolve_expression(condition)?;
+                    let first_token = body
+                        .first()
+                        .map_or(&condition.first_token, |s| &s.first_token)
+                        .clone();
+                    self.resolve_block(body, ScopeKind::Block, &first_token)?;
+                }
+                if let Some(body) = &mut stmt.else_body {
+                    let first_token = body.first().map_or(t, |s| &s.first_token).clone();
+                    self.resolve_block(body, ScopeKind::Block, &first_token)?;
+                }
+                Ok(())
+            }
+            StatementValue::For(stmt) => {
+                self.resolve_expression(&mut stmt.iterable)?;
+                let types = match self.type_of(&stmt.iterable).and_then(|t| t.element()) {
+                    Some(element) => stmt.list_bindings(&element),
+                    None => vec![],
+                };
+                let parent = self.scope;
+                self.scope = self.tree.push_for(parent, stmt, &types, t);
+                let res = self.resolve_statements(&mut stmt.body);
+                self.scope = parent;
+                res
+            }
+            StatementValue::While(stmt) => {
+                self.resolve_expression(&mut stmt.condition)?;
+                self.resolve_block(&mut stmt.body, ScopeKind::Loop, t)
+            }
+            StatementValue::Loop(body) => self.resolve_block(body, ScopeKind::Loop, t),
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &mut Expression) -> Result<()> {
+        match &mut expr.value {
+            ExpressionValue::Identifier(ident) => {
+                if let Some(resolved) = self.split_value_path(ident, &expr.first_token) {
+                    *expr = resolved;
+                    self.check_member_access(expr)?;
+                }
+                Ok(())
+            }
+            ExpressionValue::FunctionCall(call) => {
+                for arg in &mut call.args {
+                    self.resolve_expression(arg)?;
+                }
+
+                let (receiver, method) = match &mut call.function.value {
+                    ExpressionValue::Identifier(ident)
+                        if ident.namespace.is_empty()
+                            && call.args.len() == 1
+                            && Type::from(ident.name.as_str()).is_numeric()
+                            && !self.functions.contains_key(&ident.name) =>
+                    {
+                        // Conversion such as `int32(x)`
+                        let ttype = Type::from(ident.name.as_str());
+                        *expr = Expression::conversion(call.args.pop().unwrap(), ttype);
+                        return Ok(());
+                    }
+                    ExpressionValue::Identifier(ident) if !ident.namespace.is_empty() => {
+                        match self.value_path(&ident.namespace, &call.function.first_token) {
+                            Some(receiver) => (receiver, ident.name.clone()),
+                            None => return Ok(()), // Call to a function from another module
+                        }
+                    }
+                    ExpressionValue::MemberAccess(access) => {
+                        (*access.object.clone(), access.member.clone())
+                    }
+                    _ => return self.resolve_expression(&mut call.function),
+                };
+
+                let mut receiver = receiver;
+                self.resolve_expression(&mut receiver)?;
+                *expr = Expression::method_call(receiver, method, call.args.clone());
+                self.check_method_call(expr)
+            }
+            ExpressionValue::MemberAccess(access) => {
+                self.resolve_expression(&mut access.object)?;
+                self.check_member_access(expr)
+            }
+            ExpressionValue::MethodCall(call) => {
+                self.resolve_expression(&mut call.receiver)?;
+                for arg in &mut call.args {
+                    self.resolve_expression(arg)?;
+                }
+                self.check_method_call(expr)
+            }
+            ExpressionValue::StructLiteral(literal) => {
+                for (_, value) in &mut literal.fields {
+                    self.resolve_expression(value)?;
+                }
+                let info = match self.types.get(&literal.ttype) {
+                    Some(info) => info,
+                    None => {
+                        return Err(Error::at_token(
+                            &expr.first_token,
+                            ErrorKind::UndefinedType(literal.ttype.clone()),
+                            "".into(),
+                        ))
+                    }
+                };
+                for (field, _) in &literal.fields {
+                    if !info.fields.contains_key(field) {
+                        return Err(Error::at_token(
+                            &expr.first_token,
+                            ErrorKind::UnknownMember(literal.ttype.clone(), field.clone()),
+                            "".into(),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            ExpressionValue::ListLiteral(literal) => {
+                for element in &mut literal.elements {
+                    self.resolve_expression(element)?;
+                }
+                Ok(())
+            }
+            ExpressionValue::Index(index) => {
+                self.resolve_expression(&mut index.object)?;
+                self.resolve_expression(&mut index.index)
+            }
+            ExpressionValue::Tuple(elements) => {
+                for element in elements {
+                    self.resolve_expression(element)?;
+                }
+                Ok(())
+            }
+            ExpressionValue::OptionCheck(check) => self.resolve_expression(&mut check.operand),
+            ExpressionValue::Propagate(operand) => self.resolve_expression(operand),
+            ExpressionValue::Conversion(conversion) => {
+                self.resolve_expression(&mut conversion.operand)
+            }
+            ExpressionValue::BinaryOperation(op) => {
+                for operand in &mut op.operands {
+                    self.resolve_expression(operand)?;
+                }
+                Ok(())
+            }
+            ExpressionValue::UnaryOperation(op) => self.resolve_expression(&mut op.operand),
+            ExpressionValue::Literal(_) => Ok(()),
+        }
+    }
+
+    /// split_value_path turns an identifier like `p.origin.x` into the member accesses
+    /// `((p).origin).x` if its first word refers to a value.
+    fn split_value_path(&self, ident: &Identifier, first_token: &Token) -> Option<Expression> {
+        if ident.namespace.is_empty() {
+            return None;
+        }
+        let mut words = ident.namespace.clone();
+        words.push(ident.name.clone());
+        self.value_path(&words, first_token)
+    }
+
+    /// value_path builds a chain of member accesses from `words` if the first word refers to a
+    /// value.
+    fn value_path(&self, words: &[String], first_token: &Token) -> Option<Expression> {
+        let root = words.first()?;
+        self.lookup(root)?;
+
+        let mut res = Expression::identifier(vec![root.clone()], first_token.clone());
+        for member in words.iter().skip(1) {
+            res = Expression::member_access(res, member.clone());
+        }
+        Some(res)
+    }
+
+    fn lookup(&self, ident: &str) -> Option<&Declaration> {
+        self.tree.lookup_value(self.scope, ident)
+    }
+
+    /// type_of returns the type of `expr` as far as it is needed to resolve member accesses, or
+    /// None if it isn't known.
+    fn type_of(&self, expr: &Expression) -> Option<Type> {
+        match &expr.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                self.lookup(&ident.name)?.ttype.clone()
+            }
+            ExpressionValue::MemberAccess(access) => match self.type_of(&access.object)? {
+                Type::Struct(s) => self.types.get(&s)?.fields.get(&access.member).cloned(),
+                _ => None,
+            },
+            ExpressionValue::MethodCall(call) => match self.type_of(&call.receiver)? {
+                Type::Struct(s) => self
+                    .types
+                    .get(&s)?
+                    .methods
+                    .get(&call.method)
+                    .map(|(t, _)| t.clone()),
+                _ => None,
+            },
+            ExpressionValue::FunctionCall(call) => match &call.function.value {
+                ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                    self.functions.get(&ident.name).cloned()
+                }
+                _ => None,
+            },
+            ExpressionValue::StructLiteral(literal) => Some(Type::Struct(literal.ttype.clone())),
+            ExpressionValue::ListLiteral(literal) => {
+                Some(Type::List(Box::new(literal.ttype.clone())))
+            }
+            ExpressionValue::Index(index) => match self.type_of(&index.object)? {
+                Type::List(element) => Some(*element),
+                _ => None,
+            },
+            ExpressionValue::Conversion(conversion) => Some(conversion.ttype.clone()),
+            ExpressionValue::Propagate(operand) => match self.type_of(operand)? {
+                Type::Result(t, _) => Some(*t),
+                _ => None,
+            },
+            ExpressionValue::Tuple(elements) => Some(Type::Tuple(
+                elements
+                    .iter()
+                    .map(|e| self.type_of(e))
+                    .collect::<Option<Vec<Type>>>()?,
+            )),
+            ExpressionValue::Literal(literal) => Some(match literal {
+                Literal::Integer(_) => Type::Int,
+                Literal::Float(_) => Type::Float,
+                Literal::String(_) => Type::Text,
+                Literal::Char(_) => Type::Character,
+                Literal::Bool(_) => Type::Bool,
+                Literal::Nil => return None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// is_assignable returns true if `expr` refers to a place that can be written to: a
+    /// variable, a mutable receiver, a field of one of those, or an element of a list.
+    fn is_assignable(&self, expr: &Expression) -> bool {
+        match &expr.value {
+            ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                self.lookup(&ident.name).is_some_and(|decl| decl.mutable)
+            }
+            ExpressionValue::MemberAccess(access) => self.is_assignable(&access.object),
+            ExpressionValue::Index(_) => true,
+            _ => false,
+        }
+    }
+
+    fn check_member_access(&self, expr: &Expression) -> Result<()> {
+        let access = match &expr.value {
+            ExpressionValue::MemberAccess(access) => access,
+            _ => return Ok(()),
+        };
+        self.check_member_access(&access.object)?;
+
+        let ttype = match self.type_of(&access.object) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let found = match &ttype {
+            // An undefined struct type is reported by the name check
+            Type::Struct(s) => self
+                .types
+                .get(s)
+                .is_none_or(|info| info.fields.contains_key(&access.member)),
+            // Reported by the nil-safety check
+            Type::Optional(_) => true,
+            _ => false,
+        };
+        if found {
+            return Ok(());
+        }
+
+        Err(Error::at_token(
+            &expr.first_token,
+            ErrorKind::UnknownMember(ttype.to_string(), access.member.clone()),
+            "".into(),
+        ))
+    }
+
+    fn check_method_call(&self, expr: &Expression) -> Result<()> {
+        let call = match &expr.value {
+            ExpressionValue::MethodCall(call) => call,
+            _ => return Ok(()),
+        };
+
+        let ttype = match self.type_of(&call.receiver) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let method = match &ttype {
+            Type::Struct(s) => match self.types.get(s) {
+                Some(info) => info.methods.get(&call.method),
+                // Reported by the name check
+                None => return Ok(()),
+            },
+            // Reported by the nil-safety check
+            Type::Optional(_) => return Ok(()),
+            _ => None,
+        };
+
+        match method {
+            None => Err(Error::at_token(
+                &expr.first_token,
+                ErrorKind::UnknownMember(ttype.to_string(), call.method.clone()),
+                "".into(),
+            )),
+            Some((_, true)) if !self.is_assignable(&call.receiver) => Err(Error::at_token(
+                &expr.first_token,
+                ErrorKind::ImmutableReceiver(call.method.clone()),
+                "".into(),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
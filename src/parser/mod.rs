@@ -1,5 +1,5 @@
 use crate::{
-    lang::{Expression, Module, Symbol, Type},
+    lang::{Expression, Func, Module, Symbol, Type},
     tokenizer::{self, Token, TokenStream, TokenValue},
 };
 use std::io::Read;
@@ -10,17 +10,24 @@ pub use error::{Error, ErrorKind};
 pub type Result<T> = std::result::Result<T, Error>;
 
 mod expression;
+mod function;
+mod member;
+mod statement;
 
-pub(self) mod token_matcher;
+mod token_matcher;
 
 pub struct Parser {
     module: Module,
+    // Methods are attached to their struct type in finalize, since the type may be declared
+    // after the method.
+    methods: Vec<(String, Func)>,
 }
 
 impl Parser {
     pub fn new(module_id: String) -> Self {
         Self {
             module: Module::new(module_id),
+            methods: vec![],
         }
     }
 
@@ -38,7 +45,43 @@ impl Parser {
         Ok(())
     }
 
-    pub fn finalize(self) -> Result<Module> {
+    /// finalize completes the module once all of its sources have been added. It attaches methods
+    /// to their types and tells member accesses and method calls apart from module-qualified
+    /// identifiers.
+    pub fn finalize(mut self) -> Result<Module> {
+        for (ident, method) in self.methods {
+            let receiver = method.signature.receiver.as_ref().unwrap();
+            let type_name = match &receiver.ttype {
+                Type::Struct(s) => s.clone(),
+                t => {
+                    return Err(Error::at_token(
+                        &receiver.first_token,
+                        ErrorKind::InvalidReceiver(t.to_string()),
+                        "methods can only be declared on struct types".into(),
+                    ))
+                }
+            };
+            let ttype = match self.module.types.get_mut(&type_name) {
+                Some(t) => t,
+                None => {
+                    return Err(Error::at_token(
+                        &receiver.first_token,
+                        ErrorKind::UndefinedType(type_name),
+                        "".into(),
+                    ))
+                }
+            };
+            if ttype.methods.contains_key(&ident) || ttype.fields.iter().any(|(f, _)| *f == ident) {
+                return Err(Error::at_token(
+                    &method.first_token,
+                    ErrorKind::SymbolRedefined(format!("{}.{}", type_name, ident)),
+                    "".into(),
+                ));
+            }
+            ttype.methods.insert(ident, method);
+        }
+
+        member::resolve(&mut self.module)?;
         Ok(self.module)
     }
 
@@ -76,27 +119,49 @@ impl Parser {
             alias.into()
         };
 
-        let import = Symbol::new_import(import, first_token);
-
-        self.module
-            .define(ident.clone(), import)
-            .map_err(|_| Error::redefined_symbol(token_stream, &ident))?;
+        let import = Symbol::new_import(import, first_token.clone());
+        if self.module.define(ident.clone(), import).is_err() {
+            let message = match self.module.imports.get(&ident) {
+                Some(other) => format!("already imported from `{}`", other.path),
+                None => "".into(),
+            };
+            return Err(Error::at_token(
+                &first_token,
+                ErrorKind::SymbolRedefined(ident),
+                message,
+            ));
+        }
         Ok(())
     }
 
+    /// redefined returns the error for a declaration of `ident`, which is already defined. A
+    /// declaration with the name of an import is reported at the import.
+    fn redefined<R: Read>(&self, token_stream: &TokenStream<R>, ident: &str) -> Error {
+        match self.module.imports.get(ident) {
+            Some(import) => Error::at_token(
+                &import.first_token,
+                ErrorKind::SymbolRedefined(ident.into()),
+                "also declared in this module".into(),
+            ),
+            None => Error::redefined_symbol(token_stream, ident),
+        }
+    }
+
+    #[allow(clippy::needless_return, clippy::match_like_matches_macro)]
     fn maybe_parse_use_block<R: Read>(&mut self, token_stream: &mut TokenStream<R>) -> Result<()> {
-        if scan_for_keyword(
+        if !scan_for_keyword(
             token_stream,
             TokenValue::KeywordUse,
             vec![
                 TokenValue::KeywordFunc,
                 TokenValue::KeywordTest,
                 TokenValue::KeywordStruct,
+                TokenValue::KeywordExtern,
+                TokenValue::KeywordExport,
                 TokenValue::KeywordConst,
                 TokenValue::KeywordVar,
             ],
-        )? == false
-        {
+        )? {
             return Ok(());
         }
         skip_while(token_stream, token_matcher::newline)?;
@@ -162,51 +227,53 @@ impl Parser {
         &mut self,
         token_stream: &mut TokenStream<R>,
     ) -> Result<()> {
-        if scan_for_keyword(
+        if !scan_for_keyword(
             token_stream,
             TokenValue::KeywordConst,
             vec![
                 TokenValue::KeywordFunc,
                 TokenValue::KeywordTest,
                 TokenValue::KeywordStruct,
+                TokenValue::KeywordExtern,
+                TokenValue::KeywordExport,
                 TokenValue::KeywordVar,
             ],
-        )? == false
-        {
+        )? {
             return Ok(()); // No const block
         }
 
         let consts = parse_declaration_block(token_stream)?;
         for decl in consts {
-            let c = Symbol::new_const(Type::from(decl.ttype), decl.value, decl.first_token);
+            let c = Symbol::new_const(decl.ttype, decl.value, decl.first_token);
             self.module
                 .define(decl.identifier.clone(), c)
-                .map_err(|_| Error::redefined_symbol(token_stream, &decl.identifier))?;
+                .map_err(|_| self.redefined(token_stream, &decl.identifier))?;
         }
 
         Ok(())
     }
 
     fn maybe_parse_var_block<R: Read>(&mut self, token_stream: &mut TokenStream<R>) -> Result<()> {
-        if scan_for_keyword(
+        if !scan_for_keyword(
             token_stream,
-            TokenValue::KeywordConst,
+            TokenValue::KeywordVar,
             vec![
                 TokenValue::KeywordFunc,
                 TokenValue::KeywordTest,
                 TokenValue::KeywordStruct,
+                TokenValue::KeywordExtern,
+                TokenValue::KeywordExport,
             ],
-        )? == false
-        {
-            return Ok(()); // No const block
+        )? {
+            return Ok(()); // No var block
         }
 
         let vars = parse_declaration_block(token_stream)?;
         for decl in vars {
-            let v = Symbol::new_var(Type::from(decl.ttype), decl.value, decl.first_token);
+            let v = Symbol::new_var(decl.ttype, decl.value, decl.first_token);
             self.module
                 .define(decl.identifier.clone(), v)
-                .map_err(|_| Error::redefined_symbol(token_stream, &decl.identifier))?;
+                .map_err(|_| self.redefined(token_stream, &decl.identifier))?;
         }
 
         Ok(())
@@ -215,10 +282,69 @@ impl Parser {
     fn parse_module_body<R: Read>(&mut self, token_stream: &mut TokenStream<R>) -> Result<()> {
         skip_while(token_stream, token_matcher::newline)?;
 
-        if let Some(t) = token_stream.peek() {
-            let t = t?;
+        let t = match token_stream.next_token() {
+            Some(t) => t?,
+            None => return Ok(()), // EOF
+        };
+
+        match t.value {
+            TokenValue::KeywordFunc => {
+                let (ident, func) = function::parse_func(token_stream, t, true)?;
+                if func.signature.receiver.is_some() {
+                    self.methods.push((ident, func));
+                    return Ok(());
+                }
+                self.module
+                    .define(ident.clone(), Symbol::Function(func))
+                    .map_err(|_| self.redefined(token_stream, &ident))
+            }
+            TokenValue::KeywordTest => {
+                let (ident, func) = function::parse_func(token_stream, t, false)?;
+                if self.module.tests.contains_key(&ident) {
+                    return Err(Error::redefined_symbol(token_stream, &ident));
+                }
+                self.module.tests.insert(ident, func);
+                Ok(())
+            }
+            TokenValue::KeywordExport => {
+                let t = consume_token(
+                    token_stream,
+                    |t| t.value == TokenValue::KeywordFunc,
+                    "expected `func` after `export`".into(),
+                )?;
+                let (ident, func) = function::parse_func(token_stream, t, false)?;
+                let signature = func.signature.clone();
+                self.module
+                    .define(ident.clone(), Symbol::Function(func))
+                    .map_err(|_| self.redefined(token_stream, &ident))?;
+                self.module.exports.insert(ident, signature);
+                Ok(())
+            }
+            TokenValue::KeywordExtern => {
+                let (ident, path, signature) = function::parse_extern(token_stream, t)?;
+                let mut import = Symbol::new_import(path, signature.first_token.clone());
+                if let Symbol::Import(import) = &mut import {
+                    import.signature = Some(signature);
+                    import.external = true;
+                }
+                self.module
+                    .define(ident.clone(), import)
+                    .map_err(|_| self.redefined(token_stream, &ident))
+            }
+            TokenValue::KeywordStruct => {
+                let (ident, fields) = parse_struct(token_stream)?;
+                self.module
+                    .define(
+                        ident.text.clone(),
+                        Symbol::new_struct(ident.text.clone(), fields, ident.clone()),
+                    )
+                    .map_err(|_| self.redefined(token_stream, &ident.text))
+            }
+            _ => Err(Error::unexpected_token(
+                t,
+                "expected `func`, `test`, `struct`, `extern` or `export`".into(),
+            )),
         }
-        todo!()
     }
 }
 
@@ -246,6 +372,18 @@ where
     }
 }
 
+/// next_token_is returns true if the next token in the stream matches, without consuming it.
+fn next_token_is<R: Read, F>(token_stream: &mut TokenStream<R>, matcher: F) -> Result<bool>
+where
+    F: Fn(&Token) -> bool,
+{
+    match token_stream.peek() {
+        Some(t) => Ok(matcher(&t?)),
+        None => Ok(false),
+    }
+}
+
+#[allow(clippy::needless_return)]
 fn consume_token<R: Read, F>(
     token_stream: &mut TokenStream<R>,
     matcher: F,
@@ -271,6 +409,7 @@ where
     }
 }
 
+#[allow(clippy::needless_return)]
 fn scan_for_keyword<R: Read>(
     token_stream: &mut TokenStream<R>,
     keyword: TokenValue,
@@ -284,7 +423,9 @@ fn scan_for_keyword<R: Read>(
             _ = token_stream.next_token();
             return Ok(true);
         }
-        if non_error_keywords.contains(&t.value) {}
+        if non_error_keywords.contains(&t.value) {
+            return Ok(false);
+        }
         return Err(Error::unexpected_token(
             t,
             format!("while looking for `{}` block", keyword),
@@ -326,19 +467,158 @@ struct Declaration {
 }
 
 fn parse_type<R: Read>(token_stream: &mut TokenStream<R>) -> Result<Type> {
-    // TODO: list types
+    if next_token_is(token_stream, |t| t.value == TokenValue::QuestionMark)? {
+        let first_token = token_stream.next_token().unwrap()?;
+        return optional_type(first_token, parse_type(token_stream)?);
+    }
+
+    if next_token_is(token_stream, token_matcher::open_paren)? {
+        let first_token = token_stream.next_token().unwrap()?;
+        let mut types = vec![];
+        loop {
+            types.push(parse_type(token_stream)?);
+            let t = consume_token(
+                token_stream,
+                |t| token_matcher::comma(t) || token_matcher::close_paren(t),
+                "expected `,` or `)` in tuple type".into(),
+            )?;
+            if token_matcher::close_paren(&t) {
+                break;
+            }
+        }
+        if types.len() < 2 {
+            return Err(Error::unexpected_token(
+                first_token,
+                "tuple types need at least two elements".into(),
+            ));
+        }
+        return Ok(Type::Tuple(types));
+    }
+
     let ttype = consume_token(
         token_stream,
         token_matcher::identifier,
         "expected type definition".into(),
     )?;
-    match ttype.value {
-        TokenValue::Identifier(s) => Ok(s.into()),
+    match &ttype.value {
+        TokenValue::Identifier(s) if s == "option" => {
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::OpenBracket,
+                "expected `[` after `option`".into(),
+            )?;
+            let inner = parse_type(token_stream)?;
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::CloseBracket,
+                "expected `]` after optional type".into(),
+            )?;
+            optional_type(ttype, inner)
+        }
+        TokenValue::Identifier(s) if s == "result" => {
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::OpenBracket,
+                "expected `[` after `result`".into(),
+            )?;
+            let value = parse_type(token_stream)?;
+            consume_token(
+                token_stream,
+                token_matcher::comma,
+                "expected `,` between the value and error type of a result".into(),
+            )?;
+            let error = parse_type(token_stream)?;
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::CloseBracket,
+                "expected `]` after result type".into(),
+            )?;
+            Ok(Type::Result(Box::new(value), Box::new(error)))
+        }
+        TokenValue::Identifier(s) if s == "list" => {
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::OpenBracket,
+                "expected `[` after `list`".into(),
+            )?;
+            let element = parse_type(token_stream)?;
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::CloseBracket,
+                "expected `]` after list type".into(),
+            )?;
+            Ok(Type::List(Box::new(element)))
+        }
+        TokenValue::Identifier(s) => Ok(s.as_str().into()),
         _ => unreachable!(),
     }
 }
 
+fn optional_type(first_token: Token, inner: Type) -> Result<Type> {
+    match inner {
+        Type::Optional(_) | Type::Void => Err(Error::unexpected_token(
+            first_token,
+            format!("`{}` cannot be made optional", inner),
+        )),
+        _ => Ok(Type::Optional(Box::new(inner))),
+    }
+}
+
+/// parse_struct parses a struct declaration, starting after the `struct` keyword. It returns the
+/// token holding the name of the struct together with its fields.
+fn parse_struct<R: Read>(
+    token_stream: &mut TokenStream<R>,
+) -> Result<(Token, Vec<(String, Type)>)> {
+    let ident = consume_token(
+        token_stream,
+        token_matcher::identifier,
+        "expected struct name".into(),
+    )?;
+    consume_token(
+        token_stream,
+        token_matcher::open_brace,
+        "expected `{` after struct name".into(),
+    )?;
+
+    let mut fields: Vec<(String, Type)> = vec![];
+    loop {
+        skip_while(token_stream, |t| {
+            token_matcher::newline(t) || token_matcher::comma(t)
+        })?;
+        let t = match token_stream.next_token() {
+            Some(t) => t?,
+            None => {
+                return Err(Error::new(
+                    token_stream,
+                    ErrorKind::UnexpectedEOF,
+                    "missing `}` after struct fields".into(),
+                ))
+            }
+        };
+
+        let field = match &t.value {
+            TokenValue::CloseBrace => return Ok((ident, fields)),
+            TokenValue::Identifier(field) => field.clone(),
+            _ => {
+                return Err(Error::unexpected_token(
+                    t,
+                    "expected field name or `}`".into(),
+                ))
+            }
+        };
+        if fields.iter().any(|(f, _)| *f == field) {
+            return Err(Error::at_token(
+                &t,
+                ErrorKind::SymbolRedefined(field),
+                "duplicate field name".into(),
+            ));
+        }
+        fields.push((field, parse_type(token_stream)?));
+    }
+}
+
 /// parse_declaration_block parses the "body" of a var or const block, including the opening and closing brace.
+#[allow(clippy::match_like_matches_macro)]
 fn parse_declaration_block<R: Read>(token_stream: &mut TokenStream<R>) -> Result<Vec<Declaration>> {
     skip_while(token_stream, token_matcher::newline)?;
 
@@ -349,8 +629,12 @@ fn parse_declaration_block<R: Read>(token_stream: &mut TokenStream<R>) -> Result
     )?;
 
     let mut res = vec![];
-    while let Some(t) = token_stream.peek() {
-        let t = t?;
+    loop {
+        skip_while(token_stream, token_matcher::newline)?;
+        let t = match token_stream.peek() {
+            Some(t) => t?,
+            None => break,
+        };
         if t.value == TokenValue::CloseBrace {
             break;
         }
@@ -396,7 +680,10 @@ impl From<tokenizer::Error> for Error {
             line: value.line,
             column: value.column,
             source: value.source.clone(),
-            kind: ErrorKind::TokenizerError(value),
+            kind: ErrorKind::TokenizerError(Box::new(value)),
         }
     }
 }
+
+#[cfg(test)]
+mod test;
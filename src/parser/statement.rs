@@ -0,0 +1,297 @@
+use std::io::Read;
+
+use crate::{
+    lang::{
+        Assignment, Expression, ExpressionValue, For, Func, If, MultiAssignment, Statement,
+        StatementValue, Symbol, VarDeclaration, While,
+    },
+    tokenizer::{AssignOperator, Token, TokenStream, TokenValue},
+};
+
+use super::{
+    consume_token, ensure_next_token, expression, next_token_is, parse_declaration_block,
+    skip_while, token_matcher, Error, ErrorKind, Result,
+};
+
+/// parse_block parses a block of statements, including the opening and closing brace.
+///
+/// Constants declared anywhere in the block are added to `func`, variables are declared by
+/// statements of the block.
+pub fn parse_block<R: Read>(
+    token_stream: &mut TokenStream<R>,
+    func: &mut Func,
+) -> Result<Vec<Statement>> {
+    consume_token(
+        token_stream,
+        token_matcher::open_brace,
+        "expected `{` at the start of a block".into(),
+    )?;
+
+    let mut res = vec![];
+    loop {
+        skip_while(token_stream, token_matcher::newline)?;
+
+        let t = match token_stream.peek() {
+            Some(t) => t?,
+            None => {
+                return Err(Error::new(
+                    token_stream,
+                    ErrorKind::UnexpectedEOF,
+                    "missing `}` at the end of a block".into(),
+                ))
+            }
+        };
+
+        if t.value == TokenValue::CloseBrace {
+            _ = token_stream.next_token();
+            return Ok(res);
+        }
+
+        parse_statement(token_stream, func, &mut res)?;
+        ensure_next_token(
+            token_stream,
+            token_matcher::end_of_statement,
+            "expected end of statement".into(),
+        )?;
+    }
+}
+
+/// declares returns true if one of `statements`, the statements of a block so far, declares a
+/// variable `ident`. Variables are declared per block, blocks side by side can use the same
+/// names.
+fn declares(statements: &[Statement], ident: &str) -> bool {
+    statements.iter().any(|s| match &s.value {
+        StatementValue::VarDeclaration(decl) => decl.ident == ident,
+        _ => false,
+    })
+}
+
+/// parse_statement parses a single statement and appends it to `statements`. A `var` block adds
+/// one statement per variable, a `const` block adds none.
+fn parse_statement<R: Read>(
+    token_stream: &mut TokenStream<R>,
+    func: &mut Func,
+    statements: &mut Vec<Statement>,
+) -> Result<()> {
+    let first_token = match token_stream.peek() {
+        Some(t) => t?,
+        None => {
+            return Err(Error::new(
+                token_stream,
+                ErrorKind::UnexpectedEOF,
+                "statement expected".into(),
+            ))
+        }
+    };
+
+    let value = match first_token.value {
+        TokenValue::KeywordVar => {
+            _ = token_stream.next_token();
+            for decl in parse_declaration_block(token_stream)? {
+                if declares(statements, &decl.identifier)
+                    || func.constants.contains_key(&decl.identifier)
+                {
+                    return Err(Error::redefined_symbol(token_stream, &decl.identifier));
+                }
+                statements.push(Statement::new(
+                    StatementValue::VarDeclaration(VarDeclaration {
+                        ident: decl.identifier,
+                        ttype: decl.ttype,
+                        value: decl.value,
+                    }),
+                    decl.first_token,
+                ));
+            }
+            return Ok(());
+        }
+        TokenValue::KeywordConst => {
+            _ = token_stream.next_token();
+            for decl in parse_declaration_block(token_stream)? {
+                if declares(statements, &decl.identifier)
+                    || func.constants.contains_key(&decl.identifier)
+                {
+                    return Err(Error::redefined_symbol(token_stream, &decl.identifier));
+                }
+                if let Symbol::Constant(c) =
+                    Symbol::new_const(decl.ttype, decl.value, decl.first_token)
+                {
+                    func.constants.insert(decl.identifier, c);
+                }
+            }
+            return Ok(());
+        }
+        TokenValue::KeywordReturn => {
+            _ = token_stream.next_token();
+            if next_token_is(token_stream, token_matcher::end_of_statement)? {
+                StatementValue::Return(None)
+            } else {
+                // `return q, r` returns the tuple `(q, r)`
+                let t = token_stream.peek().unwrap()?;
+                let mut values =
+                    expression::parse_list(token_stream, &token_matcher::end_of_statement)?;
+                if values.len() == 1 {
+                    StatementValue::Return(values.pop())
+                } else {
+                    StatementValue::Return(Some(Expression::tuple(values, t)))
+                }
+            }
+        }
+        TokenValue::KeywordBreak => {
+            _ = token_stream.next_token();
+            StatementValue::Break
+        }
+        TokenValue::KeywordContinue => {
+            _ = token_stream.next_token();
+            StatementValue::Continue
+        }
+        TokenValue::KeywordDefer => {
+            let t = token_stream.next_token().unwrap()?;
+            let call = expression::parse(token_stream, &token_matcher::end_of_statement)?;
+            match call.value {
+                ExpressionValue::FunctionCall(_) => StatementValue::Defer(call),
+                _ => {
+                    return Err(Error::unexpected_token(
+                        t,
+                        "only function and method calls can be deferred".into(),
+                    ))
+                }
+            }
+        }
+        TokenValue::KeywordIf => parse_if(token_stream, func)?,
+        TokenValue::KeywordFor => {
+            _ = token_stream.next_token();
+            let bindings = parse_bindings(token_stream)?;
+            consume_token(
+                token_stream,
+                |t| t.value == TokenValue::KeywordIn,
+                "expected `in` after loop variables".into(),
+            )?;
+            let iterable = expression::parse(token_stream, &token_matcher::open_brace)?;
+            let body = parse_block(token_stream, func)?;
+            StatementValue::For(For {
+                bindings,
+                iterable,
+                body,
+            })
+        }
+        TokenValue::KeywordWhile => {
+            _ = token_stream.next_token();
+            let condition = expression::parse(token_stream, &token_matcher::open_brace)?;
+            let body = parse_block(token_stream, func)?;
+            StatementValue::While(While { condition, body })
+        }
+        TokenValue::KeywordLoop => {
+            _ = token_stream.next_token();
+            StatementValue::Loop(parse_block(token_stream, func)?)
+        }
+        _ => {
+            let expr = expression::parse(
+                token_stream,
+                &token_matcher::either(
+                    token_matcher::end_of_statement,
+                    token_matcher::either(token_matcher::assignment, token_matcher::comma),
+                ),
+            )?;
+            if next_token_is(token_stream, token_matcher::comma)? {
+                // Assignment to several variables, as in `q, r = divmod(7, 2)`
+                _ = token_stream.next_token(); // Pop ','
+                let mut targets = vec![expr];
+                targets.extend(expression::parse_list(
+                    token_stream,
+                    &token_matcher::either(
+                        token_matcher::end_of_statement,
+                        token_matcher::assignment,
+                    ),
+                )?);
+                let mut bindings = vec![];
+                for target in targets {
+                    match target.value {
+                        ExpressionValue::Identifier(ident) if ident.namespace.is_empty() => {
+                            bindings.push(ident.name)
+                        }
+                        _ => {
+                            return Err(Error::unexpected_token(
+                                target.first_token,
+                                "only variables can be assigned from a tuple".into(),
+                            ))
+                        }
+                    }
+                }
+                consume_token(
+                    token_stream,
+                    |t| t.value == TokenValue::Assignment(AssignOperator::Assign),
+                    "expected `=` after the variables of a multiple assignment".into(),
+                )?;
+                let value = expression::parse(token_stream, &token_matcher::end_of_statement)?;
+                StatementValue::MultiAssignment(MultiAssignment { bindings, value })
+            } else if next_token_is(token_stream, token_matcher::assignment)? {
+                let operator = match token_stream.next_token().unwrap()?.value {
+                    TokenValue::Assignment(op) => op,
+                    _ => unreachable!(),
+                };
+                let value = expression::parse(token_stream, &token_matcher::end_of_statement)?;
+                StatementValue::Assignment(Assignment {
+                    target: expr,
+                    operator,
+                    value,
+                })
+            } else {
+                StatementValue::Expression(expr)
+            }
+        }
+    };
+
+    statements.push(Statement::new(value, first_token));
+    Ok(())
+}
+
+/// parse_if parses an `if` statement together with all of its `else if` and `else` branches.
+fn parse_if<R: Read>(token_stream: &mut TokenStream<R>, func: &mut Func) -> Result<StatementValue> {
+    let mut branches = vec![];
+    let mut else_body = None;
+
+    loop {
+        consume_token(
+            token_stream,
+            |t| t.value == TokenValue::KeywordIf,
+            "expected `if`".into(),
+        )?;
+        let condition = expression::parse(token_stream, &token_matcher::open_brace)?;
+        let body = parse_block(token_stream, func)?;
+        branches.push((condition, body));
+
+        if !next_token_is(token_stream, |t| t.value == TokenValue::KeywordElse)? {
+            break;
+        }
+        _ = token_stream.next_token(); // Pop `else`
+
+        if !next_token_is(token_stream, |t| t.value == TokenValue::KeywordIf)? {
+            else_body = Some(parse_block(token_stream, func)?);
+            break;
+        }
+    }
+
+    Ok(StatementValue::If(If {
+        branches,
+        else_body,
+    }))
+}
+
+/// parse_bindings parses a comma separated list of identifiers, such as the loop variables in
+/// `for num, response in answers`.
+fn parse_bindings<R: Read>(token_stream: &mut TokenStream<R>) -> Result<Vec<String>> {
+    let mut res = vec![];
+    loop {
+        let ident: Token = consume_token(
+            token_stream,
+            token_matcher::identifier,
+            "expected identifier".into(),
+        )?;
+        res.push(ident.text);
+
+        if !next_token_is(token_stream, token_matcher::comma)? {
+            return Ok(res);
+        }
+        _ = token_stream.next_token(); // Pop ','
+    }
+}
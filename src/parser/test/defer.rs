@@ -0,0 +1,55 @@
+use crate::lang::{ExpressionValue, StatementValue};
+
+use super::parse_module;
+
+#[test]
+fn calls_can_be_deferred() {
+    let module = parse_module(
+        "
+struct File {
+	fd int
+}
+
+func (f File) close() {
+}
+
+func open(path text) File {
+	return File{fd = 3}
+}
+
+func main() {
+	var {
+		f File = open(\"a.txt\")
+	}
+	defer f.close()
+	defer print(\"done\")
+}
+",
+    )
+    .unwrap();
+    let main = &module.functions["main"];
+
+    let deferred: Vec<&ExpressionValue> = main
+        .statements
+        .iter()
+        .filter_map(|s| match &s.value {
+            StatementValue::Defer(e) => Some(&e.value),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(deferred.len(), 2);
+    assert!(matches!(deferred[0], ExpressionValue::MethodCall(c) if c.method == "close"));
+    assert!(matches!(deferred[1], ExpressionValue::FunctionCall(_)));
+}
+
+#[test]
+fn only_calls_can_be_deferred() {
+    let res = parse_module(
+        "
+func main() {
+	defer 1 + 2
+}
+",
+    );
+    assert!(res.is_err());
+}
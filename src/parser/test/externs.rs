@@ -0,0 +1,61 @@
+use crate::lang::Type;
+
+use super::parse_module;
+
+#[test]
+fn extern_functions_are_imports_with_a_signature() {
+    let module = parse_module(
+        "
+extern \"builtin\" func print(s text)
+extern func now() int64
+
+func main() {
+	print(\"hello\")
+}
+",
+    )
+    .unwrap();
+
+    let print = &module.imports["print"];
+    assert_eq!(print.path, "builtin.print");
+    assert!(print.external);
+    let signature = print.signature.as_ref().unwrap();
+    assert_eq!(signature.args.len(), 1);
+    assert_eq!(signature.args[0].ident, "s");
+    assert_eq!(signature.args[0].ttype, Type::Text);
+    assert_eq!(signature.return_value, Type::Void);
+
+    let now = &module.imports["now"];
+    assert_eq!(now.path, "env.now");
+    assert_eq!(now.signature.as_ref().unwrap().return_value, Type::Int64);
+}
+
+#[test]
+fn extern_functions_have_no_body() {
+    let res = parse_module(
+        "
+extern func now() int64 {
+	return 0
+}
+",
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+fn exported_functions_are_listed() {
+    let module = parse_module(
+        "
+export func double(x int) int {
+	return x * 2
+}
+
+func helper() {
+}
+",
+    )
+    .unwrap();
+    assert!(module.functions.contains_key("double"));
+    let exports: Vec<&String> = module.exports.keys().collect();
+    assert_eq!(exports, ["double"]);
+}
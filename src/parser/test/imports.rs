@@ -0,0 +1,46 @@
+use super::parse_module;
+
+#[test]
+fn declarations_named_like_an_import_are_reported_at_the_import() {
+    let err = parse_module(
+        "
+use {
+	io.print_line
+}
+
+func print_line(s text) {
+}
+",
+    )
+    .err()
+    .unwrap();
+
+    assert_eq!((err.line, err.column), (3, 2));
+    assert_eq!(
+        err.to_string(),
+        "-:3:2: `print_line` is already defined (also declared in this module)"
+    );
+}
+
+#[test]
+fn imports_of_the_same_name_are_reported_at_the_second() {
+    let err = parse_module(
+        "
+use {
+	a.f
+	b.f
+}
+
+func main() {
+}
+",
+    )
+    .err()
+    .unwrap();
+
+    assert_eq!((err.line, err.column), (4, 2));
+    assert_eq!(
+        err.to_string(),
+        "-:4:2: `f` is already defined (already imported from `a.f`)"
+    );
+}
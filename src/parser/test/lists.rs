@@ -0,0 +1,80 @@
+use crate::lang::{ExpressionValue, StatementValue, Type};
+
+use super::parse_module;
+
+const ANSWERS: &str = "
+func main() {
+	var {
+		answers list[text] = list[text]{\"fizz\", \"buzz\"}
+		grid list[list[int]] = list[list[int]]{
+			list[int]{1, 2},
+			list[int]{},
+		}
+	}
+	answers[0] = answers[1]
+	for i, answer in answers {
+		grid[i][0] = i
+	}
+}
+";
+
+#[test]
+fn list_types_and_literals() {
+    let module = parse_module(ANSWERS).unwrap();
+    let main = &module.functions["main"];
+
+    match &main.statements[0].value {
+        StatementValue::VarDeclaration(decl) => {
+            assert_eq!(decl.ttype, Type::List(Box::new(Type::Text)))
+        }
+        _ => panic!("expected a variable declaration"),
+    }
+    match &main.statements[1].value {
+        StatementValue::VarDeclaration(decl) => {
+            let int_list = Type::List(Box::new(Type::Int));
+            assert_eq!(decl.ttype, Type::List(Box::new(int_list.clone())));
+            match &decl.value.value {
+                ExpressionValue::ListLiteral(literal) => {
+                    assert_eq!(literal.ttype, int_list);
+                    assert_eq!(literal.elements.len(), 2);
+                }
+                _ => panic!("expected a list literal"),
+            }
+        }
+        _ => panic!("expected a variable declaration"),
+    }
+}
+
+#[test]
+fn elements_are_indexed() {
+    let module = parse_module(ANSWERS).unwrap();
+    let main = &module.functions["main"];
+
+    match &main.statements[2].value {
+        StatementValue::Assignment(assignment) => {
+            assert!(matches!(assignment.target.value, ExpressionValue::Index(_)));
+            assert!(matches!(assignment.value.value, ExpressionValue::Index(_)));
+        }
+        _ => panic!("expected `answers[0] = answers[1]` to be an assignment"),
+    }
+    match &main.statements[3].value {
+        StatementValue::For(stmt) => {
+            assert_eq!(stmt.bindings, vec!["i", "answer"]);
+            assert_eq!(stmt.list_bindings(&Type::Text), vec![Type::Int, Type::Text]);
+        }
+        _ => panic!("expected a for loop"),
+    }
+}
+
+#[test]
+fn list_literals_need_a_closing_brace() {
+    let err = parse_module("func main() {\n\tx = list[int]{1, 2")
+        .err()
+        .unwrap();
+    assert_eq!(err.message, "missing `}` after list literal");
+
+    let err = parse_module("func main() {\n\tx = list[int}{1}\n}")
+        .err()
+        .unwrap();
+    assert_eq!((err.line, err.column), (2, 14));
+}
@@ -0,0 +1,171 @@
+use crate::{
+    lang::{ExpressionValue, Func, StatementValue, Type},
+    parser::ErrorKind,
+};
+
+use super::parse_module;
+
+const POINT: &str = "
+use {
+	io
+}
+
+struct Point {
+	x int
+	y int
+}
+
+func (p Point) length_squared() int {
+	return p.x * p.x + p.y * p.y
+}
+
+func (var p Point) scale(f int) {
+	p.x *= f
+	p.y *= f
+}
+";
+
+fn expression_of(func: &Func, statement: usize) -> &ExpressionValue {
+    match &func.statements[statement].value {
+        StatementValue::Expression(e) => &e.value,
+        StatementValue::VarDeclaration(d) => &d.value.value,
+        StatementValue::Return(Some(e)) => &e.value,
+        _ => panic!("statement {} has no expression", statement),
+    }
+}
+
+#[test]
+fn method_sets_are_stored_on_struct_types() {
+    let module = parse_module(POINT).unwrap();
+    let point = &module.types["Point"];
+
+    assert_eq!(point.methods.len(), 2);
+    let receiver = point.methods["length_squared"]
+        .signature
+        .receiver
+        .as_ref()
+        .unwrap();
+    assert_eq!(receiver.ident, "p");
+    assert_eq!(receiver.ttype, Type::Struct("Point".into()));
+    assert!(!receiver.mutable);
+    assert!(
+        point.methods["scale"]
+            .signature
+            .receiver
+            .as_ref()
+            .unwrap()
+            .mutable
+    );
+    assert!(module.functions.is_empty());
+}
+
+#[test]
+fn member_access_and_method_calls_are_resolved() {
+    let src = format!(
+        "{}
+func main() {{
+	var {{
+		p Point = Point{{x = 3, y = 4}}
+		x int = p.x
+	}}
+	p.scale(2)
+	io.print_line(p.length_squared())
+}}",
+        POINT
+    );
+    let module = parse_module(&src).unwrap();
+    let main = &module.functions["main"];
+
+    match expression_of(main, 1) {
+        ExpressionValue::MemberAccess(access) => assert_eq!(access.member, "x"),
+        _ => panic!("expected `p.x` to be a member access"),
+    }
+    match expression_of(main, 2) {
+        ExpressionValue::MethodCall(call) => assert_eq!(call.method, "scale"),
+        _ => panic!("expected `p.scale(2)` to be a method call"),
+    }
+    match expression_of(main, 3) {
+        ExpressionValue::FunctionCall(call) => {
+            match &call.function.value {
+                ExpressionValue::Identifier(ident) => {
+                    assert_eq!(ident.namespace, vec!["io".to_string()]);
+                    assert_eq!(ident.name, "print_line");
+                }
+                _ => panic!("expected `io.print_line` to stay a qualified identifier"),
+            }
+            match &call.args[0].value {
+                ExpressionValue::MethodCall(call) => assert_eq!(call.method, "length_squared"),
+                _ => panic!("expected `p.length_squared()` to be a method call"),
+            }
+        }
+        _ => panic!("expected a function call"),
+    }
+}
+
+#[test]
+fn mutable_receivers_need_assignable_values() {
+    let src = format!(
+        "{}
+func grow(p Point) {{
+	p.scale(2)
+}}",
+        POINT
+    );
+    let err = parse_module(&src).err().unwrap();
+    assert!(matches!(err.kind, ErrorKind::ImmutableReceiver(m) if m == "scale"));
+
+    let src = format!(
+        "{}
+func main() {{
+	const {{
+		origin Point = Point{{}}
+	}}
+	origin.scale(2)
+}}",
+        POINT
+    );
+    let err = parse_module(&src).err().unwrap();
+    assert!(matches!(err.kind, ErrorKind::ImmutableReceiver(_)));
+}
+
+#[test]
+fn unknown_members_are_reported() {
+    let src = format!(
+        "{}
+func main() {{
+	var {{
+		p Point = Point{{}}
+	}}
+	p.z = 1
+}}",
+        POINT
+    );
+    let err = parse_module(&src).err().unwrap();
+    assert!(matches!(err.kind, ErrorKind::UnknownMember(t, m) if t == "Point" && m == "z"));
+
+    let src = format!(
+        "{}
+func main() {{
+	var {{
+		p Point = Point{{}}
+	}}
+	p.length()
+}}",
+        POINT
+    );
+    let err = parse_module(&src).err().unwrap();
+    assert!(matches!(err.kind, ErrorKind::UnknownMember(_, m) if m == "length"));
+}
+
+#[test]
+fn methods_need_a_struct_receiver() {
+    let err = parse_module("func (s Shape) area() int {\n\treturn 0\n}")
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind, ErrorKind::UndefinedType(t) if t == "Shape"));
+
+    let err = parse_module("func (i int) double() int {\n\treturn i * 2\n}")
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind, ErrorKind::InvalidReceiver(t) if t == "int"));
+}
@@ -0,0 +1,17 @@
+use crate::lang::Module;
+
+use super::{Parser, Result};
+
+mod defer;
+mod externs;
+mod imports;
+mod lists;
+mod methods;
+mod tuples;
+mod variadic;
+
+fn parse_module(src: &str) -> Result<Module> {
+    let mut parser = Parser::new("test".into());
+    parser.add_source(src.as_bytes(), None)?;
+    parser.finalize()
+}
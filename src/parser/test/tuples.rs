@@ -0,0 +1,100 @@
+use crate::lang::{ExpressionValue, StatementValue, Type};
+
+use super::parse_module;
+
+const DIVMOD: &str = "
+func divmod(a int, b int) (int, int) {
+	return a / b, a % b
+}
+
+func main() {
+	var {
+		q int = 0
+		r int = 0
+		pair (int, int) = (1, 2)
+	}
+	q, r = divmod(7, 2)
+	_, r = pair
+}
+";
+
+#[test]
+fn functions_return_tuples() {
+    let module = parse_module(DIVMOD).unwrap();
+    let divmod = &module.functions["divmod"];
+
+    assert_eq!(
+        divmod.signature.return_value,
+        Type::Tuple(vec![Type::Int, Type::Int])
+    );
+    match &divmod.statements[0].value {
+        StatementValue::Return(Some(e)) => match &e.value {
+            ExpressionValue::Tuple(elements) => assert_eq!(elements.len(), 2),
+            _ => panic!("expected `return a / b, a % b` to return a tuple"),
+        },
+        _ => panic!("expected a return statement"),
+    }
+}
+
+#[test]
+fn tuples_are_destructured_by_assignments() {
+    let module = parse_module(DIVMOD).unwrap();
+    let main = &module.functions["main"];
+
+    match &main.statements[2].value {
+        StatementValue::VarDeclaration(decl) => {
+            assert_eq!(decl.ttype, Type::Tuple(vec![Type::Int, Type::Int]));
+            assert!(matches!(&decl.value.value, ExpressionValue::Tuple(e) if e.len() == 2))
+        }
+        _ => panic!("expected a variable declaration"),
+    }
+    match &main.statements[3].value {
+        StatementValue::MultiAssignment(assignment) => {
+            assert_eq!(assignment.bindings, vec!["q", "r"]);
+            assert!(matches!(
+                assignment.value.value,
+                ExpressionValue::FunctionCall(_)
+            ));
+        }
+        _ => panic!("expected `q, r = divmod(7, 2)` to be a multiple assignment"),
+    }
+    match &main.statements[4].value {
+        StatementValue::MultiAssignment(assignment) => {
+            assert_eq!(assignment.bindings, vec!["_", "r"])
+        }
+        _ => panic!("expected `_, r = pair` to be a multiple assignment"),
+    }
+}
+
+#[test]
+fn parentheses_without_commas_are_not_tuples() {
+    let module = parse_module("func f() int {\n\treturn (1 + 2) * 3\n}").unwrap();
+    match &module.functions["f"].statements[0].value {
+        StatementValue::Return(Some(e)) => {
+            assert!(matches!(e.value, ExpressionValue::BinaryOperation(_)))
+        }
+        _ => panic!("expected a return statement"),
+    }
+}
+
+#[test]
+fn only_variables_are_assigned_from_tuples() {
+    let src = "func main() {\n\tq, p.x = divmod(7, 2)\n}";
+    let err = parse_module(src).err().unwrap();
+    assert_eq!((err.line, err.column), (2, 5));
+    assert_eq!(err.message, "only variables can be assigned from a tuple");
+
+    let src = "func main() {\n\tf(), r = pair\n}";
+    let err = parse_module(src).err().unwrap();
+    assert_eq!((err.line, err.column), (2, 2));
+}
+
+#[test]
+fn tuple_types_have_two_elements_or_more() {
+    let err = parse_module("func f(a (int)) {\n}").err().unwrap();
+    assert_eq!((err.line, err.column), (1, 10));
+    assert_eq!(err.message, "tuple types need at least two elements");
+
+    let err = parse_module("func f() (int) {\n}").err().unwrap();
+    assert_eq!((err.line, err.column), (1, 10));
+}
@@ -0,0 +1,34 @@
+use crate::lang::Type;
+
+use super::parse_module;
+
+#[test]
+fn variadic_arguments_are_lists() {
+    let module = parse_module(
+        "
+func join(separator text, values ...text) text {
+	return separator
+}
+",
+    )
+    .unwrap();
+
+    let signature = &module.functions["join"].signature;
+    assert!(signature.variadic);
+    assert_eq!(signature.args[0].ttype, Type::Text);
+    assert_eq!(signature.args[1].ident, "values");
+    assert_eq!(signature.args[1].ttype, Type::List(Box::new(Type::Text)));
+}
+
+#[test]
+fn only_the_last_argument_is_variadic() {
+    let res = parse_module(
+        "
+func join(values ...text, separator text) text {
+	return separator
+}
+",
+    );
+    let err = res.err().unwrap();
+    assert_eq!((err.line, err.column), (2, 25));
+}
@@ -1,3 +1,6 @@
+// The matchers are written as match expressions, so that they all read the same.
+#![allow(clippy::match_like_matches_macro)]
+
 use crate::tokenizer::{BinaryOperator, Token, TokenValue};
 
 pub fn identifier(t: &Token) -> bool {
@@ -22,6 +25,13 @@ pub fn comma(t: &Token) -> bool {
     }
 }
 
+pub fn open_paren(t: &Token) -> bool {
+    match t.value {
+        TokenValue::OpenParen => true,
+        _ => false,
+    }
+}
+
 pub fn close_paren(t: &Token) -> bool {
     match t.value {
         TokenValue::CloseParen => true,
@@ -29,6 +39,40 @@ pub fn close_paren(t: &Token) -> bool {
     }
 }
 
+pub fn open_brace(t: &Token) -> bool {
+    match t.value {
+        TokenValue::OpenBrace => true,
+        _ => false,
+    }
+}
+
+pub fn close_brace(t: &Token) -> bool {
+    match t.value {
+        TokenValue::CloseBrace => true,
+        _ => false,
+    }
+}
+
+pub fn close_bracket(t: &Token) -> bool {
+    match t.value {
+        TokenValue::CloseBracket => true,
+        _ => false,
+    }
+}
+
+pub fn assignment(t: &Token) -> bool {
+    match t.value {
+        TokenValue::Assignment(_) => true,
+        _ => false,
+    }
+}
+
+/// end_of_statement matches the tokens that can follow a statement: a newline, or the `}` closing
+/// the enclosing block.
+pub fn end_of_statement(t: &Token) -> bool {
+    newline(t) || close_brace(t)
+}
+
 pub fn either<F1, F2>(term1: F1, term2: F2) -> Box<dyn Fn(&Token) -> bool>
 where
     F1: Fn(&Token) -> bool + 'static,
@@ -37,22 +81,14 @@ where
     Box::new(move |t| term1(t) || term2(t))
 }
 
-pub fn term_delimiter_for<F>(t: &Token) -> Box<dyn Fn(&Token) -> bool> {
-    let p1 = OperatorPrecedence::from(t);
-
-    Box::new(move |t| {
-        let p2 = OperatorPrecedence::from(t);
-        p2 > p1
-    })
-}
-
 #[derive(Eq, PartialEq, Ord, PartialOrd)]
-enum OperatorPrecedence {
+pub enum OperatorPrecedence {
     NotAnOperator,
     UnaryOperator,
     Multiplication,
     Sum,
     BitwiseShift,
+    NilCoalesce,
     Comparison,
     BitwiseAnd,
     BitwiseXor,
@@ -82,7 +118,10 @@ impl From<&Token> for OperatorPrecedence {
                 | BinaryOperator::GreaterThanOrEquals
                 | BinaryOperator::LessThanOrEquals
                 | BinaryOperator::NotEquals => Self::Comparison,
+                BinaryOperator::NilCoalesce => Self::NilCoalesce,
             },
+            // `x is some` and `x is nil` bind like comparisons
+            TokenValue::KeywordIs => Self::Comparison,
             _ => Self::NotAnOperator,
         }
     }
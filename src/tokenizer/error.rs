@@ -23,10 +23,10 @@ impl std::fmt::Display for Error {
             "{}:{}:{}: {}",
             self.source, self.line, self.column, self.kind
         ))?;
-	if self.message.is_empty() {
-	    return Ok(());
-	}
-	f.write_fmt(format_args!(" ({})", self.message))
+        if self.message.is_empty() {
+            return Ok(());
+        }
+        f.write_fmt(format_args!(" ({})", self.message))
     }
 }
 
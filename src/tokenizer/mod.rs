@@ -43,6 +43,7 @@ pub enum BinaryOperator {
     GreaterThanOrEquals,
     LessThanOrEquals,
     NotEquals,
+    NilCoalesce,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -60,6 +61,7 @@ pub enum TokenValue {
     StringLiteral(String),
     CharLiteral(char),
     BoolLiteral(bool),
+    NilLiteral,
 
     UnaryOperator(UnaryOperator),
     BinaryOperator(BinaryOperator),
@@ -72,7 +74,10 @@ pub enum TokenValue {
     OpenBracket,
     CloseBracket,
     Dot,
+    /// `...`, which marks a variadic argument.
+    Ellipsis,
     Comma,
+    QuestionMark,
     Newline,
 
     KeywordFunc,
@@ -89,6 +94,13 @@ pub enum TokenValue {
     KeywordAs,
     KeywordIn,
     KeywordReturn,
+    KeywordBreak,
+    KeywordContinue,
+    KeywordDefer,
+    KeywordExtern,
+    KeywordExport,
+    KeywordIs,
+    KeywordSome,
 }
 
 /// TokenStream provides an easy way to iterate over the tokenized contents of some tiger source input.
@@ -98,6 +110,8 @@ pub struct TokenStream<R: Read> {
     stream_column: usize,
     stream_line: usize,
     lookahead_buf: VecDeque<char>,
+    // Positions of the last characters read, most recent last, so that they can be pushed back.
+    char_positions: Vec<(usize, usize)>,
     finished: bool,
     cached_token: Option<Token>,
 
@@ -107,6 +121,7 @@ pub struct TokenStream<R: Read> {
 
 impl<R: Read> TokenStream<R> {
     /// Create a new TokenStream from a reader and an optional source path.
+    #[allow(clippy::needless_return)]
     pub fn new(r: R, path: Option<String>) -> Self {
         let path = if let Some(p) = path {
             p
@@ -121,6 +136,7 @@ impl<R: Read> TokenStream<R> {
             token_column: 1,
             token_line: 1,
             lookahead_buf: VecDeque::new(),
+            char_positions: vec![],
             finished: false,
             cached_token: None,
         };
@@ -131,13 +147,20 @@ impl<R: Read> TokenStream<R> {
         self.finished && self.cached_token.is_none()
     }
 
+    #[allow(clippy::needless_return)]
     fn next_char(&mut self) -> Option<Result<char, io::Error>> {
-        let res: Option<Result<char, io::Error>>;
-
-        if !self.lookahead_buf.is_empty() {
-            res = self.lookahead_buf.pop_front().map(|v| Ok(v));
+        let res: Option<Result<char, io::Error>> = if !self.lookahead_buf.is_empty() {
+            self.lookahead_buf.pop_front().map(Ok)
         } else {
-            res = read_char(&mut self.stream);
+            read_char(&mut self.stream)
+        };
+
+        if let Some(Ok(_)) = res {
+            if self.char_positions.len() == MAX_PUSHBACK {
+                self.char_positions.remove(0);
+            }
+            self.char_positions
+                .push((self.stream_line, self.stream_column));
         }
 
         match res {
@@ -163,11 +186,8 @@ impl<R: Read> TokenStream<R> {
     pub fn peek(&mut self) -> Option<Result<Token, Error>> {
         let res = self.next_token();
 
-        match &res {
-            Some(Ok(t)) => {
-                self.cached_token = Some(t.clone());
-            }
-            _ => (),
+        if let Some(Ok(t)) = &res {
+            self.cached_token = Some(t.clone());
         }
 
         res
@@ -178,7 +198,7 @@ impl<R: Read> TokenStream<R> {
     /// Returns None when EOF has been reached without errors.
     pub fn next_token(&mut self) -> Option<Result<Token, Error>> {
         if self.cached_token.is_some() {
-            let res = self.cached_token.take().map(|t| Ok(t));
+            let res = self.cached_token.take().map(Ok);
             return res;
         }
 
@@ -205,7 +225,7 @@ impl<R: Read> TokenStream<R> {
                 Some(Ok('(')) => self.build_token(TokenValue::OpenParen, "("),
                 Some(Ok(')')) => self.build_token(TokenValue::CloseParen, ")"),
                 Some(Ok(',')) => self.build_token(TokenValue::Comma, ","),
-                Some(Ok('.')) => self.build_token(TokenValue::Dot, "."),
+                Some(Ok('.')) => return self.read_dots(),
                 Some(Ok('"')) => return self.read_string(),
                 Some(Ok('\'')) => return self.read_char(),
                 Some(Ok(c)) if is_operator(c) => {
@@ -250,6 +270,8 @@ impl<R: Read> TokenStream<R> {
                     if c.is_alphanumeric() || c == '_' {
                         value.push(c);
                     } else {
+                        // Start of the next token
+                        self.push_char(c);
                         break;
                     }
                 }
@@ -283,12 +305,21 @@ impl<R: Read> TokenStream<R> {
             "as" => Some(self.build_token(TokenValue::KeywordAs, s)),
             "in" => Some(self.build_token(TokenValue::KeywordIn, s)),
             "return" => Some(self.build_token(TokenValue::KeywordReturn, s)),
+            "break" => Some(self.build_token(TokenValue::KeywordBreak, s)),
+            "continue" => Some(self.build_token(TokenValue::KeywordContinue, s)),
+            "defer" => Some(self.build_token(TokenValue::KeywordDefer, s)),
+            "extern" => Some(self.build_token(TokenValue::KeywordExtern, s)),
+            "export" => Some(self.build_token(TokenValue::KeywordExport, s)),
+            "is" => Some(self.build_token(TokenValue::KeywordIs, s)),
+            "some" => Some(self.build_token(TokenValue::KeywordSome, s)),
+            "nil" => Some(self.build_token(TokenValue::NilLiteral, s)),
             "true" => Some(self.build_token(TokenValue::BoolLiteral(true), s)),
             "false" => Some(self.build_token(TokenValue::BoolLiteral(false), s)),
             _ => None,
         }
     }
 
+    #[allow(clippy::needless_return)]
     fn read_number(&mut self) -> Option<Result<Token, Error>> {
         let mut base = 10;
         let mut floating_point = false;
@@ -302,19 +333,33 @@ impl<R: Read> TokenStream<R> {
                         '-' if value.is_empty() => {
                             value.push(c);
                         }
-                        '.' if value.is_empty() => {
+                        '.' if !value.is_empty() => {
                             if base != 10 || floating_point {
                                 return Some(Err(self.error(
                                     "unexpected character '.' in a number literal".to_string(),
                                 )));
                             }
                             floating_point = true;
+                            value.push(c);
+                        }
+                        'x' | 'b' | 'o' if base == 10 && value == ['0'] => {
+                            base = match c {
+                                'x' => 16,
+                                'b' => 2,
+                                _ => 8,
+                            };
+                            value.clear();
                         }
-                        'x' if value.is_empty() => base = 16,
-                        'b' if value.is_empty() => base = 2,
-                        'o' if value.is_empty() => base = 8,
                         c if is_numeric(c, base) => value.push(c),
                         _ => {
+                            if value.last().is_some_and(|c| *c == '.') {
+                                // A dot at the end of a number is interpreted as a dot token, not part of a floating point number
+                                value.pop();
+                                self.push_char(c);
+                                self.push_char('.');
+                                floating_point = false;
+                                break;
+                            }
                             if c.is_alphanumeric() || c == '_' {
                                 return Some(Err(self.error(format!(
                                     "unexpected character '{}' in a number literal",
@@ -349,7 +394,7 @@ impl<R: Read> TokenStream<R> {
                 Err(e) => {
                     return Some(Err(self.internal_error(format!(
                         "internal error while reading number: {}",
-                        e.to_string()
+                        e
                     ))))
                 }
             }
@@ -365,7 +410,7 @@ impl<R: Read> TokenStream<R> {
             Err(e) => {
                 return Some(Err(self.internal_error(format!(
                     "internal error while reading number: {}",
-                    e.to_string()
+                    e
                 ))))
             }
         }
@@ -466,6 +511,18 @@ impl<R: Read> TokenStream<R> {
                             self.build_operator(format!("{}", c1).as_str())
                         }
                     }
+                    '?' => {
+                        // can be c or cc
+                        match self.next_char() {
+                            Some(Ok('?')) => self.build_operator("??"),
+                            Some(Ok(c2)) => {
+                                self.push_char(c2);
+                                self.build_operator("?")
+                            }
+                            Some(Err(e)) => Some(Err(self.io_error(e))),
+                            None => self.build_operator("?"),
+                        }
+                    }
                     '&' | '|' | '<' | '>' => {
                         // can be c, cc, c= or cc=
                         let mut res = format!("{}", c1);
@@ -577,6 +634,11 @@ impl<R: Read> TokenStream<R> {
                 TokenValue::BinaryOperator(BinaryOperator::NotEquals),
                 op,
             ))),
+            "??" => Some(Ok(self.build_token(
+                TokenValue::BinaryOperator(BinaryOperator::NilCoalesce),
+                op,
+            ))),
+            "?" => Some(Ok(self.build_token(TokenValue::QuestionMark, op))),
             "!" => Some(Ok(
                 self.build_token(TokenValue::UnaryOperator(UnaryOperator::Not), op)
             )),
@@ -637,6 +699,31 @@ impl<R: Read> TokenStream<R> {
         }
     }
 
+    /// read_dots reads the token starting with a dot, after that dot: either `...` or a single
+    /// dot.
+    fn read_dots(&mut self) -> Option<Result<Token, Error>> {
+        let mut read = vec![];
+        while read.len() < 2 {
+            match self.next_char() {
+                Some(Ok('.')) => read.push('.'),
+                Some(Ok(c)) => {
+                    self.push_char(c);
+                    break;
+                }
+                Some(Err(e)) => return Some(Err(self.io_error(e))),
+                None => break,
+            }
+        }
+        if read.len() == 2 {
+            return Some(Ok(self.build_token(TokenValue::Ellipsis, "...")));
+        }
+        for c in read {
+            self.push_char(c);
+        }
+        Some(Ok(self.build_token(TokenValue::Dot, ".")))
+    }
+
+    #[allow(clippy::needless_return)]
     fn read_escape_sequence(&mut self) -> Result<char, Error> {
         match self.next_char() {
             Some(Ok(c)) => match c {
@@ -652,10 +739,18 @@ impl<R: Read> TokenStream<R> {
         }
     }
 
+    /// push_char puts back the last character read that hasn't been pushed back yet, so that it
+    /// is the next one to be read. Characters are thus pushed back in the reverse order they were
+    /// read in, and the stream position moves back to the position of `c`.
     fn push_char(&mut self, c: char) {
-        self.lookahead_buf.push_back(c)
+        if let Some((line, column)) = self.char_positions.pop() {
+            self.stream_line = line;
+            self.stream_column = column;
+        }
+        self.lookahead_buf.push_front(c)
     }
 
+    #[allow(clippy::needless_return)]
     fn internal_error(&self, msg: String) -> Error {
         return Error {
             message: msg,
@@ -695,6 +790,7 @@ impl<R: Read> TokenStream<R> {
     }
 }
 
+#[allow(clippy::needless_return)]
 fn read_char<R: Read>(r: &mut R) -> Option<io::Result<char>> {
     let mut rune_len = 0;
     let mut rune = [0u8; 4];
@@ -711,14 +807,13 @@ fn read_char<R: Read>(r: &mut R) -> Option<io::Result<char>> {
                 }
             }
             Ok(_) => {
-                rune_len = rune_len + 1;
+                rune_len += 1;
             }
             Err(e) => return Some(Err(e)),
         };
 
-        match std::str::from_utf8(&rune) {
-            Ok(s) => return Some(Ok(s.chars().next().unwrap())),
-            Err(_) => (),
+        if let Ok(s) = std::str::from_utf8(&rune) {
+            return Some(Ok(s.chars().next().unwrap()));
         }
     }
     return Some(Err(io::Error::new(
@@ -730,12 +825,15 @@ fn read_char<R: Read>(r: &mut R) -> Option<io::Result<char>> {
     )));
 }
 
+// The largest number of characters pushed back at once, by a number followed by a dot.
+const MAX_PUSHBACK: usize = 2;
+
 fn is_numeric(c: char, base: u32) -> bool {
-    c.to_digit(base).is_some()
+    c.is_digit(base)
 }
 
 fn is_operator(c: char) -> bool {
-    "+-/*%!&|^=<>".contains(c)
+    "+-/*%!&|^=<>?".contains(c)
 }
 
 impl<R: Read> Iterator for TokenStream<R> {
@@ -746,9 +844,9 @@ impl<R: Read> Iterator for TokenStream<R> {
     }
 }
 
-impl Into<String> for Token {
-    fn into(self) -> String {
-        self.text
+impl From<Token> for String {
+    fn from(val: Token) -> Self {
+        val.text
     }
 }
 
@@ -761,6 +859,7 @@ impl std::fmt::Display for TokenValue {
             TokenValue::StringLiteral(s) => format!("string literal \"{}\"", s),
             TokenValue::CharLiteral(c) => format!("character literal '{}'", c),
             TokenValue::BoolLiteral(b) => format!("boolean literal `{}`", b),
+            TokenValue::NilLiteral => "`nil`".into(),
             TokenValue::UnaryOperator(o) => format!("operator `{}`", o),
             TokenValue::BinaryOperator(o) => format!("operator `{}`", o),
             TokenValue::Assignment(o) => format!("operator `{}`", o),
@@ -771,7 +870,9 @@ impl std::fmt::Display for TokenValue {
             TokenValue::OpenBracket => "`[`".into(),
             TokenValue::CloseBracket => "`]`".into(),
             TokenValue::Dot => "`.`".into(),
+            TokenValue::Ellipsis => "`...`".into(),
             TokenValue::Comma => "`,`".into(),
+            TokenValue::QuestionMark => "`?`".into(),
             TokenValue::Newline => "newline".into(),
             TokenValue::KeywordFunc => "keyword `func`".into(),
             TokenValue::KeywordTest => "keyword `test`".into(),
@@ -787,6 +888,13 @@ impl std::fmt::Display for TokenValue {
             TokenValue::KeywordAs => "keyword `as`".into(),
             TokenValue::KeywordIn => "keyword `in`".into(),
             TokenValue::KeywordReturn => "keyword `return`".into(),
+            TokenValue::KeywordBreak => "keyword `break`".into(),
+            TokenValue::KeywordContinue => "keyword `continue`".into(),
+            TokenValue::KeywordDefer => "keyword `defer`".into(),
+            TokenValue::KeywordExtern => "keyword `extern`".into(),
+            TokenValue::KeywordExport => "keyword `export`".into(),
+            TokenValue::KeywordIs => "keyword `is`".into(),
+            TokenValue::KeywordSome => "keyword `some`".into(),
         };
         f.write_str(&s)
     }
@@ -795,9 +903,9 @@ impl std::fmt::Display for TokenValue {
 impl std::fmt::Display for UnaryOperator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
-            UnaryOperator::Not => "!".into(),
-            UnaryOperator::Minus => "-".into(),
-            UnaryOperator::Plus => "+".into(),
+            UnaryOperator::Not => "!",
+            UnaryOperator::Minus => "-",
+            UnaryOperator::Plus => "+",
         };
         f.write_str(s)
     }
@@ -824,6 +932,7 @@ impl std::fmt::Display for BinaryOperator {
             BinaryOperator::GreaterThanOrEquals => ">=",
             BinaryOperator::LessThanOrEquals => "<=",
             BinaryOperator::NotEquals => "!=",
+            BinaryOperator::NilCoalesce => "??",
         };
         f.write_str(s)
     }
@@ -1 +1,2 @@
 mod read_char;
+mod token_stream;
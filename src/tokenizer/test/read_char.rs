@@ -5,8 +5,6 @@
 // U+0800 	            U+FFFF 	            1110xxxx 	10xxxxxx 	10xxxxxx
 // U+10000 	            U+10FFFF    	    11110xxx 	10xxxxxx 	10xxxxxx 	10xxxxxx
 
-use std::u8;
-
 use crate::tokenizer::read_char;
 
 struct AllUTF8CharReader {
@@ -16,6 +14,15 @@ struct AllUTF8CharReader {
 impl AllUTF8CharReader {
     // This will return all UTF-8 encoded Unicode codepoints in sequence.
     // The invalid range of 0xD800-0xDFFF will all be replaced by the next valid character instead (0xE000)
+    #[allow(
+        clippy::needless_return,
+        clippy::partialeq_to_none,
+        clippy::identity_op,
+        clippy::zero_prefixed_literal,
+        clippy::manual_range_contains,
+        clippy::needless_ifs,
+        clippy::redundant_comparisons
+    )]
     fn next_u8(&mut self) -> Option<u8> {
         let mut res = None;
 
@@ -85,6 +92,7 @@ impl AllUTF8CharReader {
 }
 
 impl std::io::Read for AllUTF8CharReader {
+    #[allow(clippy::needless_return, clippy::needless_range_loop)]
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         for i in 0..buf.len() {
             match self.next_u8() {
@@ -97,6 +105,7 @@ impl std::io::Read for AllUTF8CharReader {
 }
 
 #[test]
+#[allow(clippy::collapsible_match)]
 fn read_char_test() {
     let mut r = AllUTF8CharReader { pos: 0 };
 
@@ -0,0 +1,87 @@
+use crate::tokenizer::{TokenStream, TokenValue};
+
+/// tokens returns the value, line and column of every token of `src`.
+fn tokens(src: &str) -> Vec<(TokenValue, usize, usize)> {
+    TokenStream::new(src.as_bytes(), None)
+        .map(|t| {
+            let t = t.unwrap();
+            (t.value, t.line, t.column)
+        })
+        .collect()
+}
+
+#[test]
+fn numbers_with_a_base() {
+    assert_eq!(
+        tokens("0x1f 0b101 0o17"),
+        vec![
+            (TokenValue::IntegerLiteral(31), 1, 1),
+            (TokenValue::IntegerLiteral(5), 1, 6),
+            (TokenValue::IntegerLiteral(15), 1, 12),
+        ]
+    );
+}
+
+#[test]
+fn floating_point_numbers() {
+    assert_eq!(
+        tokens("1.5 0.25"),
+        vec![
+            (TokenValue::FloatingPointLiteral(1.5), 1, 1),
+            (TokenValue::FloatingPointLiteral(0.25), 1, 5),
+        ]
+    );
+}
+
+#[test]
+fn number_followed_by_a_dot() {
+    assert_eq!(
+        tokens("1.abs"),
+        vec![
+            (TokenValue::IntegerLiteral(1), 1, 1),
+            (TokenValue::Dot, 1, 2),
+            (TokenValue::Identifier("abs".into()), 1, 3),
+        ]
+    );
+}
+
+#[test]
+fn identifier_followed_by_a_paren() {
+    assert_eq!(
+        tokens("f("),
+        vec![
+            (TokenValue::Identifier("f".into()), 1, 1),
+            (TokenValue::OpenParen, 1, 2),
+        ]
+    );
+}
+
+#[test]
+fn pushed_back_newlines_keep_their_position() {
+    assert_eq!(
+        tokens("abc\n1.\nx"),
+        vec![
+            (TokenValue::Identifier("abc".into()), 1, 1),
+            (TokenValue::Newline, 1, 4),
+            (TokenValue::IntegerLiteral(1), 2, 1),
+            (TokenValue::Dot, 2, 2),
+            (TokenValue::Newline, 2, 3),
+            (TokenValue::Identifier("x".into()), 3, 1),
+        ]
+    );
+}
+
+#[test]
+fn dots() {
+    assert_eq!(
+        tokens("a ...text ..b"),
+        vec![
+            (TokenValue::Identifier("a".into()), 1, 1),
+            (TokenValue::Ellipsis, 1, 3),
+            (TokenValue::Identifier("text".into()), 1, 6),
+            (TokenValue::Dot, 1, 11),
+            (TokenValue::Dot, 1, 12),
+            (TokenValue::Identifier("b".into()), 1, 13),
+        ]
+    );
+}
@@ -0,0 +1,143 @@
+//! Runs programs built for the `wasi` target under wasmtime, with the WASI implementation of
+//! `wasmtime-wasi`, and checks what they write to the standard output and their exit code.
+
+use std::{fs, path::Path};
+
+use tiger_lang::{
+    check,
+    codegen::{self, Target},
+    hir, link, opt,
+};
+use wasi_common::pipe::WritePipe;
+use wasmtime::{Engine, Linker, Module, Store};
+use wasmtime_wasi::{I32Exit, WasiCtx, WasiCtxBuilder};
+
+/// build compiles the program whose entry point is at `path` for the `wasi` target. Modules are
+/// looked up next to it, and then in the standard library.
+fn build(path: &Path, level: opt::Level) -> Result<Vec<u8>, String> {
+    let dirs = [path.parent().unwrap().to_path_buf()];
+    let assembly = link::load(path, &dirs).map_err(|e| format!("{:?}", e))?;
+    let mut modules = vec![];
+    for mut module in assembly.modules {
+        let errors = check::check_module(&mut module);
+        if let Some(e) = errors.iter().find(|e| e.severity == check::Severity::Error) {
+            return Err(e.to_string());
+        }
+        modules.push(hir::lower(&module).map_err(|e| e.to_string())?);
+    }
+    let mut module = link::merge(modules);
+    opt::optimize(&mut module, level);
+    let (wasm, _) = codegen::compile_for(&module, Target::Wasi).map_err(|e| e.to_string())?;
+    codegen::validate(&wasm).map_err(|e| e.to_string())?;
+    Ok(wasm)
+}
+
+/// run runs the program with `args` and the environment variables `env`, and returns what it
+/// wrote and its exit code.
+fn run(wasm: &[u8], args: &[&str], env: &[&str]) -> (String, i32) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).unwrap();
+    let mut linker: Linker<WasiCtx> = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |ctx| ctx).unwrap();
+
+    let stdout = WritePipe::new_in_memory();
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    let env: Vec<(String, String)> = (env.iter())
+        .map(|s| s.split_once('=').unwrap())
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    let ctx = WasiCtxBuilder::new()
+        .args(&args)
+        .unwrap()
+        .envs(&env)
+        .unwrap()
+        .stdout(Box::new(stdout.clone()))
+        .build();
+    let mut store = Store::new(&engine, ctx);
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let start = instance
+        .get_typed_func::<(), ()>(&mut store, "_start")
+        .unwrap();
+    let exit_code = match start.call(&mut store, ()) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(I32Exit(code)) => *code,
+            None => panic!("{}", e),
+        },
+    };
+    // The store holds the other end of the pipe
+    drop(store);
+    let stdout = stdout.try_into_inner().unwrap().into_inner();
+    (String::from_utf8(stdout).unwrap(), exit_code)
+}
+
+#[test]
+fn examples_print_what_is_expected() {
+    // Each example runs and prints its expected output, but those without `main`, which only
+    // hold tests and cannot be built for wasi
+    for entry in fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap();
+        let expected = Path::new("tests/wasi").join(name).with_extension("stdout");
+        let expected = match fs::read_to_string(&expected) {
+            Ok(expected) => expected,
+            Err(_) => {
+                let e = build(&path, opt::Level::O0).unwrap_err();
+                assert!(e.ends_with("needs a `main` function"), "{:?}: {}", path, e);
+                continue;
+            }
+        };
+
+        let wasm = build(&path, opt::Level::O0).unwrap_or_else(|e| panic!("{:?}: {}", path, e));
+        assert_eq!(run(&wasm, &["example"], &[]), (expected.clone(), 0));
+
+        let optimized = build(&path, opt::Level::O2).unwrap();
+        assert_eq!(run(&optimized, &["example"], &[]), (expected, 0));
+    }
+}
+
+#[test]
+fn programs_see_their_arguments_and_environment() {
+    let dir = std::env::temp_dir().join(format!("tiger-wasi-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.tiger");
+    fs::write(
+        &path,
+        "
+use {
+	io.print
+	io.print_line
+	os.arg
+	os.arg_count
+	os.env
+	os.exit
+}
+
+func main() int {
+	print_line(arg_count(), arg(1), arg(5) == \"\")
+	print_line(env(\"HOME\") ?? \"unset\", env(\"HOM\") ?? \"unset\", env(\"SHELL\") ?? \"unset\")
+	print(1.5, -0.25, 2.0000004, 'é', '€', -9223372036854775807 - 1)
+	print(\"\\n\")
+	if arg(1) == \"fail\" {
+		exit(3)
+		print_line(\"not printed\")
+	}
+	return 7
+}
+",
+    )
+    .unwrap();
+    let wasm = build(&path, opt::Level::O0);
+    fs::remove_dir_all(&dir).unwrap();
+    let wasm = wasm.unwrap();
+
+    let env = ["HOME=/home/tiger", "SHELL="];
+    // `SHELL` is set, but empty
+    let stdout = "3 one true\n/home/tiger unset \n1.5 -0.25 2 é € -9223372036854775808\n";
+    assert_eq!(
+        run(&wasm, &["main", "one", "two"], &env),
+        (stdout.to_string(), 7)
+    );
+    let (_, exit_code) = run(&wasm, &["main", "fail"], &env);
+    assert_eq!(exit_code, 3);
+}